@@ -0,0 +1,198 @@
+//! Resolve user-typed area names to entries of the NHK config.
+//!
+//! Accepts the config's romaji code (`tokyo`), its Japanese name (`東京`),
+//! kana readings (`とうきょう`, `トウキョウ`), legacy numeric codes (`130`),
+//! and unambiguous prefixes of any of those.
+
+use anyhow::Result;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::types::StreamData;
+
+use crate::error::ErrorKind;
+
+/// Kana readings of the broadcast areas, keyed by romaji code
+const READINGS: [(&str, &str); 8] = [
+    ("sapporo", "さっぽろ"),
+    ("sendai", "せんだい"),
+    ("tokyo", "とうきょう"),
+    ("nagoya", "なごや"),
+    ("osaka", "おおさか"),
+    ("hiroshima", "ひろしま"),
+    ("matsuyama", "まつやま"),
+    ("fukuoka", "ふくおか"),
+];
+
+/// Old numeric area codes, kept for backwards compatibility
+const LEGACY_CODES: [(&str, &str); 8] = [
+    ("010", "sapporo"),
+    ("040", "sendai"),
+    ("130", "tokyo"),
+    ("300", "nagoya"),
+    ("400", "osaka"),
+    ("540", "hiroshima"),
+    ("580", "matsuyama"),
+    ("810", "fukuoka"),
+];
+
+/// Find the area matching `query`
+///
+/// Exact matches win over prefix matches; a prefix that fits several areas
+/// is an error listing the candidates.
+pub fn resolve_area<'a>(query: &str, areas: &'a [StreamData]) -> Result<&'a StreamData> {
+    let query = normalize(query);
+    if query.is_empty() {
+        anyhow::bail!("Area must not be empty");
+    }
+
+    let code = LEGACY_CODES
+        .iter()
+        .find(|(legacy, _)| *legacy == query)
+        .map_or(query.as_str(), |(_, code)| *code);
+
+    let exact: Vec<&StreamData> = areas
+        .iter()
+        .filter(|d| names(d).iter().any(|name| *name == code))
+        .collect();
+    if let [area] = exact[..] {
+        return Ok(area);
+    }
+
+    let prefixed: Vec<&StreamData> = areas
+        .iter()
+        .filter(|d| names(d).iter().any(|name| name.starts_with(code)))
+        .collect();
+    match prefixed[..] {
+        [area] => Ok(area),
+        [] => Err(ErrorKind::AreaNotFound.error(format!(
+            "Area not found: {}. Available: {}",
+            query,
+            describe(areas.iter())
+        ))),
+        _ => Err(ErrorKind::AreaNotFound.error(format!(
+            "Ambiguous area '{}': could be {}",
+            query,
+            describe(prefixed.into_iter())
+        ))),
+    }
+}
+
+/// Whether `query` fuzzily matches one of the area's names, i.e. its
+/// characters appear in order (`tky` matches `tokyo`)
+///
+/// An empty query matches every area, for filtering a list as it is typed.
+pub fn area_matches(query: &str, area: &StreamData) -> bool {
+    let query = normalize(query);
+    names(area).iter().any(|name| {
+        let mut chars = name.chars();
+        query.chars().all(|q| chars.any(|c| c == q))
+    })
+}
+
+/// Every normalized name an area can be referred to by
+fn names(area: &StreamData) -> Vec<String> {
+    let mut names = vec![normalize(&area.area), normalize(&area.areajp)];
+    if let Some((_, reading)) = READINGS.iter().find(|(code, _)| *code == area.area) {
+        names.push(reading.to_string());
+    }
+    names
+}
+
+fn describe<'a>(areas: impl Iterator<Item = &'a StreamData>) -> String {
+    areas
+        .map(|d| format!("{} ({})", d.areajp, d.area))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Fold width, case, katakana, and romaji long-vowel spellings
+fn normalize(text: &str) -> String {
+    let folded: String = text
+        .trim()
+        .nfkc()
+        .flat_map(char::to_lowercase)
+        .map(katakana_to_hiragana)
+        .collect();
+    // "toukyou", "oosaka", and "ohsaka" are all common spellings
+    folded
+        .replace("ou", "o")
+        .replace("oo", "o")
+        .replace("oh", "o")
+        .replace("uu", "u")
+}
+
+fn katakana_to_hiragana(c: char) -> char {
+    match c {
+        'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::stream_data;
+
+    fn areas() -> Vec<StreamData> {
+        vec![
+            stream_data("sapporo", "札幌"),
+            stream_data("sendai", "仙台"),
+            stream_data("tokyo", "東京"),
+            stream_data("osaka", "大阪"),
+        ]
+    }
+
+    fn resolve(query: &str) -> Result<String> {
+        resolve_area(query, &areas()).map(|d| d.area.clone())
+    }
+
+    #[test]
+    fn test_fuzzy_matches() {
+        let matching = |query: &str| {
+            areas()
+                .into_iter()
+                .filter(|d| area_matches(query, d))
+                .map(|d| d.area)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matching("").len(), 4);
+        assert_eq!(matching("tky"), ["tokyo"]);
+        assert_eq!(matching("s"), ["sapporo", "sendai", "osaka"]);
+        assert_eq!(matching("さか"), ["osaka"]);
+        assert!(matching("xyz").is_empty());
+    }
+
+    #[test]
+    fn test_exact_names() {
+        assert_eq!(resolve("tokyo").unwrap(), "tokyo");
+        assert_eq!(resolve("Tokyo").unwrap(), "tokyo");
+        assert_eq!(resolve("大阪").unwrap(), "osaka");
+        assert_eq!(resolve("130").unwrap(), "tokyo");
+    }
+
+    #[test]
+    fn test_kana_and_romaji_variants() {
+        assert_eq!(resolve("とうきょう").unwrap(), "tokyo");
+        assert_eq!(resolve("オオサカ").unwrap(), "osaka");
+        assert_eq!(resolve("toukyou").unwrap(), "tokyo");
+        assert_eq!(resolve("ｏｓａｋａ").unwrap(), "osaka");
+    }
+
+    #[test]
+    fn test_prefixes() {
+        assert_eq!(resolve("tok").unwrap(), "tokyo");
+        assert_eq!(resolve("sap").unwrap(), "sapporo");
+
+        let err = resolve("s").unwrap_err().to_string();
+        assert!(err.contains("Ambiguous"));
+        assert!(err.contains("札幌 (sapporo)"));
+        assert!(err.contains("仙台 (sendai)"));
+
+        assert!(
+            resolve("kyoto")
+                .unwrap_err()
+                .to_string()
+                .contains("not found")
+        );
+    }
+}
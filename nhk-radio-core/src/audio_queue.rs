@@ -0,0 +1,208 @@
+//! Bounded PCM queue between the stream loop and the audio thread.
+//!
+//! The sender never blocks: once more than the buffer target is waiting, the
+//! oldest audio is dropped so memory stays flat and playback stays close to
+//! live. An empty batch still means "clear", and it overtakes anything queued.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{RecvTimeoutError, SendError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::player::{CHANNELS, SAMPLE_RATE};
+
+/// Default seconds of decoded audio buffered ahead of playback
+pub const DEFAULT_BUFFER_SECS: u32 = 15;
+
+/// Create a queue holding at most `buffer_secs` of audio
+pub fn audio_queue(buffer_secs: f64) -> (AudioSender, AudioReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State::default()),
+        ready: Condvar::new(),
+        buffer_secs,
+    });
+    (
+        AudioSender {
+            shared: shared.clone(),
+        },
+        AudioReceiver { shared },
+    )
+}
+
+struct Shared {
+    state: Mutex<State>,
+    ready: Condvar,
+    buffer_secs: f64,
+}
+
+#[derive(Default)]
+struct State {
+    chunks: VecDeque<Vec<i16>>,
+    samples: usize,
+    /// Samples discarded to stay within the buffer target, ever
+    dropped: u64,
+    clear_pending: bool,
+    sender_gone: bool,
+    receiver_gone: bool,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn capacity_samples(&self) -> usize {
+        (self.buffer_secs * SAMPLE_RATE as f64 * CHANNELS as f64) as usize
+    }
+}
+
+pub struct AudioSender {
+    shared: Arc<Shared>,
+}
+
+impl AudioSender {
+    /// Queue a batch of samples, or clear the queue and playback if empty
+    ///
+    /// Fails only once the audio thread has gone away.
+    pub fn send(&self, samples: Vec<i16>) -> Result<(), SendError<Vec<i16>>> {
+        let mut state = self.shared.lock();
+        if state.receiver_gone {
+            return Err(SendError(samples));
+        }
+
+        if samples.is_empty() {
+            state.chunks.clear();
+            state.samples = 0;
+            state.clear_pending = true;
+        } else {
+            state.samples += samples.len();
+            state.chunks.push_back(samples);
+            let capacity = self.shared.capacity_samples();
+            while state.samples > capacity && state.chunks.len() > 1 {
+                if let Some(old) = state.chunks.pop_front() {
+                    state.samples -= old.len();
+                    state.dropped += old.len() as u64;
+                    log::debug!("Audio queue full, dropped {} samples", old.len());
+                }
+            }
+        }
+        self.shared.ready.notify_one();
+        Ok(())
+    }
+
+    /// Samples dropped so far because the queue was over its target
+    pub fn dropped_samples(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+}
+
+impl Drop for AudioSender {
+    fn drop(&mut self) {
+        self.shared.lock().sender_gone = true;
+        self.shared.ready.notify_one();
+    }
+}
+
+pub struct AudioReceiver {
+    shared: Arc<Shared>,
+}
+
+impl AudioReceiver {
+    /// The buffer target the queue was created with
+    pub fn buffer_secs(&self) -> f64 {
+        self.shared.buffer_secs
+    }
+
+    /// Wait up to `timeout` for the next batch
+    ///
+    /// With `accept_audio` false only a pending clear is returned, so a
+    /// well-stocked player can leave audio waiting here without missing a
+    /// channel switch or seek.
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+        accept_audio: bool,
+    ) -> Result<Vec<i16>, RecvTimeoutError> {
+        let state = self.shared.lock();
+        let (mut state, _) = self
+            .shared
+            .ready
+            .wait_timeout_while(state, timeout, |s| {
+                let has_audio = !s.chunks.is_empty();
+                let ready =
+                    s.clear_pending || (accept_audio && has_audio) || (s.sender_gone && !has_audio);
+                !ready
+            })
+            .unwrap_or_else(|e| e.into_inner());
+
+        if state.clear_pending {
+            state.clear_pending = false;
+            return Ok(Vec::new());
+        }
+        if accept_audio && let Some(chunk) = state.chunks.pop_front() {
+            state.samples -= chunk.len();
+            return Ok(chunk);
+        }
+        // Audio left behind by a finished sender is still played out
+        if state.sender_gone && state.chunks.is_empty() {
+            Err(RecvTimeoutError::Disconnected)
+        } else {
+            Err(RecvTimeoutError::Timeout)
+        }
+    }
+}
+
+impl Drop for AudioReceiver {
+    fn drop(&mut self) {
+        self.shared.lock().receiver_gone = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_WAIT: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn test_drops_oldest_beyond_capacity() {
+        // One second of stereo 48 kHz audio
+        let (tx, rx) = audio_queue(1.0);
+        tx.send(vec![1; 60_000]).unwrap();
+        tx.send(vec![2; 60_000]).unwrap();
+        assert_eq!(rx.recv_timeout(NO_WAIT, true).unwrap()[0], 2);
+        assert_eq!(tx.dropped_samples(), 60_000);
+        assert_eq!(
+            rx.recv_timeout(NO_WAIT, true),
+            Err(RecvTimeoutError::Timeout)
+        );
+    }
+
+    #[test]
+    fn test_clear_overtakes_held_audio() {
+        let (tx, rx) = audio_queue(10.0);
+        tx.send(vec![1; 10]).unwrap();
+        assert_eq!(
+            rx.recv_timeout(NO_WAIT, false),
+            Err(RecvTimeoutError::Timeout)
+        );
+        tx.send(vec![]).unwrap();
+        tx.send(vec![3; 10]).unwrap();
+        assert!(rx.recv_timeout(NO_WAIT, false).unwrap().is_empty());
+        assert_eq!(rx.recv_timeout(NO_WAIT, true).unwrap()[0], 3);
+    }
+
+    #[test]
+    fn test_disconnect() {
+        let (tx, rx) = audio_queue(10.0);
+        drop(tx);
+        assert_eq!(
+            rx.recv_timeout(NO_WAIT, true),
+            Err(RecvTimeoutError::Disconnected)
+        );
+
+        let (tx, rx) = audio_queue(10.0);
+        drop(rx);
+        assert!(tx.send(vec![1]).is_err());
+    }
+}
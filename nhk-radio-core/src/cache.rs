@@ -0,0 +1,199 @@
+use anyhow::Result;
+use md5::{Digest, Md5};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::types::RadiruConfig;
+use crate::world;
+
+const CONFIG_CACHE_FILE: &str = "config_web.xml";
+const IMAGE_CACHE_DIR: &str = "images";
+const EPG_CACHE_DIR: &str = "epg";
+
+/// How long a downloaded image is kept before it is fetched again
+const IMAGE_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Snapshot of NHK's config_web.xml
+const BUILTIN_CONFIG: &str = include_str!("config_web.xml");
+
+/// Directory used for cached NHK data
+pub fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("nhk-radio-player"))
+}
+
+/// Load the last successfully fetched config, if any
+pub fn load_config() -> Option<RadiruConfig> {
+    let path = cache_dir()?.join(CONFIG_CACHE_FILE);
+    let text = fs::read_to_string(&path).ok()?;
+    match serde_xml_rs::de::from_str(&text) {
+        Ok(mut config) => {
+            world::add_stations(&mut config);
+            Some(config)
+        }
+        Err(e) => {
+            log::debug!("Ignoring unreadable config cache {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// The areas and stream URLs known when this crate was built, for when
+/// neither NHK nor the cache has a config to offer
+pub fn builtin_config() -> RadiruConfig {
+    let mut config: RadiruConfig =
+        serde_xml_rs::de::from_str(BUILTIN_CONFIG).expect("built-in config_web.xml is valid");
+    world::add_stations(&mut config);
+    config
+}
+
+/// Load the cached config if it was fetched less than `max_age` ago
+pub fn load_config_within(max_age: Duration) -> Option<RadiruConfig> {
+    let path = cache_dir()?.join(CONFIG_CACHE_FILE);
+    let age = fs::metadata(&path).ok()?.modified().ok()?.elapsed().ok()?;
+    if age >= max_age {
+        return None;
+    }
+    load_config()
+}
+
+/// Save raw config XML so the next startup can skip the network round-trip
+pub fn store_config(xml: &str) -> Result<()> {
+    let dir = cache_dir().ok_or_else(|| anyhow::anyhow!("No cache directory available"))?;
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(CONFIG_CACHE_FILE), xml)?;
+    Ok(())
+}
+
+/// Where what was downloaded from `url` is kept in the cache's `dir`
+///
+/// Files are named by the MD5 of the URL, which stays the same across
+/// builds, unlike `std`'s hasher.
+fn url_path(dir: &str, url: &str) -> Option<PathBuf> {
+    Some(cache_dir()?.join(dir).join(url_key(url)))
+}
+
+fn url_key(url: &str) -> String {
+    Md5::digest(url.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Delete the files in `dir` last written more than `max_age` ago
+fn prune(dir: &Path, max_age: Duration) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > max_age);
+        if expired && let Err(e) = fs::remove_file(entry.path()) {
+            log::debug!("Failed to remove {}: {}", entry.path().display(), e);
+        }
+    }
+}
+
+/// Where an image downloaded from `url` is kept
+fn image_path(url: &str) -> Option<PathBuf> {
+    url_path(IMAGE_CACHE_DIR, url)
+}
+
+/// Load a previously downloaded image, e.g. a program's eyecatch, unless it
+/// is due to be fetched again
+pub fn load_image(url: &str) -> Option<Vec<u8>> {
+    let path = image_path(url)?;
+    let age = fs::metadata(&path).ok()?.modified().ok()?.elapsed().ok()?;
+    if age > IMAGE_MAX_AGE {
+        return None;
+    }
+    fs::read(path).ok()
+}
+
+/// Save a downloaded image so it is not fetched again for a while, and
+/// delete images saved long ago
+pub fn store_image(url: &str, bytes: &[u8]) -> Result<()> {
+    let path = image_path(url).ok_or_else(|| anyhow::anyhow!("No cache directory available"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+        prune(dir, IMAGE_MAX_AGE);
+    }
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// Load program guide JSON from `url` if it was fetched less than
+/// `max_age` ago
+///
+/// The URL names the area, and for timetables the channel and day, so each
+/// listing is cached on its own; when it was fetched is the file's
+/// modification time.
+pub fn load_epg_within(url: &str, max_age: Duration) -> Option<String> {
+    let path = url_path(EPG_CACHE_DIR, url)?;
+    let age = fs::metadata(&path).ok()?.modified().ok()?.elapsed().ok()?;
+    if age >= max_age {
+        return None;
+    }
+    fs::read_to_string(path).ok()
+}
+
+/// Save program guide JSON from `url` for other commands to reuse, and
+/// delete listings saved more than `max_age` ago, which none will
+pub fn store_epg(url: &str, json: &str, max_age: Duration) -> Result<()> {
+    let path = url_path(EPG_CACHE_DIR, url)
+        .ok_or_else(|| anyhow::anyhow!("No cache directory available"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+        prune(dir, max_age);
+    }
+    fs::write(path, json)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::area::resolve_area;
+
+    #[test]
+    fn test_builtin_config() {
+        let config = builtin_config();
+        let tokyo = resolve_area("tokyo", &config.stream_url.data).unwrap();
+        assert_eq!(tokyo.areakey, "130");
+        assert!(tokyo.r1hls.starts_with("https://"));
+        assert!(config.url_program_day.contains("{date}"));
+        assert!(resolve_area("world-en", &config.stream_url.data).is_ok());
+
+        let (stream_data, program_url) = config.find_area("tokyo").unwrap();
+        assert_eq!(stream_data.areakey, "130");
+        assert!(program_url.starts_with("https://") && program_url.contains("130"));
+        assert!(config.find_area("atlantis").is_err());
+    }
+
+    #[test]
+    fn test_url_key_is_stable() {
+        assert_eq!(
+            url_key("https://example.com/eyecatch.jpg"),
+            "5d5a734eaccac6820065104d0775b320"
+        );
+    }
+
+    #[test]
+    fn test_prune() {
+        let dir = std::env::temp_dir().join(format!("nhk-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("new"), "").unwrap();
+        let old = fs::File::create(dir.join("old")).unwrap();
+        let hour_ago = std::time::SystemTime::now() - Duration::from_secs(60 * 60);
+        old.set_modified(hour_ago).unwrap();
+
+        prune(&dir, Duration::from_secs(60));
+        assert!(!dir.join("old").exists());
+        assert!(dir.join("new").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
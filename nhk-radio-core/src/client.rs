@@ -0,0 +1,538 @@
+use anyhow::Result;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, StatusCode};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::cache;
+use crate::dump::StreamDump;
+use crate::error::ErrorKind;
+use crate::throttle::{Throttle, jittered};
+use crate::types::{ByteRange, DaySchedule, OndemandCorners, OndemandSeries, RadiruConfig, Root};
+use crate::world;
+
+/// Where the station config is fetched from unless configured otherwise
+pub const DEFAULT_CONFIG_URL: &str = "https://www.nhk.or.jp/radio/config/config_web.xml";
+
+/// Base of the 聴き逃し API unless configured otherwise
+pub const DEFAULT_ONDEMAND_API_URL: &str = "https://www.nhk.or.jp/radio-api/app/v1/web/ondemand";
+
+/// User-Agent sent unless configured otherwise
+pub const DEFAULT_USER_AGENT: &str = concat!("nhk-radio-player/", env!("CARGO_PKG_VERSION"));
+
+/// How long a fetched station config is reused by default
+pub const DEFAULT_CONFIG_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a fetched program listing or timetable is reused by default
+pub const DEFAULT_EPG_TTL: Duration = Duration::from_secs(60);
+
+/// Requests a client has in flight at once by default
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// Timeouts, retries, and connection settings for HTTP requests
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    pub connect_timeout: Duration,
+    /// Longest wait for the next bytes of a response
+    pub read_timeout: Duration,
+    /// Extra attempts for playlist, key, and segment fetches that fail transiently
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each one after
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Part of each retry delay, from 0 to 1, that is randomly cut off so
+    /// failed clients do not retry in step
+    pub retry_jitter: f64,
+    /// Requests in flight at once, across everything using the client
+    pub max_concurrent_requests: usize,
+    /// Shortest time between the starts of two requests; zero for no limit
+    pub min_request_interval: Duration,
+    /// Proxy for every request; without it `HTTP_PROXY`/`HTTPS_PROXY` apply
+    pub proxy: Option<String>,
+    pub user_agent: String,
+    /// Extra headers sent with every request
+    pub headers: Vec<(String, String)>,
+    /// PEM certificate to trust in addition to the system roots
+    pub ca_cert: Option<PathBuf>,
+    /// How long a cached station config is used before fetching it again;
+    /// zero always fetches
+    pub config_ttl: Duration,
+    /// How long a cached program listing or timetable is used before
+    /// fetching it again; zero always fetches
+    pub epg_ttl: Duration,
+    /// Directory to save a copy of every playlist, key, and segment fetched
+    /// in, for debugging (see [`crate::dump`])
+    pub dump_dir: Option<PathBuf>,
+    /// Leave the contents of keys out of the dump
+    pub redact_keys: bool,
+    /// URL of the station config, whose stream and program URLs lead to
+    /// everything else; pointed elsewhere to test against a local server
+    pub config_url: String,
+    /// Base URL of the 聴き逃し API
+    pub ondemand_api_url: String,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(10),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+            retry_jitter: 0.5,
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            min_request_interval: Duration::ZERO,
+            proxy: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            headers: Vec::new(),
+            ca_cert: None,
+            config_ttl: DEFAULT_CONFIG_TTL,
+            epg_ttl: DEFAULT_EPG_TTL,
+            dump_dir: None,
+            redact_keys: false,
+            config_url: DEFAULT_CONFIG_URL.to_string(),
+            ondemand_api_url: DEFAULT_ONDEMAND_API_URL.to_string(),
+        }
+    }
+}
+
+impl HttpOptions {
+    /// Delay before retry number `attempt` (starting at 1)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// [`backoff`](Self::backoff) with [`retry_jitter`](Self::retry_jitter)
+    /// applied, for actually waiting
+    pub fn retry_delay(&self, attempt: u32) -> Duration {
+        jittered(self.backoff(attempt), self.retry_jitter)
+    }
+
+    /// A client builder with these timeouts, user agent, proxy, and CA
+    /// certificate, but not the extra headers, which are meant for NHK;
+    /// also for requests to other services, e.g. scrobbling
+    pub fn client_builder(&self) -> Result<reqwest::ClientBuilder> {
+        let mut builder = Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .read_timeout(self.read_timeout)
+            .user_agent(self.user_agent.as_str());
+        if let Some(ref proxy) = self.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| anyhow::anyhow!("Invalid proxy {}: {}", proxy, e))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(ref path) = self.ca_cert {
+            let pem = std::fs::read(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+        }
+        Ok(builder)
+    }
+}
+
+pub struct NhkRadioClient {
+    client: Client,
+    options: HttpOptions,
+    throttle: Throttle,
+    retries: AtomicU64,
+    dump: Option<StreamDump>,
+}
+
+impl NhkRadioClient {
+    pub fn new() -> Self {
+        Self::with_options(HttpOptions::default()).unwrap_or_else(|e| {
+            log::warn!("Failed to configure HTTP client, using defaults: {}", e);
+            let options = HttpOptions::default();
+            Self {
+                client: Client::new(),
+                throttle: Throttle::new(
+                    options.max_concurrent_requests,
+                    options.min_request_interval,
+                ),
+                options,
+                retries: AtomicU64::new(0),
+                dump: None,
+            }
+        })
+    }
+
+    /// Build a client from `options`, failing on an invalid proxy, header, or
+    /// certificate, or a dump directory that cannot be created
+    pub fn with_options(options: HttpOptions) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &options.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| anyhow::anyhow!("Invalid header name {:?}: {}", name, e))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| anyhow::anyhow!("Invalid value for header {}: {}", name, e))?;
+            headers.insert(name, value);
+        }
+
+        let builder = options.client_builder()?.default_headers(headers);
+        let dump = options
+            .dump_dir
+            .as_deref()
+            .map(|dir| StreamDump::new(dir, options.redact_keys))
+            .transpose()?;
+        Ok(Self {
+            client: builder.build()?,
+            throttle: Throttle::new(
+                options.max_concurrent_requests,
+                options.min_request_interval,
+            ),
+            options,
+            retries: AtomicU64::new(0),
+            dump,
+        })
+    }
+
+    /// Number of requests retried since the client was created
+    pub fn retry_count(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    /// GET `url` (or `range` of it), retrying transient failures with backoff
+    async fn get_with_retry(
+        &self,
+        url: &str,
+        range: Option<ByteRange>,
+    ) -> Result<(StatusCode, Vec<u8>)> {
+        let mut attempt = 0;
+        loop {
+            let mut request = self.client.get(url);
+            if let Some(range) = range {
+                request = request.header(reqwest::header::RANGE, range.header_value());
+            }
+            let permit = self.throttle.acquire().await;
+            let result = match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(response) => {
+                    let status = response.status();
+                    response.bytes().await.map(|body| (status, body.to_vec()))
+                }
+                Err(e) => Err(e),
+            };
+            drop(permit);
+
+            match result {
+                Ok(response) => return Ok(response),
+                Err(e) if attempt < self.options.max_retries && is_transient(&e) => {
+                    attempt += 1;
+                    let total = self.retries.fetch_add(1, Ordering::Relaxed) + 1;
+                    let delay = self.options.retry_delay(attempt);
+                    log::warn!(
+                        "Request to {} failed: {}. Retry {}/{} in {:?} ({} retries so far)",
+                        url,
+                        e,
+                        attempt,
+                        self.options.max_retries,
+                        delay,
+                        total
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Body of the response to `request`, sent once and failing on an error
+    /// status
+    async fn get_text(&self, request: reqwest::RequestBuilder) -> Result<String> {
+        let _permit = self.throttle.acquire().await;
+        Ok(request.send().await?.error_for_status()?.text().await?)
+    }
+
+    /// JSON body of the response to `request`, sent once and failing on an
+    /// error status
+    async fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T> {
+        let _permit = self.throttle.acquire().await;
+        Ok(request.send().await?.error_for_status()?.json().await?)
+    }
+
+    /// NHK Radio configuration, from the cache while it is younger than
+    /// [`HttpOptions::config_ttl`]
+    ///
+    /// When fetching or parsing fails, an older cached config is used, or
+    /// failing that the one built in, so playback keeps working. Neither
+    /// stands in for a config from [`HttpOptions::config_url`] other than
+    /// NHK's, which is always fetched.
+    pub async fn fetch_config(&self) -> Result<RadiruConfig> {
+        if self.options.config_url != DEFAULT_CONFIG_URL {
+            return self.refresh_config().await;
+        }
+        if let Some(config) = cache::load_config_within(self.options.config_ttl) {
+            return Ok(config);
+        }
+        match self.refresh_config().await {
+            Ok(config) => Ok(config),
+            Err(e) => Ok(match cache::load_config() {
+                Some(config) => {
+                    log::warn!("Failed to fetch config, using the cached one: {}", e);
+                    config
+                }
+                None => {
+                    log::warn!("Failed to fetch config, using the built-in one: {}", e);
+                    cache::builtin_config()
+                }
+            }),
+        }
+    }
+
+    /// Fetch NHK Radio configuration, bypassing the cache
+    pub async fn refresh_config(&self) -> Result<RadiruConfig> {
+        let text = self
+            .get_text(self.client.get(&self.options.config_url))
+            .await?;
+        let mut config: RadiruConfig = serde_xml_rs::de::from_str(&text)?;
+        if self.options.config_url == DEFAULT_CONFIG_URL
+            && let Err(e) = cache::store_config(&text)
+        {
+            log::debug!("Failed to cache config: {}", e);
+        }
+        world::add_stations(&mut config);
+        Ok(config)
+    }
+
+    /// Fetch program information
+    ///
+    /// Radio Japan areas have none, so they get an empty listing. A listing
+    /// fetched less than [`HttpOptions::epg_ttl`] ago, by this or another
+    /// command, is reused as long as every program on air in it still is.
+    pub async fn fetch_program(&self, program_url: &str) -> Result<Root> {
+        if world::is_program_url(program_url) {
+            return Ok(Root::default());
+        }
+        if let Some(program) = self.cached_epg::<Root>(program_url)
+            && program.is_current(chrono::Utc::now().fixed_offset())
+        {
+            return Ok(program);
+        }
+        self.fetch_epg(program_url).await
+    }
+
+    /// Fetch one channel's schedule for a day, reusing one fetched less than
+    /// [`HttpOptions::epg_ttl`] ago
+    pub async fn fetch_program_day(&self, url: &str) -> Result<DaySchedule> {
+        if world::is_program_url(url) {
+            return Ok(DaySchedule::default());
+        }
+        if let Some(schedule) = self.cached_epg(url) {
+            return Ok(schedule);
+        }
+        self.fetch_epg(url).await
+    }
+
+    /// Whether program guide data is cached; only NHK's is, so a test
+    /// server's never mixes with it
+    fn caches_epg(&self) -> bool {
+        self.options.config_url == DEFAULT_CONFIG_URL && !self.options.epg_ttl.is_zero()
+    }
+
+    /// Program guide data from `url` cached by any command lately, if it
+    /// still parses
+    fn cached_epg<T: serde::de::DeserializeOwned>(&self, url: &str) -> Option<T> {
+        if !self.caches_epg() {
+            return None;
+        }
+        let text = cache::load_epg_within(url, self.options.epg_ttl)?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Fetch program guide data from `url`, caching it for other commands
+    async fn fetch_epg<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let text = self.get_text(self.client.get(url)).await?;
+        let parsed = serde_json::from_str(&text).map_err(|e| {
+            // The first 500 characters; a byte index could split a
+            // multibyte one in the Japanese text
+            let end = text.char_indices().nth(500).map_or(text.len(), |(i, _)| i);
+            ErrorKind::Parse.error(format!(
+                "Failed to parse JSON: {}. Response: {}",
+                e,
+                &text[..end]
+            ))
+        })?;
+        if self.caches_epg()
+            && let Err(e) = cache::store_epg(url, &text, self.options.epg_ttl)
+        {
+            log::debug!("Failed to cache program guide data: {}", e);
+        }
+        Ok(parsed)
+    }
+
+    /// Fetch recently added 聴き逃し corners
+    pub async fn fetch_ondemand_corners(&self) -> Result<OndemandCorners> {
+        let url = format!("{}/corners/new_arrivals", self.options.ondemand_api_url);
+        self.get_json(self.client.get(&url)).await
+    }
+
+    /// Fetch a 聴き逃し series and its episodes
+    pub async fn fetch_ondemand_series(
+        &self,
+        series_site_id: &str,
+        corner_site_id: &str,
+    ) -> Result<OndemandSeries> {
+        let url = format!("{}/series", self.options.ondemand_api_url);
+        let request = self.client.get(&url).query(&[
+            ("site_id", series_site_id),
+            ("corner_site_id", corner_site_id),
+        ]);
+        self.get_json(request).await
+    }
+
+    /// Fetch an image such as a program's eyecatch, from the cache when it
+    /// was downloaded before
+    pub async fn fetch_image(&self, url: &str) -> Result<Vec<u8>> {
+        if let Some(bytes) = cache::load_image(url) {
+            return Ok(bytes);
+        }
+        let (_, bytes) = self.get_with_retry(url, None).await?;
+        if let Err(e) = cache::store_image(url, &bytes) {
+            log::debug!("Failed to cache image {}: {}", url, e);
+        }
+        Ok(bytes)
+    }
+
+    /// Fetch M3U8 playlist content
+    pub async fn fetch_m3u8(&self, url: &str) -> Result<String> {
+        let (_, body) = self.get_with_retry(url, None).await?;
+        let content = String::from_utf8_lossy(&body).into_owned();
+        if let Some(ref dump) = self.dump {
+            dump.playlist(url, &content);
+        }
+        Ok(content)
+    }
+
+    /// Fetch decryption key
+    pub async fn fetch_key(&self, key_url: &str) -> Result<Vec<u8>> {
+        let (_, bytes) = self.get_with_retry(key_url, None).await?;
+        if let Some(ref dump) = self.dump {
+            dump.key(key_url, &bytes);
+        }
+        if bytes.len() != 16 {
+            anyhow::bail!("Invalid key length: expected 16, got {}", bytes.len());
+        }
+        Ok(bytes)
+    }
+
+    /// Fetch segment data, or just `range` of it with a Range request
+    pub async fn fetch_segment(&self, url: &str, range: Option<ByteRange>) -> Result<Vec<u8>> {
+        let (status, bytes) = self.get_with_retry(url, range).await?;
+        let partial = status == StatusCode::PARTIAL_CONTENT;
+
+        let data = match range {
+            // The server ignored the Range header and sent the whole file
+            Some(range) if !partial => {
+                let start = range.offset as usize;
+                let end = start + range.length as usize;
+                bytes.get(start..end).map(<[u8]>::to_vec).ok_or_else(|| {
+                    anyhow::anyhow!("Byte range {}-{} is past the end of {}", start, end, url)
+                })?
+            }
+            _ => bytes,
+        };
+        if let Some(ref dump) = self.dump {
+            dump.segment(url, &data);
+        }
+        Ok(data)
+    }
+
+    /// Save media segment `seq_no` from `url` once decrypted, when dumping
+    pub fn dump_decrypted(&self, url: &str, seq_no: u64, data: &[u8]) {
+        if let Some(ref dump) = self.dump {
+            dump.decrypted(url, seq_no, data);
+        }
+    }
+}
+
+/// The requests the HLS pipeline makes
+///
+/// The stream and on-demand loops are generic over this so they can run
+/// against canned playlists and segments, e.g. [`MockHttp`](crate::mock::MockHttp).
+pub trait RadioHttp: Send + Sync {
+    /// Fetch M3U8 playlist content
+    fn fetch_m3u8(&self, url: &str) -> impl Future<Output = Result<String>> + Send;
+
+    /// Fetch a 16-byte AES key
+    fn fetch_key(&self, key_url: &str) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    /// Fetch segment data, or just `range` of it
+    fn fetch_segment(
+        &self,
+        url: &str,
+        range: Option<ByteRange>,
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    /// Keep a copy of media segment `seq_no` from `url` once decrypted, for
+    /// clients that dump what they fetch
+    fn dump_decrypted(&self, _url: &str, _seq_no: u64, _data: &[u8]) {}
+}
+
+impl RadioHttp for NhkRadioClient {
+    async fn fetch_m3u8(&self, url: &str) -> Result<String> {
+        NhkRadioClient::fetch_m3u8(self, url).await
+    }
+
+    async fn fetch_key(&self, key_url: &str) -> Result<Vec<u8>> {
+        NhkRadioClient::fetch_key(self, key_url).await
+    }
+
+    async fn fetch_segment(&self, url: &str, range: Option<ByteRange>) -> Result<Vec<u8>> {
+        NhkRadioClient::fetch_segment(self, url, range).await
+    }
+
+    fn dump_decrypted(&self, url: &str, seq_no: u64, data: &[u8]) {
+        NhkRadioClient::dump_decrypted(self, url, seq_no, data);
+    }
+}
+
+/// Whether a failed request is worth repeating
+fn is_transient(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        None => e.is_timeout() || e.is_connect() || e.is_request() || e.is_body(),
+    }
+}
+
+impl Default for NhkRadioClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let options = HttpOptions::default();
+        assert_eq!(options.backoff(1), Duration::from_millis(500));
+        assert_eq!(options.backoff(2), Duration::from_secs(1));
+        assert_eq!(options.backoff(4), Duration::from_secs(4));
+        assert_eq!(options.backoff(10), Duration::from_secs(8));
+        assert_eq!(options.backoff(100), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_invalid_options_are_errors() {
+        let options = HttpOptions {
+            headers: vec![("Bad Header".to_string(), "x".to_string())],
+            ..Default::default()
+        };
+        assert!(NhkRadioClient::with_options(options).is_err());
+
+        let options = HttpOptions {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(NhkRadioClient::with_options(options).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use aes::Aes128;
+use aes::cipher::{
+    BlockDecryptMut, KeyIvInit,
+    block_padding::{NoPadding, Pkcs7},
+};
+use anyhow::Result;
+use cbc::Decryptor;
+
+use crate::decoder::id3_len;
+use crate::error::ErrorKind;
+
+type Aes128CbcDec = Decryptor<Aes128>;
+
+/// Encryption applied to a segment, from the EXT-X-KEY METHOD
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EncryptionMethod {
+    /// The whole segment is AES-128-CBC encrypted with PKCS#7 padding
+    #[default]
+    Aes128,
+    /// Only parts of each audio frame are encrypted
+    SampleAes,
+}
+
+/// Decrypt segment data encrypted with `method`
+pub fn decrypt_segment(
+    data: &[u8],
+    key: &[u8],
+    iv_hex: Option<&str>,
+    seq_no: u64,
+    method: EncryptionMethod,
+) -> Result<Vec<u8>> {
+    if key.len() != 16 {
+        return Err(ErrorKind::Decrypt.error(format!(
+            "Invalid key length: expected 16, got {}",
+            key.len()
+        )));
+    }
+
+    let iv = segment_iv(iv_hex, seq_no)?;
+    match method {
+        EncryptionMethod::Aes128 => decrypt_aes128(data, key, &iv),
+        EncryptionMethod::SampleAes => decrypt_sample_aes(data, key, &iv),
+    }
+}
+
+/// The IV from the playlist, or the media sequence number when it has none
+fn segment_iv(iv_hex: Option<&str>, seq_no: u64) -> Result<Vec<u8>> {
+    let iv = if let Some(iv_str) = iv_hex {
+        let iv_str = iv_str.trim_start_matches("0x").trim_start_matches("0X");
+        hex::decode(iv_str)?
+    } else {
+        let mut iv_bytes = vec![0u8; 16];
+        let seq_bytes = seq_no.to_be_bytes();
+        iv_bytes[8..16].copy_from_slice(&seq_bytes);
+        iv_bytes
+    };
+
+    if iv.len() != 16 {
+        return Err(
+            ErrorKind::Decrypt.error(format!("Invalid IV length: expected 16, got {}", iv.len()))
+        );
+    }
+    Ok(iv)
+}
+
+fn decrypt_aes128(data: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+    let cipher = Aes128CbcDec::new_from_slices(key, iv)
+        .map_err(|e| ErrorKind::Decrypt.error(format!("Failed to create cipher: {:?}", e)))?;
+
+    let mut buffer = data.to_vec();
+    let decrypted = cipher
+        .decrypt_padded_mut::<Pkcs7>(&mut buffer)
+        .map_err(|e| ErrorKind::Decrypt.error(format!("Decryption failed: {:?}", e)))?;
+
+    Ok(decrypted.to_vec())
+}
+
+/// Decrypt SAMPLE-AES packed audio: ADTS frames, optionally behind an ID3 tag
+///
+/// Each frame keeps its header and the first 16 bytes of payload in the
+/// clear. The whole 16-byte blocks after that are CBC encrypted, restarting
+/// from the IV for every frame, and a shorter tail is left in the clear.
+fn decrypt_sample_aes(data: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+    if data.first() == Some(&0x47) {
+        return Err(
+            ErrorKind::Decrypt.error("SAMPLE-AES is only supported for packed audio, not MPEG-TS")
+        );
+    }
+
+    let mut out = data.to_vec();
+    let mut pos = id3_len(&out);
+    while pos + 7 <= out.len() {
+        if out[pos] != 0xFF || out[pos + 1] & 0xF0 != 0xF0 {
+            return Err(ErrorKind::Decrypt.error(format!("Lost ADTS sync at byte {}", pos)));
+        }
+        // protection_absent = 0 means a CRC follows the header
+        let header_len = if out[pos + 1] & 0x01 == 0 { 9 } else { 7 };
+        let frame_len = ((out[pos + 3] as usize & 0x03) << 11)
+            | ((out[pos + 4] as usize) << 3)
+            | (out[pos + 5] as usize >> 5);
+        if frame_len < header_len || pos + frame_len > out.len() {
+            return Err(ErrorKind::Decrypt.error(format!("Truncated ADTS frame at byte {}", pos)));
+        }
+
+        let payload = &mut out[pos + header_len..pos + frame_len];
+        if payload.len() > 16 {
+            let encrypted = &mut payload[16..];
+            let blocks = encrypted.len() / 16 * 16;
+            Aes128CbcDec::new_from_slices(key, iv)
+                .map_err(|e| ErrorKind::Decrypt.error(format!("Failed to create cipher: {:?}", e)))?
+                .decrypt_padded_mut::<NoPadding>(&mut encrypted[..blocks])
+                .map_err(|e| ErrorKind::Decrypt.error(format!("Decryption failed: {:?}", e)))?;
+        }
+        pos += frame_len;
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decrypt_segment() {
+        let key = vec![0u8; 16];
+        let data = vec![0u8; 32];
+        let _result = decrypt_segment(&data, &key, None, 0, EncryptionMethod::Aes128);
+    }
+
+    #[test]
+    fn test_decrypt_sample_aes_frame() {
+        use aes::cipher::BlockEncryptMut;
+
+        let key = [7u8; 16];
+        let iv = [9u8; 16];
+        let payload: Vec<u8> = (0..40).collect();
+        let frame_len = 7 + payload.len();
+        let header = [
+            0xFF,
+            0xF1,
+            0x50,
+            0x80 | (frame_len >> 11) as u8,
+            (frame_len >> 3) as u8,
+            ((frame_len & 0x07) << 5) as u8 | 0x1F,
+            0xFC,
+        ];
+
+        let mut encrypted = payload.clone();
+        cbc::Encryptor::<Aes128>::new_from_slices(&key, &iv)
+            .unwrap()
+            .encrypt_padded_mut::<NoPadding>(&mut encrypted[16..32], 16)
+            .unwrap();
+        let mut segment = b"ID3\x04\x00\x00\x00\x00\x00\x02ab".to_vec();
+        segment.extend_from_slice(&header);
+        segment.extend_from_slice(&encrypted);
+
+        let decrypted = decrypt_segment(
+            &segment,
+            &key,
+            Some("0x09090909090909090909090909090909"),
+            0,
+            EncryptionMethod::SampleAes,
+        )
+        .unwrap();
+        assert_eq!(&decrypted[19..], &payload[..]);
+    }
+}
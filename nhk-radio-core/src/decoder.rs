@@ -0,0 +1,403 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::io::{Cursor, Read};
+use std::sync::{Arc, Mutex};
+use symphonia::core::audio::{AudioBufferRef, Signal};
+use symphonia::core::codecs::{CODEC_TYPE_AAC, Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::{MediaSourceStream, ReadOnlySource};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::player::{CHANNELS, SAMPLE_RATE};
+use crate::resample;
+
+/// Sample rate and channel count of decoded PCM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcmSpec {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl PcmSpec {
+    /// The format the player, timeshift buffer, and recorder work in
+    pub const PLAYBACK: PcmSpec = PcmSpec {
+        sample_rate: SAMPLE_RATE,
+        channels: CHANNELS,
+    };
+}
+
+impl Default for PcmSpec {
+    fn default() -> Self {
+        Self::PLAYBACK
+    }
+}
+
+/// A batch of decoded PCM samples (interleaved i16)
+#[derive(Debug, Clone, Default)]
+pub struct PcmChunk {
+    pub samples: Vec<i16>,
+    /// Format the samples were decoded in
+    pub spec: PcmSpec,
+}
+
+impl PcmChunk {
+    /// The samples converted to [`PcmSpec::PLAYBACK`]
+    pub fn to_playback(&self) -> Vec<i16> {
+        resample::convert(&self.samples, self.spec, PcmSpec::PLAYBACK)
+    }
+}
+
+/// Decodes consecutive AAC segments with one long-lived demuxer and decoder
+///
+/// Segments are chained into a single byte stream, so the stream is probed
+/// once and the codec keeps its state across segment boundaries. Each call
+/// to [`StreamDecoder::decode`] returns the PCM for the packets that became
+/// complete.
+#[derive(Default)]
+pub struct StreamDecoder {
+    queue: SegmentQueue,
+    state: Option<DecoderState>,
+}
+
+struct DecoderState {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+}
+
+impl StreamDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a segment to the stream and decode what it completes
+    pub fn decode(&mut self, segment: &[u8]) -> Result<PcmChunk> {
+        // Packed audio repeats an ID3 timestamp tag at the start of every segment
+        self.queue.push(&segment[id3_len(segment)..]);
+
+        if self.state.is_none() {
+            let source = ReadOnlySource::new(self.queue.clone());
+            self.state = open(
+                MediaSourceStream::new(Box::new(source), Default::default()),
+                "aac",
+            );
+            if self.state.is_none() {
+                self.queue.clear();
+                return Ok(PcmChunk::default());
+            }
+        }
+
+        let mut chunk = PcmChunk::default();
+        if let Some(state) = &mut self.state
+            && let Err(e) = drain(state, &mut chunk)
+        {
+            log::debug!("Restarting decoder after error: {}", e);
+            self.reset();
+        }
+        Ok(chunk)
+    }
+
+    /// Forget buffered data and decoder state, e.g. after a seek or channel change
+    pub fn reset(&mut self) {
+        self.state = None;
+        self.queue.clear();
+    }
+}
+
+/// Bytes of the segments pushed so far, read by symphonia as one stream
+///
+/// Reading past the end returns 0 rather than blocking; the demuxer sees
+/// that as end of stream and picks up again once more is pushed.
+#[derive(Clone, Default)]
+struct SegmentQueue(Arc<Mutex<VecDeque<u8>>>);
+
+impl SegmentQueue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<u8>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, data: &[u8]) {
+        self.lock().extend(data);
+    }
+
+    fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+impl Read for SegmentQueue {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.lock().read(buf)
+    }
+}
+
+/// Decode AAC audio data to PCM samples
+pub fn decode_aac_to_pcm(aac_data: &[u8]) -> Result<PcmChunk> {
+    StreamDecoder::new().decode(aac_data)
+}
+
+/// Decode an fMP4 media segment to PCM samples
+///
+/// Fragments carry no codec setup of their own, so the initialization
+/// section from EXT-X-MAP is demuxed in front of them.
+pub fn decode_fmp4_to_pcm(init: &[u8], fragment: &[u8]) -> Result<PcmChunk> {
+    let mut data = Vec::with_capacity(init.len() + fragment.len());
+    data.extend_from_slice(init);
+    data.extend_from_slice(fragment);
+
+    let mss = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+    let mut chunk = PcmChunk::default();
+    if let Some(mut state) = open(mss, "mp4")
+        && let Err(e) = drain(&mut state, &mut chunk)
+    {
+        log::debug!("Error reading packet: {}", e);
+    }
+    Ok(chunk)
+}
+
+/// Probe `mss` and set up a decoder for its AAC track
+fn open(mss: MediaSourceStream, extension: &str) -> Option<DecoderState> {
+    let mut hint = Hint::new();
+    hint.with_extension(extension);
+
+    let format_opts = FormatOptions::default();
+    let metadata_opts = MetadataOptions::default();
+
+    let probed =
+        match symphonia::default::get_probe().format(&hint, mss, &format_opts, &metadata_opts) {
+            Ok(p) => p,
+            Err(e) => {
+                log::debug!("Failed to probe {} data: {}", extension, e);
+                return None;
+            }
+        };
+
+    let format = probed.format;
+
+    let track = match format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec == CODEC_TYPE_AAC)
+    {
+        Some(t) => t,
+        None => {
+            log::debug!("No AAC track found");
+            return None;
+        }
+    };
+
+    let track_id = track.id;
+
+    let dec_opts = DecoderOptions::default();
+    let decoder = match symphonia::default::get_codecs().make(&track.codec_params, &dec_opts) {
+        Ok(d) => d,
+        Err(e) => {
+            log::debug!("Failed to create decoder: {}", e);
+            return None;
+        }
+    };
+
+    Some(DecoderState {
+        format,
+        decoder,
+        track_id,
+    })
+}
+
+/// Decode every packet available, stopping cleanly at the end of the data
+fn drain(state: &mut DecoderState, chunk: &mut PcmChunk) -> Result<(), SymphoniaError> {
+    loop {
+        let packet = match state.format.next_packet() {
+            Ok(p) => p,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        if packet.track_id() != state.track_id {
+            continue;
+        }
+
+        let decoded = match state.decoder.decode(&packet) {
+            Ok(d) => d,
+            Err(e) => {
+                log::debug!("Decode error: {}", e);
+                continue;
+            }
+        };
+
+        let spec = decoded.spec();
+        chunk.spec = PcmSpec {
+            sample_rate: spec.rate,
+            channels: spec.channels.count() as u16,
+        };
+        convert_audio_buffer_to_pcm(&decoded, &mut chunk.samples);
+    }
+}
+
+/// Length of the ID3 tag a packed audio segment starts with, if any
+pub(crate) fn id3_len(data: &[u8]) -> usize {
+    if data.len() < 10 || &data[..3] != b"ID3" {
+        return 0;
+    }
+    // The size is syncsafe: 7 bits per byte
+    let size = data[6..10]
+        .iter()
+        .fold(0usize, |size, b| (size << 7) | (*b as usize & 0x7F));
+    let footer = if data[5] & 0x10 != 0 { 10 } else { 0 };
+    (10 + size + footer).min(data.len())
+}
+
+fn convert_audio_buffer_to_pcm(audio_buf: &AudioBufferRef, pcm_samples: &mut Vec<i16>) {
+    match audio_buf {
+        AudioBufferRef::F32(buf) => {
+            let channels = buf.spec().channels.count();
+            let frames = buf.frames();
+            for frame_idx in 0..frames {
+                for ch in 0..channels {
+                    let sample = buf.chan(ch)[frame_idx];
+                    let sample_i16 = (sample.clamp(-1.0, 1.0) * 32767.0) as i16;
+                    pcm_samples.push(sample_i16);
+                }
+            }
+        }
+        AudioBufferRef::F64(buf) => {
+            let channels = buf.spec().channels.count();
+            let frames = buf.frames();
+            for frame_idx in 0..frames {
+                for ch in 0..channels {
+                    let sample = buf.chan(ch)[frame_idx];
+                    let sample_i16 = (sample.clamp(-1.0, 1.0) * 32767.0) as i16;
+                    pcm_samples.push(sample_i16);
+                }
+            }
+        }
+        AudioBufferRef::S16(buf) => {
+            let channels = buf.spec().channels.count();
+            let frames = buf.frames();
+            for frame_idx in 0..frames {
+                for ch in 0..channels {
+                    pcm_samples.push(buf.chan(ch)[frame_idx]);
+                }
+            }
+        }
+        AudioBufferRef::S32(buf) => {
+            let channels = buf.spec().channels.count();
+            let frames = buf.frames();
+            for frame_idx in 0..frames {
+                for ch in 0..channels {
+                    let sample = buf.chan(ch)[frame_idx];
+                    let sample_i16 = (sample >> 16) as i16;
+                    pcm_samples.push(sample_i16);
+                }
+            }
+        }
+        AudioBufferRef::U8(buf) => {
+            let channels = buf.spec().channels.count();
+            let frames = buf.frames();
+            for frame_idx in 0..frames {
+                for ch in 0..channels {
+                    let sample = buf.chan(ch)[frame_idx];
+                    let sample_i16 = ((sample as i32 - 128) * 256) as i16;
+                    pcm_samples.push(sample_i16);
+                }
+            }
+        }
+        AudioBufferRef::U16(buf) => {
+            let channels = buf.spec().channels.count();
+            let frames = buf.frames();
+            for frame_idx in 0..frames {
+                for ch in 0..channels {
+                    let sample = buf.chan(ch)[frame_idx];
+                    let sample_i16 = (sample as i32 - 32768) as i16;
+                    pcm_samples.push(sample_i16);
+                }
+            }
+        }
+        AudioBufferRef::U32(buf) => {
+            let channels = buf.spec().channels.count();
+            let frames = buf.frames();
+            for frame_idx in 0..frames {
+                for ch in 0..channels {
+                    let sample = buf.chan(ch)[frame_idx];
+                    let sample_i16 = ((sample >> 16) as i32 - 32768) as i16;
+                    pcm_samples.push(sample_i16);
+                }
+            }
+        }
+        AudioBufferRef::S8(buf) => {
+            let channels = buf.spec().channels.count();
+            let frames = buf.frames();
+            for frame_idx in 0..frames {
+                for ch in 0..channels {
+                    let sample = buf.chan(ch)[frame_idx];
+                    let sample_i16 = (sample as i16) * 256;
+                    pcm_samples.push(sample_i16);
+                }
+            }
+        }
+        AudioBufferRef::S24(buf) => {
+            let channels = buf.spec().channels.count();
+            let frames = buf.frames();
+            for frame_idx in 0..frames {
+                for ch in 0..channels {
+                    let sample = buf.chan(ch)[frame_idx];
+                    let sample_i32: i32 = sample.inner();
+                    let sample_i16 = (sample_i32 >> 8) as i16;
+                    pcm_samples.push(sample_i16);
+                }
+            }
+        }
+        AudioBufferRef::U24(buf) => {
+            let channels = buf.spec().channels.count();
+            let frames = buf.frames();
+            for frame_idx in 0..frames {
+                for ch in 0..channels {
+                    let sample = buf.chan(ch)[frame_idx];
+                    let sample_u32: u32 = sample.inner();
+                    let sample_i16 = ((sample_u32 >> 8) as i32 - 32768) as i16;
+                    pcm_samples.push(sample_i16);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_empty() {
+        let result = decode_aac_to_pcm(&[]);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_segment_queue_chains_pushes() {
+        let mut queue = SegmentQueue::default();
+        let mut buf = [0u8; 8];
+        queue.push(b"abc");
+        queue.push(b"de");
+        assert_eq!(queue.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"abcde");
+        assert_eq!(queue.read(&mut buf).unwrap(), 0);
+        queue.push(b"f");
+        assert_eq!(queue.read(&mut buf).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_id3_len() {
+        assert_eq!(id3_len(b"ID3\x04\x00\x00\x00\x00\x00\x02abcd"), 12);
+        assert_eq!(id3_len(b"\xff\xf1"), 0);
+    }
+
+    #[test]
+    fn test_decode_empty_fmp4() {
+        let result = decode_fmp4_to_pcm(&[], &[]);
+        assert!(result.unwrap().samples.is_empty());
+    }
+}
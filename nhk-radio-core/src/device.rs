@@ -0,0 +1,116 @@
+//! Audio output device discovery.
+//!
+//! Devices are picked by name, as reported by the platform's default host.
+//! A name may also be an unambiguous, case-insensitive part of one, so
+//! `--device usb` finds "USB Audio DAC".
+
+use anyhow::Result;
+use rodio::cpal::traits::HostTrait;
+use rodio::{DeviceTrait, OutputStream, OutputStreamHandle};
+
+use crate::error::ErrorKind;
+
+/// Names of the available output devices
+pub fn output_devices() -> Result<Vec<String>> {
+    let host = rodio::cpal::default_host();
+    Ok(host
+        .output_devices()?
+        .filter_map(|device| device.name().ok())
+        .collect())
+}
+
+/// Name of the device used when none is chosen
+pub fn default_device() -> Option<String> {
+    rodio::cpal::default_host()
+        .default_output_device()
+        .and_then(|device| device.name().ok())
+}
+
+/// The device after `current` in `devices`, wrapping around through the
+/// default (`None`)
+pub fn next_device(devices: &[String], current: Option<&str>) -> Option<String> {
+    let next = match current {
+        None => 0,
+        Some(current) => match devices.iter().position(|name| name == current) {
+            Some(index) => index + 1,
+            None => 0,
+        },
+    };
+    devices.get(next).cloned()
+}
+
+/// Open the output named by `query`, or the default one
+pub fn open_output(query: Option<&str>) -> Result<(OutputStream, OutputStreamHandle)> {
+    let Some(query) = query else {
+        return Ok(OutputStream::try_default()?);
+    };
+
+    let devices: Vec<_> = rodio::cpal::default_host().output_devices()?.collect();
+    let names: Vec<String> = devices
+        .iter()
+        .map(|device| device.name().unwrap_or_default())
+        .collect();
+    let index = find_device(&names, query)?;
+    Ok(OutputStream::try_from_device(&devices[index])?)
+}
+
+/// Index of the device `query` names: an exact match, or else the only one
+/// containing it
+///
+/// Also used for other kinds of named output, such as cast targets.
+pub fn find_device(names: &[String], query: &str) -> Result<usize> {
+    if let Some(index) = names.iter().position(|name| name == query) {
+        return Ok(index);
+    }
+
+    let needle = query.to_lowercase();
+    let matches: Vec<usize> = names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| name.to_lowercase().contains(&needle))
+        .map(|(index, _)| index)
+        .collect();
+    match matches.as_slice() {
+        [index] => Ok(*index),
+        [] => Err(ErrorKind::AudioDevice.error(format!("Output device not found: {}", query))),
+        _ => Err(ErrorKind::AudioDevice.error(format!(
+            "Output device name is ambiguous: {} (matches {})",
+            query,
+            matches
+                .iter()
+                .map(|&i| names[i].as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_device() {
+        let names = vec![
+            "Built-in Output".to_string(),
+            "USB Audio DAC".to_string(),
+            "USB Audio DAC (2)".to_string(),
+            "HDMI".to_string(),
+        ];
+        assert_eq!(find_device(&names, "USB Audio DAC").unwrap(), 1);
+        assert_eq!(find_device(&names, "hdmi").unwrap(), 3);
+        assert_eq!(find_device(&names, "built").unwrap(), 0);
+        assert!(find_device(&names, "usb").is_err());
+        assert!(find_device(&names, "bluetooth").is_err());
+    }
+
+    #[test]
+    fn test_next_device_cycles_through_default() {
+        let names = vec!["A".to_string(), "B".to_string()];
+        assert_eq!(next_device(&names, None).as_deref(), Some("A"));
+        assert_eq!(next_device(&names, Some("A")).as_deref(), Some("B"));
+        assert_eq!(next_device(&names, Some("B")), None);
+        assert_eq!(next_device(&names, Some("gone")).as_deref(), Some("A"));
+        assert_eq!(next_device(&[], None), None);
+    }
+}
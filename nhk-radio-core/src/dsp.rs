@@ -0,0 +1,150 @@
+//! Processing of decoded audio on its way from the decoder to the output.
+//!
+//! [`Dsp`] is the one insert point: the stream loop passes every batch of
+//! playback-format PCM through it before queueing it, so the equalizer and
+//! loudness normalization apply alike to the sound card, `--output`, and
+//! anything else fed from the queue. Recordings are taken before it and keep
+//! the broadcast sound.
+
+use std::f64::consts::PI;
+
+use crate::equalizer::{EqPreset, Equalizer};
+use crate::loudness::Normalizer;
+use crate::player::SAMPLE_RATE;
+
+/// One second-order IIR filter section
+///
+/// The constructors follow the formulas of Robert Bristow-Johnson's Audio EQ
+/// Cookbook, at the playback sample rate.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    /// A section with the given coefficients, `a` without the leading 1
+    pub(crate) fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Biquad {
+            b,
+            a,
+            ..Default::default()
+        }
+    }
+
+    /// Normalize by `a0` before making the section
+    fn from_cookbook(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self::new(b.map(|b| b / a[0]), [a[1] / a[0], a[2] / a[0]])
+    }
+
+    /// Angle per sample of `freq`, and its cosine and sine
+    fn angle(freq: f64) -> (f64, f64) {
+        let w0 = 2.0 * PI * freq / SAMPLE_RATE as f64;
+        (w0.cos(), w0.sin())
+    }
+
+    /// Cut everything below `freq`
+    pub(crate) fn high_pass(freq: f64, q: f64) -> Self {
+        let (cos, sin) = Self::angle(freq);
+        let alpha = sin / (2.0 * q);
+        Self::from_cookbook(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Raise or lower a band around `freq` by `gain_db`
+    pub(crate) fn peaking(freq: f64, q: f64, gain_db: f64) -> Self {
+        let (cos, sin) = Self::angle(freq);
+        let alpha = sin / (2.0 * q);
+        let a = 10f64.powf(gain_db / 40.0);
+        Self::from_cookbook(
+            [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a],
+            [1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+        )
+    }
+
+    /// Raise or lower everything below `freq` by `gain_db`
+    pub(crate) fn low_shelf(freq: f64, gain_db: f64) -> Self {
+        let (cos, sin) = Self::angle(freq);
+        let a = 10f64.powf(gain_db / 40.0);
+        // 2√A·α, with α for a shelf slope of 1
+        let k = a.sqrt() * sin * 2f64.sqrt();
+        Self::from_cookbook(
+            [
+                a * ((a + 1.0) - (a - 1.0) * cos + k),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - k),
+            ],
+            [
+                (a + 1.0) + (a - 1.0) * cos + k,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - k,
+            ],
+        )
+    }
+
+    /// Raise or lower everything above `freq` by `gain_db`
+    pub(crate) fn high_shelf(freq: f64, gain_db: f64) -> Self {
+        let (cos, sin) = Self::angle(freq);
+        let a = 10f64.powf(gain_db / 40.0);
+        // 2√A·α, with α for a shelf slope of 1
+        let k = a.sqrt() * sin * 2f64.sqrt();
+        Self::from_cookbook(
+            [
+                a * ((a + 1.0) + (a - 1.0) * cos + k),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - k),
+            ],
+            [
+                (a + 1.0) - (a - 1.0) * cos + k,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - k,
+            ],
+        )
+    }
+
+    pub(crate) fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// Everything done to decoded audio before it is played, in order: the
+/// equalizer, then loudness normalization with its limiter
+#[derive(Debug, Clone)]
+pub struct Dsp {
+    equalizer: Equalizer,
+    normalizer: Option<Normalizer>,
+}
+
+impl Dsp {
+    /// Equalize with `preset` and, with `target_lufs`, normalize loudness
+    pub fn new(preset: EqPreset, target_lufs: Option<f64>) -> Self {
+        Dsp {
+            equalizer: Equalizer::new(preset),
+            normalizer: target_lufs.map(Normalizer::new),
+        }
+    }
+
+    /// Switch to another equalizer preset from the next batch on
+    pub fn set_equalizer(&mut self, preset: EqPreset) {
+        if self.equalizer.preset() != preset {
+            self.equalizer = Equalizer::new(preset);
+        }
+    }
+
+    /// Process interleaved stereo `samples` in place
+    pub fn process(&mut self, samples: &mut [i16]) {
+        self.equalizer.process(samples);
+        if let Some(normalizer) = self.normalizer.as_mut() {
+            normalizer.process(samples);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Copies of what the HLS pipeline fetched, for reproducing problems offline.
+//!
+//! With [`HttpOptions::dump_dir`](crate::client::HttpOptions::dump_dir) set,
+//! the client saves every playlist, key, and segment it fetches, and the
+//! stream loops save each segment again once decrypted. Files are numbered in
+//! the order they were written and named after what they hold and the URL
+//! they came from, e.g. `000042-segment-seg1234.aac` followed by
+//! `000043-decrypted-1234-seg1234.aac`. Keys can be left out, keeping only
+//! their length, so a dump can be attached to a bug report.
+
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of the next file written by any dump, so files from several
+/// clients sharing a directory do not collide
+static NEXT_FILE: AtomicU64 = AtomicU64::new(1);
+
+/// A directory fetched data is copied into
+#[derive(Debug)]
+pub struct StreamDump {
+    dir: PathBuf,
+    redact_keys: bool,
+}
+
+impl StreamDump {
+    /// Dump into `dir`, creating it if need be
+    pub fn new(dir: &Path, redact_keys: bool) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", dir.display(), e))?;
+        Ok(StreamDump {
+            dir: dir.to_path_buf(),
+            redact_keys,
+        })
+    }
+
+    pub fn playlist(&self, url: &str, content: &str) {
+        self.save("playlist", url, content.as_bytes());
+    }
+
+    /// Save a key, or only its length when keys are redacted
+    pub fn key(&self, url: &str, key: &[u8]) {
+        if self.redact_keys {
+            let note = format!("{} bytes, redacted\n", key.len());
+            self.save("key-redacted", url, note.as_bytes());
+        } else {
+            self.save("key", url, key);
+        }
+    }
+
+    /// Save a segment or init section as downloaded, before decryption
+    pub fn segment(&self, url: &str, data: &[u8]) {
+        self.save("segment", url, data);
+    }
+
+    /// Save media segment `seq_no` once decrypted
+    pub fn decrypted(&self, url: &str, seq_no: u64, data: &[u8]) {
+        self.save(&format!("decrypted-{}", seq_no), url, data);
+    }
+
+    /// Write `data` as the next numbered file
+    ///
+    /// Failures are only logged: a full disk should not stop playback.
+    fn save(&self, kind: &str, url: &str, data: &[u8]) {
+        let number = NEXT_FILE.fetch_add(1, Ordering::Relaxed);
+        let path = self.dir.join(file_name(number, kind, url));
+        if let Err(e) = fs::write(&path, data) {
+            log::warn!("Failed to dump {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// `000042-kind-name`, where `name` is the last path segment of `url` with
+/// anything unsafe in a file name replaced
+fn file_name(number: u64, kind: &str, url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let name: String = path
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{:06}-{}-{}", number, kind, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(
+            file_name(
+                42,
+                "segment",
+                "https://example.com/fm/seg1234.aac?token=a b"
+            ),
+            "000042-segment-seg1234.aac"
+        );
+        assert_eq!(
+            file_name(7, "playlist", "https://example.com/fm/プレイリスト.m3u8"),
+            "000007-playlist-______.m3u8"
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        let dir = std::env::temp_dir().join(format!("nhk-dump-{}", std::process::id()));
+        let dump = StreamDump::new(&dir, true).unwrap();
+        dump.playlist("https://example.com/fm/media.m3u8", "#EXTM3U\n");
+        dump.key("https://example.com/fm/key", &[3; 16]);
+        dump.segment("https://example.com/fm/seg7.aac", b"encrypted");
+        dump.decrypted("https://example.com/fm/seg7.aac", 7, b"decrypted");
+
+        let mut files: Vec<(String, Vec<u8>)> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, fs::read(&path).unwrap())
+            })
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        files.sort();
+
+        // Numbered in the order written, whatever the name
+        let kinds: Vec<&str> = files.iter().map(|(name, _)| &name[7..]).collect();
+        assert_eq!(
+            kinds,
+            [
+                "playlist-media.m3u8",
+                "key-redacted-key",
+                "segment-seg7.aac",
+                "decrypted-7-seg7.aac"
+            ]
+        );
+        assert_eq!(files[1].1, b"16 bytes, redacted\n");
+        assert_eq!(files[3].1, b"decrypted");
+    }
+}
@@ -0,0 +1,141 @@
+//! PCM encoder stage used for transcoded recordings and re-streaming
+//! (`transcode` feature).
+//!
+//! Encoding is delegated to an `ffmpeg` child process fed raw s16le PCM on
+//! stdin, which covers MP3, Opus, and FLAC without bundling codec libraries.
+
+use anyhow::Result;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use crate::recorder::RecordFormat;
+
+pub struct Encoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+}
+
+impl Encoder {
+    /// Start encoding to `path`, attaching `metadata` tags
+    pub fn spawn(
+        path: &Path,
+        format: RecordFormat,
+        sample_rate: u32,
+        channels: u16,
+        metadata: &[(&str, &str)],
+    ) -> Result<Self> {
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-f", "s16le"])
+            .args(["-ar", &sample_rate.to_string()])
+            .args(["-ac", &channels.to_string()])
+            .args(["-i", "-"])
+            .args(codec_args(format)?);
+        for (key, value) in metadata {
+            command.arg("-metadata").arg(format!("{}={}", key, value));
+        }
+        let mut child = command
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(spawn_error)?;
+
+        let stdin = child.stdin.take();
+        Ok(Self { child, stdin })
+    }
+
+    pub fn write_pcm(&mut self, samples: &[i16]) -> Result<usize> {
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Encoder already finished"))?;
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        stdin.write_all(&bytes)?;
+        Ok(bytes.len())
+    }
+
+    /// Close the PCM pipe and wait for ffmpeg to finalize the file
+    pub fn finish(&mut self) -> Result<()> {
+        drop(self.stdin.take());
+        let status = self.child.wait()?;
+        if !status.success() {
+            anyhow::bail!("ffmpeg exited with {}", status);
+        }
+        Ok(())
+    }
+}
+
+/// Re-encode an existing audio file into `format`, attaching `metadata` tags
+pub fn transcode_file(
+    input: &Path,
+    output: &Path,
+    format: RecordFormat,
+    metadata: &[(&str, &str)],
+) -> Result<()> {
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-y"])
+        .arg("-i")
+        .arg(input)
+        .args(codec_args(format)?);
+    for (key, value) in metadata {
+        command.arg("-metadata").arg(format!("{}={}", key, value));
+    }
+
+    let status = command
+        .arg(output)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .map_err(spawn_error)?;
+    if !status.success() {
+        anyhow::bail!("ffmpeg exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Start ffmpeg encoding s16le PCM written to its stdin into the format
+/// `codec_args` select, read back from its stdout
+///
+/// Used for live re-streaming, where the encoded audio never touches disk.
+pub fn spawn_stream(codec_args: &[String], sample_rate: u32, channels: u16) -> Result<Child> {
+    Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error"])
+        .args(["-f", "s16le"])
+        .args(["-ar", &sample_rate.to_string()])
+        .args(["-ac", &channels.to_string()])
+        .args(["-i", "-"])
+        .args(codec_args)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(spawn_error)
+}
+
+fn codec_args(format: RecordFormat) -> Result<&'static [&'static str]> {
+    Ok(match format {
+        RecordFormat::Mp3 => &["-c:a", "libmp3lame", "-q:a", "2", "-f", "mp3"],
+        RecordFormat::Opus => &["-c:a", "libopus", "-b:a", "96k", "-f", "ogg"],
+        RecordFormat::Flac => &["-c:a", "flac", "-f", "flac"],
+        RecordFormat::Wav => &["-c:a", "pcm_s16le", "-f", "wav"],
+        RecordFormat::Raw => anyhow::bail!("Raw recordings do not need an encoder"),
+    })
+}
+
+fn spawn_error(e: std::io::Error) -> anyhow::Error {
+    anyhow::anyhow!("Failed to start ffmpeg (is it installed?): {}", e)
+}
+
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        if self.stdin.is_some()
+            && let Err(e) = self.finish()
+        {
+            log::error!("Failed to finalize recording: {}", e);
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! One live playback pipeline that any front end can drive.
+//!
+//! [`PlaybackEngine`] owns the stream loop, the audio thread and any pipe
+//! writers it fans out to, and the channels between them. Front ends change channel, pause, or rewind through its
+//! methods, read a [`PlaybackState`] snapshot to draw, and drain
+//! [`PlaybackEvent`]s for things that happen on the pipeline's side.
+
+use chrono::{DateTime, FixedOffset, Utc};
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use crate::audio_queue::audio_queue;
+use crate::client::RadioHttp;
+use crate::equalizer::EqPreset;
+use crate::fanout::AudioFanout;
+use crate::pipe::run_pipe_thread;
+use crate::player::{
+    AudioCommand, AudioControls, ChannelKind, PlaybackEvent, PlayerOptions, StreamControls,
+    run_audio_thread, run_stream_loop,
+};
+use crate::recorder::{Recorder, RecordingStatus};
+use crate::stats::{StreamStats, StreamStatsSnapshot};
+use crate::types::StreamData;
+
+/// What the engine is doing right now
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaybackState {
+    pub channel: ChannelKind,
+    pub paused: bool,
+    /// Volume from 0.0 to 1.0
+    pub volume: f32,
+    /// Seconds playback is behind live due to rewinding
+    pub timeshift_secs: f64,
+    /// Seconds of received audio not yet heard
+    pub queued_secs: f64,
+    /// RMS level of the left and right channel of what is heard now, from
+    /// 0.0 to 1.0; silent while paused, whatever the volume
+    pub levels: [f32; 2],
+    /// Wall-clock end of the newest audio received, when the playlist has
+    /// EXT-X-PROGRAM-DATE-TIME
+    pub live_edge: Option<DateTime<FixedOffset>>,
+}
+
+impl PlaybackState {
+    /// Seconds between the broadcast and what is heard, if the stream
+    /// carries wall-clock times
+    pub fn latency_secs(&self) -> Option<f64> {
+        self.live_edge.map(|edge| {
+            let behind = Utc::now() - edge.with_timezone(&Utc);
+            behind.num_milliseconds() as f64 / 1000.0 + self.queued_secs
+        })
+    }
+}
+
+/// A running live stream with its audio output
+///
+/// Dropping the engine stops the stream loop; the audio thread exits once it
+/// has played out what was already queued.
+pub struct PlaybackEngine {
+    channel_tx: watch::Sender<ChannelKind>,
+    area_tx: watch::Sender<StreamData>,
+    paused_tx: watch::Sender<bool>,
+    volume_tx: watch::Sender<f32>,
+    device_tx: watch::Sender<Option<String>>,
+    equalizer_tx: watch::Sender<EqPreset>,
+    command_tx: Sender<AudioCommand>,
+    timeshift_rx: watch::Receiver<f64>,
+    queued_rx: watch::Receiver<f64>,
+    levels_rx: watch::Receiver<[f32; 2]>,
+    live_edge_rx: watch::Receiver<Option<DateTime<FixedOffset>>>,
+    stats: Arc<StreamStats>,
+    started: Instant,
+    recorder: Arc<Mutex<Option<Recorder>>>,
+    event_tx: Sender<PlaybackEvent>,
+    event_rx: Mutex<Receiver<PlaybackEvent>>,
+    stream_task: tokio::task::JoinHandle<()>,
+    stream_error: Arc<Mutex<Option<anyhow::Error>>>,
+}
+
+impl PlaybackEngine {
+    /// Start playing `channel` of `stream_data`
+    ///
+    /// Must be called within a Tokio runtime; the audio thread, with
+    /// [`PlayerOptions::speaker`], and a writer for each of
+    /// [`PlayerOptions::pipes`] are spawned alongside it.
+    pub fn start<H: RadioHttp + 'static>(
+        client: Arc<H>,
+        stream_data: StreamData,
+        channel: ChannelKind,
+        options: PlayerOptions,
+    ) -> Self {
+        let (channel_tx, channel_rx) = watch::channel(channel);
+        let (area_tx, area_rx) = watch::channel(stream_data);
+        let (paused_tx, paused_rx) = watch::channel(false);
+        let (volume_tx, volume_rx) = watch::channel(options.volume as f32 / 100.0);
+        let (device_tx, device_rx) = watch::channel(options.device.clone());
+        let (equalizer_tx, equalizer_rx) = watch::channel(options.equalizer);
+        let (command_tx, command_rx) = std::sync::mpsc::channel();
+        let (timeshift_tx, timeshift_rx) = watch::channel(0.0);
+        let (queued_tx, queued_rx) = watch::channel(0.0);
+        let (levels_tx, levels_rx) = watch::channel([0.0; 2]);
+        let (live_edge_tx, live_edge_rx) = watch::channel(None);
+        let (event_tx, event_rx) = std::sync::mpsc::channel();
+        let stats = Arc::new(StreamStats::default());
+        let recorder = Arc::new(Mutex::new(options.recorder));
+
+        let controls = AudioControls {
+            paused_rx: paused_rx.clone(),
+            volume_rx,
+            device_rx,
+            command_rx,
+            timeshift_tx,
+            queued_tx,
+            levels_tx,
+            stats: stats.clone(),
+        };
+        // Each output drains a queue of its own, so one that stalls or fails
+        // leaves the others playing
+        let mut audio_tx = AudioFanout::new();
+        if options.speaker {
+            let (tx, audio_rx) = audio_queue(options.buffer_secs as f64);
+            audio_tx.add("speaker", tx);
+            let timeshift_minutes = options.timeshift_minutes;
+            let audio_events = event_tx.clone();
+            std::thread::spawn(move || {
+                let result =
+                    run_audio_thread(audio_rx, controls, timeshift_minutes, audio_events.clone());
+                if let Err(e) = result {
+                    let _ = audio_events.send(PlaybackEvent::Error(e.to_string()));
+                }
+            });
+        }
+        for pipe in options.pipes {
+            let (tx, pipe_rx) = audio_queue(options.buffer_secs as f64);
+            let name = pipe.to_string();
+            audio_tx.add(name.clone(), tx);
+            let pipe_events = event_tx.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = run_pipe_thread(pipe_rx, pipe, pipe_events.clone()) {
+                    let message = format!("Output to {} failed: {}", name, e);
+                    let _ = pipe_events.send(PlaybackEvent::Error(message));
+                }
+            });
+        }
+
+        let stream_events = event_tx.clone();
+        let stream_stats = stats.clone();
+        let stream_recorder = recorder.clone();
+        let stream_error = Arc::new(Mutex::new(None));
+        let stream_failure = stream_error.clone();
+        let stream_task = tokio::spawn(async move {
+            let controls = StreamControls {
+                channel_rx,
+                area_rx,
+                paused_rx,
+                audio_tx,
+                buffer: Duration::from_secs(options.buffer_secs.into()),
+                live_edge_tx,
+                stats: stream_stats,
+                target_lufs: options.target_lufs,
+                equalizer_rx,
+                silence_alert: options.silence_alert,
+                events: stream_events.clone(),
+                recorder: stream_recorder,
+            };
+            let result = run_stream_loop(client, controls, options.stream).await;
+            if let Err(e) = result {
+                log::error!("Stream loop stopped: {}", e);
+                let _ = stream_events.send(PlaybackEvent::Error(e.to_string()));
+                *stream_failure.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+            }
+        });
+
+        PlaybackEngine {
+            channel_tx,
+            area_tx,
+            paused_tx,
+            volume_tx,
+            device_tx,
+            equalizer_tx,
+            command_tx,
+            timeshift_rx,
+            queued_rx,
+            levels_rx,
+            live_edge_rx,
+            stats,
+            started: Instant::now(),
+            recorder,
+            event_tx,
+            event_rx: Mutex::new(event_rx),
+            stream_task,
+            stream_error,
+        }
+    }
+
+    pub fn channel(&self) -> ChannelKind {
+        *self.channel_tx.borrow()
+    }
+
+    /// Switch to `channel`, clearing what is queued from the old one
+    pub fn set_channel(&self, channel: ChannelKind) {
+        if self.channel_tx.send_replace(channel) != channel {
+            let _ = self.event_tx.send(PlaybackEvent::ChannelChanged(channel));
+        }
+    }
+
+    /// Area whose streams are playing
+    pub fn area(&self) -> StreamData {
+        self.area_tx.borrow().clone()
+    }
+
+    /// Retune to the same channel in another area
+    pub fn set_area(&self, stream_data: StreamData) {
+        self.area_tx.send_if_modified(|current| {
+            let changed = current.area != stream_data.area;
+            *current = stream_data;
+            changed
+        });
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused_tx.borrow()
+    }
+
+    /// Pause or resume; audio arriving while paused is dropped so playback
+    /// resumes at the live edge
+    pub fn set_paused(&self, paused: bool) {
+        self.paused_tx.send_replace(paused);
+    }
+
+    pub fn toggle_pause(&self) {
+        self.paused_tx.send_modify(|paused| *paused = !*paused);
+    }
+
+    pub fn volume(&self) -> f32 {
+        *self.volume_tx.borrow()
+    }
+
+    /// Set the volume from 0.0 to 1.0
+    pub fn set_volume(&self, volume: f32) {
+        self.volume_tx.send_replace(volume.clamp(0.0, 1.0));
+    }
+
+    /// Output device in use, or `None` for the default
+    pub fn device(&self) -> Option<String> {
+        self.device_tx.borrow().clone()
+    }
+
+    /// Move playback to another output device, or the default with `None`
+    pub fn set_device(&self, device: Option<String>) {
+        self.device_tx.send_replace(device);
+    }
+
+    pub fn equalizer(&self) -> EqPreset {
+        *self.equalizer_tx.borrow()
+    }
+
+    /// Change the equalizer curve; audio already queued keeps the old one
+    pub fn set_equalizer(&self, preset: EqPreset) {
+        self.equalizer_tx.send_replace(preset);
+    }
+
+    /// Jump back by `duration` within the timeshift buffer
+    pub fn rewind(&self, duration: Duration) {
+        let _ = self.command_tx.send(AudioCommand::Rewind(duration));
+    }
+
+    /// Drop any timeshift and continue from the newest audio
+    pub fn go_live(&self) {
+        let _ = self.command_tx.send(AudioCommand::Live);
+    }
+
+    pub fn state(&self) -> PlaybackState {
+        PlaybackState {
+            channel: self.channel(),
+            paused: self.is_paused(),
+            volume: self.volume(),
+            timeshift_secs: *self.timeshift_rx.borrow(),
+            queued_secs: *self.queued_rx.borrow(),
+            levels: *self.levels_rx.borrow(),
+            live_edge: *self.live_edge_rx.borrow(),
+        }
+    }
+
+    /// Counters from the stream loop, for diagnosing stutters
+    pub fn stats(&self) -> StreamStatsSnapshot {
+        self.stats.snapshot()
+    }
+
+    /// Whether segments are still being fetched; the stream loop only stops
+    /// on an error, such as reaching the bandwidth cap
+    pub fn is_streaming(&self) -> bool {
+        !self.stream_task.is_finished()
+    }
+
+    /// The error the stream loop stopped on, once [`Self::is_streaming`] is
+    /// false; taken, so later calls give `None`
+    pub fn take_stream_error(&self) -> Option<anyhow::Error> {
+        self.stream_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// How long the engine has been running
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Start teeing the stream into `recorder`, ending any recording already
+    /// in progress
+    pub fn start_recording(&self, recorder: Recorder) {
+        // Held until the lock is released, so a transcoder finishes outside it
+        let _previous = self.lock_recorder().replace(recorder);
+    }
+
+    /// End the recording in progress, returning the file it was written to
+    pub fn stop_recording(&self) -> Option<PathBuf> {
+        // Taken out first so a transcoder is finished without holding the lock
+        let recorder = self.lock_recorder().take()?;
+        Some(recorder.path().to_path_buf())
+    }
+
+    /// The recording in progress, if any; a recording that fails to write
+    /// ends on its own
+    pub fn recording(&self) -> Option<RecordingStatus> {
+        self.lock_recorder().as_ref().map(Recorder::status)
+    }
+
+    fn lock_recorder(&self) -> std::sync::MutexGuard<'_, Option<Recorder>> {
+        self.recorder.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The next pending event, without waiting
+    pub fn try_event(&self) -> Option<PlaybackEvent> {
+        self.event_rx
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .try_recv()
+            .ok()
+    }
+}
+
+impl Drop for PlaybackEngine {
+    fn drop(&mut self) {
+        self.stream_task.abort();
+    }
+}
@@ -0,0 +1,140 @@
+//! A few fixed equalizer curves for decoded audio.
+//!
+//! Rather than a band-by-band graphic EQ, each [`EqPreset`] is a short chain
+//! of filters tuned for one listening situation.
+
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::Biquad;
+use crate::player::CHANNELS;
+
+/// Equalizer curve applied to playback
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum EqPreset {
+    /// As broadcast
+    #[default]
+    Flat,
+    /// Clearer voices: less rumble and boom, more presence
+    Speech,
+    /// A little more bass and air
+    Music,
+    /// No low bass for small speakers that distort on it
+    BassCut,
+}
+
+impl EqPreset {
+    pub const ALL: [EqPreset; 4] = [
+        EqPreset::Flat,
+        EqPreset::Speech,
+        EqPreset::Music,
+        EqPreset::BassCut,
+    ];
+
+    /// The preset after this one, wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&p| p == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// The filters making up the curve
+    fn filters(self) -> Vec<Biquad> {
+        match self {
+            EqPreset::Flat => vec![],
+            EqPreset::Speech => vec![
+                Biquad::high_pass(80.0, 0.707),
+                Biquad::low_shelf(200.0, -4.0),
+                Biquad::peaking(3000.0, 1.0, 4.0),
+                Biquad::high_shelf(10000.0, -3.0),
+            ],
+            EqPreset::Music => vec![
+                Biquad::low_shelf(100.0, 4.0),
+                Biquad::peaking(2500.0, 1.0, -1.0),
+                Biquad::high_shelf(8000.0, 3.0),
+            ],
+            EqPreset::BassCut => vec![
+                Biquad::high_pass(150.0, 0.707),
+                Biquad::low_shelf(300.0, -3.0),
+            ],
+        }
+    }
+}
+
+/// An [`EqPreset`]'s filters, with their state for each channel
+#[derive(Debug, Clone)]
+pub struct Equalizer {
+    preset: EqPreset,
+    channels: [Vec<Biquad>; CHANNELS as usize],
+}
+
+impl Equalizer {
+    pub fn new(preset: EqPreset) -> Self {
+        Equalizer {
+            preset,
+            channels: [preset.filters(), preset.filters()],
+        }
+    }
+
+    pub fn preset(&self) -> EqPreset {
+        self.preset
+    }
+
+    /// Filter interleaved stereo `samples` in place
+    pub fn process(&mut self, samples: &mut [i16]) {
+        if self.preset == EqPreset::Flat {
+            return;
+        }
+        for frame in samples.chunks_exact_mut(CHANNELS as usize) {
+            for (sample, filters) in frame.iter_mut().zip(self.channels.iter_mut()) {
+                let mut x = *sample as f64;
+                for filter in filters.iter_mut() {
+                    x = filter.process(x);
+                }
+                *sample = x.clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::SAMPLE_RATE;
+
+    /// RMS level of a 1 s sine at `freq` after `preset`, relative to before
+    fn response(preset: EqPreset, freq: f64) -> f64 {
+        let input: Vec<i16> = (0..SAMPLE_RATE as usize)
+            .flat_map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let s = (8000.0 * (2.0 * std::f64::consts::PI * freq * t).sin()) as i16;
+                [s, s]
+            })
+            .collect();
+        let mut output = input.clone();
+        Equalizer::new(preset).process(&mut output);
+        // Skip the filters settling in
+        let rms = |samples: &[i16]| {
+            let tail = &samples[samples.len() / 2..];
+            (tail.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / tail.len() as f64).sqrt()
+        };
+        rms(&output) / rms(&input)
+    }
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(response(EqPreset::Flat, 50.0), 1.0);
+        assert!(response(EqPreset::BassCut, 50.0) < 0.2);
+        assert!(response(EqPreset::BassCut, 1000.0) > 0.9);
+        assert!(response(EqPreset::Speech, 3000.0) > 1.4);
+        assert!(response(EqPreset::Music, 60.0) > 1.3);
+    }
+
+    #[test]
+    fn test_next() {
+        assert_eq!(EqPreset::Flat.next(), EqPreset::Speech);
+        assert_eq!(EqPreset::BassCut.next(), EqPreset::Flat);
+        let json = serde_json::to_string(&EqPreset::BassCut).unwrap();
+        assert_eq!(json, "\"bass-cut\"");
+    }
+}
@@ -0,0 +1,122 @@
+//! Broad kinds of failure, for programs wrapping the player to tell apart.
+//!
+//! Errors stay `anyhow` errors throughout. [`ErrorKind::of`] looks through an
+//! error's chain for what caused it: an error raised with
+//! [`ErrorKind::error`], or one from the HTTP client, a parser, or the audio
+//! backend, which are recognized by type.
+
+use serde::Serialize;
+use std::fmt;
+
+/// What went wrong, broadly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    /// NHK could not be reached, or answered with an error
+    Network,
+    /// No area matches the one asked for
+    AreaNotFound,
+    /// A response, playlist, or file could not be understood
+    Parse,
+    /// No usable audio output device
+    AudioDevice,
+    /// A segment could not be decrypted
+    Decrypt,
+    /// Anything else
+    Other,
+}
+
+impl ErrorKind {
+    /// Exit status of a process that failed this way
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Network => 3,
+            ErrorKind::AreaNotFound => 4,
+            ErrorKind::Parse => 5,
+            ErrorKind::AudioDevice => 6,
+            ErrorKind::Decrypt => 7,
+        }
+    }
+
+    /// An error of this kind, shown as `message`
+    pub fn error(self, message: impl Into<String>) -> anyhow::Error {
+        anyhow::Error::new(KindError {
+            kind: self,
+            message: message.into(),
+        })
+    }
+
+    /// The kind of `error`, from the outermost cause in its chain that tells
+    pub fn of(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(Self::of_cause)
+            .unwrap_or(ErrorKind::Other)
+    }
+
+    fn of_cause(cause: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        if let Some(e) = cause.downcast_ref::<KindError>() {
+            return Some(e.kind);
+        }
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return Some(if e.is_decode() {
+                ErrorKind::Parse
+            } else {
+                ErrorKind::Network
+            });
+        }
+        if cause.is::<serde_json::Error>() || cause.is::<serde_xml_rs::Error>() {
+            return Some(ErrorKind::Parse);
+        }
+        if cause.is::<rodio::StreamError>()
+            || cause.is::<rodio::PlayError>()
+            || cause.is::<rodio::DevicesError>()
+        {
+            return Some(ErrorKind::AudioDevice);
+        }
+        None
+    }
+}
+
+/// An error raised with its [`ErrorKind`]
+#[derive(Debug)]
+struct KindError {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl fmt::Display for KindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for KindError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_error_kind() {
+        let error = ErrorKind::AreaNotFound.error("Area not found: atlantis");
+        assert_eq!(error.to_string(), "Area not found: atlantis");
+        assert_eq!(ErrorKind::of(&error), ErrorKind::AreaNotFound);
+
+        // Found behind context added on the way up
+        let error = Err::<(), _>(ErrorKind::Decrypt.error("Decryption failed"))
+            .context("Segment 12")
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&error), ErrorKind::Decrypt);
+
+        let json = serde_json::from_str::<u32>("{").unwrap_err();
+        assert_eq!(ErrorKind::of(&json.into()), ErrorKind::Parse);
+        assert_eq!(
+            ErrorKind::of(&anyhow::anyhow!("Something else")),
+            ErrorKind::Other
+        );
+        assert_eq!(ErrorKind::Other.exit_code(), 1);
+    }
+}
@@ -0,0 +1,113 @@
+//! Delivery of decoded audio to several sinks at once.
+//!
+//! The stream loop hands each batch to an [`AudioFanout`], which copies it
+//! into one [`audio_queue`](crate::audio_queue) per sink: the sound device,
+//! and any files or stdout given with `--output`. Every sink drains its own
+//! queue on its own thread, so a slow reader only drops its own audio, and a
+//! sink that fails or goes away is left out while the others carry on.
+
+use std::sync::mpsc::SendError;
+
+use crate::audio_queue::AudioSender;
+
+/// One queue per sink, fed with the same audio
+#[derive(Default)]
+pub struct AudioFanout {
+    sinks: Vec<FanoutSink>,
+}
+
+struct FanoutSink {
+    /// What the sink is, for the log
+    name: String,
+    tx: AudioSender,
+}
+
+impl AudioFanout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deliver to `tx` too, calling it `name` in the log
+    pub fn add(&mut self, name: impl Into<String>, tx: AudioSender) {
+        self.sinks.push(FanoutSink {
+            name: name.into(),
+            tx,
+        });
+    }
+
+    /// Number of sinks still taking audio
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Queue a batch of samples on every sink, or clear them all if empty
+    ///
+    /// Sinks that have gone away are dropped. Fails only once none is left.
+    pub fn send(&mut self, samples: Vec<i16>) -> Result<(), SendError<Vec<i16>>> {
+        self.sinks.retain(|sink| {
+            let open = sink.tx.send(samples.clone()).is_ok();
+            if !open {
+                log::warn!("Audio output {} stopped; the others carry on", sink.name);
+            }
+            open
+        });
+        if self.sinks.is_empty() {
+            Err(SendError(samples))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Most samples any sink has dropped for being over its buffer target
+    pub fn dropped_samples(&self) -> u64 {
+        self.sinks
+            .iter()
+            .map(|sink| sink.tx.dropped_samples())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_queue::audio_queue;
+    use std::time::Duration;
+
+    const NO_WAIT: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn test_every_sink_gets_the_audio() {
+        let (speaker_tx, speaker_rx) = audio_queue(10.0);
+        let (file_tx, file_rx) = audio_queue(10.0);
+        let mut fanout = AudioFanout::new();
+        fanout.add("speaker", speaker_tx);
+        fanout.add("file", file_tx);
+
+        fanout.send(vec![1, 2]).unwrap();
+        assert_eq!(speaker_rx.recv_timeout(NO_WAIT, true).unwrap(), [1, 2]);
+        assert_eq!(file_rx.recv_timeout(NO_WAIT, true).unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn test_failed_sink_is_left_out() {
+        let (speaker_tx, speaker_rx) = audio_queue(10.0);
+        let (file_tx, file_rx) = audio_queue(10.0);
+        let mut fanout = AudioFanout::new();
+        fanout.add("speaker", speaker_tx);
+        fanout.add("file", file_tx);
+
+        drop(speaker_rx);
+        fanout.send(vec![3]).unwrap();
+        assert_eq!(fanout.len(), 1);
+        assert_eq!(file_rx.recv_timeout(NO_WAIT, true).unwrap(), [3]);
+
+        drop(file_rx);
+        assert!(fanout.send(vec![4]).is_err());
+        assert!(fanout.is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+//! NHK Radio (らじる★らじる) client, HLS pipeline, and playback engine.
+//!
+//! This is the reusable half of `nhk-radio-player`: everything needed to find
+//! a station, follow its live playlist, decrypt and decode segments, and play
+//! the result, without any terminal UI.
+//!
+//! [`NhkRadio`] is the simplest way in. It loads the station config once,
+//! lists the [areas](NhkRadio::areas), and opens decoded [audio
+//! streams](NhkRadio::stream) and [program-change streams](NhkRadio::now_playing)
+//! that can feed any audio sink or UI toolkit. The lower-level modules stay
+//! public for callers that want to drive the pipeline themselves.
+
+pub mod area;
+pub mod audio_queue;
+pub mod cache;
+pub mod client;
+pub mod crypto;
+pub mod decoder;
+pub mod device;
+pub mod dsp;
+pub mod dump;
+#[cfg(feature = "transcode")]
+pub mod encoder;
+pub mod engine;
+pub mod equalizer;
+pub mod error;
+pub mod fanout;
+pub mod loudness;
+pub mod m3u8;
+pub mod mock;
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
+pub mod ondemand;
+pub mod pacer;
+pub mod pipe;
+pub mod player;
+pub mod radio;
+pub mod recorder;
+pub mod resample;
+pub mod stats;
+pub mod stream;
+pub mod throttle;
+pub mod timeshift;
+pub mod types;
+pub mod world;
+
+pub use client::{HttpOptions, RadioHttp};
+pub use decoder::{PcmChunk, PcmSpec};
+pub use engine::{PlaybackEngine, PlaybackState};
+pub use error::ErrorKind;
+pub use player::{ChannelKind, PlaybackEvent, PlayerOptions};
+pub use radio::{NhkRadio, NowPlaying};
+pub use stats::StreamStatsSnapshot;
+pub use stream::{SegmentData, StreamOptions, live_audio, live_segments};
+pub use types::StreamData;
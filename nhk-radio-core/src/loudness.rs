@@ -0,0 +1,275 @@
+//! Loudness normalization of decoded audio.
+//!
+//! Talk on R1 and music on FM are broadcast at quite different levels. The
+//! [`Normalizer`] measures loudness as ITU-R BS.1770 / EBU R128 does
+//! (K-weighting, 400 ms blocks, absolute and relative gating), integrated
+//! over the last few minutes so it follows program changes, and turns the
+//! audio up or down towards a target. A peak limiter keeps the added gain
+//! from clipping.
+
+use std::collections::VecDeque;
+
+use crate::dsp::Biquad;
+use crate::player::{CHANNELS, SAMPLE_RATE};
+
+/// Target loudness of EBU R128, in LUFS
+pub const EBU_R128_TARGET: f64 = -23.0;
+
+/// Stretch of audio the integrated loudness covers
+const WINDOW_SECS: usize = 300;
+
+/// Blocks are 400 ms long and start every 100 ms
+const HOP_FRAMES: usize = SAMPLE_RATE as usize / 10;
+const HOPS_PER_BLOCK: usize = 4;
+
+/// Blocks quieter than this are silence and left out
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// Blocks this far below the ungated loudness are left out too
+const RELATIVE_GATE: f64 = 10.0;
+
+/// Most the audio is turned up or down, in dB
+const MAX_BOOST: f64 = 12.0;
+const MAX_CUT: f64 = 20.0;
+
+/// Seconds the gain takes to move most of the way to a new value
+const GAIN_TIME_CONSTANT: f64 = 3.0;
+
+/// Highest peak the limiter lets through, -1 dBFS
+const CEILING: f64 = 0.891;
+
+/// Seconds the limiter takes to let go after a peak
+const LIMITER_RELEASE: f64 = 0.1;
+
+/// Loudness of a block's mean square, per BS.1770
+fn loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+fn db_to_gain(db: f64) -> f64 {
+    10f64.powf(db / 20.0)
+}
+
+/// Per-sample smoothing coefficient for a time constant of `secs`
+fn smoothing(secs: f64) -> f64 {
+    1.0 - (-1.0 / (secs * SAMPLE_RATE as f64)).exp()
+}
+
+/// The K-weighting filter of BS.1770 at 48 kHz: a high shelf for the head's
+/// effect, then a high-pass
+#[derive(Debug, Clone, Copy)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new() -> Self {
+        KWeighting {
+            shelf: Biquad::new(
+                [1.53512485958697, -2.69169618940638, 1.19839281085285],
+                [-1.69065929318241, 0.73248077421585],
+            ),
+            high_pass: Biquad::new([1.0, -2.0, 1.0], [-1.99004745483398, 0.99007225036621]),
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(x))
+    }
+}
+
+/// Gated loudness of the last [`WINDOW_SECS`] of 48 kHz stereo audio
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    filters: [KWeighting; 2],
+    /// Sum of squares of the hop being filled, and its frames so far
+    hop_sum: f64,
+    hop_frames: usize,
+    /// Mean squares of the latest hops, for the block they end
+    hops: VecDeque<f64>,
+    /// Mean squares of the blocks in the window
+    blocks: VecDeque<f64>,
+}
+
+impl Default for LoudnessMeter {
+    fn default() -> Self {
+        LoudnessMeter {
+            filters: [KWeighting::new(); 2],
+            hop_sum: 0.0,
+            hop_frames: 0,
+            hops: VecDeque::with_capacity(HOPS_PER_BLOCK),
+            blocks: VecDeque::new(),
+        }
+    }
+}
+
+impl LoudnessMeter {
+    /// Measure one frame of samples scaled to -1.0..1.0; true when it ended
+    /// a block
+    fn push_frame(&mut self, left: f64, right: f64) -> bool {
+        let left = self.filters[0].process(left);
+        let right = self.filters[1].process(right);
+        self.hop_sum += left * left + right * right;
+        self.hop_frames += 1;
+        if self.hop_frames < HOP_FRAMES {
+            return false;
+        }
+
+        if self.hops.len() == HOPS_PER_BLOCK {
+            self.hops.pop_front();
+        }
+        self.hops.push_back(self.hop_sum / HOP_FRAMES as f64);
+        self.hop_sum = 0.0;
+        self.hop_frames = 0;
+        if self.hops.len() < HOPS_PER_BLOCK {
+            return false;
+        }
+        if self.blocks.len() == WINDOW_SECS * 10 {
+            self.blocks.pop_front();
+        }
+        self.blocks
+            .push_back(self.hops.iter().sum::<f64>() / HOPS_PER_BLOCK as f64);
+        true
+    }
+
+    /// Measure interleaved stereo `samples`
+    pub fn push(&mut self, samples: &[i16]) {
+        for frame in samples.chunks_exact(CHANNELS as usize) {
+            self.push_frame(frame[0] as f64 / 32768.0, frame[1] as f64 / 32768.0);
+        }
+    }
+
+    /// Integrated loudness in LUFS, or `None` until a block louder than
+    /// silence has been measured
+    pub fn integrated(&self) -> Option<f64> {
+        let mean = |blocks: &mut dyn Iterator<Item = f64>| {
+            let (sum, count) = blocks.fold((0.0, 0usize), |(sum, n), z| (sum + z, n + 1));
+            (count > 0).then(|| sum / count as f64)
+        };
+        let audible = |z: &f64| loudness(*z) > ABSOLUTE_GATE;
+        let ungated = mean(&mut self.blocks.iter().copied().filter(audible))?;
+        let gate = loudness(ungated) - RELATIVE_GATE;
+        let gated = mean(
+            &mut self
+                .blocks
+                .iter()
+                .copied()
+                .filter(|z| audible(z) && loudness(*z) > gate),
+        )?;
+        Some(loudness(gated))
+    }
+}
+
+/// Turns 48 kHz stereo audio up or down towards a target loudness
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    target: f64,
+    meter: LoudnessMeter,
+    /// Gain the audio is moving towards, and the gain applied now
+    target_gain: f64,
+    gain: f64,
+    /// Extra gain below 1.0 while the limiter holds a peak down
+    limiter: f64,
+}
+
+impl Normalizer {
+    /// Normalize to `target_lufs`, e.g. [`EBU_R128_TARGET`]
+    pub fn new(target_lufs: f64) -> Self {
+        Normalizer {
+            target: target_lufs,
+            meter: LoudnessMeter::default(),
+            target_gain: 1.0,
+            gain: 1.0,
+            limiter: 1.0,
+        }
+    }
+
+    /// Gain currently applied, in dB
+    pub fn gain_db(&self) -> f64 {
+        20.0 * self.gain.log10()
+    }
+
+    /// Normalize interleaved stereo `samples` in place
+    pub fn process(&mut self, samples: &mut [i16]) {
+        let gain_step = smoothing(GAIN_TIME_CONSTANT);
+        let release = smoothing(LIMITER_RELEASE);
+        for frame in samples.chunks_exact_mut(CHANNELS as usize) {
+            let left = frame[0] as f64 / 32768.0;
+            let right = frame[1] as f64 / 32768.0;
+            if self.meter.push_frame(left, right)
+                && let Some(measured) = self.meter.integrated()
+            {
+                let db = (self.target - measured).clamp(-MAX_CUT, MAX_BOOST);
+                self.target_gain = db_to_gain(db);
+            }
+            self.gain += (self.target_gain - self.gain) * gain_step;
+
+            let peak = left.abs().max(right.abs()) * self.gain;
+            if peak * self.limiter > CEILING {
+                self.limiter = CEILING / peak;
+            } else {
+                self.limiter += (1.0 - self.limiter) * release;
+            }
+            let gain = self.gain * self.limiter;
+            frame[0] = (left * gain * 32768.0).clamp(-32768.0, 32767.0) as i16;
+            frame[1] = (right * gain * 32768.0).clamp(-32768.0, 32767.0) as i16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `secs` of a 1 kHz sine at `dbfs` on both channels
+    fn sine(dbfs: f64, secs: f64) -> Vec<i16> {
+        let amplitude = db_to_gain(dbfs) * 32767.0;
+        (0..(secs * SAMPLE_RATE as f64) as usize)
+            .flat_map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let s = (amplitude * (2.0 * std::f64::consts::PI * 1000.0 * t).sin()) as i16;
+                [s, s]
+            })
+            .collect()
+    }
+
+    fn peak(samples: &[i16]) -> f64 {
+        samples.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0) as f64 / 32768.0
+    }
+
+    #[test]
+    fn test_meter() {
+        let mut meter = LoudnessMeter::default();
+        assert_eq!(meter.integrated(), None);
+        meter.push(&vec![0; SAMPLE_RATE as usize * 2]);
+        // Silence is gated out
+        assert_eq!(meter.integrated(), None);
+
+        // BS.1770 reads a 1 kHz sine on both channels at about its level in dBFS
+        meter.push(&sine(-20.0, 5.0));
+        let measured = meter.integrated().unwrap();
+        assert!((measured - -20.0).abs() < 0.5, "{}", measured);
+    }
+
+    #[test]
+    fn test_normalizer() {
+        // Quiet talk is turned up towards the target
+        let mut normalizer = Normalizer::new(EBU_R128_TARGET);
+        normalizer.process(&mut sine(-35.0, 30.0));
+        assert!((normalizer.gain_db() - MAX_BOOST).abs() < 0.5);
+
+        let mut normalizer = Normalizer::new(EBU_R128_TARGET);
+        let mut loud = sine(-13.0, 30.0);
+        normalizer.process(&mut loud);
+        assert!((normalizer.gain_db() - -10.0).abs() < 0.5);
+        let settled = &loud[loud.len() - 9600..];
+        assert!((peak(settled) - db_to_gain(-23.0)).abs() < 0.01);
+
+        // A target too loud for the peaks is held under the ceiling
+        let mut normalizer = Normalizer::new(0.0);
+        let mut samples = sine(-6.0, 20.0);
+        normalizer.process(&mut samples);
+        assert!(peak(&samples) <= CEILING + 0.001);
+    }
+}
@@ -0,0 +1,401 @@
+use anyhow::Result;
+use m3u8_rs::{ExtTag, KeyMethod, MediaPlaylist, Playlist};
+use url::Url;
+
+use crate::crypto::EncryptionMethod;
+use crate::error::ErrorKind;
+use crate::types::{ByteRange, Segment};
+
+/// One entry of a master playlist
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub url: String,
+    /// Peak bits per second from the BANDWIDTH attribute
+    pub bandwidth: u64,
+    pub codecs: Option<String>,
+}
+
+/// Either the variants of a master playlist or the segments of a media playlist
+#[derive(Debug)]
+pub enum ParsedPlaylist {
+    /// Variants sorted by bandwidth, highest first
+    Master(Vec<Variant>),
+    Media(Vec<Segment>),
+}
+
+/// Parse a playlist of either kind
+pub fn parse_playlist(content: &str, base_url: &str) -> Result<ParsedPlaylist> {
+    match m3u8_rs::parse_playlist_res(content.as_bytes()) {
+        Ok(Playlist::MasterPlaylist(master)) => {
+            let mut variants: Vec<Variant> = master
+                .variants
+                .iter()
+                .filter(|v| !v.is_i_frame)
+                .map(|v| Variant {
+                    url: normalize_url(base_url, &v.uri),
+                    bandwidth: v.bandwidth,
+                    codecs: v.codecs.clone(),
+                })
+                .collect();
+            if variants.is_empty() {
+                anyhow::bail!("No variants found in master playlist");
+            }
+            variants.sort_by_key(|v| std::cmp::Reverse(v.bandwidth));
+            Ok(ParsedPlaylist::Master(variants))
+        }
+        Ok(Playlist::MediaPlaylist(media)) => Ok(ParsedPlaylist::Media(parse_media_playlist(
+            &media, base_url,
+        )?)),
+        Err(e) => Err(ErrorKind::Parse.error(format!("Failed to parse M3U8 playlist: {}", e))),
+    }
+}
+
+/// Index of the best variant within `max_bitrate`
+///
+/// `variants` must be sorted highest bandwidth first. When every variant is
+/// over the limit the lowest one is used.
+pub fn select_variant(variants: &[Variant], max_bitrate: Option<u64>) -> usize {
+    match max_bitrate {
+        Some(limit) => variants
+            .iter()
+            .position(|v| v.bandwidth <= limit)
+            .unwrap_or(variants.len().saturating_sub(1)),
+        None => 0,
+    }
+}
+
+/// Segments of a media playlist with the timing needed to refresh it
+#[derive(Debug)]
+pub struct MediaPlaylistInfo {
+    /// EXT-X-TARGETDURATION in seconds
+    pub target_duration: f64,
+    pub segments: Vec<Segment>,
+    /// EXT-X-ENDLIST: no segments will be added, as with on-demand streams
+    pub end_list: bool,
+}
+
+/// Parse M3U8 playlist and extract segment information
+pub fn parse_m3u8(content: &str, base_url: &str) -> Result<Vec<Segment>> {
+    Ok(parse_media_m3u8(content, base_url)?.segments)
+}
+
+/// Parse a media playlist, keeping its target duration
+pub fn parse_media_m3u8(content: &str, base_url: &str) -> Result<MediaPlaylistInfo> {
+    let parsed = m3u8_rs::parse_playlist_res(content.as_bytes());
+
+    match parsed {
+        Ok(Playlist::MasterPlaylist(master)) => anyhow::bail!(
+            "Expected a media playlist, got a master playlist with {} variants",
+            master.variants.len()
+        ),
+        Ok(Playlist::MediaPlaylist(media)) => Ok(MediaPlaylistInfo {
+            target_duration: media.target_duration as f64,
+            segments: parse_media_playlist(&media, base_url)?,
+            end_list: media.end_list,
+        }),
+        Err(e) => Err(ErrorKind::Parse.error(format!("Failed to parse M3U8 playlist: {}", e))),
+    }
+}
+
+fn parse_media_playlist(playlist: &MediaPlaylist, base_url: &str) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    // An EXT-X-KEY applies to every following segment until the next one
+    let mut current_key: Option<(Option<String>, Option<String>, EncryptionMethod)> = None;
+    // Segments without their own EXT-X-PROGRAM-DATE-TIME follow on from the previous one
+    let mut next_date_time = None;
+    // Like keys, an EXT-X-MAP covers every segment until the next one
+    let mut current_map: Option<String> = None;
+    // A byte range without an offset starts where the previous one in the same file ended
+    let mut previous_range: Option<(String, u64)> = None;
+
+    for (idx, segment) in playlist.segments.iter().enumerate() {
+        let url = normalize_url(base_url, &segment.uri);
+
+        if let Some(key) = &segment.key {
+            let method = match &key.method {
+                KeyMethod::None => None,
+                KeyMethod::AES128 => Some(EncryptionMethod::Aes128),
+                KeyMethod::SampleAES => Some(EncryptionMethod::SampleAes),
+                KeyMethod::Other(other) => {
+                    anyhow::bail!("Unsupported encryption method: {}", other)
+                }
+            };
+            current_key = method.map(|method| {
+                let k_url = key
+                    .uri
+                    .as_ref()
+                    .filter(|uri| !uri.is_empty())
+                    .map(|uri| normalize_url(base_url, uri));
+                let iv_val = key.iv.clone().filter(|iv| !iv.is_empty());
+                (k_url, iv_val, method)
+            });
+        } else if segment.unknown_tags.iter().any(is_key_method_none) {
+            current_key = None;
+        }
+        let (key_url, iv, key_method) = current_key.clone().unwrap_or_default();
+
+        if let Some(map) = &segment.map {
+            current_map = Some(normalize_url(base_url, &map.uri));
+        }
+
+        let byte_range = segment.byte_range.as_ref().map(|range| {
+            let offset = range.offset.unwrap_or_else(|| match &previous_range {
+                Some((prev_url, end)) if *prev_url == url => *end,
+                _ => 0,
+            });
+            ByteRange {
+                offset,
+                length: range.length,
+            }
+        });
+        previous_range = byte_range.map(|range| (url.clone(), range.offset + range.length));
+
+        let duration = segment.duration as f64;
+        let program_date_time = segment.program_date_time.or(next_date_time);
+        next_date_time = program_date_time
+            .map(|t| t + chrono::Duration::milliseconds((duration * 1000.0) as i64));
+
+        segments.push(Segment {
+            url,
+            key_url,
+            key_method,
+            iv,
+            // The default IV is the segment's media sequence number, which
+            // keeps counting as the live window slides
+            seq_no: playlist.media_sequence + idx as u64,
+            duration,
+            program_date_time,
+            map_url: current_map.clone(),
+            byte_range,
+        });
+    }
+
+    Ok(segments)
+}
+
+/// Whether `tag` is an `EXT-X-KEY:METHOD=NONE`
+///
+/// m3u8-rs insists on an IV even for METHOD=NONE, so the usual IV-less form
+/// ends up among a segment's unknown tags instead of in its key.
+fn is_key_method_none(tag: &ExtTag) -> bool {
+    tag.tag == "X-KEY"
+        && tag
+            .rest
+            .as_deref()
+            .is_some_and(|attrs| attrs.split(',').any(|attr| attr.trim() == "METHOD=NONE"))
+}
+
+/// Normalize URL - handle relative URLs
+pub fn normalize_url(base_url: &str, relative_url: &str) -> String {
+    if relative_url.starts_with("http://") || relative_url.starts_with("https://") {
+        return relative_url.to_string();
+    }
+
+    if relative_url.starts_with("//") {
+        return format!("https:{}", relative_url);
+    }
+
+    let base = match Url::parse(base_url) {
+        Ok(url) => url,
+        Err(_) => return relative_url.to_string(),
+    };
+
+    if relative_url.starts_with('/') {
+        if let Some(domain) = base.domain() {
+            return format!("{}://{}{}", base.scheme(), domain, relative_url);
+        }
+    }
+
+    match base.join(relative_url) {
+        Ok(url) => url.to_string(),
+        Err(_) => relative_url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = "#EXTM3U
+#EXT-X-STREAM-INF:BANDWIDTH=48000,CODECS=\"mp4a.40.5\"
+low/index.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=192000,CODECS=\"mp4a.40.2\"
+high/index.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=96000,CODECS=\"mp4a.40.2\"
+mid/index.m3u8
+";
+
+    #[test]
+    fn test_parse_master_variants() {
+        let parsed = parse_playlist(MASTER, "https://example.com/live/master.m3u8").unwrap();
+        let ParsedPlaylist::Master(variants) = parsed else {
+            panic!("expected a master playlist");
+        };
+        let bandwidths: Vec<u64> = variants.iter().map(|v| v.bandwidth).collect();
+        assert_eq!(bandwidths, vec![192000, 96000, 48000]);
+        assert_eq!(variants[0].url, "https://example.com/live/high/index.m3u8");
+        assert_eq!(variants[0].codecs.as_deref(), Some("mp4a.40.2"));
+    }
+
+    #[test]
+    fn test_media_parser_rejects_master() {
+        let err = parse_m3u8(MASTER, "https://a/b.m3u8")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("master playlist with 3 variants"));
+
+        let parsed = parse_playlist("#EXTM3U\n#EXTINF:5.0,\na.aac\n", "https://a/b.m3u8").unwrap();
+        let ParsedPlaylist::Media(segments) = parsed else {
+            panic!("expected a media playlist");
+        };
+        assert_eq!(segments[0].url, "https://a/a.aac");
+    }
+
+    #[test]
+    fn test_select_variant() {
+        let ParsedPlaylist::Master(variants) = parse_playlist(MASTER, "https://a/b.m3u8").unwrap()
+        else {
+            panic!("expected a master playlist");
+        };
+        assert_eq!(select_variant(&variants, None), 0);
+        assert_eq!(select_variant(&variants, Some(100_000)), 1);
+        assert_eq!(select_variant(&variants, Some(10_000)), 2);
+    }
+
+    #[test]
+    fn test_media_sequence_and_key_carry_over() {
+        let playlist = "#EXTM3U
+#EXT-X-TARGETDURATION:5
+#EXT-X-MEDIA-SEQUENCE:1042
+#EXT-X-KEY:METHOD=AES-128,URI=\"key.bin\"
+#EXTINF:5.0,
+seg1042.aac
+#EXTINF:5.0,
+seg1043.aac
+#EXT-X-KEY:METHOD=NONE
+#EXTINF:5.0,
+seg1044.aac
+#EXT-X-KEY:METHOD=SAMPLE-AES,URI=\"key2.bin\"
+#EXTINF:5.0,
+seg1045.aac
+";
+        let parsed = parse_media_m3u8(playlist, "https://example.com/live/index.m3u8").unwrap();
+        assert_eq!(parsed.target_duration, 5.0);
+        assert!(!parsed.end_list);
+        let segments = parsed.segments;
+        let seq: Vec<u64> = segments.iter().map(|s| s.seq_no).collect();
+        assert_eq!(seq, vec![1042, 1043, 1044, 1045]);
+        let key = Some("https://example.com/live/key.bin".to_string());
+        assert_eq!(segments[0].key_url, key);
+        assert_eq!(segments[1].key_url, key);
+        assert_eq!(segments[2].key_url, None);
+        assert_eq!(segments[1].key_method, EncryptionMethod::Aes128);
+        assert_eq!(segments[3].key_method, EncryptionMethod::SampleAes);
+    }
+
+    #[test]
+    fn test_end_list() {
+        let playlist = "#EXTM3U
+#EXT-X-TARGETDURATION:10
+#EXTINF:10.0,
+seg0.aac
+#EXT-X-ENDLIST
+";
+        let parsed = parse_media_m3u8(playlist, "https://example.com/vod/index.m3u8").unwrap();
+        assert!(parsed.end_list);
+    }
+
+    #[test]
+    fn test_program_date_time_follows_on() {
+        let playlist = "#EXTM3U
+#EXT-X-TARGETDURATION:5
+#EXT-X-PROGRAM-DATE-TIME:2025-01-02T09:00:00.000+09:00
+#EXTINF:5.0,
+a.aac
+#EXTINF:5.0,
+b.aac
+";
+        let segments = parse_m3u8(playlist, "https://example.com/index.m3u8").unwrap();
+        let times: Vec<String> = segments
+            .iter()
+            .map(|s| s.program_date_time.unwrap().to_rfc3339())
+            .collect();
+        assert_eq!(
+            times,
+            vec!["2025-01-02T09:00:00+09:00", "2025-01-02T09:00:05+09:00"]
+        );
+    }
+
+    #[test]
+    fn test_map_applies_to_following_segments() {
+        let playlist = "#EXTM3U
+#EXT-X-TARGETDURATION:5
+#EXT-X-MAP:URI=\"init.mp4\"
+#EXTINF:5.0,
+a.m4s
+#EXTINF:5.0,
+b.m4s
+";
+        let segments = parse_m3u8(playlist, "https://example.com/live/index.m3u8").unwrap();
+        let init = Some("https://example.com/live/init.mp4".to_string());
+        assert_eq!(segments[0].map_url, init);
+        assert_eq!(segments[1].map_url, init);
+    }
+
+    #[test]
+    fn test_byte_ranges_follow_on() {
+        let playlist = "#EXTM3U
+#EXT-X-TARGETDURATION:5
+#EXTINF:5.0,
+#EXT-X-BYTERANGE:1000@200
+all.aac
+#EXTINF:5.0,
+#EXT-X-BYTERANGE:500
+all.aac
+#EXTINF:5.0,
+single.aac
+";
+        let segments = parse_m3u8(playlist, "https://example.com/index.m3u8").unwrap();
+        let ranges: Vec<Option<ByteRange>> = segments.iter().map(|s| s.byte_range).collect();
+        assert_eq!(
+            ranges,
+            vec![
+                Some(ByteRange {
+                    offset: 200,
+                    length: 1000
+                }),
+                Some(ByteRange {
+                    offset: 1200,
+                    length: 500
+                }),
+                None,
+            ]
+        );
+        assert_eq!(ranges[1].unwrap().header_value(), "bytes=1200-1699");
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        let base = "https://example.com/path/to/playlist.m3u8";
+
+        assert_eq!(
+            normalize_url(base, "https://other.com/segment.ts"),
+            "https://other.com/segment.ts"
+        );
+
+        assert_eq!(
+            normalize_url(base, "//cdn.example.com/segment.ts"),
+            "https://cdn.example.com/segment.ts"
+        );
+
+        assert_eq!(
+            normalize_url(base, "/absolute/segment.ts"),
+            "https://example.com/absolute/segment.ts"
+        );
+
+        assert_eq!(
+            normalize_url(base, "segment.ts"),
+            "https://example.com/path/to/segment.ts"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! In-memory [`RadioHttp`] for exercising the pipeline without the network,
+//! and canned program listings to serve from it.
+
+use anyhow::Result;
+#[cfg(any(test, feature = "mock-server"))]
+use chrono::{DateTime, FixedOffset};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::client::RadioHttp;
+use crate::types::ByteRange;
+#[cfg(any(test, feature = "mock-server"))]
+use crate::types::StreamData;
+
+/// Serves canned responses by URL and records what was requested
+///
+/// Responses can be replaced while a stream is running, e.g. to advance a
+/// live playlist between reloads.
+#[derive(Default)]
+pub struct MockHttp {
+    responses: Mutex<HashMap<String, Vec<u8>>>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl MockHttp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `body` for `url`, replacing any earlier response
+    pub fn insert(&self, url: impl Into<String>, body: impl Into<Vec<u8>>) {
+        lock(&self.responses).insert(url.into(), body.into());
+    }
+
+    /// Every URL requested so far, in order
+    pub fn requests(&self) -> Vec<String> {
+        lock(&self.requests).clone()
+    }
+
+    fn get(&self, url: &str) -> Result<Vec<u8>> {
+        lock(&self.requests).push(url.to_string());
+        lock(&self.responses)
+            .get(url)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No mock response for {}", url))
+    }
+}
+
+impl RadioHttp for MockHttp {
+    async fn fetch_m3u8(&self, url: &str) -> Result<String> {
+        Ok(String::from_utf8_lossy(&self.get(url)?).into_owned())
+    }
+
+    async fn fetch_key(&self, key_url: &str) -> Result<Vec<u8>> {
+        let key = self.get(key_url)?;
+        if key.len() != 16 {
+            anyhow::bail!("Invalid key length: expected 16, got {}", key.len());
+        }
+        Ok(key)
+    }
+
+    async fn fetch_segment(&self, url: &str, range: Option<ByteRange>) -> Result<Vec<u8>> {
+        let data = self.get(url)?;
+        match range {
+            Some(range) => {
+                let start = range.offset as usize;
+                let end = start + range.length as usize;
+                data.get(start..end).map(<[u8]>::to_vec).ok_or_else(|| {
+                    anyhow::anyhow!("Byte range {}-{} is past the end of {}", start, end, url)
+                })
+            }
+            None => Ok(data),
+        }
+    }
+}
+
+/// Area `area`, `areajp` in Japanese, whose FM live playlist is at
+/// `https://example.com/fm/master.m3u8`
+#[cfg(any(test, feature = "mock-server"))]
+pub fn stream_data(area: &str, areajp: &str) -> StreamData {
+    StreamData {
+        areajp: areajp.to_string(),
+        area: area.to_string(),
+        apikey: String::new(),
+        areakey: "130".to_string(),
+        r1hls: String::new(),
+        r2hls: String::new(),
+        fmhls: "https://example.com/fm/master.m3u8".to_string(),
+    }
+}
+
+/// A `BroadcastEvent` as NHK's program listings give it, named `name` and
+/// airing from `start` to `end`, with every other required field empty
+#[cfg(any(test, feature = "mock-server"))]
+pub fn broadcast_event(
+    name: &str,
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "type": "BroadcastEvent",
+        "id": name,
+        "name": name,
+        "description": "",
+        "startDate": start.to_rfc3339(),
+        "endDate": end.to_rfc3339(),
+        "location": { "id": "130", "name": "東京" },
+        "identifierGroup": {},
+        "misc": {
+            "displayVideoMode": "", "displayVideoRange": "", "supportCaption": false,
+            "supportSign": false, "supportHybridcast": false,
+            "supportDataBroadcast": false, "isInteractive": false,
+            "isChangeable": false, "releaseLevel": "", "programType": "",
+            "coverage": "", "eventShareStatus": "", "playControlSimul": false
+        },
+        "url": "",
+        "isLiveBroadcast": true,
+        "detailedDescription": {},
+        "duration": ""
+    })
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serves_ranges_and_records_requests() {
+        let http = MockHttp::new();
+        http.insert("https://example.com/a.aac", b"0123456789".to_vec());
+
+        let range = ByteRange {
+            offset: 2,
+            length: 3,
+        };
+        let data = http
+            .fetch_segment("https://example.com/a.aac", Some(range))
+            .await
+            .unwrap();
+        assert_eq!(data, b"234");
+        assert!(
+            http.fetch_m3u8("https://example.com/missing")
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            http.requests(),
+            ["https://example.com/a.aac", "https://example.com/missing"]
+        );
+    }
+}
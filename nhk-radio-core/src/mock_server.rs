@@ -0,0 +1,289 @@
+//! A local imitation of NHK's servers for end-to-end tests (`mock-server`
+//! feature).
+//!
+//! [`MockServer`] serves over HTTP what the player fetches from NHK: a
+//! station config with one area, master and live media playlists for each
+//! channel, AES-128 encrypted segments of silent AAC, and program listings.
+//! A client built from [`MockServer::http_options`] finds all of it through
+//! the config, so the whole pipeline runs as it does against the real
+//! service, without the network.
+//!
+//! Where [`MockHttp`](crate::mock::MockHttp) stands in for the client, this
+//! stands in for the server, covering the client itself too.
+
+use aes::cipher::{BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
+use anyhow::Result;
+use axum::{
+    Router,
+    extract::{Path, Request, State},
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::{DateTime, Duration, DurationRound, FixedOffset, Local};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::client::HttpOptions;
+
+/// The one area in the config
+pub const AREA: &str = "tokyo";
+
+const AREA_KEY: &str = "130";
+
+/// Key every segment is encrypted with
+pub const KEY: [u8; 16] = *b"mock-server-key!";
+
+/// Sample rate of the segments' AAC, which is mono
+pub const SAMPLE_RATE: u32 = 48_000;
+
+/// AAC frames in each segment, about a second of audio
+pub const SEGMENT_FRAMES: usize = 47;
+
+/// Samples of each AAC frame
+pub const FRAME_SAMPLES: usize = 1024;
+
+/// Segments listed in a live playlist; one is added every segment duration
+/// after the server starts
+pub const WINDOW_SEGMENTS: u64 = 6;
+
+/// Title of the program on air on every channel
+pub const PROGRAM_TITLE: &str = "テスト放送";
+
+/// One ADTS frame of AAC-LC, mono at 48 kHz, whose single channel has no
+/// spectral data and so decodes to silence
+const SILENT_FRAME: [u8; 11] = [
+    0xff, 0xf1, 0x4c, 0x40, 0x01, 0x7f, 0xfc, 0x00, 0xc8, 0x00, 0x07,
+];
+
+/// NHK's service names for the channels
+const CHANNELS: [&str; 3] = ["r1", "r2", "fm"];
+
+/// Length of a segment in seconds
+fn segment_secs() -> f64 {
+    (SEGMENT_FRAMES * FRAME_SAMPLES) as f64 / SAMPLE_RATE as f64
+}
+
+/// A running server, stopped when dropped
+pub struct MockServer {
+    state: Arc<ServerState>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+struct ServerState {
+    base_url: String,
+    started: Instant,
+    /// Broadcast time of the start of segment 0
+    epoch: DateTime<FixedOffset>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl MockServer {
+    /// Serve on a free port on the loopback interface
+    ///
+    /// Must be called within a Tokio runtime.
+    pub async fn start() -> Result<Self> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let state = Arc::new(ServerState {
+            base_url: format!("http://{}", listener.local_addr()?),
+            started: Instant::now(),
+            epoch: Local::now().fixed_offset(),
+            requests: Mutex::new(Vec::new()),
+        });
+
+        let app = Router::new()
+            .route("/radio/config/config_web.xml", get(config))
+            .route("/hls/{channel}/master.m3u8", get(master_playlist))
+            .route("/hls/{channel}/media.m3u8", get(media_playlist))
+            .route("/hls/{channel}/key", get(key))
+            .route("/hls/{channel}/{segment}", get(segment))
+            .route("/pg/now/{area}/now.json", get(now_on_air))
+            .route("/pg/date/{service}/{area}/{date}", get(day_schedule))
+            .layer(middleware::from_fn_with_state(state.clone(), record))
+            .with_state(state.clone());
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                log::error!("Mock server stopped: {}", e);
+            }
+        });
+        Ok(MockServer { state, task })
+    }
+
+    /// Full URL of `path` on the server
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.state.base_url, path)
+    }
+
+    /// HTTP settings that lead a client here, failing fast instead of
+    /// retrying
+    pub fn http_options(&self) -> HttpOptions {
+        HttpOptions {
+            config_url: self.url("/radio/config/config_web.xml"),
+            ondemand_api_url: self.url("/ondemand"),
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Path of every request served so far, in order
+    pub fn requests(&self) -> Vec<String> {
+        self.state
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn record(State(state): State<Arc<ServerState>>, request: Request, next: Next) -> Response {
+    state
+        .requests
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(request.uri().path().to_string());
+    next.run(request).await
+}
+
+async fn config(State(state): State<Arc<ServerState>>) -> Response {
+    let base = &state.base_url;
+    let xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<radiru_config>
+  <info>Mock server</info>
+  <stream_url>
+    <data>
+      <areajp>東京</areajp>
+      <area>{AREA}</area>
+      <apikey>001</apikey>
+      <areakey>{AREA_KEY}</areakey>
+      <r1hls>{base}/hls/r1/master.m3u8</r1hls>
+      <r2hls>{base}/hls/r2/master.m3u8</r2hls>
+      <fmhls>{base}/hls/fm/master.m3u8</fmhls>
+    </data>
+  </stream_url>
+  <url_program_noa>{base}/pg/now/{{area}}/now.json</url_program_noa>
+  <url_program_day>{base}/pg/date/{{service}}/{{area}}/{{date}}.json</url_program_day>
+  <url_program_detail>{base}/pg/list/{{area}}/{{service}}/{{date}}.json</url_program_detail>
+  <radiru_twitter_timeline>{base}/timeline</radiru_twitter_timeline>
+</radiru_config>
+"#
+    );
+    ([(header::CONTENT_TYPE, "application/xml")], xml).into_response()
+}
+
+async fn master_playlist(Path(channel): Path<String>) -> Response {
+    if !CHANNELS.contains(&channel.as_str()) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=48000,CODECS=\"mp4a.40.2\"\nmedia.m3u8\n".into_response()
+}
+
+/// The newest [`WINDOW_SEGMENTS`] segments as of now
+async fn media_playlist(
+    State(state): State<Arc<ServerState>>,
+    Path(channel): Path<String>,
+) -> Response {
+    if !CHANNELS.contains(&channel.as_str()) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let newest =
+        WINDOW_SEGMENTS - 1 + (state.started.elapsed().as_secs_f64() / segment_secs()) as u64;
+    let first = newest + 1 - WINDOW_SEGMENTS;
+
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:{}\n\
+         #EXT-X-KEY:METHOD=AES-128,URI=\"key\"\n",
+        segment_secs().ceil(),
+        first
+    );
+    for seq_no in first..=newest {
+        let start =
+            state.epoch + Duration::milliseconds((seq_no as f64 * segment_secs() * 1000.0) as i64);
+        playlist.push_str(&format!(
+            "#EXT-X-PROGRAM-DATE-TIME:{}\n#EXTINF:{:.5},\nseg{}.aac\n",
+            start.to_rfc3339(),
+            segment_secs(),
+            seq_no
+        ));
+    }
+    playlist.into_response()
+}
+
+async fn key(Path(channel): Path<String>) -> Response {
+    if !CHANNELS.contains(&channel.as_str()) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    KEY.to_vec().into_response()
+}
+
+/// `seg{n}.aac`: [`SEGMENT_FRAMES`] frames of silence, encrypted with [`KEY`] and the sequence number as the IV
+async fn segment(Path((channel, name)): Path<(String, String)>) -> Response {
+    let seq_no = name
+        .strip_prefix("seg")
+        .and_then(|rest| rest.strip_suffix(".aac"))
+        .and_then(|n| n.parse::<u64>().ok());
+    let Some(seq_no) = seq_no.filter(|_| CHANNELS.contains(&channel.as_str())) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let mut iv = [0u8; 16];
+    iv[8..].copy_from_slice(&seq_no.to_be_bytes());
+    let plain = SILENT_FRAME.repeat(SEGMENT_FRAMES);
+    let mut buf = plain.clone();
+    buf.resize(plain.len() + 16 - plain.len() % 16, 0);
+    let encrypted = cbc::Encryptor::<aes::Aes128>::new(&KEY.into(), &iv.into())
+        .encrypt_padded_mut::<Pkcs7>(&mut buf, plain.len())
+        .map(<[u8]>::to_vec);
+    match encrypted {
+        Ok(data) => data.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// The hour on air now, and the programs either side of it
+fn on_air_hour() -> (DateTime<FixedOffset>, DateTime<FixedOffset>) {
+    let now = Local::now().fixed_offset();
+    let start = now.duration_trunc(Duration::hours(1)).unwrap_or(now);
+    (start, start + Duration::hours(1))
+}
+
+fn broadcast_event(title: &str, start: DateTime<FixedOffset>) -> serde_json::Value {
+    let mut event = crate::mock::broadcast_event(title, start, start + Duration::hours(1));
+    event["id"] = format!("mock-{}", start.timestamp()).into();
+    event["location"]["id"] = AREA_KEY.into();
+    event["duration"] = "PT1H".into();
+    event
+}
+
+async fn now_on_air(Path(area): Path<String>) -> Response {
+    if area != AREA_KEY {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let (start, end) = on_air_hour();
+    let channel = serde_json::json!({
+        "previous": broadcast_event("前の番組", start - Duration::hours(1)),
+        "present": broadcast_event(PROGRAM_TITLE, start),
+        "following": broadcast_event("次の番組", end),
+    });
+    axum::Json(serde_json::json!({ "r1": channel, "r2": channel, "r3": channel })).into_response()
+}
+
+async fn day_schedule(Path((_service, area, _date)): Path<(String, String, String)>) -> Response {
+    if area != AREA_KEY {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let (start, end) = on_air_hour();
+    axum::Json(serde_json::json!({
+        "publication": [
+            { "name": PROGRAM_TITLE, "startDate": start.to_rfc3339(), "endDate": end.to_rfc3339() }
+        ]
+    }))
+    .into_response()
+}
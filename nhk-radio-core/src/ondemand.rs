@@ -0,0 +1,270 @@
+//! Playback of 聴き逃し (on-demand) episodes.
+//!
+//! Unlike the live loop, a VOD playlist is fetched once and walked from a
+//! seek position. Segments are fetched only a little ahead of the playback
+//! clock so seeking stays instant and memory stays flat.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use crate::audio_queue::AudioSender;
+use crate::client::RadioHttp;
+use crate::crypto::decrypt_segment;
+use crate::decoder::{StreamDecoder, decode_fmp4_to_pcm};
+use crate::m3u8::{ParsedPlaylist, parse_m3u8, parse_playlist};
+use crate::types::Segment;
+
+/// How far ahead of the playback position segments are fetched
+pub const PREFETCH_SECS: f64 = 20.0;
+
+/// All segments of an on-demand episode
+pub struct VodPlaylist {
+    pub segments: Vec<Segment>,
+    /// Start offset of each segment in seconds
+    starts: Vec<f64>,
+    pub duration: f64,
+}
+
+impl VodPlaylist {
+    pub async fn load(client: &impl RadioHttp, url: &str) -> Result<Self> {
+        let content = client.fetch_m3u8(url).await?;
+        let segments = match parse_playlist(&content, url)? {
+            ParsedPlaylist::Media(segments) => segments,
+            ParsedPlaylist::Master(variants) => {
+                // Episodes have no live edge to keep up with, so take the best variant
+                let media_url = &variants[0].url;
+                let content = client.fetch_m3u8(media_url).await?;
+                parse_m3u8(&content, media_url)?
+            }
+        };
+        if segments.is_empty() {
+            anyhow::bail!("Episode playlist has no segments");
+        }
+        Ok(Self::new(segments))
+    }
+
+    pub fn new(segments: Vec<Segment>) -> Self {
+        let mut starts = Vec::with_capacity(segments.len());
+        let mut duration = 0.0;
+        for segment in &segments {
+            starts.push(duration);
+            duration += segment.duration;
+        }
+        Self {
+            segments,
+            starts,
+            duration,
+        }
+    }
+
+    /// Index and start time of the segment containing `secs`
+    pub fn locate(&self, secs: f64) -> (usize, f64) {
+        let secs = secs.clamp(0.0, self.duration);
+        let index = self
+            .starts
+            .partition_point(|&start| start <= secs)
+            .saturating_sub(1);
+        (index, self.starts.get(index).copied().unwrap_or(0.0))
+    }
+}
+
+/// Wall-clock playback position that stops while paused
+struct PlayClock {
+    base: f64,
+    started: Option<Instant>,
+    paused_at: Option<Instant>,
+    paused_total: Duration,
+}
+
+impl PlayClock {
+    fn new(base: f64) -> Self {
+        Self {
+            base,
+            started: None,
+            paused_at: None,
+            paused_total: Duration::ZERO,
+        }
+    }
+
+    fn start(&mut self) {
+        if self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        match (paused, self.paused_at) {
+            (true, None) => self.paused_at = Some(Instant::now()),
+            (false, Some(at)) => {
+                self.paused_total += at.elapsed();
+                self.paused_at = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn position(&self) -> f64 {
+        let Some(started) = self.started else {
+            return self.base;
+        };
+        let paused = self.paused_total + self.paused_at.map_or(Duration::ZERO, |at| at.elapsed());
+        self.base + started.elapsed().saturating_sub(paused).as_secs_f64()
+    }
+}
+
+/// Feed an episode to the audio thread, restarting whenever a seek arrives
+///
+/// `seek_rx` carries the requested position in seconds; the current position
+/// is published on `position_tx`.
+pub async fn run_vod_loop<H: RadioHttp + 'static>(
+    client: Arc<H>,
+    playlist: Arc<VodPlaylist>,
+    mut seek_rx: watch::Receiver<f64>,
+    paused_rx: watch::Receiver<bool>,
+    audio_tx: AudioSender,
+    position_tx: watch::Sender<f64>,
+) -> Result<()> {
+    let mut keys: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut inits: HashMap<String, Vec<u8>> = HashMap::new();
+
+    'seek: loop {
+        let (mut index, start) = playlist.locate(*seek_rx.borrow_and_update());
+        let mut clock = PlayClock::new(start);
+        let mut fed_until = start;
+        // Seeking breaks the segment chain, so start over with a fresh decoder
+        let mut decoder = StreamDecoder::new();
+
+        loop {
+            if seek_rx.has_changed().unwrap_or(false) {
+                let _ = audio_tx.send(vec![]);
+                continue 'seek;
+            }
+
+            clock.set_paused(*paused_rx.borrow());
+            let position = clock.position().min(playlist.duration);
+            let _ = position_tx.send(position);
+
+            let finished = index >= playlist.segments.len();
+            if finished || fed_until - position > PREFETCH_SECS {
+                if finished && position >= playlist.duration {
+                    // Wait for a seek back into the episode
+                    if seek_rx.changed().await.is_err() {
+                        return Ok(());
+                    }
+                    let _ = audio_tx.send(vec![]);
+                    continue 'seek;
+                }
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                continue;
+            }
+
+            let segment = &playlist.segments[index];
+            index += 1;
+            fed_until += segment.duration;
+
+            let mut data = match client.fetch_segment(&segment.url, segment.byte_range).await {
+                Ok(d) => d,
+                Err(e) => {
+                    log::error!("Failed to fetch segment: {}", e);
+                    continue;
+                }
+            };
+
+            if let Some(ref key_url) = segment.key_url {
+                if !keys.contains_key(key_url) {
+                    match client.fetch_key(key_url).await {
+                        Ok(key) => {
+                            keys.insert(key_url.clone(), key);
+                        }
+                        Err(e) => {
+                            log::error!("Failed to fetch key: {}", e);
+                            continue;
+                        }
+                    }
+                }
+                let key = &keys[key_url];
+                let iv = segment.iv.as_deref();
+                data = match decrypt_segment(&data, key, iv, segment.seq_no, segment.key_method) {
+                    Ok(d) => {
+                        client.dump_decrypted(&segment.url, segment.seq_no, &d);
+                        d
+                    }
+                    Err(e) => {
+                        log::error!("Failed to decrypt: {}", e);
+                        continue;
+                    }
+                };
+            }
+
+            if let Some(ref map_url) = segment.map_url
+                && !inits.contains_key(map_url)
+            {
+                match client.fetch_segment(map_url, None).await {
+                    Ok(init) => {
+                        inits.insert(map_url.clone(), init);
+                    }
+                    Err(e) => {
+                        log::error!("Failed to fetch init section: {}", e);
+                        continue;
+                    }
+                }
+            }
+
+            let decoded = match segment.map_url {
+                Some(ref map_url) => decode_fmp4_to_pcm(&inits[map_url], &data),
+                None => decoder.decode(&data),
+            };
+            match decoded {
+                Ok(chunk) if !chunk.samples.is_empty() => {
+                    clock.start();
+                    if audio_tx.send(chunk.to_playback()).is_err() {
+                        return Ok(());
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => log::debug!("Failed to decode AAC: {}", e),
+            }
+        }
+    }
+}
+
+/// Format seconds as `H:MM:SS` or `M:SS`
+pub fn format_position(secs: f64) -> String {
+    let total = secs.max(0.0) as u64;
+    let (h, m, s) = (total / 3600, (total / 60) % 60, total % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(duration: f64) -> Segment {
+        Segment {
+            duration,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_locate() {
+        let playlist = VodPlaylist::new(vec![segment(5.0), segment(5.0), segment(5.0)]);
+        assert_eq!(playlist.duration, 15.0);
+        assert_eq!(playlist.locate(0.0), (0, 0.0));
+        assert_eq!(playlist.locate(7.5), (1, 5.0));
+        assert_eq!(playlist.locate(100.0), (2, 10.0));
+    }
+
+    #[test]
+    fn test_format_position() {
+        assert_eq!(format_position(65.0), "1:05");
+        assert_eq!(format_position(3725.0), "1:02:05");
+    }
+}
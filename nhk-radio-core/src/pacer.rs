@@ -0,0 +1,162 @@
+//! Release of decoded audio at the pace it plays.
+//!
+//! Segments arrive in bursts: several at once when tuning in or after the
+//! network comes back, then one per playlist reload. The stream loop hands
+//! each to a [`Pacer`], which passes audio on whenever the outputs hold no
+//! more than their target, going by segment durations and the wall clock.
+//! The rest waits here, and once more than the target is waiting the oldest
+//! is dropped, so how far playback trails live stays bounded however long
+//! the session runs and however the network behaves.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::player::{CHANNELS, SAMPLE_RATE};
+
+/// Audio waiting to go to the outputs, in order
+#[derive(Debug)]
+pub struct Pacer<T> {
+    /// How much audio the outputs should hold ahead of what is heard
+    lead: Duration,
+    /// When everything released so far will have been heard
+    played_until: Option<Instant>,
+    waiting: VecDeque<(T, usize)>,
+    waiting_samples: usize,
+    /// Samples discarded for waiting too long, ever
+    dropped: u64,
+}
+
+impl<T> Pacer<T> {
+    /// Keep the outputs about `lead` ahead of playback
+    pub fn new(lead: Duration) -> Self {
+        Pacer {
+            lead,
+            played_until: None,
+            waiting: VecDeque::new(),
+            waiting_samples: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Queue `item`, holding `samples` interleaved samples, dropping the
+    /// oldest waiting audio beyond the target
+    pub fn push(&mut self, item: T, samples: usize) {
+        self.waiting_samples += samples;
+        self.waiting.push_back((item, samples));
+        let capacity = duration_samples(self.lead);
+        while self.waiting_samples > capacity && self.waiting.len() > 1 {
+            if let Some((_, old)) = self.waiting.pop_front() {
+                self.waiting_samples -= old;
+                self.dropped += old as u64;
+                log::debug!(
+                    "Audio backlog over the buffer target, dropped {} samples",
+                    old
+                );
+            }
+        }
+    }
+
+    /// When the oldest waiting audio is due, or `None` if nothing waits
+    pub fn due(&self, now: Instant) -> Option<Instant> {
+        if self.waiting.is_empty() {
+            return None;
+        }
+        let played_until = self.played_until.unwrap_or(now).max(now);
+        Some(played_until.checked_sub(self.lead).unwrap_or(now).max(now))
+    }
+
+    /// Take the oldest waiting audio if it is due at `now`
+    pub fn pop(&mut self, now: Instant) -> Option<T> {
+        if self.due(now)? > now {
+            return None;
+        }
+        let (item, samples) = self.waiting.pop_front()?;
+        self.waiting_samples -= samples;
+        // After running dry the outputs pick up again from now
+        let from = self.played_until.unwrap_or(now).max(now);
+        self.played_until = Some(from + samples_duration(samples));
+        Some(item)
+    }
+
+    /// Forget what is waiting and what was released, e.g. on a channel
+    /// change, after which the outputs start empty
+    pub fn clear(&mut self) {
+        self.waiting.clear();
+        self.waiting_samples = 0;
+        self.played_until = None;
+    }
+
+    /// Samples dropped so far because too much was waiting
+    pub fn dropped_samples(&self) -> u64 {
+        self.dropped
+    }
+}
+
+fn samples_duration(samples: usize) -> Duration {
+    Duration::from_secs_f64(samples as f64 / (SAMPLE_RATE as f64 * CHANNELS as f64))
+}
+
+fn duration_samples(duration: Duration) -> usize {
+    (duration.as_secs_f64() * SAMPLE_RATE as f64 * CHANNELS as f64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: usize = SAMPLE_RATE as usize * CHANNELS as usize;
+
+    #[test]
+    fn test_releases_up_to_the_lead() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(Duration::from_secs(10));
+        // A burst goes out until the outputs hold the lead
+        for segment in 0..3 {
+            pacer.push(segment, 5 * SECOND);
+            assert_eq!(pacer.pop(start), Some(segment));
+        }
+        pacer.push(3, 5 * SECOND);
+        assert_eq!(pacer.pop(start), None);
+        // Then one segment each time one has been heard
+        assert_eq!(pacer.due(start), Some(start + Duration::from_secs(5)));
+        assert_eq!(pacer.pop(start + Duration::from_secs(5)), Some(3));
+        assert_eq!(pacer.due(start + Duration::from_secs(5)), None);
+    }
+
+    #[test]
+    fn test_backlog_is_bounded() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(Duration::from_secs(10));
+        pacer.push(0, 10 * SECOND);
+        assert_eq!(pacer.pop(start), Some(0));
+        pacer.push(1, 5 * SECOND);
+        assert_eq!(pacer.pop(start), Some(1));
+        // A long outage comes back as a burst bigger than the target
+        for segment in 2..6 {
+            pacer.push(segment, 5 * SECOND);
+        }
+        assert_eq!(pacer.dropped_samples(), 10 * SECOND as u64);
+        // The outputs ran dry meanwhile, so what is left goes out at once
+        let later = start + Duration::from_secs(60);
+        assert_eq!(pacer.pop(later), Some(4));
+        assert_eq!(pacer.pop(later), Some(5));
+    }
+
+    #[test]
+    fn test_clear() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(Duration::from_secs(5));
+        for segment in 0..3 {
+            pacer.push(segment, 5 * SECOND);
+            pacer.pop(start);
+        }
+        pacer.push(3, 5 * SECOND);
+        assert_eq!(pacer.pop(start), None);
+        pacer.clear();
+        assert_eq!(pacer.due(start), None);
+        // A new channel starts with empty outputs
+        pacer.push(4, 5 * SECOND);
+        assert_eq!(pacer.pop(start), Some(4));
+    }
+}
@@ -0,0 +1,221 @@
+//! Writing decoded audio to stdout or a file.
+//!
+//! Each pipe is one more output at the end of the pipeline, next to or
+//! instead of the sound device (see [`crate::fanout`]), so the player can
+//! feed tools like `sox` or `ffmpeg`:
+//!
+//! ```sh
+//! nhk-radio-player play tokyo fm --output - | sox -t raw -r 48000 -e signed -b 16 -c 2 - out.flac
+//! ```
+
+use anyhow::Result;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+
+use crate::audio_queue::AudioReceiver;
+use crate::decoder::PcmSpec;
+use crate::player::PlaybackEvent;
+
+/// Layout of the written audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcmFormat {
+    /// Headerless interleaved signed 16-bit little-endian samples
+    Raw,
+    /// The same samples behind a WAV header
+    Wav,
+}
+
+/// A file or stdout that decoded audio is written to
+///
+/// Parsed from `-` (raw to stdout), `wav:-`, `raw:PATH`, `wav:PATH`, or a bare
+/// path whose `.wav` extension selects WAV.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcmPipe {
+    pub format: PcmFormat,
+    /// File to write, or `None` for stdout
+    pub path: Option<PathBuf>,
+}
+
+impl PcmPipe {
+    pub fn is_stdout(&self) -> bool {
+        self.path.is_none()
+    }
+
+    fn open(&self) -> Result<Box<dyn Write>> {
+        Ok(match self.path {
+            Some(ref path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(std::io::stdout().lock()),
+        })
+    }
+}
+
+impl std::fmt::Display for PcmPipe {
+    /// The target as given, e.g. `wav:out.wav` or `-`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = match self.format {
+            PcmFormat::Raw => "raw",
+            PcmFormat::Wav => "wav",
+        };
+        match self.path {
+            Some(ref path) => write!(f, "{}:{}", format, path.display()),
+            None => write!(f, "{}:-", format),
+        }
+    }
+}
+
+impl std::str::FromStr for PcmPipe {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (format, target) = match s.split_once(':') {
+            Some(("raw", target)) => (Some(PcmFormat::Raw), target),
+            Some(("wav", target)) => (Some(PcmFormat::Wav), target),
+            _ => (None, s),
+        };
+        if target.is_empty() {
+            anyhow::bail!("Missing output path in {:?}; use - for stdout", s);
+        }
+
+        let path = (target != "-").then(|| PathBuf::from(target));
+        let format = format.unwrap_or_else(|| match path {
+            Some(ref path)
+                if path
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("wav")) =>
+            {
+                PcmFormat::Wav
+            }
+            _ => PcmFormat::Raw,
+        });
+        Ok(PcmPipe { format, path })
+    }
+}
+
+/// Header for 16-bit WAV audio in `spec` with `data_len` bytes of samples
+///
+/// For a stream of unknown length, pass `u32::MAX`: the sizes are then left
+/// at their maximum, which readers take to mean "until the end of the
+/// stream".
+pub(crate) fn wav_header(spec: PcmSpec, data_len: u32) -> Vec<u8> {
+    let block_align = spec.channels * 2;
+    let byte_rate = spec.sample_rate * block_align as u32;
+
+    let mut header = Vec::with_capacity(WAV_HEADER_LEN);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&riff_len(data_len).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    header.extend_from_slice(&spec.channels.to_le_bytes());
+    header.extend_from_slice(&spec.sample_rate.to_le_bytes());
+    header.extend_from_slice(&byte_rate.to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    header
+}
+
+/// Length of a WAV header from [`wav_header`]
+pub(crate) const WAV_HEADER_LEN: usize = 44;
+
+/// The RIFF chunk size of a WAV file with `data_len` bytes of samples
+pub(crate) fn riff_len(data_len: u32) -> u32 {
+    data_len.saturating_add(WAV_HEADER_LEN as u32 - 8)
+}
+
+/// Write audio from `rx` to `pipe` until the sender goes away
+///
+/// Runs alongside or instead of
+/// [`run_audio_thread`](crate::player::run_audio_thread). Clears only mark where the next audio starts; nothing already written can
+/// be taken back.
+pub fn run_pipe_thread(
+    rx: AudioReceiver,
+    pipe: PcmPipe,
+    events: Sender<PlaybackEvent>,
+) -> Result<()> {
+    let mut out = pipe.open()?;
+    if pipe.format == PcmFormat::Wav {
+        out.write_all(&wav_header(PcmSpec::PLAYBACK, u32::MAX))?;
+    }
+
+    let mut waiting_for_new_samples = true;
+    loop {
+        match rx.recv_timeout(std::time::Duration::from_millis(100), true) {
+            Ok(samples) if samples.is_empty() => waiting_for_new_samples = true,
+            Ok(samples) => {
+                let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+                // A closed pipe means the reader is done with us
+                if let Err(e) = out.write_all(&bytes).and_then(|_| out.flush()) {
+                    log::info!("Audio output closed: {}", e);
+                    return Ok(());
+                }
+                if waiting_for_new_samples {
+                    let _ = events.send(PlaybackEvent::Started);
+                    waiting_for_new_samples = false;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pipe() {
+        let stdout_raw: PcmPipe = "-".parse().unwrap();
+        assert_eq!(stdout_raw.format, PcmFormat::Raw);
+        assert!(stdout_raw.is_stdout());
+
+        let stdout_wav: PcmPipe = "wav:-".parse().unwrap();
+        assert_eq!(stdout_wav.format, PcmFormat::Wav);
+        assert!(stdout_wav.is_stdout());
+
+        let file: PcmPipe = "out.WAV".parse().unwrap();
+        assert_eq!(file.format, PcmFormat::Wav);
+        assert_eq!(file.path, Some(PathBuf::from("out.WAV")));
+
+        let forced: PcmPipe = "raw:out.wav".parse().unwrap();
+        assert_eq!(forced.format, PcmFormat::Raw);
+
+        assert!("wav:".parse::<PcmPipe>().is_err());
+
+        // Shown the way it is given, so it parses back the same
+        for pipe in [stdout_wav, file, forced] {
+            assert_eq!(pipe.to_string().parse::<PcmPipe>().unwrap(), pipe);
+        }
+    }
+
+    #[test]
+    fn test_wav_header() {
+        let header = wav_header(PcmSpec::PLAYBACK, u32::MAX);
+        assert_eq!(header.len(), WAV_HEADER_LEN);
+        assert_eq!(&header[..4], b"RIFF");
+        assert_eq!(&header[36..40], b"data");
+        // 48 kHz stereo 16-bit
+        assert_eq!(
+            u32::from_le_bytes(header[28..32].try_into().unwrap()),
+            192_000
+        );
+        assert_eq!(
+            u32::from_le_bytes(header[4..8].try_into().unwrap()),
+            u32::MAX
+        );
+
+        let mono = wav_header(
+            PcmSpec {
+                sample_rate: 24000,
+                channels: 1,
+            },
+            1000,
+        );
+        assert_eq!(u32::from_le_bytes(mono[4..8].try_into().unwrap()), 1036);
+        assert_eq!(u32::from_le_bytes(mono[40..44].try_into().unwrap()), 1000);
+    }
+}
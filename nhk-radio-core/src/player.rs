@@ -0,0 +1,826 @@
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use futures::StreamExt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use crate::audio_queue::AudioReceiver;
+use crate::client::{HttpOptions, RadioHttp};
+use crate::decoder::StreamDecoder;
+use crate::device::open_output;
+use crate::dsp::Dsp;
+use crate::equalizer::EqPreset;
+use crate::fanout::AudioFanout;
+use crate::pacer::Pacer;
+use crate::pipe::PcmPipe;
+use crate::recorder::Recorder;
+use crate::stats::StreamStats;
+use crate::stream::{StreamOptions, segment_stream_with_stats};
+use crate::timeshift::TimeshiftBuffer;
+use crate::types::StreamData;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ChannelKind {
+    R1,
+    R2,
+    Fm,
+}
+
+impl ChannelKind {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ChannelKind::R1 => "ラジオ第1",
+            ChannelKind::R2 => "ラジオ第2",
+            ChannelKind::Fm => "FM",
+        }
+    }
+
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            ChannelKind::R1 => "R1",
+            ChannelKind::R2 => "R2",
+            ChannelKind::Fm => "FM",
+        }
+    }
+
+    pub fn get_url(&self, data: &StreamData) -> String {
+        match self {
+            ChannelKind::R1 => data.r1hls.clone(),
+            ChannelKind::R2 => data.r2hls.clone(),
+            ChannelKind::Fm => data.fmhls.clone(),
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ChannelKind::R1 => ChannelKind::R2,
+            ChannelKind::R2 => ChannelKind::Fm,
+            ChannelKind::Fm => ChannelKind::R1,
+        }
+    }
+
+    pub fn prev(&self) -> Self {
+        match self {
+            ChannelKind::R1 => ChannelKind::Fm,
+            ChannelKind::R2 => ChannelKind::R1,
+            ChannelKind::Fm => ChannelKind::R2,
+        }
+    }
+}
+
+impl std::str::FromStr for ChannelKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "r1" => Ok(ChannelKind::R1),
+            "r2" => Ok(ChannelKind::R2),
+            "fm" => Ok(ChannelKind::Fm),
+            _ => anyhow::bail!("Invalid kind: {}. Must be one of: r1, r2, fm", s),
+        }
+    }
+}
+
+/// Sample rate decoded audio is converted to for playback
+pub const SAMPLE_RATE: u32 = 48000;
+/// Channel count decoded audio is converted to for playback
+pub const CHANNELS: u16 = 2;
+
+/// Default length of the timeshift buffer
+pub const DEFAULT_TIMESHIFT_MINUTES: u32 = 5;
+
+/// Default stretch of near silence after which it is reported
+pub const DEFAULT_SILENCE_ALERT_SECS: u64 = 30;
+
+/// Options shared by the interactive and headless players
+pub struct PlayerOptions {
+    /// Initial volume in percent (0-100)
+    pub volume: u8,
+    pub recorder: Option<Recorder>,
+    /// How much decoded audio to keep for rewinding
+    pub timeshift_minutes: u32,
+    /// Seconds of audio to buffer ahead of playback
+    pub buffer_secs: u32,
+    /// Output device name, or the default device
+    pub device: Option<String>,
+    /// Play on the sound device
+    pub speaker: bool,
+    /// Also write decoded audio to each of these, e.g. a file and stdout
+    pub pipes: Vec<PcmPipe>,
+    /// Loudness in LUFS to normalize the audio to, or `None` to leave it as
+    /// broadcast
+    pub target_lufs: Option<f64>,
+    /// Equalizer curve to start with
+    pub equalizer: EqPreset,
+    /// How long the stream may stay near silent before
+    /// [`PlaybackEvent::Silence`] is sent, or `None` not to watch for it
+    pub silence_alert: Option<Duration>,
+    pub http: HttpOptions,
+    pub stream: StreamOptions,
+}
+
+/// Requests sent from the UI to the audio thread
+#[derive(Debug, Clone, Copy)]
+pub enum AudioCommand {
+    /// Jump back by the given duration within the timeshift buffer
+    Rewind(std::time::Duration),
+    /// Drop any timeshift and continue from the newest audio
+    Live,
+}
+
+/// Something that happened in the pipeline, reported back to the front end
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaybackEvent {
+    /// A different channel was requested; its audio follows shortly
+    ChannelChanged(ChannelKind),
+    /// Audio is playing again after starting, a channel change, or a clear
+    Started,
+    /// The output device stopped taking audio; it is being reopened
+    OutputLost,
+    /// Output was reopened after [`PlaybackEvent::OutputLost`] and playback
+    /// resumed at the live edge
+    OutputRestored,
+    /// Segments keep arriving but their audio has been near silent for a
+    /// while, so the broadcast itself has gone quiet
+    Silence,
+    /// Audible sound is back after [`PlaybackEvent::Silence`]
+    SoundReturned,
+    /// The stream loop or audio thread stopped with an error
+    Error(String),
+}
+
+/// Control inputs and feedback for the audio thread
+pub struct AudioControls {
+    pub paused_rx: watch::Receiver<bool>,
+    pub volume_rx: watch::Receiver<f32>,
+    /// Output device to play on; a change moves playback to the new device
+    pub device_rx: watch::Receiver<Option<String>>,
+    pub command_rx: std::sync::mpsc::Receiver<AudioCommand>,
+    /// Reports how many seconds playback is behind live due to rewinding
+    pub timeshift_tx: watch::Sender<f64>,
+    /// Reports how many seconds of received audio the listener has yet to hear
+    pub queued_tx: watch::Sender<f64>,
+    /// Reports the RMS level of each channel of what is heard now, from 0.0
+    /// to 1.0 of full scale
+    pub levels_tx: watch::Sender<[f32; 2]>,
+    /// Where audio played and running out of it are counted
+    pub stats: Arc<StreamStats>,
+}
+
+/// Inputs and outputs of the stream loop
+pub struct StreamControls {
+    pub channel_rx: watch::Receiver<ChannelKind>,
+    /// Area whose streams are played; a change retunes like a channel change
+    pub area_rx: watch::Receiver<StreamData>,
+    pub paused_rx: watch::Receiver<bool>,
+    /// The outputs decoded audio is delivered to
+    pub audio_tx: AudioFanout,
+    /// How much audio to deliver ahead of what is heard; the rest is held
+    /// back and sent as it falls due
+    pub buffer: Duration,
+    /// Wall-clock end of the newest audio sent, when the playlist carries
+    /// EXT-X-PROGRAM-DATE-TIME
+    pub live_edge_tx: watch::Sender<Option<DateTime<FixedOffset>>>,
+    /// Where fetches, failures, and dropped audio are counted
+    pub stats: Arc<StreamStats>,
+    /// Loudness to normalize what is played to, if any
+    pub target_lufs: Option<f64>,
+    /// Equalizer curve applied to what is played, changeable at any time
+    pub equalizer_rx: watch::Receiver<EqPreset>,
+    /// How long decoded audio may stay near silent before it is reported
+    pub silence_alert: Option<Duration>,
+    /// Where silence and its end are reported
+    pub events: std::sync::mpsc::Sender<PlaybackEvent>,
+    /// The recording the stream is teed into, started and stopped at will
+    pub recorder: Arc<Mutex<Option<Recorder>>>,
+}
+
+fn samples_to_secs(samples: usize) -> f64 {
+    samples as f64 / (SAMPLE_RATE as f64 * CHANNELS as f64)
+}
+
+fn secs_to_samples(secs: f64) -> usize {
+    (secs * SAMPLE_RATE as f64 * CHANNELS as f64) as usize
+}
+
+/// RMS level, from 0.0 to 1.0 of full scale, below which audio counts as
+/// silent: -60 dBFS, well under the quietest talk
+const SILENCE_LEVEL: f64 = 0.001;
+
+/// Tracks how long decoded audio has stayed near silent
+///
+/// Time is counted in audio received rather than on the clock, so a stalled
+/// download is never mistaken for a silent broadcast.
+struct SilenceDetector {
+    limit: usize,
+    silent_samples: usize,
+    reported: bool,
+}
+
+impl SilenceDetector {
+    fn new(limit: Duration) -> Self {
+        SilenceDetector {
+            limit: secs_to_samples(limit.as_secs_f64()),
+            silent_samples: 0,
+            reported: false,
+        }
+    }
+
+    /// Measure the next batch of interleaved stereo samples, returning the
+    /// event to report when silence starts or ends
+    fn update(&mut self, samples: &[i16]) -> Option<PlaybackEvent> {
+        let sum: f64 = samples.iter().map(|&s| (s as f64 / 32768.0).powi(2)).sum();
+        let rms = (sum / samples.len().max(1) as f64).sqrt();
+        if rms >= SILENCE_LEVEL {
+            self.silent_samples = 0;
+            return std::mem::take(&mut self.reported).then_some(PlaybackEvent::SoundReturned);
+        }
+        self.silent_samples += samples.len();
+        if self.silent_samples >= self.limit && !self.reported {
+            self.reported = true;
+            return Some(PlaybackEvent::Silence);
+        }
+        None
+    }
+}
+
+/// Stretch of audio the level meter averages over
+const LEVEL_WINDOW_SECS: f64 = 0.05;
+
+/// RMS level of the left and right channel over the window starting
+/// `offset` samples into an interleaved stereo chunk
+///
+/// Near the end of the chunk the window is its last stretch instead.
+pub fn chunk_levels(chunk: &[i16], offset: usize) -> [f32; 2] {
+    let window = secs_to_samples(LEVEL_WINDOW_SECS);
+    let start = offset.min(chunk.len().saturating_sub(window)) & !1;
+    let end = (start + window).min(chunk.len());
+
+    let mut sums = [0.0f64; 2];
+    let mut frames = 0usize;
+    for frame in chunk[start..end].chunks_exact(2) {
+        for (sum, &sample) in sums.iter_mut().zip(frame) {
+            let sample = sample as f64 / 32768.0;
+            *sum += sample * sample;
+        }
+        frames += 1;
+    }
+    if frames == 0 {
+        return [0.0; 2];
+    }
+    sums.map(|sum| (sum / frames as f64).sqrt() as f32)
+}
+
+/// How long the old audio fades out, and the new audio in, when playback is
+/// cleared for a channel switch
+const FADE: Duration = Duration::from_millis(300);
+
+/// Volume steps the fade-out takes
+const FADE_OUT_STEPS: u32 = 10;
+
+/// Turn a playing sink down to silence over [`FADE`], from `volume`
+fn fade_out(sink: &rodio::Sink, volume: f32) {
+    for step in (0..FADE_OUT_STEPS).rev() {
+        sink.set_volume(volume * step as f32 / FADE_OUT_STEPS as f32);
+        std::thread::sleep(FADE / FADE_OUT_STEPS);
+    }
+}
+
+/// Ramp interleaved stereo `samples` up from silence over [`FADE`],
+/// continuing a fade-in `position` frames along; returns the position after
+/// them, or `None` once the fade is done
+fn fade_in(samples: &mut [i16], position: usize) -> Option<usize> {
+    let length = secs_to_samples(FADE.as_secs_f64()) / CHANNELS as usize;
+    let mut frame = position;
+    for pair in samples.chunks_exact_mut(CHANNELS as usize) {
+        if frame >= length {
+            break;
+        }
+        let gain = frame as f32 / length as f32;
+        for sample in pair {
+            *sample = (*sample as f32 * gain) as i16;
+        }
+        frame += 1;
+    }
+    (frame < length).then_some(frame)
+}
+
+/// How long a playing sink may go without progress before its device is
+/// considered gone
+const STALL_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Notices when a sink that should be playing stops consuming audio
+///
+/// Neither rodio nor cpal report a vanished device to the caller; the only
+/// symptom is a sink whose position and queue freeze.
+#[derive(Default)]
+struct StallDetector {
+    last: Option<(Duration, usize)>,
+    since: Option<Instant>,
+}
+
+impl StallDetector {
+    /// Record the sink's position and queue length; true once it has been
+    /// stuck for [`STALL_TIMEOUT`]
+    fn update(&mut self, playing: bool, pos: Duration, queued: usize, now: Instant) -> bool {
+        let progress = self.last != Some((pos, queued));
+        self.last = Some((pos, queued));
+        if !playing || queued == 0 || progress {
+            self.since = None;
+            return false;
+        }
+        let since = *self.since.get_or_insert(now);
+        now.duration_since(since) >= STALL_TIMEOUT
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Refill the sink with buffered audio starting at chunk `from`
+fn replay_from(sink: &rodio::Sink, timeshift: &TimeshiftBuffer, from: u64, paused: bool) {
+    use rodio::buffer::SamplesBuffer;
+
+    sink.clear();
+    for index in from..timeshift.end_index() {
+        if let Some(chunk) = timeshift.get(index) {
+            sink.append(SamplesBuffer::new(CHANNELS, SAMPLE_RATE, chunk.clone()));
+        }
+    }
+    if !paused {
+        sink.play();
+    }
+}
+
+/// Open `device` (or the default) and a sink on it, retrying until one works
+///
+/// A named device that fails to open falls back to the default one.
+fn open_sink(device: Option<&str>) -> (rodio::OutputStream, rodio::Sink) {
+    loop {
+        let output = open_output(device).or_else(|e| match device {
+            Some(_) => {
+                log::warn!("{}; using the default device", e);
+                open_output(None)
+            }
+            None => Err(e),
+        });
+        let opened = output.and_then(|(stream, handle)| {
+            let sink = rodio::Sink::try_new(&handle)?;
+            Ok((stream, sink))
+        });
+        match opened {
+            Ok(opened) => {
+                log::info!(
+                    "Audio output initialized on {}",
+                    device.unwrap_or("the default device")
+                );
+                return opened;
+            }
+            Err(e) => {
+                log::error!("Failed to open audio output: {}", e);
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+        }
+    }
+}
+
+/// Handles audio playback in a separate thread
+pub fn run_audio_thread(
+    rx: AudioReceiver,
+    mut controls: AudioControls,
+    timeshift_minutes: u32,
+    events: std::sync::mpsc::Sender<PlaybackEvent>,
+) -> Result<()> {
+    use rodio::buffer::SamplesBuffer;
+
+    log::info!("Audio thread starting...");
+
+    // Keep the stream alive as long as its sink
+    let (mut _stream, mut sink) = open_sink(controls.device_rx.borrow_and_update().as_deref());
+
+    sink.set_volume(*controls.volume_rx.borrow_and_update());
+
+    let mut timeshift = TimeshiftBuffer::new(secs_to_samples(timeshift_minutes as f64 * 60.0));
+    let mut shift_secs = 0.0;
+    let mut sample_count = 0u64;
+    let mut waiting_for_new_samples = true;
+    let mut stall = StallDetector::default();
+    let mut starved = false;
+    // Frames into the fade-in after a clear, until it is done
+    let mut fading_in: Option<usize> = None;
+
+    loop {
+        if controls.paused_rx.has_changed().unwrap_or(false) {
+            if *controls.paused_rx.borrow_and_update() {
+                log::info!("Audio: pausing sink");
+                sink.pause();
+            } else {
+                log::info!("Audio: resuming sink");
+                sink.play();
+            }
+        }
+
+        if controls.volume_rx.has_changed().unwrap_or(false) {
+            sink.set_volume(*controls.volume_rx.borrow_and_update());
+        }
+
+        if controls.device_rx.has_changed().unwrap_or(false) {
+            let device = controls.device_rx.borrow_and_update().clone();
+            let playing = timeshift
+                .end_index()
+                .saturating_sub(sink.len() as u64)
+                .max(timeshift.first_index());
+            sink.stop();
+            (_stream, sink) = open_sink(device.as_deref());
+            sink.set_volume(*controls.volume_rx.borrow());
+            // Pick up where the old device left off, give or take a chunk
+            replay_from(&sink, &timeshift, playing, *controls.paused_rx.borrow());
+            stall.reset();
+        }
+
+        let playing = !*controls.paused_rx.borrow();
+        if stall.update(playing, sink.get_pos(), sink.len(), Instant::now()) {
+            log::warn!("Audio output stopped responding, reopening it");
+            let _ = events.send(PlaybackEvent::OutputLost);
+            sink.stop();
+            (_stream, sink) = open_sink(controls.device_rx.borrow().as_deref());
+            sink.set_volume(*controls.volume_rx.borrow());
+            // What was queued is stale by now, so resume at the live edge
+            let newest = timeshift.end_index().saturating_sub(1);
+            replay_from(&sink, &timeshift, newest, false);
+            shift_secs = 0.0;
+            let _ = controls.timeshift_tx.send(shift_secs);
+            stall.reset();
+            let _ = events.send(PlaybackEvent::OutputRestored);
+        }
+
+        while let Ok(command) = controls.command_rx.try_recv() {
+            let paused = *controls.paused_rx.borrow();
+            // Everything buffered has been appended, so the chunk playing now
+            // is as far from the end as the sink's queue is long
+            let playing = timeshift
+                .end_index()
+                .saturating_sub(sink.len() as u64)
+                .max(timeshift.first_index());
+            match command {
+                AudioCommand::Rewind(duration) => {
+                    let target =
+                        timeshift.index_before(playing, secs_to_samples(duration.as_secs_f64()));
+                    if target < playing {
+                        shift_secs += samples_to_secs(timeshift.samples_between(target, playing));
+                        log::info!("Audio: rewinding to {:.0}s behind live", shift_secs);
+                        replay_from(&sink, &timeshift, target, paused);
+                    }
+                }
+                AudioCommand::Live => {
+                    if shift_secs > 0.0 {
+                        log::info!("Audio: returning to live");
+                        let newest = timeshift.end_index().saturating_sub(1);
+                        replay_from(&sink, &timeshift, newest, paused);
+                        shift_secs = 0.0;
+                    }
+                }
+            }
+            let _ = controls.timeshift_tx.send(shift_secs);
+        }
+
+        let playing = timeshift
+            .end_index()
+            .saturating_sub(sink.len() as u64)
+            .max(timeshift.first_index());
+        let queued = samples_to_secs(timeshift.samples_between(playing, timeshift.end_index()))
+            - sink.get_pos().as_secs_f64();
+        controls.queued_tx.send_replace(queued.max(0.0));
+
+        let levels = match timeshift.get(playing) {
+            Some(chunk) if !sink.is_paused() && !sink.empty() => {
+                chunk_levels(chunk, secs_to_samples(sink.get_pos().as_secs_f64()))
+            }
+            _ => [0.0; 2],
+        };
+        controls.levels_tx.send_replace(levels);
+
+        // Count each time a playing sink runs dry, but not before the first
+        // audio of a channel arrives
+        let dry = !*controls.paused_rx.borrow() && !waiting_for_new_samples && sink.empty();
+        if dry && !starved {
+            log::debug!("Audio: output ran out of audio");
+            controls.stats.underrun();
+        }
+        starved = dry;
+
+        // Leave new audio queued while enough is lined up, except while
+        // rewound: then it has to reach the timeshift buffer to be replayed
+        let accept_audio = shift_secs > 0.0 || queued < rx.buffer_secs();
+        match rx.recv_timeout(std::time::Duration::from_millis(100), accept_audio) {
+            Ok(mut samples) => {
+                if samples.is_empty() {
+                    log::info!("Audio: Received clear signal, clearing sink");
+                    // Fade rather than cut the old audio off mid-waveform
+                    if !sink.is_paused() && !sink.empty() {
+                        fade_out(&sink, *controls.volume_rx.borrow());
+                    }
+                    sink.clear();
+                    sink.set_volume(*controls.volume_rx.borrow());
+                    fading_in = Some(0);
+                    if !*controls.paused_rx.borrow() {
+                        sink.play();
+                    }
+                    timeshift.clear();
+                    shift_secs = 0.0;
+                    let _ = controls.timeshift_tx.send(shift_secs);
+                    waiting_for_new_samples = true;
+                } else {
+                    sample_count += 1;
+                    if sample_count % 10 == 1 {
+                        log::debug!(
+                            "Audio: Received samples batch #{}, {} samples, sink empty: {}",
+                            sample_count,
+                            samples.len(),
+                            sink.empty()
+                        );
+                    }
+                    fading_in = fading_in.and_then(|position| fade_in(&mut samples, position));
+                    timeshift.push(samples.clone());
+                    controls.stats.audio_played(samples.len());
+                    let buffer = SamplesBuffer::new(CHANNELS, SAMPLE_RATE, samples);
+                    sink.append(buffer);
+
+                    if waiting_for_new_samples {
+                        let _ = events.send(PlaybackEvent::Started);
+                        waiting_for_new_samples = false;
+                    }
+                }
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                continue;
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                log::info!("Audio thread: channel disconnected, exiting");
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles HLS streaming and segment fetching
+pub async fn run_stream_loop<H: RadioHttp + 'static>(
+    client: Arc<H>,
+    controls: StreamControls,
+    stream_options: StreamOptions,
+) -> Result<()> {
+    let StreamControls {
+        mut channel_rx,
+        mut area_rx,
+        paused_rx,
+        mut audio_tx,
+        buffer,
+        live_edge_tx,
+        stats,
+        target_lufs,
+        mut equalizer_rx,
+        silence_alert,
+        events,
+        recorder,
+    } = controls;
+    let mut current_channel = *channel_rx.borrow_and_update();
+    let mut stream_data = area_rx.borrow_and_update().clone();
+    // Decoded audio with the wall-clock end of its segment, on its way to the outputs
+    let mut pacer: Pacer<(Vec<i16>, Option<DateTime<FixedOffset>>)> = Pacer::new(buffer);
+
+    loop {
+        // A new channel is a new stream for the decoder too
+        let mut decoder = StreamDecoder::new();
+        // Loudness is measured afresh for each channel, which has its own levels
+        let mut dsp = Dsp::new(*equalizer_rx.borrow_and_update(), target_lufs);
+        let mut silence = silence_alert.map(SilenceDetector::new);
+        let mut segments = std::pin::pin!(segment_stream_with_stats(
+            client.clone(),
+            stream_data.clone(),
+            current_channel,
+            stream_options,
+            stats.clone()
+        ));
+
+        loop {
+            let release = pacer.due(tokio::time::Instant::now());
+            tokio::select! {
+                _ = tokio::time::sleep_until(release.unwrap_or_else(tokio::time::Instant::now)), if release.is_some() => {
+                    if *paused_rx.borrow() {
+                        pacer.clear();
+                        continue;
+                    }
+                    while let Some((samples, end)) = pacer.pop(tokio::time::Instant::now()) {
+                        let _ = audio_tx.send(samples);
+                        live_edge_tx.send_replace(end);
+                    }
+                    stats.set_dropped_samples(audio_tx.dropped_samples() + pacer.dropped_samples());
+                }
+                changed = channel_rx.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    let new_channel = *channel_rx.borrow_and_update();
+                    if new_channel != current_channel {
+                        log::info!(
+                            "Channel changed from {:?} to {:?}",
+                            current_channel,
+                            new_channel
+                        );
+                        current_channel = new_channel;
+                        pacer.clear();
+                        let _ = audio_tx.send(vec![]);
+                        live_edge_tx.send_replace(None);
+                        break;
+                    }
+                }
+                changed = area_rx.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    let new_area = area_rx.borrow_and_update().clone();
+                    if new_area.area != stream_data.area {
+                        log::info!("Area changed from {} to {}", stream_data.area, new_area.area);
+                        stream_data = new_area;
+                        pacer.clear();
+                        let _ = audio_tx.send(vec![]);
+                        live_edge_tx.send_replace(None);
+                        break;
+                    }
+                }
+                segment = segments.next() => match segment {
+                    Some(segment) => {
+                        // Tee point: recording sees every segment, even while paused
+                        let record_pcm = {
+                            let mut recorder = recorder.lock().unwrap_or_else(|e| e.into_inner());
+                            write_recording(&mut recorder, |rec| rec.write_segment(&segment.data));
+                            recorder.as_ref().is_some_and(|r| r.format().needs_pcm())
+                        };
+
+                        // Drop audio while paused so playback resumes at the live edge
+                        let paused = *paused_rx.borrow();
+                        if paused {
+                            pacer.clear();
+                            if !record_pcm {
+                                continue;
+                            }
+                        }
+
+                        let chunk = match segment.decode(&mut decoder) {
+                            Ok(chunk) if !chunk.samples.is_empty() => chunk,
+                            Ok(_) => continue,
+                            Err(e) => {
+                                log::debug!("Failed to decode AAC: {}", e);
+                                stats.decode_error();
+                                continue;
+                            }
+                        };
+
+                        if record_pcm {
+                            let mut recorder = recorder.lock().unwrap_or_else(|e| e.into_inner());
+                            write_recording(&mut recorder, |rec| rec.write_pcm(&chunk));
+                        }
+                        let samples = chunk.to_playback();
+
+                        if !paused {
+                            // Measured before the volume and DSP, so a muted
+                            // output is never taken for a silent broadcast
+                            if let Some(event) = silence.as_mut().and_then(|s| s.update(&samples)) {
+                                if event == PlaybackEvent::Silence {
+                                    log::warn!("{} has been silent while still streaming", current_channel.short_name());
+                                }
+                                let _ = events.send(event);
+                            }
+                            let mut samples = samples;
+                            if equalizer_rx.has_changed().unwrap_or(false) {
+                                dsp.set_equalizer(*equalizer_rx.borrow_and_update());
+                            }
+                            dsp.process(&mut samples);
+                            let end = segment.program_date_time.map(|start| {
+                                start + chrono::Duration::milliseconds((segment.duration * 1000.0) as i64)
+                            });
+                            let len = samples.len();
+                            pacer.push((samples, end), len);
+                        }
+                    }
+                    None => {
+                        if let Some(cap) = stream_options.max_bandwidth
+                            && stream_options.over_bandwidth(stats.downloaded())
+                        {
+                            anyhow::bail!(
+                                "Stopped after downloading the {:.1} MB bandwidth cap",
+                                cap as f64 / 1_000_000.0
+                            );
+                        }
+                        break;
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Write to the recording in progress, if any, ending it when the write fails
+fn write_recording(
+    recorder: &mut Option<Recorder>,
+    write: impl FnOnce(&mut Recorder) -> Result<()>,
+) {
+    if let Some(rec) = recorder.as_mut()
+        && let Err(e) = write(rec)
+    {
+        log::error!("Recording to {} failed: {}", rec.path().display(), e);
+        *recorder = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stall_detector() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let pos = Duration::from_millis(500);
+        let mut stall = StallDetector::default();
+
+        assert!(!stall.update(true, pos, 3, at(0)));
+        // Frozen from 1s on, but not for long enough yet
+        assert!(!stall.update(true, pos, 3, at(1)));
+        assert!(!stall.update(true, pos, 3, at(3)));
+        assert!(stall.update(true, pos, 3, at(4)));
+
+        // Paused or empty sinks are expected to sit still
+        stall.reset();
+        assert!(!stall.update(false, pos, 3, at(0)));
+        assert!(!stall.update(false, pos, 3, at(10)));
+        assert!(!stall.update(true, pos, 0, at(20)));
+
+        // Any progress restarts the clock
+        stall.reset();
+        assert!(!stall.update(true, pos, 3, at(0)));
+        assert!(!stall.update(true, pos, 3, at(2)));
+        assert!(!stall.update(true, pos * 2, 3, at(4)));
+        assert!(!stall.update(true, pos * 2, 3, at(6)));
+        assert!(stall.update(true, pos * 2, 3, at(9)));
+    }
+
+    #[test]
+    fn test_fade_in() {
+        let length = secs_to_samples(FADE.as_secs_f64()) / CHANNELS as usize;
+        let mut samples = vec![1000i16; length];
+        // Half the fade's frames, starting from silence
+        assert_eq!(fade_in(&mut samples, 0), Some(length / 2));
+        assert_eq!(samples[..2], [0, 0]);
+        assert!(samples[length - 2] > 0 && samples[length - 2] < 500);
+
+        // The rest ramps up to full level and ends the fade
+        let mut rest = vec![1000i16; length * 2];
+        assert_eq!(fade_in(&mut rest, length / 2), None);
+        assert!(rest[0] >= 499);
+        assert_eq!(rest[length + 2], 1000);
+    }
+
+    #[test]
+    fn test_silence_detector() {
+        let second = secs_to_samples(1.0);
+        let quiet = vec![10i16; second];
+        let talk = vec![2000i16; second];
+        let mut silence = SilenceDetector::new(Duration::from_secs(3));
+
+        assert_eq!(silence.update(&quiet), None);
+        assert_eq!(silence.update(&quiet), None);
+        // Sound in between starts the count again
+        assert_eq!(silence.update(&talk), None);
+        assert_eq!(silence.update(&quiet), None);
+        assert_eq!(silence.update(&quiet), None);
+        assert_eq!(silence.update(&quiet), Some(PlaybackEvent::Silence));
+        // Reported once, however long it goes on
+        assert_eq!(silence.update(&quiet), None);
+        assert_eq!(silence.update(&talk), Some(PlaybackEvent::SoundReturned));
+        assert_eq!(silence.update(&talk), None);
+        assert_eq!(silence.update(&[]), None);
+    }
+
+    #[test]
+    fn test_chunk_levels() {
+        // Full-scale square wave on the left, silence on the right
+        let chunk: Vec<i16> = (0..SAMPLE_RATE as usize)
+            .flat_map(|i| [if i % 2 == 0 { i16::MAX } else { -i16::MAX }, 0])
+            .collect();
+        let [left, right] = chunk_levels(&chunk, 0);
+        assert!((left - 1.0).abs() < 0.001);
+        assert_eq!(right, 0.0);
+
+        // An offset past the end falls back to the chunk's last stretch
+        let [left, _] = chunk_levels(&chunk, chunk.len() * 2);
+        assert!((left - 1.0).abs() < 0.001);
+        // An odd offset still keeps left and right apart
+        assert_eq!(chunk_levels(&chunk, 1)[1], 0.0);
+        assert_eq!(chunk_levels(&[], 0), [0.0, 0.0]);
+    }
+}
@@ -0,0 +1,229 @@
+//! High-level entry point for embedding live playback.
+//!
+//! [`NhkRadio`] fetches the station config once and hands out decoded audio
+//! and program-change streams for any area and channel in it.
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use futures::Stream;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::area::resolve_area;
+use crate::client::{HttpOptions, NhkRadioClient};
+use crate::decoder::PcmChunk;
+use crate::player::ChannelKind;
+use crate::stream::{SegmentData, StreamOptions, pcm_stream, segment_stream};
+use crate::types::{BroadcastEvent, RadiruConfig, Root, StreamData, absolute_url};
+
+/// How often [`now_playing_stream`] re-checks the program listing
+const NOW_PLAYING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The program currently on air on one channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NowPlaying {
+    pub channel: ChannelKind,
+    pub title: String,
+    pub description: String,
+    pub start: Option<DateTime<FixedOffset>>,
+    pub end: Option<DateTime<FixedOffset>>,
+}
+
+impl NowPlaying {
+    /// The program `program` lists as on air now on `channel`
+    pub fn from_program(program: &Root, channel: ChannelKind) -> Option<Self> {
+        let listing = match channel {
+            ChannelKind::R1 => &program.r1,
+            ChannelKind::R2 => &program.r2,
+            ChannelKind::Fm => &program.r3,
+        };
+        listing
+            .present
+            .as_ref()
+            .map(|event| Self::from_event(event, channel))
+    }
+
+    fn from_event(event: &BroadcastEvent, channel: ChannelKind) -> Self {
+        let (title, description) = match &event.about {
+            Some(about) => (about.name.clone(), about.description.clone()),
+            None => (event.name.clone(), event.description.clone()),
+        };
+        NowPlaying {
+            channel,
+            title,
+            description,
+            start: DateTime::parse_from_rfc3339(&event.start_date).ok(),
+            end: DateTime::parse_from_rfc3339(&event.end_date).ok(),
+        }
+    }
+}
+
+/// A connection to NHK Radio with its station config loaded
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use nhk_radio_core::{ChannelKind, NhkRadio};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let radio = NhkRadio::connect().await?;
+/// for area in radio.areas() {
+///     println!("{} ({})", area.areajp, area.area);
+/// }
+///
+/// let mut audio = std::pin::pin!(radio.stream("tokyo", ChannelKind::Fm)?);
+/// while let Some(chunk) = audio.next().await {
+///     // Interleaved i16 samples in chunk.spec's rate and channel count
+///     println!("{} samples", chunk.samples.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct NhkRadio {
+    client: Arc<NhkRadioClient>,
+    config: RadiruConfig,
+    options: StreamOptions,
+}
+
+impl NhkRadio {
+    /// Connect with default HTTP settings
+    pub async fn connect() -> Result<Self> {
+        Self::with_client(NhkRadioClient::new()).await
+    }
+
+    /// Connect with custom timeouts, retries, proxy, or headers
+    pub async fn with_options(options: HttpOptions) -> Result<Self> {
+        Self::with_client(NhkRadioClient::with_options(options)?).await
+    }
+
+    async fn with_client(client: NhkRadioClient) -> Result<Self> {
+        let config = client.fetch_config().await?;
+        Ok(NhkRadio {
+            client: Arc::new(client),
+            config,
+            options: StreamOptions::default(),
+        })
+    }
+
+    /// Use `options` for streams opened from now on
+    pub fn set_stream_options(&mut self, options: StreamOptions) {
+        self.options = options;
+    }
+
+    /// Every area in the station config
+    pub fn areas(&self) -> &[StreamData] {
+        &self.config.stream_url.data
+    }
+
+    /// Look up an area by code, Japanese name, reading, or unambiguous prefix
+    pub fn area(&self, query: &str) -> Result<&StreamData> {
+        resolve_area(query, self.areas())
+    }
+
+    /// Decoded PCM audio for `area`'s `channel`
+    ///
+    /// The stream follows the live edge until dropped.
+    pub fn stream(&self, area: &str, channel: ChannelKind) -> Result<impl Stream<Item = PcmChunk>> {
+        Ok(pcm_stream(self.segments(area, channel)?))
+    }
+
+    /// Decrypted but undecoded segments for `area`'s `channel`
+    pub fn segments(
+        &self,
+        area: &str,
+        channel: ChannelKind,
+    ) -> Result<impl Stream<Item = SegmentData>> {
+        let stream_data = self.area(area)?.clone();
+        Ok(segment_stream(
+            self.client.clone(),
+            stream_data,
+            channel,
+            self.options,
+        ))
+    }
+
+    /// The program on air on `area`'s `channel`, yielded whenever it changes
+    ///
+    /// The first item arrives as soon as the listing is fetched.
+    pub fn now_playing(
+        &self,
+        area: &str,
+        channel: ChannelKind,
+    ) -> Result<impl Stream<Item = NowPlaying>> {
+        let program_url = self.config.program_url(&self.area(area)?.areakey);
+        Ok(now_playing_stream(
+            self.client.clone(),
+            program_url,
+            channel,
+        ))
+    }
+}
+
+/// Poll the listing at `program_url` and yield `channel`'s program whenever
+/// it changes
+///
+/// Failed fetches are logged and retried on the next poll.
+pub fn now_playing_stream(
+    client: Arc<NhkRadioClient>,
+    program_url: String,
+    channel: ChannelKind,
+) -> impl Stream<Item = NowPlaying> {
+    async_stream::stream! {
+        let mut last: Option<NowPlaying> = None;
+        loop {
+            match client.fetch_program(&program_url).await {
+                Ok(program) => {
+                    let current = NowPlaying::from_program(&program, channel);
+                    if let Some(current) = current
+                        && last.as_ref() != Some(&current)
+                    {
+                        last = Some(current.clone());
+                        yield current;
+                    }
+                }
+                Err(e) => log::warn!("Failed to fetch program info: {}", e),
+            }
+            tokio::time::sleep(NOW_PLAYING_INTERVAL).await;
+        }
+    }
+}
+
+/// Today's date in Japan, which NHK's timetables go by
+pub fn today_jst() -> NaiveDate {
+    let jst = FixedOffset::east_opt(9 * 3600).expect("valid offset");
+    Utc::now().with_timezone(&jst).date_naive()
+}
+
+/// Fill in the `{area}`, `{service}`, and `{date}` placeholders of `url_program_day`
+pub fn program_day_url(
+    template: &str,
+    areakey: &str,
+    channel: ChannelKind,
+    date: NaiveDate,
+) -> String {
+    let service = match channel {
+        ChannelKind::R1 => "r1",
+        ChannelKind::R2 => "r2",
+        ChannelKind::Fm => "r3",
+    };
+    absolute_url(template)
+        .replace("{area}", areakey)
+        .replace("{service}", service)
+        .replace("{date}", &date.format("%Y-%m-%d").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_day_url() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 2).unwrap();
+        let url = program_day_url(
+            "//api.nhk.jp/r7/pg/date/{service}/{area}/{date}.json",
+            "130",
+            ChannelKind::Fm,
+            date,
+        );
+        assert_eq!(url, "https://api.nhk.jp/r7/pg/date/r3/130/2025-01-02.json");
+    }
+}
@@ -0,0 +1,383 @@
+use anyhow::Result;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::decoder::{PcmChunk, PcmSpec};
+#[cfg(feature = "transcode")]
+use crate::encoder::Encoder;
+use crate::pipe::{WAV_HEADER_LEN, riff_len, wav_header};
+#[cfg(feature = "transcode")]
+use crate::player::{CHANNELS, SAMPLE_RATE};
+use crate::resample;
+
+/// Output format for recordings
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum RecordFormat {
+    /// Decrypted source segments as-is (AAC/TS)
+    #[default]
+    Raw,
+    Mp3,
+    Opus,
+    Flac,
+    /// Decoded PCM as it came from the decoder, with no encoder involved
+    Wav,
+}
+
+impl RecordFormat {
+    /// Guess the format from a file extension, defaulting to raw segments
+    pub fn from_path(path: &Path) -> Self {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match ext.as_str() {
+            "mp3" => RecordFormat::Mp3,
+            "opus" | "ogg" => RecordFormat::Opus,
+            "flac" => RecordFormat::Flac,
+            "wav" => RecordFormat::Wav,
+            _ => RecordFormat::Raw,
+        }
+    }
+
+    /// Usual file extension for recordings in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordFormat::Raw => "aac",
+            RecordFormat::Mp3 => "mp3",
+            RecordFormat::Opus => "ogg",
+            RecordFormat::Flac => "flac",
+            RecordFormat::Wav => "wav",
+        }
+    }
+
+    /// Whether this format is produced from decoded PCM rather than segments
+    pub fn needs_pcm(&self) -> bool {
+        *self != RecordFormat::Raw
+    }
+}
+
+enum Output {
+    Raw(BufWriter<File>),
+    Wav(WavWriter),
+    #[cfg(feature = "transcode")]
+    Encoded(Encoder),
+}
+
+/// A WAV file in the format of the first PCM written to it
+///
+/// The sizes in the header are brought up to date after every write, so the
+/// file stays valid if we are killed. Past 4 GiB (about six hours at 48 kHz
+/// stereo) they stay at their maximum, which readers take as "until the end
+/// of the file".
+struct WavWriter {
+    writer: BufWriter<File>,
+    /// Format of the header, once written
+    spec: Option<PcmSpec>,
+    data_len: u32,
+}
+
+impl WavWriter {
+    /// Append `chunk`, converted to the format of the file if it differs,
+    /// returning the bytes written
+    fn write(&mut self, chunk: &PcmChunk) -> Result<usize> {
+        let spec = match self.spec {
+            Some(spec) => spec,
+            None => {
+                self.writer.write_all(&wav_header(chunk.spec, 0))?;
+                *self.spec.insert(chunk.spec)
+            }
+        };
+        let samples = resample::convert(&chunk.samples, chunk.spec, spec);
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.writer.write_all(&bytes)?;
+
+        self.data_len = u32::try_from(bytes.len())
+            .ok()
+            .and_then(|len| self.data_len.checked_add(len))
+            .unwrap_or(u32::MAX);
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&riff_len(self.data_len).to_le_bytes())?;
+        self.writer
+            .seek(SeekFrom::Start(WAV_HEADER_LEN as u64 - 4))?;
+        self.writer.write_all(&self.data_len.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(bytes.len())
+    }
+}
+
+/// Tags written into a recording; empty values are left out
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordingTags {
+    pub title: String,
+    pub album: String,
+    pub artist: String,
+    /// Air date, e.g. "2025-11-25"
+    pub date: String,
+    pub comment: String,
+}
+
+impl RecordingTags {
+    /// An ID3v2 tag, for the start of an ADTS file
+    pub fn to_id3(&self) -> id3::Tag {
+        use id3::TagLike;
+        let mut tag = id3::Tag::new();
+        if !self.title.is_empty() {
+            tag.set_title(&self.title);
+        }
+        if !self.album.is_empty() {
+            tag.set_album(&self.album);
+        }
+        if !self.artist.is_empty() {
+            tag.set_artist(&self.artist);
+        }
+        if !self.date.is_empty() {
+            tag.set_text("TDRC", &self.date);
+        }
+        if !self.comment.is_empty() {
+            tag.add_frame(id3::frame::Comment {
+                lang: "jpn".to_string(),
+                description: String::new(),
+                text: self.comment.clone(),
+            });
+        }
+        tag
+    }
+
+    /// Key-value pairs for ffmpeg's `-metadata`
+    pub fn metadata(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("title", &self.title),
+            ("album", &self.album),
+            ("artist", &self.artist),
+            ("date", &self.date),
+            ("comment", &self.comment),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| (key, value.as_str()))
+        .collect()
+    }
+}
+
+/// Writes the live stream to a single continuous file
+pub struct Recorder {
+    path: PathBuf,
+    format: RecordFormat,
+    output: Output,
+    bytes_written: u64,
+    started: Instant,
+}
+
+/// A recording in progress, as a front end shows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingStatus {
+    pub path: PathBuf,
+    pub format: RecordFormat,
+    /// Time since recording started
+    pub elapsed: Duration,
+    pub bytes_written: u64,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Self::with_format(path, RecordFormat::from_path(path))
+    }
+
+    pub fn with_format(path: impl AsRef<Path>, format: RecordFormat) -> Result<Self> {
+        Self::with_tags(path, format, None)
+    }
+
+    /// Record to `path` as `format`, tagged with `tags`
+    pub fn with_tags(
+        path: impl AsRef<Path>,
+        format: RecordFormat,
+        tags: Option<&RecordingTags>,
+    ) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let output = match format {
+            RecordFormat::Raw => {
+                let file = File::create(&path)
+                    .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path.display(), e))?;
+                let mut writer = BufWriter::new(file);
+                if let Some(tags) = tags {
+                    tags.to_id3().write_to(&mut writer, id3::Version::Id3v24)?;
+                }
+                Output::Raw(writer)
+            }
+            RecordFormat::Wav => {
+                let file = File::create(&path)
+                    .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path.display(), e))?;
+                Output::Wav(WavWriter {
+                    writer: BufWriter::new(file),
+                    spec: None,
+                    data_len: 0,
+                })
+            }
+            #[cfg(feature = "transcode")]
+            _ => Output::Encoded(Encoder::spawn(
+                &path,
+                format,
+                SAMPLE_RATE,
+                CHANNELS,
+                &tags.map(RecordingTags::metadata).unwrap_or_default(),
+            )?),
+            #[cfg(not(feature = "transcode"))]
+            _ => anyhow::bail!(
+                "Recording as {:?} requires building with `--features transcode`",
+                format
+            ),
+        };
+        log::info!("Recording to {} ({:?})", path.display(), format);
+        Ok(Self {
+            path,
+            format,
+            output,
+            bytes_written: 0,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn format(&self) -> RecordFormat {
+        self.format
+    }
+
+    /// Write one decrypted segment; ignored for recordings made from PCM
+    ///
+    /// Flushes after every segment so the file stays playable if we are killed.
+    pub fn write_segment(&mut self, data: &[u8]) -> Result<()> {
+        if let Output::Raw(ref mut writer) = self.output {
+            writer.write_all(data)?;
+            writer.flush()?;
+            self.bytes_written += data.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Write decoded PCM, as the decoder produced it; ignored for raw
+    /// recordings
+    ///
+    /// WAV files keep the decoder's sample rate and channels; the encoder
+    /// takes the playback format.
+    pub fn write_pcm(&mut self, chunk: &PcmChunk) -> Result<()> {
+        match self.output {
+            Output::Raw(_) => {}
+            Output::Wav(ref mut wav) => self.bytes_written += wav.write(chunk)? as u64,
+            #[cfg(feature = "transcode")]
+            Output::Encoded(ref mut encoder) => {
+                self.bytes_written += encoder.write_pcm(&chunk.to_playback())? as u64;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    pub fn status(&self) -> RecordingStatus {
+        RecordingStatus {
+            path: self.path.clone(),
+            format: self.format,
+            elapsed: self.started.elapsed(),
+            bytes_written: self.bytes_written,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            RecordFormat::from_path(Path::new("a.mp3")),
+            RecordFormat::Mp3
+        );
+        assert_eq!(
+            RecordFormat::from_path(Path::new("a.OPUS")),
+            RecordFormat::Opus
+        );
+        assert_eq!(
+            RecordFormat::from_path(Path::new("a.flac")),
+            RecordFormat::Flac
+        );
+        assert_eq!(
+            RecordFormat::from_path(Path::new("a.aac")),
+            RecordFormat::Raw
+        );
+        assert_eq!(
+            RecordFormat::from_path(Path::new("a.wav")),
+            RecordFormat::Wav
+        );
+        assert_eq!(RecordFormat::from_path(Path::new("a")), RecordFormat::Raw);
+    }
+
+    #[test]
+    fn test_wav_recording() {
+        let path = std::env::temp_dir().join(format!("nhk-wav-{}.wav", std::process::id()));
+        let mut recorder = Recorder::create(&path).unwrap();
+        let spec = PcmSpec {
+            sample_rate: 44100,
+            channels: 2,
+        };
+        // Segments are ignored; the header takes the decoder's format
+        recorder.write_segment(b"aac").unwrap();
+        for _ in 0..2 {
+            recorder
+                .write_pcm(&PcmChunk {
+                    samples: vec![1, -1, 2, -2],
+                    spec,
+                })
+                .unwrap();
+        }
+        assert_eq!(recorder.bytes_written(), 16);
+        drop(recorder);
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(bytes.len(), WAV_HEADER_LEN + 16);
+        assert_eq!(bytes[..WAV_HEADER_LEN], wav_header(spec, 16));
+        assert_eq!(
+            bytes[WAV_HEADER_LEN..WAV_HEADER_LEN + 2],
+            1i16.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn test_tags() {
+        let tags = RecordingTags {
+            title: "クラシック音楽館".to_string(),
+            artist: "NHK FM".to_string(),
+            date: "2025-11-25".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            tags.metadata(),
+            [
+                ("title", "クラシック音楽館"),
+                ("artist", "NHK FM"),
+                ("date", "2025-11-25")
+            ]
+        );
+
+        let path = std::env::temp_dir().join(format!("nhk-tags-{}.aac", std::process::id()));
+        let mut recorder = Recorder::with_tags(&path, RecordFormat::Raw, Some(&tags)).unwrap();
+        recorder.write_segment(b"audio").unwrap();
+        drop(recorder);
+        let tag = id3::Tag::read_from_path(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(id3::TagLike::title(&tag), Some("クラシック音楽館"));
+    }
+}
@@ -0,0 +1,106 @@
+//! Sample rate and channel conversion for decoded PCM.
+//!
+//! Streams are decoded at whatever rate and layout they were encoded with,
+//! while playback, timeshift, and recording work in one fixed format.
+
+use crate::decoder::PcmSpec;
+
+/// Convert interleaved `samples` from `from` to `to`
+///
+/// Channels are remixed first (mono is duplicated, multichannel folded down
+/// or truncated), then each chunk is resampled by linear interpolation.
+pub fn convert(samples: &[i16], from: PcmSpec, to: PcmSpec) -> Vec<i16> {
+    if from == to {
+        return samples.to_vec();
+    }
+    let remixed = remix(samples, from.channels, to.channels);
+    resample(&remixed, to.channels, from.sample_rate, to.sample_rate)
+}
+
+fn remix(samples: &[i16], from: u16, to: u16) -> Vec<i16> {
+    if from == to || from == 0 || to == 0 {
+        return samples.to_vec();
+    }
+    let (from, to) = (from as usize, to as usize);
+    samples
+        .chunks_exact(from)
+        .flat_map(|frame| {
+            (0..to).map(move |ch| match (from, to) {
+                (1, _) => frame[0],
+                (_, 1) => (frame.iter().map(|&s| s as i32).sum::<i32>() / from as i32) as i16,
+                _ => frame[ch.min(from - 1)],
+            })
+        })
+        .collect()
+}
+
+fn resample(samples: &[i16], channels: u16, from_rate: u32, to_rate: u32) -> Vec<i16> {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    if from_rate == to_rate || from_rate == 0 || frames == 0 {
+        return samples.to_vec();
+    }
+
+    let out_frames = (frames as u64 * to_rate as u64 / from_rate as u64) as usize;
+    let step = from_rate as f64 / to_rate as f64;
+    let mut out = Vec::with_capacity(out_frames * channels);
+    for i in 0..out_frames {
+        let pos = i as f64 * step;
+        let idx = (pos as usize).min(frames - 1);
+        let next = (idx + 1).min(frames - 1);
+        let frac = pos - idx as f64;
+        for ch in 0..channels {
+            let a = samples[idx * channels + ch] as f64;
+            let b = samples[next * channels + ch] as f64;
+            out.push((a + (b - a) * frac).round() as i16);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEREO_48K: PcmSpec = PcmSpec {
+        sample_rate: 48000,
+        channels: 2,
+    };
+
+    #[test]
+    fn test_same_spec_is_unchanged() {
+        assert_eq!(
+            convert(&[1, 2, 3, 4], STEREO_48K, STEREO_48K),
+            vec![1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_mono_is_duplicated() {
+        let mono = PcmSpec {
+            sample_rate: 48000,
+            channels: 1,
+        };
+        assert_eq!(convert(&[5, -5], mono, STEREO_48K), vec![5, 5, -5, -5]);
+    }
+
+    #[test]
+    fn test_upsampling_interpolates() {
+        let stereo_24k = PcmSpec {
+            sample_rate: 24000,
+            channels: 2,
+        };
+        let out = convert(&[0, 0, 100, 100], stereo_24k, STEREO_48K);
+        assert_eq!(out, vec![0, 0, 50, 50, 100, 100, 100, 100]);
+    }
+
+    #[test]
+    fn test_44k1_keeps_duration() {
+        let stereo_44k1 = PcmSpec {
+            sample_rate: 44100,
+            channels: 2,
+        };
+        let out = convert(&vec![0; 44100 * 2], stereo_44k1, STEREO_48K);
+        assert_eq!(out.len(), 48000 * 2);
+    }
+}
@@ -0,0 +1,176 @@
+//! Runtime counters for a live stream.
+//!
+//! The stream loop records what happens as it fetches and decodes segments,
+//! and the audio thread what it plays and when it runs dry. Front ends read a
+//! [`StreamStatsSnapshot`] to show whether trouble comes from the network,
+//! the decoder, or the audio output falling behind.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use crate::player::{CHANNELS, SAMPLE_RATE};
+
+/// Counters shared between the stream loop and whoever displays them
+#[derive(Debug, Default)]
+pub struct StreamStats {
+    bitrate: AtomicU64,
+    segments_fetched: AtomicU64,
+    segments_failed: AtomicU64,
+    segments_missed: AtomicU64,
+    bytes_downloaded: AtomicU64,
+    bandwidth_cap: AtomicU64,
+    decode_errors: AtomicU64,
+    dropped_samples: AtomicU64,
+    played_samples: AtomicU64,
+    underruns: AtomicU64,
+    reconnects: AtomicU64,
+    last_refresh: Mutex<Option<Instant>>,
+}
+
+/// What a [`StreamStats`] had counted at one moment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamStatsSnapshot {
+    /// Bandwidth of the variant being fetched in bits/s, when the master
+    /// playlist declares it
+    pub bitrate: Option<u64>,
+    pub segments_fetched: u64,
+    /// Segments that could not be downloaded or decrypted
+    pub segments_failed: u64,
+    /// Segments the playlist window moved past before they were fetched
+    pub segments_missed: u64,
+    /// Bytes of playlists, keys, and segments downloaded, before decryption
+    pub bytes_downloaded: u64,
+    /// Bytes the session may download, when capped
+    pub bandwidth_cap: Option<u64>,
+    pub decode_errors: u64,
+    /// Seconds of decoded audio discarded because playback fell behind
+    pub dropped_secs: f64,
+    /// Seconds of audio handed to the output device
+    pub played_secs: f64,
+    /// Times the audio output ran out of audio while playing
+    pub underruns: u64,
+    /// Times the playlist loaded again after failing to
+    pub reconnects: u64,
+    /// When the media playlist was last loaded
+    pub last_refresh: Option<Instant>,
+}
+
+impl StreamStats {
+    pub fn set_bitrate(&self, bitrate: u64) {
+        self.bitrate.store(bitrate, Ordering::Relaxed);
+    }
+
+    pub fn segment_fetched(&self) {
+        self.segments_fetched.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn segment_failed(&self) {
+        self.segments_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn segments_missed(&self, count: u64) {
+        self.segments_missed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn bytes_downloaded(&self, bytes: u64) {
+        self.bytes_downloaded.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Bytes downloaded so far
+    pub fn downloaded(&self) -> u64 {
+        self.bytes_downloaded.load(Ordering::Relaxed)
+    }
+
+    pub fn set_bandwidth_cap(&self, bytes: u64) {
+        self.bandwidth_cap.store(bytes, Ordering::Relaxed);
+    }
+
+    pub fn decode_error(&self) {
+        self.decode_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the total samples the audio queue has discarded so far
+    pub fn set_dropped_samples(&self, samples: u64) {
+        self.dropped_samples.store(samples, Ordering::Relaxed);
+    }
+
+    /// Count audio handed to the output device
+    pub fn audio_played(&self, samples: usize) {
+        self.played_samples
+            .fetch_add(samples as u64, Ordering::Relaxed);
+    }
+
+    pub fn underrun(&self) {
+        self.underruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn reconnected(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn playlist_refreshed(&self) {
+        *self.last_refresh.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    }
+
+    pub fn snapshot(&self) -> StreamStatsSnapshot {
+        let bitrate = self.bitrate.load(Ordering::Relaxed);
+        let bandwidth_cap = self.bandwidth_cap.load(Ordering::Relaxed);
+        StreamStatsSnapshot {
+            bitrate: (bitrate > 0).then_some(bitrate),
+            segments_fetched: self.segments_fetched.load(Ordering::Relaxed),
+            segments_failed: self.segments_failed.load(Ordering::Relaxed),
+            segments_missed: self.segments_missed.load(Ordering::Relaxed),
+            bytes_downloaded: self.downloaded(),
+            bandwidth_cap: (bandwidth_cap > 0).then_some(bandwidth_cap),
+            decode_errors: self.decode_errors.load(Ordering::Relaxed),
+            dropped_secs: self.dropped_samples.load(Ordering::Relaxed) as f64
+                / (SAMPLE_RATE as f64 * CHANNELS as f64),
+            played_secs: self.played_samples.load(Ordering::Relaxed) as f64
+                / (SAMPLE_RATE as f64 * CHANNELS as f64),
+            underruns: self.underruns.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            last_refresh: *self.last_refresh.lock().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let stats = StreamStats::default();
+        assert_eq!(stats.snapshot(), StreamStatsSnapshot::default());
+
+        stats.set_bitrate(48_000);
+        stats.segment_fetched();
+        stats.segment_fetched();
+        stats.segment_failed();
+        stats.segments_missed(3);
+        stats.bytes_downloaded(1_500);
+        stats.bytes_downloaded(500);
+        stats.set_bandwidth_cap(50_000_000);
+        stats.decode_error();
+        stats.underrun();
+        stats.reconnected();
+        stats.set_dropped_samples(SAMPLE_RATE as u64 * CHANNELS as u64 / 2);
+        stats.playlist_refreshed();
+        stats.audio_played(SAMPLE_RATE as usize * CHANNELS as usize * 2);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.bitrate, Some(48_000));
+        assert_eq!(snapshot.segments_fetched, 2);
+        assert_eq!(snapshot.segments_failed, 1);
+        assert_eq!(snapshot.segments_missed, 3);
+        assert_eq!(snapshot.bytes_downloaded, 2_000);
+        assert_eq!(snapshot.bandwidth_cap, Some(50_000_000));
+        assert_eq!(snapshot.decode_errors, 1);
+        assert_eq!(snapshot.underruns, 1);
+        assert_eq!(snapshot.reconnects, 1);
+        assert_eq!(snapshot.dropped_secs, 0.5);
+        assert_eq!(snapshot.played_secs, 2.0);
+        assert!(snapshot.last_refresh.is_some());
+    }
+}
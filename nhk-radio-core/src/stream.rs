@@ -0,0 +1,679 @@
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use futures::{Stream, StreamExt};
+use std::sync::Arc;
+
+use crate::client::{NhkRadioClient, RadioHttp};
+use crate::crypto::decrypt_segment;
+use crate::decoder::{PcmChunk, StreamDecoder, decode_fmp4_to_pcm};
+use crate::m3u8::{ParsedPlaylist, Variant, parse_media_m3u8, parse_playlist, select_variant};
+use crate::player::ChannelKind;
+use crate::stats::StreamStats;
+use crate::types::StreamData;
+
+/// A decrypted media segment taken from a live playlist
+#[derive(Debug, Clone)]
+pub struct SegmentData {
+    pub url: String,
+    pub seq_no: u64,
+    pub duration: f64,
+    /// Wall-clock time the segment starts, when the playlist provides it
+    pub program_date_time: Option<DateTime<FixedOffset>>,
+    /// fMP4 initialization section the data must be demuxed after
+    pub init: Option<Arc<Vec<u8>>>,
+    pub data: Vec<u8>,
+}
+
+impl SegmentData {
+    /// Decode the segment to interleaved PCM samples
+    ///
+    /// AAC segments go through `decoder`, which should see every segment of
+    /// the stream in order; fMP4 segments are decoded on their own.
+    pub fn decode(&self, decoder: &mut StreamDecoder) -> Result<PcmChunk> {
+        match &self.init {
+            Some(init) => decode_fmp4_to_pcm(init, &self.data),
+            None => decoder.decode(&self.data),
+        }
+    }
+}
+
+/// Tuning for how a live stream is fetched
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StreamOptions {
+    /// Highest variant bandwidth to pick from a master playlist, in bits/s
+    pub max_bitrate: Option<u64>,
+    /// Segments from the end of the playlist to start at when tuning in
+    /// (default: [`DEFAULT_LIVE_EDGE_SEGMENTS`], or 1 under a bandwidth cap)
+    pub live_edge_segments: Option<u64>,
+    /// Bytes a session may download before fetching stops
+    ///
+    /// Under a cap the lowest-bitrate variant is used whatever
+    /// `max_bitrate` says, and tuning in fetches only the newest segment
+    /// rather than several to fill the buffer.
+    pub max_bandwidth: Option<u64>,
+}
+
+impl StreamOptions {
+    /// Index of the variant to play among `variants`, sorted best first
+    fn variant(&self, variants: &[Variant]) -> usize {
+        match self.max_bandwidth {
+            Some(_) => variants.len().saturating_sub(1),
+            None => select_variant(variants, self.max_bitrate),
+        }
+    }
+
+    fn live_edge(&self) -> u64 {
+        self.live_edge_segments.unwrap_or(match self.max_bandwidth {
+            Some(_) => 1,
+            None => DEFAULT_LIVE_EDGE_SEGMENTS,
+        })
+    }
+
+    /// Whether `downloaded` bytes have used up the bandwidth cap
+    pub fn over_bandwidth(&self, downloaded: u64) -> bool {
+        self.max_bandwidth.is_some_and(|cap| downloaded >= cap)
+    }
+}
+
+/// Segments back from the end of a live playlist that playback starts at,
+/// the closest to live RFC 8216 §6.3.3 recommends
+///
+/// Starting at the beginning of the window instead would put playback as
+/// far behind live as the window is long.
+pub const DEFAULT_LIVE_EDGE_SEGMENTS: u64 = 3;
+
+/// Consecutive failed or slower-than-realtime segment fetches before
+/// switching to a lower-bandwidth variant
+const DOWNSWITCH_STRIKES: u32 = 3;
+
+/// Refresh interval used when a playlist omits EXT-X-TARGETDURATION
+const DEFAULT_TARGET_DURATION: f64 = 5.0;
+
+/// How long after loading a live playlist to reload it (RFC 8216 §6.3.4)
+///
+/// A playlist that gained segments is reloaded one target duration after it
+/// was loaded; an unchanged one is retried after half that.
+fn reload_interval(target_duration: f64, had_new_segments: bool) -> std::time::Duration {
+    let target = if target_duration > 0.0 {
+        target_duration
+    } else {
+        DEFAULT_TARGET_DURATION
+    };
+    let secs = if had_new_segments {
+        target
+    } else {
+        target / 2.0
+    };
+    std::time::Duration::from_secs_f64(secs)
+}
+
+/// Remembers how far into a live stream playback has got by media sequence
+/// number, so memory stays constant however long the session runs
+#[derive(Debug, Default)]
+struct SequenceTracker {
+    last: Option<u64>,
+}
+
+impl SequenceTracker {
+    /// Whether `seq` comes after every segment handled so far
+    fn is_new(&self, seq: u64) -> bool {
+        self.last.is_none_or(|last| seq > last)
+    }
+
+    fn mark(&mut self, seq: u64) {
+        self.last = Some(self.last.map_or(seq, |last| last.max(seq)));
+    }
+
+    /// Before anything is handled, treat all but the last `keep` segments
+    /// up to `newest` as handled already
+    fn start_at_edge(&mut self, newest: u64, keep: u64) {
+        let keep = keep.max(1);
+        if self.last.is_none() && newest >= keep {
+            self.last = Some(newest - keep);
+        }
+    }
+
+    /// Compare a freshly loaded window of `len` segments ending at `newest`
+    /// with what was handled, returning how many segments were missed
+    ///
+    /// When the window no longer lines up (after a variant switch, or when
+    /// the sequence restarted) tracking resumes from the newest segment.
+    fn observe_window(&mut self, newest: u64, len: u64, resync: bool) -> u64 {
+        let Some(last) = self.last else {
+            return 0;
+        };
+        let first = (newest + 1).saturating_sub(len);
+        // A window slightly behind is just a stale playlist; far behind is a restart
+        let restarted = newest + len < last;
+        let continues = first <= last + 1 && !restarted;
+        if continues {
+            return 0;
+        }
+        if resync || restarted {
+            log::info!(
+                "Media sequence moved from {} to {}, resyncing",
+                last,
+                newest
+            );
+            self.last = Some(newest.saturating_sub(1));
+            return 0;
+        }
+        first - last - 1
+    }
+}
+
+/// Stream decrypted segments for `area`'s `channel`
+///
+/// The config is fetched up front so an unknown area is reported as an error
+/// instead of an empty stream.
+pub async fn live_segments(
+    area: &str,
+    channel: ChannelKind,
+) -> Result<impl Stream<Item = SegmentData>> {
+    let client = Arc::new(NhkRadioClient::new());
+    let (stream_data, _) = client.fetch_config().await?.find_area(area)?;
+    Ok(segment_stream(
+        client,
+        stream_data,
+        channel,
+        StreamOptions::default(),
+    ))
+}
+
+/// Stream decoded PCM audio for `area`'s `channel`
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use nhk_radio_core::{ChannelKind, live_audio};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let mut audio = std::pin::pin!(live_audio("tokyo", ChannelKind::Fm).await?);
+/// while let Some(chunk) = audio.next().await {
+///     println!("{} samples", chunk.samples.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn live_audio(area: &str, channel: ChannelKind) -> Result<impl Stream<Item = PcmChunk>> {
+    Ok(pcm_stream(live_segments(area, channel).await?))
+}
+
+/// Follow a channel's live playlist and yield each new segment once, decrypted
+///
+/// The best variant within `options.max_bitrate` is used, stepping down to a
+/// lower one when segment fetches keep failing or falling behind realtime.
+/// Under `options.max_bandwidth` the stream ends once the cap is reached.
+pub fn segment_stream<H: RadioHttp + 'static>(
+    client: Arc<H>,
+    stream_data: StreamData,
+    channel: ChannelKind,
+    options: StreamOptions,
+) -> impl Stream<Item = SegmentData> {
+    segment_stream_with_stats(client, stream_data, channel, options, Arc::default())
+}
+
+/// [`segment_stream`], counting fetches, failures, bytes downloaded, playlist
+/// reloads, and reconnects in `stats` as it goes
+pub fn segment_stream_with_stats<H: RadioHttp + 'static>(
+    client: Arc<H>,
+    stream_data: StreamData,
+    channel: ChannelKind,
+    options: StreamOptions,
+    stats: Arc<StreamStats>,
+) -> impl Stream<Item = SegmentData> {
+    async_stream::stream! {
+        let m3u8_url = channel.get_url(&stream_data);
+        let mut played = SequenceTracker::default();
+        let live_edge = options.live_edge();
+        if let Some(cap) = options.max_bandwidth {
+            stats.set_bandwidth_cap(cap);
+        }
+        let mut variants: Vec<Variant> = Vec::new();
+        let mut variant_idx = 0;
+        let mut strikes = 0;
+        // The EXT-X-MAP section last fetched, shared by every segment that names it
+        let mut init: Option<(String, Arc<Vec<u8>>)> = None;
+        // Likewise the key last fetched; keys and methods may change mid-stream
+        let mut key: Option<(String, Vec<u8>)> = None;
+        // The first load of a new variant may not line up with the old one's numbering
+        let mut switched_variant = false;
+        // Set while the playlist cannot be loaded, so getting it back counts
+        // as a reconnect
+        let mut lost = false;
+
+        loop {
+            log::debug!("Fetching playlist for channel {:?}: {}", channel, m3u8_url);
+
+            // Resolve master playlist if needed (cache the result)
+            if variants.is_empty() {
+                match fetch_variants(client.as_ref(), &m3u8_url, &stats).await {
+                    Ok(v) => {
+                        variant_idx = options.variant(&v);
+                        log::info!(
+                            "Resolved playlist URL for {:?}: {} ({} bps)",
+                            channel,
+                            v[variant_idx].url,
+                            v[variant_idx].bandwidth
+                        );
+                        stats.set_bitrate(v[variant_idx].bandwidth);
+                        variants = v;
+                    }
+                    Err(e) => {
+                        log::error!("Failed to resolve master playlist: {}", e);
+                        lost = true;
+                        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                        continue;
+                    }
+                }
+            }
+            let actual_url = variants[variant_idx].url.clone();
+
+            let loaded_at = tokio::time::Instant::now();
+            let playlist_content = match client.fetch_m3u8(&actual_url).await {
+                Ok(c) => {
+                    stats.bytes_downloaded(c.len() as u64);
+                    c
+                }
+                Err(e) => {
+                    log::error!("Failed to fetch playlist: {}", e);
+                    lost = true;
+                    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                    continue;
+                }
+            };
+
+            let (segments, target_duration) = match parse_media_m3u8(&playlist_content, &actual_url) {
+                Ok(playlist) => (playlist.segments, playlist.target_duration),
+                Err(e) => {
+                    log::error!("Failed to parse playlist: {}", e);
+                    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                    continue;
+                }
+            };
+            stats.playlist_refreshed();
+            if lost {
+                log::info!("Playlist loaded again");
+                stats.reconnected();
+                lost = false;
+            }
+
+            if let Some(newest) = segments.last() {
+                let missed =
+                    played.observe_window(newest.seq_no, segments.len() as u64, switched_variant);
+                if missed > 0 {
+                    stats.segments_missed(missed);
+                    log::warn!(
+                        "Playlist window moved past {} segment(s) before they were fetched",
+                        missed
+                    );
+                }
+                played.start_at_edge(newest.seq_no, live_edge);
+            }
+            switched_variant = false;
+
+            let mut had_new_segments = false;
+            for segment in segments {
+                if !played.is_new(segment.seq_no) {
+                    continue;
+                }
+                if options.over_bandwidth(stats.downloaded()) {
+                    log::warn!("Reached the bandwidth cap, no longer fetching");
+                    return;
+                }
+
+                if let Some(ref key_url) = segment.key_url
+                    && key.as_ref().is_none_or(|(url, _)| url != key_url)
+                {
+                    match client.fetch_key(key_url).await {
+                        Ok(k) => {
+                            stats.bytes_downloaded(k.len() as u64);
+                            key = Some((key_url.clone(), k));
+                        }
+                        Err(e) => {
+                            // Leave the rest unplayed so the next reload retries them
+                            log::error!("Failed to fetch key: {}", e);
+                            break;
+                        }
+                    }
+                }
+                had_new_segments = true;
+                played.mark(segment.seq_no);
+
+                if let Some(ref map_url) = segment.map_url
+                    && init.as_ref().is_none_or(|(url, _)| url != map_url)
+                {
+                    match client.fetch_segment(map_url, None).await {
+                        Ok(data) => {
+                            stats.bytes_downloaded(data.len() as u64);
+                            init = Some((map_url.clone(), Arc::new(data)));
+                        }
+                        Err(e) => {
+                            log::error!("Failed to fetch init section: {}", e);
+                            stats.segment_failed();
+                            continue;
+                        }
+                    }
+                }
+                let init_section = segment
+                    .map_url
+                    .as_ref()
+                    .and(init.as_ref())
+                    .map(|(_, data)| data.clone());
+
+                let started = std::time::Instant::now();
+                let fetched = client.fetch_segment(&segment.url, segment.byte_range).await;
+                let too_slow = started.elapsed().as_secs_f64() > segment.duration;
+                strikes = if fetched.is_err() || too_slow { strikes + 1 } else { 0 };
+                if strikes >= DOWNSWITCH_STRIKES && variant_idx + 1 < variants.len() {
+                    variant_idx += 1;
+                    strikes = 0;
+                    switched_variant = true;
+                    stats.set_bitrate(variants[variant_idx].bandwidth);
+                    log::warn!(
+                        "Segment fetches are falling behind, switching to {} bps",
+                        variants[variant_idx].bandwidth
+                    );
+                }
+
+                let mut data = match fetched {
+                    Ok(d) => {
+                        stats.segment_fetched();
+                        stats.bytes_downloaded(d.len() as u64);
+                        d
+                    }
+                    Err(e) => {
+                        log::error!("Failed to fetch segment: {}", e);
+                        stats.segment_failed();
+                        continue;
+                    }
+                };
+
+                if segment.key_url.is_some()
+                    && let Some((_, ref k)) = key
+                {
+                    let iv = segment.iv.as_deref();
+                    data = match decrypt_segment(&data, k, iv, segment.seq_no, segment.key_method) {
+                        Ok(d) => {
+                            client.dump_decrypted(&segment.url, segment.seq_no, &d);
+                            d
+                        }
+                        Err(e) => {
+                            log::error!("Failed to decrypt: {}", e);
+                            stats.segment_failed();
+                            continue;
+                        }
+                    };
+                }
+
+                yield SegmentData {
+                    url: segment.url,
+                    seq_no: segment.seq_no,
+                    duration: segment.duration,
+                    program_date_time: segment.program_date_time,
+                    init: init_section,
+                    data,
+                };
+
+                if switched_variant {
+                    break;
+                }
+            }
+
+            if options.over_bandwidth(stats.downloaded()) {
+                log::warn!("Reached the bandwidth cap, no longer fetching");
+                return;
+            }
+            // Segment downloads count toward the wait, so a slow round may reload right away
+            tokio::time::sleep_until(loaded_at + reload_interval(target_duration, had_new_segments))
+                .await;
+        }
+    }
+}
+
+/// Decode a segment stream into PCM, skipping segments that yield no audio
+pub fn pcm_stream(segments: impl Stream<Item = SegmentData>) -> impl Stream<Item = PcmChunk> {
+    let mut decoder = StreamDecoder::new();
+    segments.filter_map(move |segment| {
+        let chunk = match segment.decode(&mut decoder) {
+            Ok(chunk) if !chunk.samples.is_empty() => Some(chunk),
+            Ok(_) => None,
+            Err(e) => {
+                log::debug!("Failed to decode AAC: {}", e);
+                None
+            }
+        };
+        futures::future::ready(chunk)
+    })
+}
+
+/// URL of the variant [`segment_stream`] would start on, for players that
+/// fetch and decrypt the stream themselves
+pub async fn variant_url(
+    client: &impl RadioHttp,
+    stream_data: &StreamData,
+    channel: ChannelKind,
+    options: StreamOptions,
+) -> Result<String> {
+    let url = channel.get_url(stream_data);
+    let variants = fetch_variants(client, &url, &StreamStats::default()).await?;
+    Ok(variants[options.variant(&variants)].url.clone())
+}
+
+/// Variants of the playlist at `url`, best first
+///
+/// A media playlist is returned as its own single variant. The download is
+/// counted in `stats`.
+pub(crate) async fn fetch_variants(
+    client: &impl RadioHttp,
+    url: &str,
+    stats: &StreamStats,
+) -> Result<Vec<Variant>> {
+    let content = client.fetch_m3u8(url).await?;
+    stats.bytes_downloaded(content.len() as u64);
+    match parse_playlist(&content, url)? {
+        ParsedPlaylist::Master(variants) => Ok(variants),
+        ParsedPlaylist::Media(_) => Ok(vec![Variant {
+            url: url.to_string(),
+            bandwidth: 0,
+            codecs: None,
+        }]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::stream_data;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sequence_tracker_gap_and_resync() {
+        let mut played = SequenceTracker::default();
+        assert_eq!(played.observe_window(10, 6, false), 0);
+        played.mark(10);
+        assert!(!played.is_new(9));
+        assert!(played.is_new(11));
+
+        // Continuing and stale windows are fine
+        assert_eq!(played.observe_window(12, 6, false), 0);
+        assert_eq!(played.observe_window(9, 6, false), 0);
+        // Segments 11..=14 fell out of the window unseen
+        assert_eq!(played.observe_window(20, 6, false), 4);
+
+        // A restarted sequence resumes at its newest segment
+        played.mark(20);
+        assert_eq!(played.observe_window(3, 6, false), 0);
+        assert!(!played.is_new(2));
+        assert!(played.is_new(3));
+
+        // So does a switched variant numbered differently
+        assert_eq!(played.observe_window(500, 6, true), 0);
+        assert!(played.is_new(500));
+        assert!(!played.is_new(499));
+    }
+
+    #[test]
+    fn test_sequence_tracker_live_edge() {
+        let mut played = SequenceTracker::default();
+        assert_eq!(played.observe_window(10, 6, false), 0);
+        played.start_at_edge(10, 3);
+        assert!(!played.is_new(7));
+        assert!(played.is_new(8));
+        // Only the first window is cut short
+        played.mark(10);
+        played.start_at_edge(16, 3);
+        assert!(played.is_new(11));
+
+        // A window shorter than the edge is played whole
+        let mut played = SequenceTracker::default();
+        played.start_at_edge(1, 3);
+        assert!(played.is_new(0));
+    }
+
+    #[test]
+    fn test_reload_interval() {
+        assert_eq!(reload_interval(6.0, true), Duration::from_secs(6));
+        assert_eq!(reload_interval(6.0, false), Duration::from_secs(3));
+        assert_eq!(reload_interval(0.0, true), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_segment_stream_fetches_and_decrypts_offline() {
+        use crate::mock::MockHttp;
+        use aes::cipher::{BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
+
+        let key = [3u8; 16];
+        let http = Arc::new(MockHttp::new());
+        http.insert(
+            "https://example.com/fm/master.m3u8",
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=48000\nmedia.m3u8\n",
+        );
+        http.insert(
+            "https://example.com/fm/media.m3u8",
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:5\n#EXT-X-MEDIA-SEQUENCE:7\n\
+             #EXT-X-KEY:METHOD=AES-128,URI=\"key\"\n\
+             #EXTINF:5.0,\nseg7.aac\n#EXTINF:5.0,\nseg8.aac\n",
+        );
+        http.insert("https://example.com/fm/key", key.to_vec());
+        for seq_no in [7u64, 8] {
+            // Without an IV attribute the media sequence number is the IV
+            let mut iv = [0u8; 16];
+            iv[8..].copy_from_slice(&seq_no.to_be_bytes());
+            let plain = format!("segment {}", seq_no);
+            let mut buf = plain.clone().into_bytes();
+            buf.resize(16, 0);
+            let encrypted = cbc::Encryptor::<aes::Aes128>::new_from_slices(&key, &iv)
+                .unwrap()
+                .encrypt_padded_mut::<Pkcs7>(&mut buf, plain.len())
+                .unwrap()
+                .to_vec();
+            http.insert(
+                format!("https://example.com/fm/seg{}.aac", seq_no),
+                encrypted,
+            );
+        }
+
+        let stream_data = stream_data("tokyo", "東京");
+        let segments: Vec<SegmentData> = segment_stream(
+            http.clone(),
+            stream_data,
+            ChannelKind::Fm,
+            StreamOptions::default(),
+        )
+        .take(2)
+        .collect()
+        .await;
+
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].seq_no, 7);
+        assert_eq!(segments[0].data, b"segment 7");
+        assert_eq!(segments[1].data, b"segment 8");
+        // The key is fetched once and reused
+        let key_fetches = http
+            .requests()
+            .iter()
+            .filter(|url| url.ends_with("/key"))
+            .count();
+        assert_eq!(key_fetches, 1);
+    }
+
+    #[tokio::test]
+    async fn test_variant_url_respects_max_bitrate() {
+        use crate::mock::MockHttp;
+
+        let http = MockHttp::new();
+        http.insert(
+            "https://example.com/fm/master.m3u8",
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=192000\nhigh.m3u8\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=48000\nlow.m3u8\n",
+        );
+        let stream_data = stream_data("tokyo", "東京");
+
+        let best = variant_url(
+            &http,
+            &stream_data,
+            ChannelKind::Fm,
+            StreamOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(best, "https://example.com/fm/high.m3u8");
+        let capped = StreamOptions {
+            max_bitrate: Some(64_000),
+            ..Default::default()
+        };
+        let low = variant_url(&http, &stream_data, ChannelKind::Fm, capped)
+            .await
+            .unwrap();
+        assert_eq!(low, "https://example.com/fm/low.m3u8");
+        let capped = StreamOptions {
+            max_bandwidth: Some(10_000_000),
+            ..Default::default()
+        };
+        let low = variant_url(&http, &stream_data, ChannelKind::Fm, capped)
+            .await
+            .unwrap();
+        assert_eq!(low, "https://example.com/fm/low.m3u8");
+    }
+
+    #[tokio::test]
+    async fn test_segment_stream_bandwidth_cap() {
+        use crate::mock::MockHttp;
+
+        let http = Arc::new(MockHttp::new());
+        let master = "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=192000\nhigh.m3u8\n\
+                      #EXT-X-STREAM-INF:BANDWIDTH=48000\nlow.m3u8\n";
+        http.insert("https://example.com/fm/master.m3u8", master);
+        let playlist = "#EXTM3U\n#EXT-X-TARGETDURATION:5\n#EXT-X-MEDIA-SEQUENCE:7\n\
+                        #EXTINF:5.0,\nseg7.aac\n#EXTINF:5.0,\nseg8.aac\n";
+        http.insert("https://example.com/fm/low.m3u8", playlist);
+        http.insert("https://example.com/fm/seg7.aac", vec![0u8; 1000]);
+        http.insert("https://example.com/fm/seg8.aac", vec![0u8; 1000]);
+        let stream_data = stream_data("tokyo", "東京");
+        let stats = Arc::new(StreamStats::default());
+        let options = StreamOptions {
+            max_bandwidth: Some((master.len() + playlist.len()) as u64 + 1),
+            ..Default::default()
+        };
+
+        // Only the newest segment of the low variant, and then the cap is hit
+        let segments: Vec<SegmentData> = segment_stream_with_stats(
+            http.clone(),
+            stream_data,
+            ChannelKind::Fm,
+            options,
+            stats.clone(),
+        )
+        .collect()
+        .await;
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].seq_no, 8);
+        assert!(!http.requests().iter().any(|url| url.ends_with("high.m3u8")));
+        let snapshot = stats.snapshot();
+        assert_eq!(
+            snapshot.bytes_downloaded,
+            (master.len() + playlist.len()) as u64 + 1000
+        );
+        assert_eq!(snapshot.bandwidth_cap, options.max_bandwidth);
+    }
+}
@@ -0,0 +1,97 @@
+//! Limits on how hard a client leans on NHK's servers.
+//!
+//! Every request an [`NhkRadioClient`](crate::client::NhkRadioClient) makes
+//! first takes a [`Throttle`] slot: at most so many requests are in flight at
+//! once, and starts are spaced at least a minimum interval apart. Retries
+//! wait a [`jittered`] delay, so clients that failed together do not all
+//! come back at the same moment.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+/// Shared by all requests of one client
+#[derive(Debug)]
+pub struct Throttle {
+    permits: Semaphore,
+    interval: Duration,
+    /// Earliest time the next request may start
+    next_start: Mutex<Instant>,
+}
+
+impl Throttle {
+    /// Allow `max_concurrent` requests at once (at least one), starting no
+    /// closer together than `interval`
+    pub fn new(max_concurrent: usize, interval: Duration) -> Self {
+        Throttle {
+            permits: Semaphore::new(max_concurrent.max(1)),
+            interval,
+            next_start: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for a turn to send a request, which lasts until the permit is
+    /// dropped
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("throttle semaphore is never closed");
+        if !self.interval.is_zero() {
+            let start = {
+                let mut next = self.next_start.lock().unwrap_or_else(|e| e.into_inner());
+                let start = (*next).max(Instant::now());
+                *next = start + self.interval;
+                start
+            };
+            tokio::time::sleep_until(start).await;
+        }
+        permit
+    }
+}
+
+/// `delay` shortened by a random part of up to `jitter` (0 to 1) of it
+pub fn jittered(delay: Duration, jitter: f64) -> Duration {
+    let jitter = jitter.clamp(0.0, 1.0);
+    delay.mul_f64(1.0 - jitter * random_fraction())
+}
+
+/// A number in `0.0..1.0` that differs from call to call
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().hash_one(std::time::SystemTime::now());
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jittered_stays_within_range() {
+        let delay = Duration::from_secs(2);
+        assert_eq!(jittered(delay, 0.0), delay);
+        for _ in 0..100 {
+            let jittered = jittered(delay, 0.5);
+            assert!(jittered <= delay && jittered >= Duration::from_secs(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_spaced_and_limited() {
+        let begun = Instant::now();
+        let throttle = Throttle::new(2, Duration::from_millis(50));
+
+        let first = throttle.acquire().await;
+        let _second = throttle.acquire().await;
+        assert!(begun.elapsed() >= Duration::from_millis(50));
+        // Both slots are taken until one is given back
+        assert!(throttle.permits.try_acquire().is_err());
+        drop(first);
+        let _third = throttle.acquire().await;
+        assert!(begun.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+
+/// Ring buffer of recent PCM chunks, addressed by absolute chunk index
+///
+/// Indices keep increasing as chunks are pushed, so a position stays valid
+/// until the chunk it refers to is evicted.
+pub struct TimeshiftBuffer {
+    chunks: VecDeque<Vec<i16>>,
+    first_index: u64,
+    total_samples: usize,
+    capacity_samples: usize,
+}
+
+impl TimeshiftBuffer {
+    pub fn new(capacity_samples: usize) -> Self {
+        Self {
+            chunks: VecDeque::new(),
+            first_index: 0,
+            total_samples: 0,
+            capacity_samples,
+        }
+    }
+
+    /// Append a chunk, evicting the oldest ones beyond capacity
+    pub fn push(&mut self, chunk: Vec<i16>) {
+        self.total_samples += chunk.len();
+        self.chunks.push_back(chunk);
+        while self.total_samples > self.capacity_samples && self.chunks.len() > 1 {
+            if let Some(old) = self.chunks.pop_front() {
+                self.total_samples -= old.len();
+                self.first_index += 1;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.first_index = self.end_index();
+        self.chunks.clear();
+        self.total_samples = 0;
+    }
+
+    pub fn first_index(&self) -> u64 {
+        self.first_index
+    }
+
+    /// Index one past the newest chunk
+    pub fn end_index(&self) -> u64 {
+        self.first_index + self.chunks.len() as u64
+    }
+
+    pub fn get(&self, index: u64) -> Option<&Vec<i16>> {
+        index
+            .checked_sub(self.first_index)
+            .and_then(|offset| self.chunks.get(offset as usize))
+    }
+
+    /// Walk back from `index` until at least `samples` samples are covered
+    pub fn index_before(&self, index: u64, samples: usize) -> u64 {
+        let mut covered = 0;
+        let mut target = index.min(self.end_index());
+        while target > self.first_index && covered < samples {
+            target -= 1;
+            covered += self.get(target).map_or(0, |c| c.len());
+        }
+        target
+    }
+
+    /// Number of samples held in chunks `[from, to)`
+    pub fn samples_between(&self, from: u64, to: u64) -> usize {
+        (from.max(self.first_index)..to.min(self.end_index()))
+            .filter_map(|i| self.get(i))
+            .map(|c| c.len())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_evicts_oldest() {
+        let mut buf = TimeshiftBuffer::new(10);
+        buf.push(vec![0; 4]);
+        buf.push(vec![0; 4]);
+        buf.push(vec![0; 4]);
+        assert_eq!(buf.first_index(), 1);
+        assert_eq!(buf.end_index(), 3);
+        assert!(buf.get(0).is_none());
+        assert!(buf.get(2).is_some());
+    }
+
+    #[test]
+    fn test_index_before() {
+        let mut buf = TimeshiftBuffer::new(100);
+        for _ in 0..5 {
+            buf.push(vec![0; 10]);
+        }
+        assert_eq!(buf.index_before(5, 15), 3);
+        assert_eq!(buf.index_before(5, 1000), 0);
+        assert_eq!(buf.samples_between(3, 5), 20);
+    }
+
+    #[test]
+    fn test_clear_keeps_indices_monotonic() {
+        let mut buf = TimeshiftBuffer::new(100);
+        buf.push(vec![0; 10]);
+        buf.push(vec![0; 10]);
+        buf.clear();
+        assert_eq!(buf.first_index(), 2);
+        assert_eq!(buf.end_index(), 2);
+        buf.push(vec![0; 10]);
+        assert!(buf.get(2).is_some());
+    }
+}
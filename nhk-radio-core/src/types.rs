@@ -1,3 +1,8 @@
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+
+use crate::crypto::EncryptionMethod;
+use crate::error::ErrorKind;
 use serde::{Deserialize, Serialize};
 
 /// NHK Radio configuration from config_web.xml
@@ -13,6 +18,38 @@ pub struct RadiruConfig {
     pub radiru_twitter_timeline: String,
 }
 
+impl RadiruConfig {
+    /// URL of the program listing for the area with `areakey`
+    pub fn program_url(&self, areakey: &str) -> String {
+        absolute_url(&self.url_program_noa).replace("{area}", areakey)
+    }
+
+    /// The stream URLs of `area` and the URL of its program listing
+    pub fn find_area(&self, area: &str) -> Result<(StreamData, String)> {
+        let stream_data = self
+            .stream_url
+            .data
+            .iter()
+            .find(|d| d.area == area)
+            .ok_or_else(|| ErrorKind::AreaNotFound.error(format!("Area not found: {}", area)))?
+            .clone();
+        let program_url = self.program_url(&stream_data.areakey);
+        Ok((stream_data, program_url))
+    }
+}
+
+/// `url` with its scheme filled in
+///
+/// The config gives NHK's own URLs protocol-relative (`//api.nhk.or.jp/...`),
+/// which are fetched over HTTPS; absolute URLs, such as a test server's, are
+/// left as they are.
+pub fn absolute_url(url: &str) -> String {
+    match url.strip_prefix("//") {
+        Some(rest) => format!("https://{}", rest),
+        None => url.to_string(),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct StreamUrl {
     #[serde(rename = "data", default)]
@@ -32,13 +69,23 @@ pub struct StreamData {
 }
 
 /// Program information root
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Default)]
 pub struct Root {
     pub r1: Channel,
     pub r2: Channel,
     pub r3: Channel,
 }
 
+impl Root {
+    /// Whether every program the listing has on air is still on at `now`
+    pub fn is_current(&self, now: DateTime<FixedOffset>) -> bool {
+        [&self.r1, &self.r2, &self.r3]
+            .into_iter()
+            .filter_map(|channel| channel.present.as_ref())
+            .all(|event| DateTime::parse_from_rfc3339(&event.end_date).is_ok_and(|end| now < end))
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Default)]
 pub struct Channel {
     #[serde(default)]
@@ -90,7 +137,7 @@ pub struct Location {
     pub name: String,
 }
 
-#[derive(Debug, Deserialize, Serialize, Default)]
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
 pub struct IdentifierGroup {
     #[serde(rename = "broadcastEventId", default)]
     pub broadcast_event_id: String,
@@ -118,7 +165,7 @@ pub struct IdentifierGroup {
     pub site_id: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Genre {
     pub id: String,
     pub name1: String,
@@ -312,6 +359,21 @@ pub struct Images {
     pub small: Option<Image>,
 }
 
+impl Images {
+    /// URL of a size suited to a thumbnail, preferring medium over small and
+    /// the larger ones last
+    pub fn thumbnail_url(&self) -> Option<String> {
+        let image = [&self.medium, &self.small, &self.main, &self.large]
+            .into_iter()
+            .find_map(Option::as_ref)?;
+        // Some listings give protocol-relative URLs
+        Some(match image.url.strip_prefix("//") {
+            Some(rest) => format!("https://{}", rest),
+            None => image.url.clone(),
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Default)]
 pub struct Style {
     #[serde(rename = "textLight", default)]
@@ -409,7 +471,7 @@ pub struct Publication {
     pub is_live_broadcast: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize, Default)]
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
 pub struct DetailedDescription {
     #[serde(default)]
     pub epg40: String,
@@ -469,13 +531,191 @@ pub struct ServiceIdentifierGroup {
     pub multi_channel_display_name: Option<String>,
 }
 
+/// One channel's programs for a day, from `url_program_day`
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct DaySchedule {
+    #[serde(default)]
+    pub publication: Vec<ScheduledProgram>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ScheduledProgram {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "startDate", default)]
+    pub start_date: String,
+    #[serde(rename = "endDate", default)]
+    pub end_date: String,
+    /// Series and genre of the program, when the timetable lists them
+    #[serde(rename = "identifierGroup", default)]
+    pub identifier_group: IdentifierGroup,
+    /// Episode and series pages, when the timetable lists them
+    #[serde(default)]
+    pub about: Option<ScheduledAbout>,
+    /// Longer descriptions, where the cast is usually listed
+    #[serde(rename = "detailedDescription", default)]
+    pub detailed_description: DetailedDescription,
+}
+
+impl ScheduledProgram {
+    /// A program named `name` airing from `start` to `end`, both RFC 3339,
+    /// with nothing else listed
+    #[cfg(any(test, feature = "mock-server"))]
+    pub fn named(name: &str, start: &str, end: &str) -> Self {
+        ScheduledProgram {
+            name: name.to_string(),
+            description: String::new(),
+            start_date: start.to_string(),
+            end_date: end.to_string(),
+            identifier_group: IdentifierGroup::default(),
+            about: None,
+            detailed_description: DetailedDescription::default(),
+        }
+    }
+
+    /// Web page of the episode, else of its series
+    pub fn page_url(&self) -> Option<&str> {
+        let about = self.about.as_ref()?;
+        [
+            about.canonical.as_deref(),
+            about
+                .part_of_series
+                .as_ref()
+                .and_then(|series| series.canonical.as_deref()),
+        ]
+        .into_iter()
+        .flatten()
+        .find(|url| !url.is_empty())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ScheduledAbout {
+    #[serde(default)]
+    pub canonical: Option<String>,
+    #[serde(rename = "partOfSeries", default)]
+    pub part_of_series: Option<SeriesLink>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct SeriesLink {
+    #[serde(default)]
+    pub canonical: Option<String>,
+}
+
+/// 聴き逃し (on-demand) corner list
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OndemandCorners {
+    #[serde(default)]
+    pub corners: Vec<OndemandCorner>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OndemandCorner {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub radio_broadcast: String,
+    #[serde(default)]
+    pub corner_name: String,
+    #[serde(default)]
+    pub onair_date: String,
+    #[serde(default)]
+    pub series_site_id: String,
+    #[serde(default)]
+    pub corner_site_id: String,
+}
+
+/// 聴き逃し series with its available episodes
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OndemandSeries {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub radio_broadcast: String,
+    #[serde(default)]
+    pub schedule: String,
+    #[serde(default)]
+    pub corner_name: String,
+    #[serde(default)]
+    pub series_description: String,
+    #[serde(default)]
+    pub series_site_id: String,
+    #[serde(default)]
+    pub corner_site_id: String,
+    #[serde(default)]
+    pub episodes: Vec<OndemandEpisode>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OndemandEpisode {
+    #[serde(default)]
+    pub program_title: String,
+    #[serde(default)]
+    pub program_sub_title: String,
+    #[serde(default)]
+    pub onair_date: String,
+    #[serde(default)]
+    pub closed_at: String,
+    #[serde(default)]
+    pub stream_url: String,
+}
+
 /// Segment information from M3U8 playlist
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 #[allow(dead_code)]
 pub struct Segment {
     pub url: String,
     pub key_url: Option<String>,
+    pub key_method: EncryptionMethod,
     pub iv: Option<String>,
     pub seq_no: u64,
     pub duration: f64,
+    /// Wall-clock time the segment starts, from EXT-X-PROGRAM-DATE-TIME
+    pub program_date_time: Option<DateTime<FixedOffset>>,
+    /// Initialization section from EXT-X-MAP, present for fMP4 segments
+    pub map_url: Option<String>,
+    /// Part of `url` holding the segment, from EXT-X-BYTERANGE
+    pub byte_range: Option<ByteRange>,
+}
+
+/// A run of bytes within a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub offset: u64,
+    pub length: u64,
+}
+
+impl ByteRange {
+    /// Value for an HTTP `Range` header requesting these bytes
+    pub fn header_value(&self) -> String {
+        let last = (self.offset + self.length).saturating_sub(1);
+        format!("bytes={}-{}", self.offset, last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::broadcast_event;
+
+    fn at(time: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(&format!("2025-11-25T{}:00+09:00", time)).unwrap()
+    }
+
+    #[test]
+    fn test_is_current() {
+        let root: Root = serde_json::from_value(serde_json::json!({
+            "r1": { "present": broadcast_event("NHKジャーナル", at("21:10"), at("22:00")) },
+            "r2": {},
+            "r3": { "present": broadcast_event("ベストオブクラシック", at("19:30"), at("21:30")) },
+        }))
+        .unwrap();
+        assert!(root.is_current(at("21:10")));
+        // Once FM moves on, a cached listing no longer says what is on
+        assert!(!root.is_current(at("21:30")));
+        assert!(!root.is_current(at("22:10")));
+    }
 }
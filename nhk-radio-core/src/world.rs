@@ -0,0 +1,111 @@
+//! NHK WORLD-JAPAN's Radio Japan, the international service.
+//!
+//! The domestic らじる★らじる streams are only served inside Japan, while
+//! Radio Japan broadcasts worldwide in 18 languages. Each language is added
+//! to the config as an extra area, `world-<code>` (e.g. `world-en`), so it
+//! can be picked wherever an area can. Radio Japan has one stream per
+//! language, so every channel of these areas plays the same stream, and
+//! there is no program guide for them.
+
+use crate::types::{RadiruConfig, StreamData};
+
+/// Live stream of a language, with `{lang}` replaced by its code
+pub const STREAM_URL: &str =
+    "https://nhkworld.webcdn.stream.ne.jp/www11/radiojapan/all/{lang}/live.m3u8";
+
+/// Area key of every Radio Japan area, standing in for a domestic one in
+/// program URLs
+pub const AREA_KEY: &str = "world";
+
+/// Radio Japan's languages: code, English name, and Japanese name
+pub const LANGUAGES: [(&str, &str, &str); 18] = [
+    ("en", "English", "英語"),
+    ("ja", "Japanese", "日本語"),
+    ("ar", "Arabic", "アラビア語"),
+    ("bn", "Bengali", "ベンガル語"),
+    ("my", "Burmese", "ビルマ語"),
+    ("zh", "Chinese", "中国語"),
+    ("fr", "French", "フランス語"),
+    ("hi", "Hindi", "ヒンディー語"),
+    ("id", "Indonesian", "インドネシア語"),
+    ("ko", "Korean", "コリア語"),
+    ("fa", "Persian", "ペルシャ語"),
+    ("pt", "Portuguese", "ポルトガル語"),
+    ("ru", "Russian", "ロシア語"),
+    ("es", "Spanish", "スペイン語"),
+    ("sw", "Swahili", "スワヒリ語"),
+    ("th", "Thai", "タイ語"),
+    ("ur", "Urdu", "ウルドゥー語"),
+    ("vi", "Vietnamese", "ベトナム語"),
+];
+
+/// One area per language
+pub fn stations() -> Vec<StreamData> {
+    LANGUAGES
+        .iter()
+        .map(|(code, _, japanese)| {
+            let url = STREAM_URL.replace("{lang}", code);
+            StreamData {
+                areajp: format!("NHK WORLD {}", japanese),
+                area: format!("world-{}", code),
+                apikey: String::new(),
+                areakey: AREA_KEY.to_string(),
+                r1hls: url.clone(),
+                r2hls: url.clone(),
+                fmhls: url,
+            }
+        })
+        .collect()
+}
+
+/// Append the Radio Japan areas after the domestic ones
+pub fn add_stations(config: &mut RadiruConfig) {
+    config.stream_url.data.extend(stations());
+}
+
+/// Whether `stream_data` is a Radio Japan area
+pub fn is_world(stream_data: &StreamData) -> bool {
+    stream_data.areakey == AREA_KEY
+}
+
+/// Whether `program_url`, or a day schedule URL, was made for a Radio Japan
+/// area, which has no program guide to fetch
+pub fn is_program_url(program_url: &str) -> bool {
+    program_url
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|part| part == AREA_KEY)
+}
+
+/// English name of a Radio Japan area's language, e.g. "English" for `world-en`
+pub fn language_name(area: &str) -> Option<&'static str> {
+    let code = area.strip_prefix("world-")?;
+    LANGUAGES
+        .iter()
+        .find(|(c, _, _)| *c == code)
+        .map(|(_, name, _)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::area::resolve_area;
+
+    #[test]
+    fn test_stations() {
+        let stations = stations();
+        assert_eq!(stations.len(), LANGUAGES.len());
+        let english = resolve_area("world-en", &stations).unwrap();
+        assert_eq!(english.areajp, "NHK WORLD 英語");
+        assert!(english.r1hls.contains("/en/"));
+        assert_eq!(english.r1hls, english.fmhls);
+        assert!(is_world(english));
+        assert_eq!(language_name("world-vi"), Some("Vietnamese"));
+        assert_eq!(language_name("tokyo"), None);
+        assert!(is_program_url(
+            "https://api.nhk.jp/r5/pg2/now/4/world/netradio.json"
+        ));
+        assert!(!is_program_url(
+            "https://api.nhk.jp/r5/pg2/now/4/130/netradio.json"
+        ));
+    }
+}
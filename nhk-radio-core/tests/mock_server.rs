@@ -0,0 +1,123 @@
+//! End-to-end tests against the mock server: config, playlists, keys, and
+//! segments fetched over HTTP, decrypted, and decoded as from NHK.
+//!
+//! Run with `cargo test -p nhk-radio-core --features mock-server`.
+
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use nhk_radio_core::client::NhkRadioClient;
+use nhk_radio_core::engine::PlaybackEngine;
+use nhk_radio_core::equalizer::EqPreset;
+use nhk_radio_core::mock_server::{
+    AREA, FRAME_SAMPLES, MockServer, PROGRAM_TITLE, SAMPLE_RATE, SEGMENT_FRAMES,
+};
+use nhk_radio_core::pipe::{PcmFormat, PcmPipe};
+use nhk_radio_core::player::{ChannelKind, PlaybackEvent, PlayerOptions};
+use nhk_radio_core::radio::NhkRadio;
+
+/// Long enough for a slow machine, short enough to notice a hang
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+#[tokio::test]
+async fn test_stream_decodes_segments() {
+    let server = MockServer::start().await.unwrap();
+    let radio = NhkRadio::with_options(server.http_options()).await.unwrap();
+    assert_eq!(radio.area(AREA).unwrap().areajp, "東京");
+
+    let stream = radio.stream(AREA, ChannelKind::Fm).unwrap();
+    let chunks: Vec<_> = tokio::time::timeout(TIMEOUT, stream.take(3).collect())
+        .await
+        .expect("no audio from the mock server");
+
+    for chunk in &chunks {
+        assert_eq!(chunk.spec.sample_rate, SAMPLE_RATE);
+        assert_eq!(chunk.spec.channels, 1);
+        assert_eq!(chunk.samples.len(), SEGMENT_FRAMES * FRAME_SAMPLES);
+        assert!(chunk.samples.iter().all(|&sample| sample == 0));
+    }
+
+    let requests = server.requests();
+    assert_eq!(requests[0], "/radio/config/config_web.xml");
+    // The key is fetched once and reused for every segment
+    let key_fetches = requests.iter().filter(|path| *path == "/hls/fm/key");
+    assert_eq!(key_fetches.count(), 1);
+    assert!(requests.iter().any(|path| path == "/hls/fm/seg5.aac"));
+}
+
+#[tokio::test]
+async fn test_now_playing() {
+    let server = MockServer::start().await.unwrap();
+    let radio = NhkRadio::with_options(server.http_options()).await.unwrap();
+
+    let mut programs = Box::pin(radio.now_playing(AREA, ChannelKind::R1).unwrap());
+    let program = tokio::time::timeout(TIMEOUT, programs.next())
+        .await
+        .expect("no program from the mock server")
+        .unwrap();
+    assert_eq!(program.channel, ChannelKind::R1);
+    assert_eq!(program.title, PROGRAM_TITLE);
+    assert!(program.start.is_some() && program.end.is_some());
+}
+
+#[tokio::test]
+async fn test_engine_writes_decoded_audio() {
+    let server = MockServer::start().await.unwrap();
+    let client = NhkRadioClient::with_options(server.http_options()).unwrap();
+    let config = client.fetch_config().await.unwrap();
+    let stream_data = config
+        .stream_url
+        .data
+        .iter()
+        .find(|data| data.area == AREA)
+        .unwrap()
+        .clone();
+
+    let path = std::env::temp_dir().join(format!("nhk-mock-server-{}.pcm", std::process::id()));
+    let options = PlayerOptions {
+        volume: 100,
+        recorder: None,
+        timeshift_minutes: 0,
+        buffer_secs: 1,
+        device: None,
+        speaker: false,
+        pipes: vec![PcmPipe {
+            format: PcmFormat::Raw,
+            path: Some(path.clone()),
+        }],
+        target_lufs: None,
+        equalizer: EqPreset::default(),
+        silence_alert: None,
+        http: server.http_options(),
+        stream: Default::default(),
+    };
+    let engine = PlaybackEngine::start(Arc::new(client), stream_data, ChannelKind::R2, options);
+
+    let started = tokio::time::timeout(TIMEOUT, async {
+        loop {
+            match engine.try_event() {
+                Some(PlaybackEvent::Started) => break,
+                Some(PlaybackEvent::Error(e)) => panic!("playback failed: {}", e),
+                _ => tokio::time::sleep(Duration::from_millis(50)).await,
+            }
+        }
+    })
+    .await;
+    assert!(started.is_ok(), "playback never started");
+    assert!(engine.is_streaming());
+    drop(engine);
+
+    let pcm = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    // Whole interleaved stereo frames of 16-bit silence
+    assert!(!pcm.is_empty());
+    assert_eq!(pcm.len() % 4, 0);
+    assert!(pcm.iter().all(|&byte| byte == 0));
+    assert!(
+        server
+            .requests()
+            .iter()
+            .any(|path| path.starts_with("/hls/r2/seg"))
+    );
+}
@@ -0,0 +1,428 @@
+//! Recording rules for the daemon, like timer recording by keyword on a PVR.
+//!
+//! Each `[[record_rule]]` in the config file says what to look for: a
+//! keyword in the title, description, or cast, a series ID, or a genre,
+//! optionally narrowed to one channel and area. While the daemon runs, the timetables
+//! those rules cover are fetched every hour, and each matching program is
+//! recorded from a minute before it starts to a minute after it ends.
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::area::resolve_area;
+use crate::client::NhkRadioClient;
+use crate::decoder::StreamDecoder;
+use crate::download::sanitize_file_name;
+use crate::player::ChannelKind;
+use crate::radio::{program_day_url, today_jst};
+use crate::recorder::{RecordFormat, Recorder};
+use crate::search;
+use crate::stream::{StreamOptions, segment_stream};
+use crate::types::{RadiruConfig, ScheduledProgram, StreamData};
+
+/// How often the timetables are fetched again
+const EPG_REFRESH: Duration = Duration::from_secs(60 * 60);
+
+/// Extra time recorded on either side of a program
+const PADDING: chrono::Duration = chrono::Duration::minutes(1);
+
+/// What to record, from a `[[record_rule]]` entry
+///
+/// Every criterion given must match; at least one of `keyword`, `series`,
+/// and `genre` is required.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RecordRule {
+    /// Name to log the rule by
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// Text the title, description, or cast contains, ignoring case
+    pub keyword: Option<String>,
+    /// `radioSeriesId` of the series
+    pub series: Option<String>,
+    /// Genre ID, a genre word (e.g. "classical"), or text its name contains
+    /// (e.g. "ジャズ"); see [`crate::genre`]
+    pub genre: Option<String>,
+    /// r1, r2, or fm; every channel when unset
+    pub channel: Option<String>,
+    /// Area name or code; the daemon's area when unset
+    pub area: Option<String>,
+}
+
+impl RecordRule {
+    /// The channels the rule watches
+    pub fn channels(&self) -> Result<Vec<ChannelKind>> {
+        match self.channel {
+            Some(ref channel) => Ok(vec![channel.parse()?]),
+            None => Ok(vec![ChannelKind::R1, ChannelKind::R2, ChannelKind::Fm]),
+        }
+    }
+
+    pub fn matches(&self, program: &ScheduledProgram) -> bool {
+        let keyword = self
+            .keyword
+            .as_deref()
+            .is_none_or(|keyword| search::mentions(program, keyword));
+        let series = self
+            .series
+            .as_deref()
+            .is_none_or(|series| program.identifier_group.radio_series_id == series);
+        let genre = self
+            .genre
+            .as_deref()
+            .is_none_or(|genre| search::in_genre(program, genre));
+        keyword && series && genre
+    }
+
+    /// The name, else the first criterion given
+    pub fn label(&self) -> &str {
+        [self.name.as_str()]
+            .into_iter()
+            .chain(self.keyword.as_deref())
+            .chain(self.series.as_deref())
+            .chain(self.genre.as_deref())
+            .find(|label| !label.is_empty())
+            .unwrap_or_default()
+    }
+
+    fn validate(&self) -> Result<()> {
+        let given =
+            |criterion: &Option<String>| criterion.as_deref().is_some_and(|c| !c.is_empty());
+        if !given(&self.keyword) && !given(&self.series) && !given(&self.genre) {
+            anyhow::bail!(
+                "A [[record_rule]] needs a keyword, series, or genre{}",
+                match self.name.as_str() {
+                    "" => String::new(),
+                    name => format!(" ({})", name),
+                }
+            );
+        }
+        Ok(())
+    }
+}
+
+/// One area's channel and the rules watching it
+struct Watch {
+    stream_data: StreamData,
+    channel: ChannelKind,
+    rules: Vec<RecordRule>,
+}
+
+/// A program a rule picked out of the timetable
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedRecording {
+    pub rule: String,
+    pub area: String,
+    pub channel: ChannelKind,
+    pub title: String,
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+}
+
+impl PlannedRecording {
+    /// e.g. "tokyo-fm-20251125-2100 クラシック音楽館.mp3"
+    pub fn file_name(&self, format: RecordFormat) -> String {
+        program_file_name(&self.area, self.channel, self.start, &self.title, format)
+    }
+}
+
+/// File name for a recording of the program `title` starting at `start`
+pub(crate) fn program_file_name(
+    area: &str,
+    channel: ChannelKind,
+    start: DateTime<FixedOffset>,
+    title: &str,
+    format: RecordFormat,
+) -> String {
+    let stem = format!(
+        "{}-{}-{} {}",
+        area,
+        channel.short_name().to_lowercase(),
+        start.format("%Y%m%d-%H%M"),
+        title
+    );
+    format!("{}.{}", sanitize_file_name(stem.trim()), format.extension())
+}
+
+/// Programs in `schedule` matching one of `rules` that have not ended by `now`
+pub fn plan(
+    rules: &[RecordRule],
+    area: &str,
+    channel: ChannelKind,
+    schedule: &[ScheduledProgram],
+    now: DateTime<FixedOffset>,
+) -> Vec<PlannedRecording> {
+    schedule
+        .iter()
+        .filter_map(|program| {
+            let rule = rules.iter().find(|rule| rule.matches(program))?;
+            let start = DateTime::parse_from_rfc3339(&program.start_date).ok()?;
+            let end = DateTime::parse_from_rfc3339(&program.end_date).ok()?;
+            (end > now).then(|| PlannedRecording {
+                rule: rule.label().to_string(),
+                area: area.to_string(),
+                channel,
+                title: program.name.clone(),
+                start,
+                end,
+            })
+        })
+        .collect()
+}
+
+/// Records programs matching the config file's rules while the daemon runs
+pub struct AutoRecorder {
+    client: Arc<NhkRadioClient>,
+    url_program_day: String,
+    watches: Vec<Watch>,
+    dir: PathBuf,
+    format: RecordFormat,
+}
+
+impl AutoRecorder {
+    /// Check the rules and resolve their areas, so mistakes stop the daemon
+    /// at startup
+    pub fn new(
+        client: Arc<NhkRadioClient>,
+        config: &RadiruConfig,
+        rules: &[RecordRule],
+        default_area: &str,
+        dir: PathBuf,
+        format: RecordFormat,
+    ) -> Result<Self> {
+        let mut watches: Vec<Watch> = Vec::new();
+        for rule in rules {
+            rule.validate()?;
+            let area = resolve_area(
+                rule.area.as_deref().unwrap_or(default_area),
+                &config.stream_url.data,
+            )?;
+            for channel in rule.channels()? {
+                match watches
+                    .iter_mut()
+                    .find(|w| w.stream_data.area == area.area && w.channel == channel)
+                {
+                    Some(watch) => watch.rules.push(rule.clone()),
+                    None => watches.push(Watch {
+                        stream_data: area.clone(),
+                        channel,
+                        rules: vec![rule.clone()],
+                    }),
+                }
+            }
+        }
+        Ok(AutoRecorder {
+            client,
+            url_program_day: config.url_program_day.clone(),
+            watches,
+            dir,
+            format,
+        })
+    }
+
+    /// Watch the timetables and record what matches; never returns unless
+    /// there are no rules
+    pub async fn run(self) -> Result<()> {
+        if self.watches.is_empty() {
+            return std::future::pending().await;
+        }
+        log::info!(
+            "Watching {} timetables for recording rules",
+            self.watches.len()
+        );
+
+        // Programs already handed to a recording task, by channel and start
+        let mut scheduled = HashSet::new();
+        loop {
+            let today = today_jst();
+            for watch in &self.watches {
+                for date in [today, today + chrono::Days::new(1)] {
+                    let url = program_day_url(
+                        &self.url_program_day,
+                        &watch.stream_data.areakey,
+                        watch.channel,
+                        date,
+                    );
+                    let day = match self.client.fetch_program_day(&url).await {
+                        Ok(day) => day,
+                        Err(e) => {
+                            log::warn!("Failed to fetch the timetable for recording rules: {}", e);
+                            continue;
+                        }
+                    };
+                    let now = Utc::now().fixed_offset();
+                    for planned in plan(
+                        &watch.rules,
+                        &watch.stream_data.area,
+                        watch.channel,
+                        &day.publication,
+                        now,
+                    ) {
+                        if !scheduled.insert((planned.area.clone(), planned.channel, planned.start))
+                        {
+                            continue;
+                        }
+                        log::info!(
+                            "Recording rule {} matched {} ({} {})",
+                            planned.rule,
+                            planned.title,
+                            planned.channel.short_name(),
+                            planned.start.format("%m/%d %H:%M")
+                        );
+                        let path = self.dir.join(planned.file_name(self.format));
+                        let client = self.client.clone();
+                        let stream_data = watch.stream_data.clone();
+                        let format = self.format;
+                        tokio::spawn(async move {
+                            if let Err(e) =
+                                record(client, stream_data, &planned, &path, format).await
+                            {
+                                log::error!("Failed to record {}: {}", planned.title, e);
+                            }
+                        });
+                    }
+                }
+            }
+            tokio::time::sleep(EPG_REFRESH).await;
+        }
+    }
+}
+
+/// Wait for `planned` to start, then record it to `path` until it ends
+async fn record(
+    client: Arc<NhkRadioClient>,
+    stream_data: StreamData,
+    planned: &PlannedRecording,
+    path: &Path,
+    format: RecordFormat,
+) -> Result<()> {
+    let until = |time: DateTime<FixedOffset>| {
+        (time.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default()
+    };
+    tokio::time::sleep(until(planned.start - PADDING)).await;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let mut recorder = Recorder::with_format(path, format)?;
+    let mut decoder = StreamDecoder::new();
+    let mut segments = std::pin::pin!(segment_stream(
+        client,
+        stream_data,
+        planned.channel,
+        StreamOptions::default(),
+    ));
+
+    let stop = planned.end + PADDING;
+    let deadline = tokio::time::sleep(until(stop));
+    tokio::pin!(deadline);
+    loop {
+        let segment = tokio::select! {
+            _ = &mut deadline => break,
+            segment = segments.next() => match segment {
+                Some(segment) => segment,
+                None => break,
+            },
+        };
+        // The stream lags the broadcast, so go by the segments' own clock when they have one
+        match segment.program_date_time {
+            Some(time) if time < planned.start - PADDING => continue,
+            Some(time) if time >= stop => break,
+            _ => {}
+        }
+        recorder.write_segment(&segment.data)?;
+        if format.needs_pcm() {
+            match segment.decode(&mut decoder) {
+                Ok(chunk) => recorder.write_pcm(&chunk)?,
+                Err(e) => log::debug!("Failed to decode AAC: {}", e),
+            }
+        }
+    }
+    log::info!(
+        "Recorded {} to {} ({} bytes)",
+        planned.title,
+        path.display(),
+        recorder.bytes_written()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Genre;
+
+    #[test]
+    fn test_matches() {
+        let mut jazz = ScheduledProgram::named("ジャズ・トゥナイト", "", "");
+        jazz.description = "Live at Blue Note".to_string();
+        jazz.identifier_group.radio_series_id = "8W9PY7MJ7W".to_string();
+        jazz.identifier_group.genre = vec![Genre {
+            id: "0409".to_string(),
+            name1: "音楽".to_string(),
+            name2: "ジャズ・フュージョン".to_string(),
+        }];
+
+        let rule = |toml: &str| toml::from_str::<RecordRule>(toml).unwrap();
+        assert!(rule("keyword = \"blue note\"").matches(&jazz));
+        assert!(rule("series = \"8W9PY7MJ7W\"").matches(&jazz));
+        assert!(rule("genre = \"0409\"").matches(&jazz));
+        assert!(rule("genre = \"ジャズ\"").matches(&jazz));
+        // Every criterion has to match
+        assert!(!rule("keyword = \"ジャズ\"\ngenre = \"クラシック\"").matches(&jazz));
+        assert!(!rule("series = \"Z9L1V2M24L\"").matches(&jazz));
+
+        assert!(RecordRule::default().validate().is_err());
+        assert!(rule("channel = \"fm\"").validate().is_err());
+        assert_eq!(
+            rule("channel = \"fm\"").channels().unwrap(),
+            [ChannelKind::Fm]
+        );
+        assert!(rule("channel = \"tv\"").channels().is_err());
+    }
+
+    #[test]
+    fn test_plan() {
+        let rules = [RecordRule {
+            name: "classic".to_string(),
+            keyword: Some("クラシック".to_string()),
+            ..Default::default()
+        }];
+        let schedule = [
+            ScheduledProgram::named(
+                "クラシックの庭",
+                "2025-11-25T14:00:00+09:00",
+                "2025-11-25T15:50:00+09:00",
+            ),
+            ScheduledProgram::named(
+                "ニュース",
+                "2025-11-25T19:00:00+09:00",
+                "2025-11-25T19:05:00+09:00",
+            ),
+            ScheduledProgram::named(
+                "クラシック音楽館",
+                "2025-11-25T21:00:00+09:00",
+                "2025-11-25T23:00:00+09:00",
+            ),
+        ];
+        let now = DateTime::parse_from_rfc3339("2025-11-25T18:00:00+09:00").unwrap();
+
+        let planned = plan(&rules, "tokyo", ChannelKind::Fm, &schedule, now);
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].rule, "classic");
+        assert_eq!(planned[0].title, "クラシック音楽館");
+        assert_eq!(
+            planned[0].file_name(RecordFormat::Mp3),
+            "tokyo-fm-20251125-2100 クラシック音楽館.mp3"
+        );
+    }
+}
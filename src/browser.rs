@@ -0,0 +1,54 @@
+//! Opening program pages and searches in the user's web browser.
+
+use anyhow::Result;
+use std::process::{Command, Stdio};
+
+/// Where hashtag searches go
+const HASHTAG_SEARCH_URL: &str = "https://x.com/search";
+
+/// Open `url` in the default browser without waiting for it to load
+pub fn open_url(url: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", url, e))?;
+    // Reap the launcher once it hands the URL over
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Search page for the latest posts with any of `hashtags`
+pub fn hashtag_search_url(hashtags: &[String]) -> Result<String> {
+    let query = hashtags.join(" OR ");
+    let url = reqwest::Url::parse_with_params(
+        HASHTAG_SEARCH_URL,
+        &[("q", query.as_str()), ("f", "live")],
+    )?;
+    Ok(url.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashtag_search_url() {
+        let hashtags = vec!["#らじる".to_string(), "#nhkfm".to_string()];
+        assert_eq!(
+            hashtag_search_url(&hashtags).unwrap(),
+            "https://x.com/search?q=%23%E3%82%89%E3%81%98%E3%82%8B+OR+%23nhkfm&f=live"
+        );
+    }
+}
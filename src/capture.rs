@@ -0,0 +1,98 @@
+//! Recording a live channel without playing it, for servers with no sound
+//! card.
+//!
+//! `record` runs the same fetch and decrypt pipeline as playback, but no
+//! audio output is ever opened: segments go straight to a [`Recorder`], and
+//! are decoded only when the format needs PCM.
+
+use anyhow::Result;
+use futures::StreamExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::client::{HttpOptions, NhkRadioClient};
+use crate::decoder::StreamDecoder;
+use crate::headless::{format_megabytes, program_line, shutdown_signal};
+use crate::i18n::{area_label, tr};
+use crate::player::ChannelKind;
+use crate::radio::now_playing_stream;
+use crate::recorder::{RecordFormat, Recorder};
+use crate::stream::{StreamOptions, segment_stream};
+
+/// What `record` writes, and for how long
+pub struct CaptureOptions {
+    pub path: PathBuf,
+    pub format: RecordFormat,
+    /// Stop after this long, or run until Ctrl-C
+    pub duration: Option<Duration>,
+    pub http: HttpOptions,
+    pub stream: StreamOptions,
+}
+
+/// Record `kind` in `area` to a file until Ctrl-C or for the duration,
+/// printing a line whenever the program changes
+pub async fn run_capture(area: String, kind: ChannelKind, options: CaptureOptions) -> Result<()> {
+    let CaptureOptions {
+        path,
+        format,
+        duration,
+        http,
+        stream,
+    } = options;
+    let client = Arc::new(NhkRadioClient::with_options(http)?);
+    let (stream_data, program_url) = client.fetch_config().await?.find_area(&area)?;
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let mut recorder = Recorder::with_format(&path, format)?;
+    let mut decoder = StreamDecoder::new();
+
+    println!(
+        "● {} {} → {}",
+        area_label(&stream_data),
+        tr().channel_name(kind),
+        path.display()
+    );
+    let mut segments = std::pin::pin!(segment_stream(client.clone(), stream_data, kind, stream));
+    let mut programs = std::pin::pin!(now_playing_stream(client, program_url, kind));
+    let deadline = async {
+        match duration {
+            Some(duration) => tokio::time::sleep(duration).await,
+            None => std::future::pending().await,
+        }
+    };
+    let mut deadline = std::pin::pin!(deadline);
+    let mut shutdown = std::pin::pin!(shutdown_signal());
+
+    loop {
+        tokio::select! {
+            segment = segments.next() => {
+                let Some(segment) = segment else {
+                    break;
+                };
+                recorder.write_segment(&segment.data)?;
+                if format.needs_pcm() {
+                    match segment.decode(&mut decoder) {
+                        Ok(chunk) => recorder.write_pcm(&chunk)?,
+                        Err(e) => log::debug!("Failed to decode AAC: {}", e),
+                    }
+                }
+            }
+            Some(program) = programs.next() => println!("{}", program_line(&program)),
+            _ = &mut deadline => break,
+            _ = &mut shutdown => break,
+        }
+    }
+
+    let written = recorder.bytes_written();
+    // Finish the file, e.g. its WAV header or the encoder's last frames
+    drop(recorder);
+    println!(
+        "{}",
+        (tr().recording_saved)(&path.display().to_string(), &format_megabytes(written))
+    );
+    Ok(())
+}
@@ -0,0 +1,574 @@
+//! Casting live playback to a Chromecast or Google Home.
+//!
+//! Devices are found over mDNS and driven with the Cast v2 protocol: JSON
+//! messages wrapped in a small protobuf envelope on a TLS connection. The
+//! device's Default Media Receiver plays the channel from a local [`Relay`],
+//! and is reloaded whenever the channel or the program on air changes so
+//! its display stays current.
+
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{Mutex, mpsc};
+use tokio_native_tls::TlsStream;
+
+use crate::client::{HttpOptions, NhkRadioClient};
+use crate::device::find_device;
+use crate::headless::program_line;
+use crate::i18n::{area_label, tr};
+use crate::player::ChannelKind;
+use crate::radio::{NowPlaying, now_playing_stream};
+use crate::relay::{Relay, local_ip_towards};
+use crate::stream::{SegmentData, StreamOptions, segment_stream};
+
+const CAST_SERVICE: &str = "_googlecast._tcp.local.";
+const DISCOVERY_TIME: Duration = Duration::from_secs(3);
+
+const NS_CONNECTION: &str = "urn:x-cast:com.google.cast.tp.connection";
+const NS_HEARTBEAT: &str = "urn:x-cast:com.google.cast.tp.heartbeat";
+const NS_RECEIVER: &str = "urn:x-cast:com.google.cast.receiver";
+const NS_MEDIA: &str = "urn:x-cast:com.google.cast.media";
+const SENDER_ID: &str = "sender-0";
+const RECEIVER_ID: &str = "receiver-0";
+/// App ID of the Default Media Receiver
+const MEDIA_RECEIVER_APP: &str = "CC1AD845";
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(15);
+const VOLUME_STEP: f64 = 0.05;
+
+/// A Chromecast found on the network
+#[derive(Debug, Clone)]
+pub struct CastDevice {
+    /// Name shown in the Google Home app
+    pub name: String,
+    pub addr: SocketAddr,
+}
+
+/// Cast devices answering on the local network within a few seconds
+pub async fn discover_cast_devices() -> Result<Vec<CastDevice>> {
+    tokio::task::spawn_blocking(|| {
+        let mdns = mdns_sd::ServiceDaemon::new()?;
+        let events = mdns.browse(CAST_SERVICE)?;
+        let deadline = std::time::Instant::now() + DISCOVERY_TIME;
+
+        let mut devices: Vec<CastDevice> = Vec::new();
+        while let Some(left) = deadline.checked_duration_since(std::time::Instant::now()) {
+            let Ok(event) = events.recv_timeout(left) else {
+                break;
+            };
+            if let mdns_sd::ServiceEvent::ServiceResolved(info) = event {
+                let name = info
+                    .get_property_val_str("fn")
+                    .unwrap_or(info.get_fullname())
+                    .to_string();
+                // Prefer IPv4; Cast devices do not always listen on IPv6
+                let Some(ip) = info
+                    .get_addresses()
+                    .iter()
+                    .min_by_key(|ip| ip.is_ipv6())
+                    .copied()
+                else {
+                    continue;
+                };
+                if !devices.iter().any(|d| d.name == name) {
+                    devices.push(CastDevice {
+                        name,
+                        addr: SocketAddr::new(ip, info.get_port()),
+                    });
+                }
+            }
+        }
+        let _ = mdns.shutdown();
+        Ok(devices)
+    })
+    .await?
+}
+
+/// Cast `kind` in `area` to the device named by `query` until Ctrl-C or `q`
+///
+/// Lines on stdin switch channel (`r1`, `r2`, `fm`) or change the device's
+/// volume (`+`, `-`).
+pub async fn run_cast(
+    area: String,
+    mut kind: ChannelKind,
+    query: String,
+    http: HttpOptions,
+    stream: StreamOptions,
+) -> Result<()> {
+    let client = Arc::new(NhkRadioClient::with_options(http)?);
+    let (stream_data, program_url) = client.fetch_config().await?.find_area(&area)?;
+
+    let devices = discover_cast_devices().await?;
+    if devices.is_empty() {
+        anyhow::bail!("No Chromecast devices found on the network");
+    }
+    let names: Vec<String> = devices.iter().map(|d| d.name.clone()).collect();
+    let device = devices[find_device(&names, &query)?].clone();
+
+    // The relay must be reachable from the device, so serve on the interface
+    // that routes to it
+    let local_ip = local_ip_towards(device.addr.ip())?;
+    let listener = tokio::net::TcpListener::bind(SocketAddr::new(local_ip, 0)).await?;
+    let url = format!("http://{}/live.m3u8", listener.local_addr()?);
+    let relay = Relay::default();
+    let mut server = std::pin::pin!(axum::serve(listener, relay.router()).into_future());
+
+    let mut session = CastSession::connect(&device).await?;
+    println!("{}", (tr().casting_to)(&device.name));
+    println!("▶ {} {}", area_label(&stream_data), tr().channel_name(kind));
+    println!("{}", tr().cast_controls);
+
+    type Boxed<T> = Pin<Box<dyn Stream<Item = T>>>;
+    let open = |kind: ChannelKind| -> (Boxed<SegmentData>, Boxed<NowPlaying>) {
+        (
+            Box::pin(segment_stream(
+                client.clone(),
+                stream_data.clone(),
+                kind,
+                stream,
+            )),
+            Box::pin(now_playing_stream(
+                client.clone(),
+                program_url.clone(),
+                kind,
+            )),
+        )
+    };
+    let (mut segments, mut programs) = open(kind);
+    let mut title: Option<String> = None;
+    let mut loaded = false;
+    let mut stdin = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    let mut stdin_open = true;
+
+    loop {
+        tokio::select! {
+            Some(segment) = segments.next() => {
+                relay.push(segment);
+                if !loaded {
+                    session.load(&url, &relay, kind, &area_label(&stream_data), title.as_deref()).await?;
+                    loaded = true;
+                }
+            }
+            Some(program) = programs.next() => {
+                println!("{}", program_line(&program));
+                let changed = title.as_deref() != Some(program.title.as_str());
+                title = Some(program.title);
+                if loaded && changed {
+                    session.load(&url, &relay, kind, &area_label(&stream_data), title.as_deref()).await?;
+                }
+            }
+            line = stdin.next_line(), if stdin_open => {
+                let Some(line) = line? else {
+                    stdin_open = false;
+                    continue;
+                };
+                match line.trim() {
+                    "q" => break,
+                    "+" => session.change_volume(VOLUME_STEP).await?,
+                    "-" => session.change_volume(-VOLUME_STEP).await?,
+                    "" => {}
+                    other => match other.parse::<ChannelKind>() {
+                        Ok(next) if next != kind => {
+                            kind = next;
+                            println!("▶ {} {}", area_label(&stream_data), tr().channel_name(kind));
+                            relay.reset();
+                            (segments, programs) = open(kind);
+                            title = None;
+                            loaded = false;
+                        }
+                        Ok(_) => {}
+                        Err(e) => eprintln!("{}", e),
+                    },
+                }
+            }
+            message = session.messages.recv() => {
+                let Some(message) = message else {
+                    anyhow::bail!("Lost the connection to {}", device.name);
+                };
+                if session.is_closed(&message) {
+                    println!("{}", (tr().cast_ended)(&device.name));
+                    return Ok(());
+                }
+            }
+            result = &mut server => return Ok(result?),
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    session.stop().await
+}
+
+/// A connection to a device running the Default Media Receiver
+struct CastSession {
+    writer: Arc<Mutex<WriteHalf<TlsStream<TcpStream>>>>,
+    messages: mpsc::UnboundedReceiver<CastMessage>,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+    transport_id: String,
+    session_id: String,
+    volume: f64,
+    request_id: u64,
+}
+
+impl CastSession {
+    async fn connect(device: &CastDevice) -> Result<Self> {
+        // Cast devices present self-signed certificates
+        let connector = tokio_native_tls::native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .danger_accept_invalid_hostnames(true)
+            .build()?;
+        let tcp = TcpStream::connect(device.addr).await?;
+        let tls = tokio_native_tls::TlsConnector::from(connector)
+            .connect(&device.addr.ip().to_string(), tcp)
+            .await?;
+        let (reader, writer) = tokio::io::split(tls);
+        let writer = Arc::new(Mutex::new(writer));
+
+        let (message_tx, messages) = mpsc::unbounded_channel();
+        let tasks = vec![
+            tokio::spawn(read_messages(reader, writer.clone(), message_tx)),
+            tokio::spawn(send_heartbeats(writer.clone())),
+        ];
+        let mut session = CastSession {
+            writer,
+            messages,
+            tasks,
+            transport_id: String::new(),
+            session_id: String::new(),
+            volume: 1.0,
+            request_id: 0,
+        };
+
+        session
+            .send(RECEIVER_ID, NS_CONNECTION, json!({"type": "CONNECT"}))
+            .await?;
+        let request_id = session.next_request_id();
+        let launch =
+            json!({"type": "LAUNCH", "appId": MEDIA_RECEIVER_APP, "requestId": request_id});
+        session.send(RECEIVER_ID, NS_RECEIVER, launch).await?;
+
+        let status = tokio::time::timeout(LAUNCH_TIMEOUT, session.launched_app())
+            .await
+            .map_err(|_| anyhow::anyhow!("{} did not start the media receiver", device.name))??;
+        session.transport_id = status["transportId"].as_str().unwrap_or_default().into();
+        session.session_id = status["sessionId"].as_str().unwrap_or_default().into();
+        let transport_id = session.transport_id.clone();
+        session
+            .send(&transport_id, NS_CONNECTION, json!({"type": "CONNECT"}))
+            .await?;
+        Ok(session)
+    }
+
+    /// Wait for a receiver status listing the media receiver, returning its
+    /// application entry
+    async fn launched_app(&mut self) -> Result<Value> {
+        while let Some(message) = self.messages.recv().await {
+            if message.namespace != NS_RECEIVER {
+                continue;
+            }
+            let payload: Value = serde_json::from_str(&message.payload)?;
+            match payload["type"].as_str() {
+                Some("RECEIVER_STATUS") => {
+                    if let Some(level) = payload["status"]["volume"]["level"].as_f64() {
+                        self.volume = level;
+                    }
+                    let app = payload["status"]["applications"]
+                        .as_array()
+                        .and_then(|apps| apps.iter().find(|a| a["appId"] == MEDIA_RECEIVER_APP));
+                    if let Some(app) = app {
+                        return Ok(app.clone());
+                    }
+                }
+                Some("LAUNCH_ERROR") => anyhow::bail!("Launch failed: {}", payload["reason"]),
+                _ => {}
+            }
+        }
+        anyhow::bail!("Connection closed while launching the media receiver")
+    }
+
+    /// (Re)load the relayed stream with the current channel and program
+    async fn load(
+        &mut self,
+        url: &str,
+        relay: &Relay,
+        kind: ChannelKind,
+        area: &str,
+        title: Option<&str>,
+    ) -> Result<()> {
+        let station = format!("NHK {} ({})", tr().channel_name(kind), area);
+        let mut media = json!({
+            "contentId": url,
+            "contentType": "application/x-mpegurl",
+            "streamType": "LIVE",
+            "metadata": {
+                "metadataType": 0,
+                "title": title.unwrap_or(&station),
+                "subtitle": station,
+            },
+        });
+        if let Some(format) = relay.segment_format() {
+            media["hlsSegmentFormat"] = format.into();
+        }
+        let request_id = self.next_request_id();
+        let load = json!({
+            "type": "LOAD",
+            "requestId": request_id,
+            "sessionId": self.session_id,
+            "media": media,
+            "autoplay": true,
+        });
+        let transport_id = self.transport_id.clone();
+        self.send(&transport_id, NS_MEDIA, load).await
+    }
+
+    async fn change_volume(&mut self, delta: f64) -> Result<()> {
+        self.volume = (self.volume + delta).clamp(0.0, 1.0);
+        println!("🔊 {}%", (self.volume * 100.0).round());
+        let request_id = self.next_request_id();
+        let payload = json!({
+            "type": "SET_VOLUME",
+            "requestId": request_id,
+            "volume": {"level": self.volume},
+        });
+        self.send(RECEIVER_ID, NS_RECEIVER, payload).await
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        let request_id = self.next_request_id();
+        let payload =
+            json!({"type": "STOP", "requestId": request_id, "sessionId": self.session_id});
+        self.send(RECEIVER_ID, NS_RECEIVER, payload).await
+    }
+
+    /// Whether `message` says our app or connection has gone away, e.g.
+    /// because someone else started casting
+    fn is_closed(&self, message: &CastMessage) -> bool {
+        let Ok(payload) = serde_json::from_str::<Value>(&message.payload) else {
+            return false;
+        };
+        match payload["type"].as_str() {
+            Some("CLOSE") => message.namespace == NS_CONNECTION,
+            Some("RECEIVER_STATUS") => !payload["status"]["applications"]
+                .as_array()
+                .is_some_and(|apps| apps.iter().any(|a| a["sessionId"] == *self.session_id)),
+            _ => false,
+        }
+    }
+
+    fn next_request_id(&mut self) -> u64 {
+        self.request_id += 1;
+        self.request_id
+    }
+
+    async fn send(&self, destination: &str, namespace: &str, payload: Value) -> Result<()> {
+        let message = CastMessage {
+            source: SENDER_ID.to_string(),
+            destination: destination.to_string(),
+            namespace: namespace.to_string(),
+            payload: payload.to_string(),
+        };
+        write_message(&self.writer, &message).await
+    }
+}
+
+impl Drop for CastSession {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn write_message(
+    writer: &Mutex<WriteHalf<TlsStream<TcpStream>>>,
+    message: &CastMessage,
+) -> Result<()> {
+    let body = message.encode();
+    let mut writer = writer.lock().await;
+    writer.write_all(&(body.len() as u32).to_be_bytes()).await?;
+    writer.write_all(&body).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Forward incoming messages, answering heartbeat pings along the way
+async fn read_messages(
+    mut reader: ReadHalf<TlsStream<TcpStream>>,
+    writer: Arc<Mutex<WriteHalf<TlsStream<TcpStream>>>>,
+    messages: mpsc::UnboundedSender<CastMessage>,
+) {
+    loop {
+        let mut len = [0u8; 4];
+        if reader.read_exact(&mut len).await.is_err() {
+            return;
+        }
+        let mut body = vec![0u8; u32::from_be_bytes(len) as usize];
+        if reader.read_exact(&mut body).await.is_err() {
+            return;
+        }
+        let Some(message) = CastMessage::decode(&body) else {
+            log::debug!("Ignoring undecodable cast message");
+            continue;
+        };
+        if message.namespace == NS_HEARTBEAT {
+            if message.payload.contains("\"PING\"") {
+                let pong = CastMessage {
+                    source: message.destination,
+                    destination: message.source,
+                    namespace: NS_HEARTBEAT.to_string(),
+                    payload: json!({"type": "PONG"}).to_string(),
+                };
+                let _ = write_message(&writer, &pong).await;
+            }
+            continue;
+        }
+        log::debug!("Cast message on {}: {}", message.namespace, message.payload);
+        if messages.send(message).is_err() {
+            return;
+        }
+    }
+}
+
+async fn send_heartbeats(writer: Arc<Mutex<WriteHalf<TlsStream<TcpStream>>>>) {
+    let ping = CastMessage {
+        source: SENDER_ID.to_string(),
+        destination: RECEIVER_ID.to_string(),
+        namespace: NS_HEARTBEAT.to_string(),
+        payload: json!({"type": "PING"}).to_string(),
+    };
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        interval.tick().await;
+        if write_message(&writer, &ping).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// The protobuf envelope every Cast v2 message travels in, limited to the
+/// string payloads the media namespaces use
+#[derive(Debug, Clone, PartialEq)]
+struct CastMessage {
+    source: String,
+    destination: String,
+    namespace: String,
+    payload: String,
+}
+
+impl CastMessage {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        // protocol_version = CASTV2_1_0
+        out.extend_from_slice(&[0x08, 0x00]);
+        for (field, value) in [
+            (2, &self.source),
+            (3, &self.destination),
+            (4, &self.namespace),
+        ] {
+            put_string(&mut out, field, value);
+        }
+        // payload_type = STRING
+        out.extend_from_slice(&[0x28, 0x00]);
+        put_string(&mut out, 6, &self.payload);
+        out
+    }
+
+    fn decode(mut data: &[u8]) -> Option<Self> {
+        let mut message = CastMessage {
+            source: String::new(),
+            destination: String::new(),
+            namespace: String::new(),
+            payload: String::new(),
+        };
+        while !data.is_empty() {
+            let key = take_varint(&mut data)?;
+            match key & 7 {
+                0 => {
+                    take_varint(&mut data)?;
+                }
+                2 => {
+                    let len = take_varint(&mut data)? as usize;
+                    if len > data.len() {
+                        return None;
+                    }
+                    let (bytes, rest) = data.split_at(len);
+                    data = rest;
+                    let field = match key >> 3 {
+                        2 => &mut message.source,
+                        3 => &mut message.destination,
+                        4 => &mut message.namespace,
+                        6 => &mut message.payload,
+                        _ => continue,
+                    };
+                    *field = String::from_utf8(bytes.to_vec()).ok()?;
+                }
+                _ => return None,
+            }
+        }
+        Some(message)
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_string(out: &mut Vec<u8>, field: u64, value: &str) {
+    put_varint(out, field << 3 | 2);
+    put_varint(out, value.len() as u64);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn take_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = data.split_first()?;
+        *data = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cast_message_roundtrip() {
+        let message = CastMessage {
+            source: SENDER_ID.to_string(),
+            destination: RECEIVER_ID.to_string(),
+            namespace: NS_RECEIVER.to_string(),
+            // Long enough to need a two-byte length
+            payload: json!({"type": "LAUNCH", "appId": MEDIA_RECEIVER_APP, "pad": "x".repeat(200)})
+                .to_string(),
+        };
+        let encoded = message.encode();
+        assert_eq!(&encoded[..2], [0x08, 0x00]);
+        assert_eq!(CastMessage::decode(&encoded), Some(message));
+
+        assert!(CastMessage::decode(&[0x12, 0x05, b'a']).is_none());
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut out = Vec::new();
+        put_varint(&mut out, 300);
+        assert_eq!(out, [0xac, 0x02]);
+        let mut data = out.as_slice();
+        assert_eq!(take_varint(&mut data), Some(300));
+        assert!(data.is_empty());
+    }
+}
@@ -1,164 +1,1522 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::builder::PossibleValuesParser;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use std::io::{self, IsTerminal};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::area::resolve_area;
+use crate::audio_queue::DEFAULT_BUFFER_SECS;
+use crate::cache;
+use crate::capture::{CaptureOptions, run_capture};
+use crate::cast::run_cast;
 use crate::client::NhkRadioClient;
-use crate::player::ChannelKind;
-use crate::tui::run_interactive_player;
+use crate::config::Config;
+use crate::control::{ControlCommand, send_command};
+use crate::daemon::{AutoRecordOptions, run_daemon};
+use crate::dlna::find_renderer;
+use crate::download::{DEFAULT_JOBS, TrackInfo, download_episode};
+use crate::equalizer::EqPreset;
+use crate::external::{ExternalPlayer, run_external_player};
+use crate::favorites::{DEFAULT_REMINDER_MINUTES, Favorite, Favorites};
+use crate::headless::{HeadlessOptions, run_headless_player};
+use crate::i18n::{self, Lang, area_label, tr};
+use crate::ical;
+#[cfg(feature = "transcode")]
+use crate::icecast::{IcecastOptions, IcyCodec, run_icecast};
+use crate::keymap::Keymap;
+use crate::logging;
+use crate::now_playing::NowPlayingFile;
+use crate::ondemand::VodPlaylist;
+use crate::ondemand_browser::{BrowserStart, run_ondemand_browser};
+use crate::output::{
+    self, AreaList, ChannelSchedule, ChannelUpcoming, DeviceList, ErrorFormat, FavoriteList,
+    NowEntry, NowPlaying, NowTable, OutputFormat, ProgramReport, SearchHit, SearchResults, Slot,
+    StreamList, Timetable, UpcomingEntry, UpcomingList,
+};
+use crate::pipe::PcmPipe;
+use crate::play_url::run_url_player;
+use crate::player::{ChannelKind, DEFAULT_TIMESHIFT_MINUTES, PlayerOptions};
+use crate::podcast::update_series;
+use crate::radio::{program_day_url, today_jst};
+use crate::recorder::{RecordFormat, Recorder};
+use crate::relay::run_relay;
+use crate::scrobble;
+use crate::search;
+use crate::state::SessionState;
+use crate::stream::StreamOptions;
+use crate::systemd;
+use crate::theme::Theme;
+use crate::tui::{RecordTarget, TuiOptions, run_interactive_player};
+use crate::wizard::run_setup_wizard;
 
 #[derive(Parser)]
 #[command(name = "nhk-radio-player")]
 #[command(about = "A CLI radio player for NHK Radio", long_about = None)]
 pub struct Cli {
+    /// What to do; without one, `resume`
     #[command(subcommand)]
-    pub command: Commands,
+    pub command: Option<Commands>,
+    /// Output format for area, list, devices, program, now, upcoming, timetable, search,
+    /// and ctl status/stats
+    #[arg(long, global = true, env = "NHK_RADIO_FORMAT", value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+    /// How a failure is reported on stderr: text, or JSON with its kind and
+    /// exit code for scripts
+    #[arg(long, global = true, env = "NHK_RADIO_ERRORS", value_enum, default_value_t = ErrorFormat::Text)]
+    pub errors: ErrorFormat,
+    /// Proxy URL for all requests (overrides the config file and HTTP_PROXY/HTTPS_PROXY)
+    #[arg(long, global = true, env = "NHK_RADIO_PROXY")]
+    pub proxy: Option<String>,
+    /// TUI color theme: default, light, or monochrome (overrides the config file)
+    #[arg(long, global = true, env = "NHK_RADIO_THEME", value_name = "NAME")]
+    pub theme: Option<String>,
+    /// Interface language (overrides the config file)
+    #[arg(long, global = true, env = "NHK_RADIO_LANG", value_enum)]
+    pub lang: Option<Lang>,
+    /// Fetch the station config and program guide from NHK instead of
+    /// using cached copies
+    #[arg(long, global = true)]
+    pub refresh: bool,
+    /// Write log records to this file instead of stderr, also while the TUI
+    /// is up; it is rotated at 5 MB (overrides the config file)
+    #[arg(long, global = true, env = "NHK_RADIO_LOG_FILE", value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+    /// Save every playlist, key, and segment fetched, raw and decrypted, in
+    /// this directory for reproducing problems offline
+    #[arg(long, global = true, value_name = "PATH")]
+    pub dump_dir: Option<PathBuf>,
+    /// Leave the contents of keys out of --dump-dir, e.g. for a bug report
+    #[arg(long, global = true, requires = "dump_dir")]
+    pub redact_keys: bool,
+    /// Log more: -v for info, -vv for debug, -vvv for trace (overrides the
+    /// config file)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
 }
 
+// Parsed once per run, so the size of `Play` costs nothing worth boxing for
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
     /// Play radio stream
     Play {
-        /// Area code (e.g., "130" or "tokyo")
-        area: String,
-        /// Channel type: r1, r2, or fm
-        kind: String,
+        /// Area (e.g., "tokyo", "東京", "とうきょう", or a prefix like "tok"); defaults to `area` in the config file
+        #[arg(env = "NHK_RADIO_AREA")]
+        area: Option<String>,
+        /// Channel type: r1, r2, or fm; defaults to `channel` in the config file
+        #[arg(env = "NHK_RADIO_KIND")]
+        kind: Option<String>,
+        /// Initial volume in percent (0-100); defaults to the last used level
+        #[arg(long, env = "NHK_RADIO_VOLUME", value_parser = clap::value_parser!(u8).range(0..=100))]
+        volume: Option<u8>,
+        /// Also write the decrypted stream to this file (e.g. out.aac)
+        #[arg(long, value_name = "PATH")]
+        record: Option<PathBuf>,
+        /// Recording format; inferred from the --record extension by default.
+        /// Also used for recordings started with `r` in the TUI (default: raw)
+        #[arg(long, env = "NHK_RADIO_RECORD_FORMAT", value_enum)]
+        record_format: Option<RecordFormat>,
+        /// Start a new recording file, named and tagged after the program,
+        /// whenever the program changes (also for `r` in the TUI)
+        #[arg(long, conflicts_with_all = ["cast", "player", "exec_player", "dlna"])]
+        split: bool,
+        /// Write a cue sheet next to each recording marking the programs and,
+        /// from the music lists, the pieces played (also for `r` in the TUI)
+        #[arg(long, conflicts_with_all = ["cast", "player", "exec_player", "dlna"])]
+        cue: bool,
+        /// Highest stream bitrate to use, in bits/s (e.g. 96000 or 96k)
+        #[arg(long, env = "NHK_RADIO_MAX_BITRATE", value_name = "BPS", value_parser = parse_bitrate)]
+        max_bitrate: Option<u64>,
+        /// Stop after downloading this much (e.g. 200M or 1G; plain numbers
+        /// are megabytes), using the lowest bitrate until then
+        #[arg(long, env = "NHK_RADIO_MAX_BANDWIDTH", value_name = "SIZE", value_parser = parse_size)]
+        max_bandwidth: Option<u64>,
+        /// Output device name or part of one (see `devices`)
+        #[arg(long, value_name = "NAME")]
+        device: Option<String>,
+        /// Write decoded 48 kHz stereo s16le audio instead of playing it:
+        /// `-` or `wav:-` for stdout, or a file path (implies --no-tui
+        /// without --tee); repeat to write several
+        #[arg(long, value_name = "TARGET")]
+        output: Vec<PcmPipe>,
+        /// Keep playing on the sound device while writing --output
+        #[arg(long, requires = "output")]
+        tee: bool,
+        /// Hand playback to an external player: mpv, vlc, ffplay, or mplayer
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["exec_player", "output", "device", "record"])]
+        player: Option<String>,
+        /// External player command line; `{url}` is replaced with the stream
+        /// URL, otherwise the decrypted stream is written to its stdin
+        #[arg(long, value_name = "COMMAND", conflicts_with_all = ["output", "device", "record"])]
+        exec_player: Option<String>,
+        /// Cast to the Chromecast or Google Home with this name, or part of it
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["player", "exec_player", "output", "device", "record"])]
+        cast: Option<String>,
+        /// Play on the DLNA/UPnP renderer with this name, or part of it,
+        /// controlled from the TUI
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["cast", "player", "exec_player", "output", "device", "record", "no_tui"])]
+        dlna: Option<String>,
+        /// Keep this file updated with the station and program playing, as
+        /// JSON when it ends in .json and one line of text otherwise
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["cast", "player", "exec_player"])]
+        now_playing_file: Option<PathBuf>,
+        /// Play without the TUI, printing each new program as it starts
+        /// (implied when stdout is not a terminal)
+        #[arg(long)]
+        no_tui: bool,
+        /// Play without the TUI, printing nothing but log records, without
+        /// colors (for systemd or a background terminal)
+        #[arg(short, long, visible_alias = "no-ui", conflicts_with_all = ["cast", "player", "exec_player", "dlna"])]
+        quiet: bool,
+        /// Stop after this long, e.g. 15m, 1h30m, or 90s (a bare number is seconds)
+        #[arg(long, value_name = "TIME", value_parser = parse_duration, conflicts_with_all = ["cast", "player", "exec_player"])]
+        duration: Option<Duration>,
+        /// Normalize loudness to this level in LUFS, e.g. -23 (EBU R128) or -16
+        #[arg(long, value_name = "LUFS", allow_negative_numbers = true, conflicts_with_all = ["cast", "player", "exec_player", "dlna"])]
+        target_lufs: Option<f64>,
+        /// Equalizer preset; `e` in the TUI switches it
+        #[arg(long, value_enum, value_name = "PRESET", conflicts_with_all = ["cast", "player", "exec_player", "dlna"])]
+        eq: Option<EqPreset>,
+    },
+    /// Play the area and channel listened to last, at the same volume (what
+    /// runs without a command)
+    Resume,
+    /// Record a channel to a file without playing it or opening any audio
+    /// device, e.g. on a server with no sound card
+    Record {
+        /// Area name or code; defaults to `area` in the config file
+        #[arg(env = "NHK_RADIO_AREA")]
+        area: Option<String>,
+        /// Channel type: r1, r2, or fm; defaults to `channel` in the config file
+        #[arg(env = "NHK_RADIO_KIND")]
+        kind: Option<String>,
+        /// File to record to (e.g. out.aac)
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+        /// Recording format; inferred from the --output extension by default
+        #[arg(long, env = "NHK_RADIO_RECORD_FORMAT", value_enum)]
+        record_format: Option<RecordFormat>,
+        /// Stop after this long, e.g. 2h, 1h30m, or 90s (a bare number is
+        /// seconds); runs until Ctrl-C otherwise
+        #[arg(long, value_name = "TIME", value_parser = parse_duration)]
+        duration: Option<Duration>,
+        /// Highest stream bitrate to use, in bits/s (e.g. 96000 or 96k)
+        #[arg(long, env = "NHK_RADIO_MAX_BITRATE", value_name = "BPS", value_parser = parse_bitrate)]
+        max_bitrate: Option<u64>,
+    },
+    /// Play any HLS audio stream, including AES-128 encrypted ones
+    PlayUrl {
+        /// URL of a master or media playlist (.m3u8)
+        url: String,
+        /// Volume in percent (0-100); defaults to the last used level
+        #[arg(long, env = "NHK_RADIO_VOLUME", value_parser = clap::value_parser!(u8).range(0..=100))]
+        volume: Option<u8>,
+        /// Highest stream bitrate to use, in bits/s (e.g. 96000 or 96k)
+        #[arg(long, env = "NHK_RADIO_MAX_BITRATE", value_name = "BPS", value_parser = parse_bitrate)]
+        max_bitrate: Option<u64>,
+        /// Stop after downloading this much (e.g. 200M or 1G; plain numbers
+        /// are megabytes), using the lowest bitrate until then
+        #[arg(long, env = "NHK_RADIO_MAX_BANDWIDTH", value_name = "SIZE", value_parser = parse_size)]
+        max_bandwidth: Option<u64>,
+        /// Output device name or part of one (see `devices`)
+        #[arg(long, value_name = "NAME")]
+        device: Option<String>,
+    },
+    /// Play in the background, controlled with `ctl`
+    Daemon {
+        /// Area name or code; defaults to `area` in the config file
+        #[arg(env = "NHK_RADIO_AREA")]
+        area: Option<String>,
+        /// Channel type: r1, r2, or fm; defaults to `channel` in the config file
+        #[arg(env = "NHK_RADIO_KIND")]
+        kind: Option<String>,
+        /// Initial volume in percent (0-100); defaults to the last used level
+        #[arg(long, env = "NHK_RADIO_VOLUME", value_parser = clap::value_parser!(u8).range(0..=100))]
+        volume: Option<u8>,
+        /// Output device name or part of one (see `devices`)
+        #[arg(long, value_name = "NAME")]
+        device: Option<String>,
+        /// Also serve the HTTP API on this address (e.g. 127.0.0.1:8080)
+        #[arg(long, value_name = "ADDR")]
+        http: Option<SocketAddr>,
+        /// Format of recordings made by `[[record_rule]]` entries in the config file (default: raw)
+        #[arg(long, env = "NHK_RADIO_RECORD_FORMAT", value_enum)]
+        record_format: Option<RecordFormat>,
+        /// Print a systemd user service running the daemon with these
+        /// arguments, instead of starting it
+        #[arg(long)]
+        generate_unit: bool,
+    },
+    /// Re-publish a channel as unencrypted HLS for other players on the network
+    Serve {
+        /// Area name or code; defaults to `area` in the config file
+        #[arg(env = "NHK_RADIO_AREA")]
+        area: Option<String>,
+        /// Channel type: r1, r2, or fm; defaults to `channel` in the config file
+        #[arg(env = "NHK_RADIO_KIND")]
+        kind: Option<String>,
+        /// Address to serve on; use 0.0.0.0:8000 to reach it from other devices
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8000")]
+        listen: SocketAddr,
+        /// Highest stream bitrate to use, in bits/s (e.g. 96000 or 96k)
+        #[arg(long, env = "NHK_RADIO_MAX_BITRATE", value_name = "BPS", value_parser = parse_bitrate)]
+        max_bitrate: Option<u64>,
+    },
+    /// Re-stream a channel as MP3 or AAC for Icecast/SHOUTcast clients
+    #[cfg(feature = "transcode")]
+    Icecast {
+        /// Area name or code; defaults to `area` in the config file
+        #[arg(env = "NHK_RADIO_AREA")]
+        area: Option<String>,
+        /// Channel type: r1, r2, or fm; defaults to `channel` in the config file
+        #[arg(env = "NHK_RADIO_KIND")]
+        kind: Option<String>,
+        /// Address to serve on; use 0.0.0.0:8000 to reach it from other devices
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8000")]
+        listen: SocketAddr,
+        /// Codec to re-encode to
+        #[arg(long, value_enum, default_value_t = IcyCodec::Mp3)]
+        codec: IcyCodec,
+        /// Encoded bitrate in kbit/s
+        #[arg(long, value_name = "KBPS", default_value_t = 128)]
+        bitrate: u32,
+    },
+    /// Control a running daemon
+    Ctl {
+        #[command(subcommand)]
+        action: CtlCommand,
     },
     /// List available areas
     Area,
     /// Show program information for an area
     Program {
-        /// Area code
+        /// Area name or code
+        #[arg(env = "NHK_RADIO_AREA")]
+        area: String,
+        /// Only show this channel: r1, r2, or fm
+        #[arg(long)]
+        channel: Option<String>,
+        /// Which program to show
+        #[arg(long, value_enum, default_value_t = Slot::Present)]
+        which: Slot,
+    },
+    /// Show what is on now and next on each channel, one line per channel
+    Now {
+        /// Area name or code
+        #[arg(env = "NHK_RADIO_AREA")]
+        area: String,
+    },
+    /// Show the next programs on each channel and how soon they start
+    Upcoming {
+        /// Area name or code
+        #[arg(env = "NHK_RADIO_AREA")]
+        area: String,
+        /// Number of programs per channel
+        #[arg(short = 'n', long, default_value_t = 1)]
+        count: usize,
+    },
+    /// Show the full day's schedule for an area
+    Timetable {
+        /// Area name or code
+        #[arg(env = "NHK_RADIO_AREA")]
         area: String,
+        /// Day to show (YYYY-MM-DD); defaults to today in JST
+        #[arg(long)]
+        date: Option<NaiveDate>,
+        /// Write the schedule as an iCalendar file instead ("-" for stdout)
+        #[arg(long, value_name = "PATH")]
+        ical: Option<PathBuf>,
+        /// Only export programs of this series ID (see `favorite list`)
+        #[arg(long, value_name = "ID", requires = "ical")]
+        series: Option<String>,
+        /// Only programs in this genre: an ID, a word such as news,
+        /// classical, or language, or part of its name (e.g. "ジャズ")
+        #[arg(long)]
+        genre: Option<String>,
+        /// Number of days to export, starting from --date
+        #[arg(long, default_value_t = 1, requires = "ical")]
+        days: u32,
+    },
+    /// Search the program guide by title, description, or cast
+    Search {
+        /// Text to look for, ignoring case
+        query: String,
+        /// Area name or code; defaults to `area` in the config file
+        #[arg(long, env = "NHK_RADIO_AREA")]
+        area: Option<String>,
+        /// First day to search (YYYY-MM-DD); defaults to today in JST
+        #[arg(long)]
+        date: Option<NaiveDate>,
+        /// Number of days to search, starting from --date
+        #[arg(long, default_value_t = 1)]
+        days: u32,
+        /// Only search this channel: r1, r2, or fm
+        #[arg(long)]
+        channel: Option<String>,
+        /// Only programs in this genre: an ID, a word such as news,
+        /// classical, or language, or part of its name (e.g. "ジャズ")
+        #[arg(long)]
+        genre: Option<String>,
     },
     /// List all available streams
     List,
+    /// List audio output devices
+    Devices,
+    /// Run the interactive setup wizard
+    Setup,
+    /// Download a 聴き逃し episode to a tagged audio file
+    Download {
+        /// Series site ID (see `ondemand list`)
+        series: String,
+        /// Corner site ID (see `ondemand list`)
+        corner: String,
+        /// Episode number as shown by `ondemand episodes`
+        #[arg(long, default_value_t = 1)]
+        episode: usize,
+        /// Output file; the extension selects the format (.aac, .mp3, .ogg, .flac)
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Number of segments to download in parallel; defaults to
+        /// `download_jobs` in the config file, else 4
+        #[arg(long)]
+        jobs: Option<usize>,
+    },
+    /// Download new episodes of the 聴き逃し series under [podcast] in the
+    /// config file, and write a podcast feed for each series
+    Podcast {
+        /// Directory to keep the series in; defaults to `dir` under
+        /// [podcast], else ./podcasts
+        #[arg(long, value_name = "PATH")]
+        dir: Option<PathBuf>,
+        /// URL the directory is served at, for the feeds' links; defaults to
+        /// `base_url` under [podcast], else a file:// URL
+        #[arg(long, value_name = "URL")]
+        base_url: Option<String>,
+        /// Format new episodes are saved in (default: raw)
+        #[arg(long, value_enum)]
+        record_format: Option<RecordFormat>,
+        /// Number of segments to download in parallel; defaults to
+        /// `download_jobs` in the config file, else 4
+        #[arg(long)]
+        jobs: Option<usize>,
+    },
+    /// Browse and play 聴き逃し (on-demand) programs
+    Ondemand {
+        #[command(subcommand)]
+        action: Option<OndemandCommand>,
+    },
+    /// Manage favorite series, announced in the TUI shortly before they air
+    Favorite {
+        #[command(subcommand)]
+        action: FavoriteCommand,
+    },
+    /// Set up scrobbling of the music heard in the TUI
+    Scrobble {
+        #[command(subcommand)]
+        action: ScrobbleCommand,
+    },
+    /// Print a completion script for a shell, completing area codes from the
+    /// cached station config
+    Completions { shell: Shell },
+}
+
+#[derive(Subcommand)]
+pub enum ScrobbleCommand {
+    /// Authorize with Last.fm in the browser and save the session
+    Login,
+}
+
+#[derive(Subcommand)]
+pub enum FavoriteCommand {
+    /// List the favorites
+    List,
+    /// Add a series by ID, or programs by title
+    Add {
+        /// `radioSeriesId` of the series (shown by `program`)
+        #[arg(long, value_name = "ID", required_unless_present = "title")]
+        series: Option<String>,
+        /// Text the program title contains, ignoring case
+        #[arg(long, value_name = "PATTERN")]
+        title: Option<String>,
+        /// Name to list the favorite by
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Remove the favorites with this name, series ID, or title pattern
+    Remove { query: String },
+}
+
+#[derive(Subcommand)]
+pub enum CtlCommand {
+    /// Switch channel (r1, r2, or fm)
+    Channel { kind: String },
+    /// Pause playback
+    Pause,
+    /// Resume playback
+    Resume,
+    /// Toggle pause
+    Toggle,
+    /// Set the volume in percent (0-100)
+    Volume {
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=100))]
+        level: u8,
+    },
+    /// Show what is playing
+    Status,
+    /// Show counters of segments, downloads, errors, and underruns since the
+    /// daemon started
+    Stats,
+    /// Stop the daemon
+    Stop,
+}
+
+#[derive(Subcommand)]
+pub enum OndemandCommand {
+    /// List recently added series
+    List,
+    /// List the episodes of a series
+    Episodes {
+        /// Series site ID (see `ondemand list`)
+        series: String,
+        /// Corner site ID (see `ondemand list`)
+        corner: String,
+    },
+    /// Play an episode with seek support
+    Play {
+        /// Series site ID
+        series: String,
+        /// Corner site ID
+        corner: String,
+        /// Episode number as shown by `ondemand episodes`
+        #[arg(long, default_value_t = 1)]
+        episode: usize,
+        /// Start position in seconds
+        #[arg(long, default_value_t = 0.0)]
+        start: f64,
+    },
 }
 
-pub async fn run_cli() -> Result<()> {
-    env_logger::init();
+pub async fn run_cli(cli: Cli) -> Result<()> {
+    let command = match cli.command.unwrap_or(Commands::Resume) {
+        Commands::Completions { shell } => {
+            print_completions(shell);
+            return Ok(());
+        }
+        Commands::Resume => resume_command(SessionState::load()),
+        command => command,
+    };
+
+    let mut config = Config::load()?;
+    config.apply_env()?;
+
+    // Quiet playback may write to a journal or a pane that is not watched,
+    // so keep color codes out of it
+    let color = !matches!(command, Commands::Play { quiet: true, .. });
+    let level = logging::verbosity_level(cli.verbose)
+        .or(config.log_level.as_deref())
+        .unwrap_or("error");
+    let log_file = cli
+        .log_file
+        .clone()
+        .or_else(|| config.log_file.clone())
+        .map(|path| {
+            logging::RotatingFile::open(path.clone())
+                .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", path.display(), e))
+        })
+        .transpose()?;
+    logging::init(level, color, log_file);
+    let lang = match (cli.lang, config.lang.as_deref()) {
+        (Some(lang), _) => lang,
+        (None, Some(code)) => Lang::from_code(code)?,
+        (None, None) => Lang::default(),
+    };
+    i18n::set_lang(lang);
+    let mut http_options = config.http_options();
+    if let Some(proxy) = cli.proxy {
+        http_options.proxy = Some(proxy);
+    }
+    if cli.refresh {
+        http_options.config_ttl = Duration::ZERO;
+        http_options.epg_ttl = Duration::ZERO;
+    }
+    http_options.dump_dir = cli.dump_dir.clone();
+    http_options.redact_keys = cli.redact_keys;
+    let client = NhkRadioClient::with_options(http_options.clone())?;
+
+    match command {
+        Commands::Play {
+            area,
+            kind,
+            volume,
+            record,
+            record_format,
+            split,
+            cue,
+            max_bitrate,
+            max_bandwidth,
+            device,
+            output,
+            tee,
+            player,
+            exec_player,
+            cast,
+            dlna,
+            now_playing_file,
+            no_tui,
+            quiet,
+            duration,
+            target_lufs,
+            eq,
+        } => {
+            if !Config::exists() && io::stdin().is_terminal() {
+                config = run_setup_wizard(&client).await?;
+                config.apply_env()?;
+            }
+
+            let (area_code, channel_kind) = resolve_station(&client, area, kind, &config).await?;
+
+            if let Some(device) = cast {
+                let stream = StreamOptions {
+                    max_bitrate,
+                    live_edge_segments: config.live_edge_segments,
+                    max_bandwidth,
+                };
+                return run_cast(area_code, channel_kind, device, http_options, stream).await;
+            }
+
+            let external = match (player, exec_player) {
+                (Some(name), _) => Some(ExternalPlayer::preset(&name)?),
+                (None, Some(template)) => Some(ExternalPlayer::from_template(&template)?),
+                (None, None) => None,
+            };
+            if let Some(player) = external {
+                let stream = StreamOptions {
+                    max_bitrate,
+                    live_edge_segments: config.live_edge_segments,
+                    max_bandwidth,
+                };
+                return run_external_player(area_code, channel_kind, player, http_options, stream)
+                    .await;
+            }
+
+            let volume = volume.or(SessionState::load().volume).unwrap_or(100);
+            let theme = Theme::load(
+                cli.theme.as_deref().or(config.theme.as_deref()),
+                &config.colors,
+            );
+            let keymap = Keymap::load(&config.keys);
+
+            let record_format =
+                record_format.or_else(|| record.as_deref().map(RecordFormat::from_path));
+            let recorder = record
+                .map(|path| Recorder::with_format(path, record_format.unwrap_or_default()))
+                .transpose()?;
+            let record_target = RecordTarget {
+                dir: config
+                    .record_dir
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(".")),
+                format: record_format.unwrap_or_default(),
+                split: split || config.split_recordings.unwrap_or(false),
+                cue: cue || config.cue_sheets.unwrap_or(false),
+            };
 
-    let cli = Cli::parse();
-    let client = NhkRadioClient::new();
+            let now_playing = now_playing_file.map(NowPlayingFile::new);
 
-    match cli.command {
-        Commands::Play { area, kind } => {
-            let channel_kind = match kind.as_str() {
-                "r1" => ChannelKind::R1,
-                "r2" => ChannelKind::R2,
-                "fm" => ChannelKind::Fm,
-                _ => anyhow::bail!("Invalid kind: {}. Must be one of: r1, r2, fm", kind),
+            let options = PlayerOptions {
+                volume,
+                recorder,
+                timeshift_minutes: config
+                    .timeshift_minutes
+                    .unwrap_or(DEFAULT_TIMESHIFT_MINUTES),
+                buffer_secs: config.buffer_secs.unwrap_or(DEFAULT_BUFFER_SECS),
+                silence_alert: config.silence_alert(),
+                device: device.or(config.device),
+                speaker: output.is_empty() || tee,
+                pipes: output,
+                target_lufs: target_lufs.or(config.target_lufs),
+                equalizer: eq.or(config.equalizer).unwrap_or_default(),
+                http: http_options.clone(),
+                stream: StreamOptions {
+                    max_bitrate,
+                    live_edge_segments: config.live_edge_segments,
+                    max_bandwidth,
+                },
             };
 
-            // Handle area name aliases
-            let area_code = normalize_area(&area);
+            if dlna.is_none()
+                && (no_tui
+                    || quiet
+                    || (!options.pipes.is_empty() && !options.speaker)
+                    || options.pipes.iter().any(PcmPipe::is_stdout)
+                    || !io::stdout().is_terminal())
+            {
+                let headless = HeadlessOptions {
+                    now_playing,
+                    hooks: config.hooks,
+                    discord: config.discord,
+                    duration,
+                    quiet,
+                    split: record_target.split,
+                    cue: record_target.cue,
+                    terminal_title: config.terminal_title.unwrap_or(true),
+                };
+                return run_headless_player(area_code, channel_kind, options, headless).await;
+            }
+            let renderer = match dlna {
+                Some(query) => Some(find_renderer(&query).await?),
+                None => None,
+            };
+            let tui_options = TuiOptions {
+                theme: theme?,
+                keymap: keymap?,
+                record_target,
+                now_playing,
+                hooks: config.hooks,
+                favorites: Favorites::load()?,
+                reminder_minutes: config.reminder_minutes.unwrap_or(DEFAULT_REMINDER_MINUTES),
+                scrobble: config.scrobble,
+                discord: config.discord,
+                duration,
+                terminal_title: config.terminal_title.unwrap_or(true),
+            };
+            run_interactive_player(area_code, channel_kind, options, renderer, tui_options).await
+        }
 
-            return run_interactive_player(area_code, channel_kind).await;
+        Commands::PlayUrl {
+            url,
+            volume,
+            max_bitrate,
+            max_bandwidth,
+            device,
+        } => {
+            let options = PlayerOptions {
+                volume: volume.or(SessionState::load().volume).unwrap_or(100),
+                recorder: None,
+                timeshift_minutes: config
+                    .timeshift_minutes
+                    .unwrap_or(DEFAULT_TIMESHIFT_MINUTES),
+                buffer_secs: config.buffer_secs.unwrap_or(DEFAULT_BUFFER_SECS),
+                silence_alert: config.silence_alert(),
+                device: device.or(config.device),
+                speaker: true,
+                pipes: Vec::new(),
+                target_lufs: config.target_lufs,
+                equalizer: config.equalizer.unwrap_or_default(),
+                http: http_options.clone(),
+                stream: StreamOptions {
+                    max_bitrate,
+                    live_edge_segments: config.live_edge_segments,
+                    max_bandwidth,
+                },
+            };
+            run_url_player(url, options).await
         }
 
-        Commands::Area => {
-            let config = client.fetch_config().await?;
-            println!("Available areas:");
-            println!("{:<10} {}", "Area Code", "Area Name");
-            println!("{:-<40}", "");
-            for data in &config.stream_url.data {
-                println!("{:<10} {}", data.area, data.areajp);
+        Commands::Daemon {
+            area,
+            kind,
+            volume,
+            device,
+            http,
+            record_format,
+            generate_unit,
+        } => {
+            if generate_unit {
+                let mut args = vec!["daemon".to_string()];
+                args.extend(area.clone());
+                args.extend(kind.clone());
+                if let Some(volume) = volume {
+                    args.extend(["--volume".to_string(), volume.to_string()]);
+                }
+                if let Some(device) = &device {
+                    args.extend(["--device".to_string(), device.clone()]);
+                }
+                if let Some(addr) = http {
+                    args.extend(["--http".to_string(), addr.to_string()]);
+                }
+                if let Some(format) = record_format {
+                    let name = format.to_possible_value().map(|v| v.get_name().to_string());
+                    args.extend(["--record-format".to_string()].into_iter().chain(name));
+                }
+                let exe = std::env::current_exe()?;
+                print!("{}", systemd::unit_file(&exe, &args));
+                return Ok(());
+            }
+            let (area_code, channel_kind) = resolve_station(&client, area, kind, &config).await?;
+            let options = PlayerOptions {
+                volume: volume.or(SessionState::load().volume).unwrap_or(100),
+                recorder: None,
+                timeshift_minutes: config
+                    .timeshift_minutes
+                    .unwrap_or(DEFAULT_TIMESHIFT_MINUTES),
+                buffer_secs: config.buffer_secs.unwrap_or(DEFAULT_BUFFER_SECS),
+                silence_alert: config.silence_alert(),
+                device: device.or(config.device),
+                speaker: true,
+                pipes: Vec::new(),
+                target_lufs: config.target_lufs,
+                equalizer: config.equalizer.unwrap_or_default(),
+                http: http_options.clone(),
+                stream: StreamOptions::default(),
+            };
+            let auto_record = AutoRecordOptions {
+                rules: config.record_rules,
+                dir: config.record_dir.unwrap_or_else(|| PathBuf::from(".")),
+                format: record_format.unwrap_or_default(),
+            };
+            run_daemon(area_code, channel_kind, options, http, auto_record).await
+        }
+
+        Commands::Record {
+            area,
+            kind,
+            output,
+            record_format,
+            duration,
+            max_bitrate,
+        } => {
+            let (area_code, channel_kind) = resolve_station(&client, area, kind, &config).await?;
+            let options = CaptureOptions {
+                format: record_format.unwrap_or_else(|| RecordFormat::from_path(&output)),
+                path: output,
+                duration,
+                http: http_options,
+                stream: StreamOptions {
+                    max_bitrate,
+                    live_edge_segments: config.live_edge_segments,
+                    max_bandwidth: None,
+                },
+            };
+            run_capture(area_code, channel_kind, options).await
+        }
+
+        Commands::Serve {
+            area,
+            kind,
+            listen,
+            max_bitrate,
+        } => {
+            let (area_code, channel_kind) = resolve_station(&client, area, kind, &config).await?;
+            let stream = StreamOptions {
+                max_bitrate,
+                live_edge_segments: config.live_edge_segments,
+                max_bandwidth: None,
+            };
+            run_relay(area_code, channel_kind, listen, http_options, stream).await
+        }
+
+        #[cfg(feature = "transcode")]
+        Commands::Icecast {
+            area,
+            kind,
+            listen,
+            codec,
+            bitrate,
+        } => {
+            let (area_code, channel_kind) = resolve_station(&client, area, kind, &config).await?;
+            let options = IcecastOptions {
+                addr: listen,
+                codec,
+                kbps: bitrate,
+                http: http_options,
+                stream: StreamOptions::default(),
+            };
+            run_icecast(area_code, channel_kind, options).await
+        }
+
+        Commands::Ctl { action } => {
+            let command = match action {
+                CtlCommand::Channel { kind } => ControlCommand::Channel { channel: kind },
+                CtlCommand::Pause => ControlCommand::Pause,
+                CtlCommand::Resume => ControlCommand::Resume,
+                CtlCommand::Toggle => ControlCommand::TogglePause,
+                CtlCommand::Volume { level } => ControlCommand::Volume { volume: level },
+                CtlCommand::Status => ControlCommand::Status,
+                CtlCommand::Stats => ControlCommand::Stats,
+                CtlCommand::Stop => ControlCommand::Stop,
+            };
+            let response = send_command(&command).await?;
+            if !response.ok {
+                anyhow::bail!("{}", response.error.unwrap_or_default());
+            }
+            if let Some(status) = response.status {
+                output::print(&status, cli.format)?;
+            }
+            if let Some(stats) = response.stats {
+                output::print(&stats, cli.format)?;
             }
             Ok(())
         }
 
-        Commands::Program { area } => {
+        Commands::Area => {
             let config = client.fetch_config().await?;
+            output::print(&AreaList::new(&config), cli.format)
+        }
 
-            for data in &config.stream_url.data {
-                if data.area == area {
-                    let area_key = &data.areakey;
-                    let url = config
-                        .url_program_noa
-                        .replace("//", "https://")
-                        .replace("{area}", area_key);
-
-                    let program = client.fetch_program(&url).await?;
-
-                    println!("\n=== R1 Current Program ===");
-                    if let Some(ref present) = program.r1.present {
-                        if let Some(ref about) = present.about {
-                            println!("ID: {}", about.id);
-                            println!("Name: {}", about.name);
-                            println!("Description: {}", about.description);
-                        } else {
-                            println!("No program information available");
-                        }
-                    } else {
-                        println!("No current program");
-                    }
+        Commands::Program {
+            area,
+            channel,
+            which,
+        } => {
+            let channels = match channel {
+                Some(channel) => vec![channel.parse()?],
+                None => vec![ChannelKind::R1, ChannelKind::R2, ChannelKind::Fm],
+            };
+            let config = client.fetch_config().await?;
+            let data = resolve_area(&area, &config.stream_url.data)?;
+            let url = config.program_url(&data.areakey);
 
-                    println!("\n=== R2 Current Program ===");
-                    if let Some(ref present) = program.r2.present {
-                        if let Some(ref about) = present.about {
-                            println!("ID: {}", about.id);
-                            println!("Name: {}", about.name);
-                            println!("Description: {}", about.description);
-                        } else {
-                            println!("No program information available");
-                        }
-                    } else {
-                        println!("No current program");
-                    }
+            let program = client.fetch_program(&url).await?;
+            let report = ProgramReport {
+                area: data.area.clone(),
+                channels: channels
+                    .into_iter()
+                    .map(|kind| {
+                        let channel = match kind {
+                            ChannelKind::R1 => &program.r1,
+                            ChannelKind::R2 => &program.r2,
+                            ChannelKind::Fm => &program.r3,
+                        };
+                        NowPlaying::new(kind, channel, which)
+                    })
+                    .collect(),
+            };
+            output::print(&report, cli.format)
+        }
+
+        Commands::Now { area } => {
+            let config = client.fetch_config().await?;
+            let data = resolve_area(&area, &config.stream_url.data)?;
+            let url = config.program_url(&data.areakey);
 
-                    println!("\n=== FM Current Program ===");
-                    if let Some(ref present) = program.r3.present {
-                        if let Some(ref about) = present.about {
-                            println!("ID: {}", about.id);
-                            println!("Name: {}", about.name);
-                            println!("Description: {}", about.description);
-                        } else {
-                            println!("No program information available");
+            let program = client.fetch_program(&url).await?;
+            let table = NowTable {
+                area: data.area.clone(),
+                channels: vec![
+                    NowEntry::new(ChannelKind::R1, &program.r1),
+                    NowEntry::new(ChannelKind::R2, &program.r2),
+                    NowEntry::new(ChannelKind::Fm, &program.r3),
+                ],
+            };
+            output::print(&table, cli.format)
+        }
+
+        Commands::Upcoming { area, count } => {
+            let config = client.fetch_config().await?;
+            let data = resolve_area(&area, &config.stream_url.data)?;
+            let url = config.program_url(&data.areakey);
+            let program = client.fetch_program(&url).await?;
+            let now = Utc::now();
+
+            let mut channels = Vec::new();
+            for (kind, channel) in [
+                (ChannelKind::R1, &program.r1),
+                (ChannelKind::R2, &program.r2),
+                (ChannelKind::Fm, &program.r3),
+            ] {
+                let mut programs: Vec<UpcomingEntry> = channel
+                    .following
+                    .iter()
+                    .map(|p| UpcomingEntry::new(&p.start_date, &p.end_date, &p.name, now))
+                    .collect();
+                // Look further ahead in today's and tomorrow's schedules
+                if count > programs.len() {
+                    let after = channel
+                        .following
+                        .as_ref()
+                        .and_then(|p| DateTime::parse_from_rfc3339(&p.start_date).ok())
+                        .map_or(now, |start| start.with_timezone(&Utc));
+                    let today = today_jst();
+                    for date in [today, today + chrono::Days::new(1)] {
+                        if programs.len() >= count {
+                            break;
                         }
-                    } else {
-                        println!("No current program");
+                        let url =
+                            program_day_url(&config.url_program_day, &data.areakey, kind, date);
+                        let schedule = client.fetch_program_day(&url).await?;
+                        programs.extend(
+                            schedule
+                                .publication
+                                .iter()
+                                .filter(|p| {
+                                    DateTime::parse_from_rfc3339(&p.start_date)
+                                        .is_ok_and(|start| start > after)
+                                })
+                                .map(|p| {
+                                    UpcomingEntry::new(&p.start_date, &p.end_date, &p.name, now)
+                                }),
+                        );
                     }
+                }
+                // A program running past midnight is on both days
+                programs.dedup_by(|a, b| a.start == b.start);
+                programs.truncate(count);
+                channels.push(ChannelUpcoming {
+                    channel: kind.short_name().to_string(),
+                    programs,
+                });
+            }
+            let upcoming = UpcomingList {
+                area: data.area.clone(),
+                channels,
+            };
+            output::print(&upcoming, cli.format)
+        }
+
+        Commands::Timetable {
+            area,
+            date,
+            ical: Some(path),
+            series,
+            genre,
+            days,
+        } => {
+            let config = client.fetch_config().await?;
+            let data = resolve_area(&area, &config.stream_url.data)?;
+            let first = date.unwrap_or_else(today_jst);
+
+            let mut programs = Vec::new();
+            for date in first.iter_days().take(days.max(1) as usize) {
+                for channel in [ChannelKind::R1, ChannelKind::R2, ChannelKind::Fm] {
+                    let url =
+                        program_day_url(&config.url_program_day, &data.areakey, channel, date);
+                    let schedule = client.fetch_program_day(&url).await?;
+                    programs.extend(
+                        schedule
+                            .publication
+                            .into_iter()
+                            .filter(|p| {
+                                series
+                                    .as_ref()
+                                    .is_none_or(|id| p.identifier_group.radio_series_id == *id)
+                            })
+                            .filter(|p| genre.as_deref().is_none_or(|g| search::in_genre(p, g)))
+                            .map(|p| (channel, p)),
+                    );
+                }
+            }
+            let calendar = ical::calendar(&programs, &area_label(data));
+            if path.as_os_str() == "-" {
+                print!("{}", calendar);
+            } else {
+                std::fs::write(&path, calendar)
+                    .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))?;
+                println!("Saved {} ({} programs)", path.display(), programs.len());
+            }
+            Ok(())
+        }
+
+        Commands::Timetable {
+            area, date, genre, ..
+        } => {
+            let config = client.fetch_config().await?;
+            let data = resolve_area(&area, &config.stream_url.data)?;
+            let date = date.unwrap_or_else(today_jst);
+
+            let mut channels = Vec::new();
+            for channel in [ChannelKind::R1, ChannelKind::R2, ChannelKind::Fm] {
+                let url = program_day_url(&config.url_program_day, &data.areakey, channel, date);
+                let schedule = client.fetch_program_day(&url).await?;
+                channels.push(ChannelSchedule::new(channel, &schedule.publication, |p| {
+                    genre.as_deref().is_none_or(|g| search::in_genre(p, g))
+                }));
+            }
+            let timetable = Timetable {
+                area: data.area.clone(),
+                date: date.to_string(),
+                channels,
+            };
+            output::print(&timetable, cli.format)
+        }
+
+        Commands::Search {
+            query,
+            area,
+            date,
+            days,
+            channel,
+            genre,
+        } => {
+            let area = area.or_else(|| config.area.clone()).ok_or_else(|| {
+                anyhow::anyhow!("No area given. Pass --area or set `area` in the config file")
+            })?;
+            let channels = match channel {
+                Some(channel) => vec![channel.parse()?],
+                None => vec![ChannelKind::R1, ChannelKind::R2, ChannelKind::Fm],
+            };
+            let config = client.fetch_config().await?;
+            let data = resolve_area(&area, &config.stream_url.data)?;
+            let first = date.unwrap_or_else(today_jst);
+
+            let mut hits = Vec::new();
+            for date in first.iter_days().take(days.max(1) as usize) {
+                for &channel in &channels {
+                    let url =
+                        program_day_url(&config.url_program_day, &data.areakey, channel, date);
+                    let schedule = client.fetch_program_day(&url).await?;
+                    hits.extend(
+                        schedule
+                            .publication
+                            .iter()
+                            .filter(|p| search::mentions(p, &query))
+                            .filter(|p| genre.as_deref().is_none_or(|g| search::in_genre(p, g)))
+                            .map(|p| SearchHit::new(&data.area, channel, p)),
+                    );
+                }
+            }
+            hits.sort_by(|a, b| a.start.cmp(&b.start));
+            output::print(&SearchResults(hits), cli.format)
+        }
+
+        Commands::Setup => {
+            run_setup_wizard(&client).await?;
+            Ok(())
+        }
 
-                    return Ok(());
+        Commands::Download {
+            series,
+            corner,
+            episode,
+            output,
+            jobs,
+        } => {
+            let series = client.fetch_ondemand_series(&series, &corner).await?;
+            let ep = series
+                .episodes
+                .get(episode.saturating_sub(1))
+                .ok_or_else(|| anyhow::anyhow!("Episode {} not found", episode))?;
+            let info = TrackInfo::new(&series, ep);
+            let path = output.unwrap_or_else(|| PathBuf::from(info.file_name(RecordFormat::Raw)));
+            let format = RecordFormat::from_path(&path);
+
+            let client = Arc::new(client);
+            let playlist = VodPlaylist::load(client.as_ref(), &ep.stream_url).await?;
+            println!("{} - {}", info.series, info.title);
+            let jobs = jobs.or(config.download_jobs).unwrap_or(DEFAULT_JOBS);
+            let bytes = download_episode(client, &playlist, &info, &path, format, jobs).await?;
+            println!(
+                "Saved {} ({:.1} MB, {:.0} min)",
+                path.display(),
+                bytes as f64 / 1_000_000.0,
+                playlist.duration / 60.0
+            );
+            Ok(())
+        }
+
+        Commands::Podcast {
+            dir,
+            base_url,
+            record_format,
+            jobs,
+        } => {
+            let podcast = config.podcast;
+            if podcast.series.is_empty() {
+                anyhow::bail!(
+                    "No series to follow: add [[podcast.series]] entries to the config file"
+                );
+            }
+            let dir = dir
+                .or(podcast.dir)
+                .unwrap_or_else(|| PathBuf::from("podcasts"));
+            std::fs::create_dir_all(&dir)
+                .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", dir.display(), e))?;
+            let base_url = match base_url.or(podcast.base_url) {
+                Some(url) => url,
+                None => format!("file://{}", dir.canonicalize()?.display()),
+            };
+
+            let jobs = jobs.or(config.download_jobs).unwrap_or(DEFAULT_JOBS);
+            let client = Arc::new(client);
+            let mut failed = 0;
+            for followed in &podcast.series {
+                let result = update_series(
+                    client.clone(),
+                    followed,
+                    &dir,
+                    &base_url,
+                    record_format.unwrap_or_default(),
+                    jobs,
+                )
+                .await;
+                match result {
+                    Ok((feed, added)) => {
+                        println!(
+                            "{}",
+                            (tr().podcast_updated)(&feed.display().to_string(), added)
+                        )
+                    }
+                    Err(e) => {
+                        eprintln!("{} {}: {}", followed.series, followed.corner, e);
+                        failed += 1;
+                    }
                 }
             }
+            if failed > 0 {
+                anyhow::bail!(
+                    "Failed to update {} of {} series",
+                    failed,
+                    podcast.series.len()
+                );
+            }
+            Ok(())
+        }
 
-            anyhow::bail!("Area not found: {}", area);
+        Commands::Ondemand { action } => {
+            let theme = Theme::load(
+                cli.theme.as_deref().or(config.theme.as_deref()),
+                &config.colors,
+            )?;
+            run_ondemand(client, action, config.device, theme).await
         }
 
         Commands::List => {
             let config = client.fetch_config().await?;
-            println!("Available streams:");
+            output::print(&StreamList::new(&config), cli.format)
+        }
+
+        Commands::Devices => output::print(&DeviceList::new()?, cli.format),
+
+        Commands::Scrobble { action } => match action {
+            ScrobbleCommand::Login => scrobble::login(&http_options).await,
+        },
+
+        Commands::Completions { .. } | Commands::Resume => {
+            unreachable!("handled before the config is loaded")
+        }
+
+        Commands::Favorite { action } => {
+            let mut favorites = Favorites::load()?;
+            match action {
+                FavoriteCommand::List => {
+                    return output::print(&FavoriteList(favorites.entries), cli.format);
+                }
+                FavoriteCommand::Add {
+                    series,
+                    title,
+                    name,
+                } => {
+                    let favorite = Favorite {
+                        series_id: series,
+                        title,
+                        name: name.unwrap_or_default(),
+                    };
+                    if !favorites.add(favorite.clone()) {
+                        println!("Already a favorite: {}", favorite.label());
+                        return Ok(());
+                    }
+                    println!("Added {}", favorite.label());
+                }
+                FavoriteCommand::Remove { query } => {
+                    let removed = favorites.remove(&query);
+                    if removed.is_empty() {
+                        anyhow::bail!("No favorite matches {}", query);
+                    }
+                    for favorite in removed {
+                        println!("Removed {}", favorite.label());
+                    }
+                }
+            }
+            favorites.save()
+        }
+    }
+}
+
+async fn run_ondemand(
+    client: NhkRadioClient,
+    action: Option<OndemandCommand>,
+    device: Option<String>,
+    theme: Theme,
+) -> Result<()> {
+    let volume = SessionState::load().volume.unwrap_or(100);
+    let client = Arc::new(client);
+
+    match action {
+        None => run_ondemand_browser(client, BrowserStart::Corners, volume, device, theme).await,
+
+        Some(OndemandCommand::List) => {
+            let corners = client.fetch_ondemand_corners().await?;
+            println!("{:<12} {:<12} {:<6} Title", "Series", "Corner", "Ch");
+            println!("{:-<60}", "");
+            for corner in &corners.corners {
+                println!(
+                    "{:<12} {:<12} {:<6} {} {}",
+                    corner.series_site_id,
+                    corner.corner_site_id,
+                    corner.radio_broadcast,
+                    corner.title,
+                    corner.corner_name
+                );
+            }
+            Ok(())
+        }
+
+        Some(OndemandCommand::Episodes { series, corner }) => {
+            let series = client.fetch_ondemand_series(&series, &corner).await?;
+            println!("{}", series.title);
             println!();
-            for data in &config.stream_url.data {
-                println!("Area: {} ({})", data.area, data.areajp);
-                println!("  R1 HLS: {}", data.r1hls);
-                println!("  R2 HLS: {}", data.r2hls);
-                println!("  FM HLS: {}", data.fmhls);
-                println!();
+            for (i, episode) in series.episodes.iter().enumerate() {
+                println!(
+                    "{:>3}. {}  {}",
+                    i + 1,
+                    episode.onair_date,
+                    episode.program_title
+                );
+                if !episode.closed_at.is_empty() {
+                    println!("     {}: {}", tr().available_until, episode.closed_at);
+                }
             }
             Ok(())
         }
+
+        Some(OndemandCommand::Play {
+            series,
+            corner,
+            episode,
+            start,
+        }) => {
+            let start = BrowserStart::Episode {
+                series_site_id: series,
+                corner_site_id: corner,
+                episode: episode.saturating_sub(1),
+                start,
+            };
+            run_ondemand_browser(client, start, volume, device, theme).await
+        }
+    }
+}
+
+/// `play` with the area and channel of the last session, or the config file's
+/// when nothing has been played yet
+fn resume_command(session: SessionState) -> Commands {
+    Commands::Play {
+        area: session.area,
+        kind: session.channel,
+        volume: None,
+        record: None,
+        record_format: None,
+        split: false,
+        cue: false,
+        max_bitrate: None,
+        max_bandwidth: None,
+        device: None,
+        output: Vec::new(),
+        tee: false,
+        player: None,
+        exec_player: None,
+        cast: None,
+        dlna: None,
+        now_playing_file: None,
+        no_tui: false,
+        quiet: false,
+        duration: None,
+        target_lufs: None,
+        eq: None,
     }
 }
 
-fn normalize_area(area: &str) -> String {
-    match area.to_lowercase().as_str() {
-        "東京" => "tokyo".to_string(),
-        "大阪" => "osaka".to_string(),
-        "名古屋" => "nagoya".to_string(),
-        "札幌" => "sapporo".to_string(),
-        "仙台" => "sendai".to_string(),
-        "広島" => "hiroshima".to_string(),
-        "松山" => "matsuyama".to_string(),
-        "福岡" => "fukuoka".to_string(),
-        // Also handle old numeric codes for backwards compatibility
-        "130" => "tokyo".to_string(),
-        "400" => "osaka".to_string(),
-        "300" => "nagoya".to_string(),
-        "010" => "sapporo".to_string(),
-        "040" => "sendai".to_string(),
-        "540" => "hiroshima".to_string(),
-        "580" => "matsuyama".to_string(),
-        "810" => "fukuoka".to_string(),
-        _ => area.to_lowercase(),
+/// Pick the area and channel from the arguments, falling back to the config file
+async fn resolve_station(
+    client: &NhkRadioClient,
+    area: Option<String>,
+    kind: Option<String>,
+    config: &Config,
+) -> Result<(String, ChannelKind)> {
+    let area = area.or_else(|| config.area.clone()).ok_or_else(|| {
+        anyhow::anyhow!("No area given. Pass one or set `area` in the config file")
+    })?;
+    let kind = kind
+        .or_else(|| config.channel.clone())
+        .unwrap_or_else(|| "r1".to_string());
+    let channel_kind = kind.parse()?;
+
+    // Served from the cache while it is fresh, which keeps startup fast
+    let config = client.fetch_config().await?;
+    let area_code = resolve_area(&area, &config.stream_url.data)?.area.clone();
+    Ok((area_code, channel_kind))
+}
+
+/// Channel kinds accepted wherever a channel is asked for
+const CHANNEL_NAMES: [&str; 3] = ["r1", "r2", "fm"];
+
+/// Write the completion script for `shell` to stdout
+///
+/// Area codes are taken from the cached station config, or the built-in one,
+/// when the script is generated; regenerate it to pick up new areas.
+fn print_completions(shell: Shell) {
+    let config = cache::load_config().unwrap_or_else(cache::builtin_config);
+    let areas: Vec<String> = config
+        .stream_url
+        .data
+        .iter()
+        .map(|data| data.area.clone())
+        .collect();
+    let mut command = completion_command(&areas);
+    clap_complete::generate(shell, &mut command, "nhk-radio-player", &mut io::stdout());
+}
+
+/// The command line with `areas` and the channel kinds as the values of
+/// every area and channel argument
+fn completion_command(areas: &[String]) -> clap::Command {
+    with_value_hints(Cli::command(), areas)
+}
+
+fn with_value_hints(mut command: clap::Command, areas: &[String]) -> clap::Command {
+    let ids: Vec<String> = command
+        .get_arguments()
+        .map(|arg| arg.get_id().to_string())
+        .collect();
+    for id in ids {
+        let values: Vec<String> = match id.as_str() {
+            "area" => areas.to_vec(),
+            "kind" | "channel" => CHANNEL_NAMES.iter().map(|name| name.to_string()).collect(),
+            _ => continue,
+        };
+        command = command.mut_arg(id, |arg| {
+            arg.value_parser(PossibleValuesParser::new(values))
+        });
+    }
+    let names: Vec<String> = command
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+    for name in names {
+        command = command.mut_subcommand(name, |sub| with_value_hints(sub, areas));
+    }
+    command
+}
+
+/// Parse a bitrate like "96000", "96k", or "1.5m"
+fn parse_bitrate(value: &str) -> Result<u64, String> {
+    let value = value.trim().to_lowercase();
+    let (number, scale) = match value.strip_suffix('k') {
+        Some(n) => (n, 1_000.0),
+        None => match value.strip_suffix('m') {
+            Some(n) => (n, 1_000_000.0),
+            None => (value.as_str(), 1.0),
+        },
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n > 0.0)
+        .map(|n| (n * scale) as u64)
+        .ok_or_else(|| format!("invalid bitrate: {}", value))
+}
+
+/// Parse an amount of data like "500k", "200M", or "1.5G" into bytes; a plain
+/// number is megabytes
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim().to_lowercase();
+    let trimmed = value.strip_suffix('b').unwrap_or(&value);
+    let (number, scale) = match trimmed.char_indices().last() {
+        Some((i, 'k')) => (&trimmed[..i], 1e3),
+        Some((i, 'm')) => (&trimmed[..i], 1e6),
+        Some((i, 'g')) => (&trimmed[..i], 1e9),
+        _ => (trimmed, 1e6),
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n > 0.0)
+        .map(|n| (n * scale) as u64)
+        .ok_or_else(|| format!("invalid size: {}", value))
+}
+
+/// Parse a duration like "90", "90s", "15m", "2h", or "1h30m"
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration: {}", value);
+    let value = value.trim().to_lowercase();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    let mut total = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let scale = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let n: u64 = number.parse().map_err(|_| invalid())?;
+        total += n * scale;
+        number.clear();
+    }
+    if !number.is_empty() || total == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bitrate() {
+        assert_eq!(parse_bitrate("96000"), Ok(96_000));
+        assert_eq!(parse_bitrate("96k"), Ok(96_000));
+        assert_eq!(parse_bitrate("1.5M"), Ok(1_500_000));
+        assert!(parse_bitrate("fast").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("200"), Ok(200_000_000));
+        assert_eq!(parse_size("200M"), Ok(200_000_000));
+        assert_eq!(parse_size("1.5GB"), Ok(1_500_000_000));
+        assert_eq!(parse_size("500k"), Ok(500_000));
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("0").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_duration("2H"), Ok(Duration::from_secs(2 * 3600)));
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("1h30").is_err());
+        assert!(parse_duration("soon").is_err());
+    }
+
+    #[test]
+    fn test_completion_values() {
+        let areas = ["tokyo".to_string(), "osaka".to_string()];
+        let command = completion_command(&areas);
+        let values = |sub: &str, id: &str| -> Vec<String> {
+            let sub = command.find_subcommand(sub).unwrap();
+            let arg = sub.get_arguments().find(|arg| arg.get_id() == id).unwrap();
+            arg.get_possible_values()
+                .iter()
+                .map(|value| value.get_name().to_string())
+                .collect()
+        };
+        assert_eq!(values("play", "area"), ["tokyo", "osaka"]);
+        assert_eq!(values("timetable", "area"), ["tokyo", "osaka"]);
+        assert_eq!(values("play", "kind"), CHANNEL_NAMES);
+        assert_eq!(values("search", "channel"), CHANNEL_NAMES);
+        let ctl = command.find_subcommand("ctl").unwrap();
+        let channel = ctl.find_subcommand("channel").unwrap();
+        assert!(
+            channel.get_arguments().any(|arg| arg.get_id() == "kind"
+                && arg.get_possible_values().len() == CHANNEL_NAMES.len())
+        );
+
+        let mut script = Vec::new();
+        let mut command = command.clone();
+        clap_complete::generate(Shell::Bash, &mut command, "nhk-radio-player", &mut script);
+        assert!(String::from_utf8(script).unwrap().contains("osaka"));
     }
 }
@@ -0,0 +1,388 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::autorecord::RecordRule;
+use crate::client::HttpOptions;
+use crate::discord::DiscordConfig;
+use crate::equalizer::EqPreset;
+use crate::hooks::Hooks;
+use crate::keymap::KeyList;
+use crate::player::DEFAULT_SILENCE_ALERT_SECS;
+use crate::podcast::PodcastConfig;
+use crate::scrobble::ScrobbleConfig;
+use crate::theme::ThemeColors;
+
+/// Prefix of the environment variables overriding settings
+pub const ENV_PREFIX: &str = "NHK_RADIO_";
+
+/// User settings stored in `~/.config/nhk-radio-player/config.toml`
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Default area code (e.g. "tokyo")
+    pub area: Option<String>,
+    /// Default channel: r1, r2, or fm
+    pub channel: Option<String>,
+    /// TUI color theme: default, light, or monochrome
+    pub theme: Option<String>,
+    /// Colors replacing single roles of the theme
+    #[serde(skip_serializing_if = "ThemeColors::is_empty")]
+    pub colors: ThemeColors,
+    /// Keys for TUI actions, replacing each named action's defaults
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, KeyList>,
+    /// Commands run on playback events
+    #[serde(skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+    /// Last.fm and ListenBrainz accounts to scrobble the music heard to
+    #[serde(skip_serializing_if = "ScrobbleConfig::is_empty")]
+    pub scrobble: ScrobbleConfig,
+    /// 聴き逃し series downloaded and listed in podcast feeds by `podcast`
+    #[serde(skip_serializing_if = "PodcastConfig::is_empty")]
+    pub podcast: PodcastConfig,
+    /// Discord Rich Presence
+    #[serde(skip_serializing_if = "DiscordConfig::is_empty")]
+    pub discord: DiscordConfig,
+    /// Interface language (ja or en)
+    pub lang: Option<String>,
+    /// Default log filter when `RUST_LOG` is not set (e.g. "info")
+    pub log_level: Option<String>,
+    /// File log records are written to instead of stderr
+    pub log_file: Option<PathBuf>,
+    /// Minutes of audio kept for rewinding
+    pub timeshift_minutes: Option<u32>,
+    /// Seconds of audio buffered ahead of playback
+    pub buffer_secs: Option<u32>,
+    /// Segments from the end of the live playlist that playback starts at
+    /// when tuning in (default: 3)
+    pub live_edge_segments: Option<u64>,
+    /// Loudness in LUFS to normalize playback to (unset: as broadcast)
+    pub target_lufs: Option<f64>,
+    /// Equalizer preset: flat, speech, music, or bass-cut
+    pub equalizer: Option<EqPreset>,
+    /// Seconds a stream may stay silent while still arriving before it is
+    /// warned about and the error hook runs (default: 30, 0 to never warn)
+    pub silence_alert_secs: Option<u64>,
+    /// Show what is playing in the terminal's title (default: true)
+    pub terminal_title: Option<bool>,
+    /// Minutes before a favorite airs that the TUI reminds of it
+    pub reminder_minutes: Option<u32>,
+    /// Directory for recordings started from the TUI or by recording rules
+    /// (default: the current directory)
+    pub record_dir: Option<PathBuf>,
+    /// Start a new recording file whenever the program changes
+    pub split_recordings: Option<bool>,
+    /// Write a cue sheet of programs and pieces next to each recording
+    pub cue_sheets: Option<bool>,
+    /// Segments fetched at once when downloading 聴き逃し episodes
+    /// (default: 4)
+    pub download_jobs: Option<usize>,
+    /// Programs the daemon records when they air
+    #[serde(rename = "record_rule", skip_serializing_if = "Vec::is_empty")]
+    pub record_rules: Vec<RecordRule>,
+    /// Audio output device name (see `nhk-radio-player devices`)
+    pub device: Option<String>,
+    /// Seconds to wait for an HTTP connection or the next bytes of a response
+    pub http_timeout_secs: Option<u64>,
+    /// Retries for playlist, key, and segment fetches that fail transiently
+    pub http_retries: Option<u32>,
+    /// Part of each retry delay, from 0 to 1, cut off at random (default: 0.5)
+    pub retry_jitter: Option<f64>,
+    /// Requests to NHK in flight at once (default: 8)
+    pub max_concurrent_requests: Option<usize>,
+    /// Requests to NHK started per second at most (default: no limit)
+    pub requests_per_second: Option<f64>,
+    /// Minutes the station config fetched from NHK is reused before it is
+    /// fetched again (default: a day)
+    pub config_ttl_minutes: Option<u64>,
+    /// Seconds a program listing or timetable fetched from NHK is reused,
+    /// by any command (default: 60; 0 always fetches)
+    pub epg_ttl_secs: Option<u64>,
+    /// Proxy URL for every request (overrides `HTTP_PROXY`/`HTTPS_PROXY`)
+    pub proxy: Option<String>,
+    /// User-Agent header to send instead of `nhk-radio-player/<version>`
+    pub user_agent: Option<String>,
+    /// PEM file with an extra CA certificate to trust, e.g. for an intercepting proxy
+    pub ca_cert: Option<PathBuf>,
+    /// Extra headers sent with every request
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// HTTP timeouts and retries, with unset values left at their defaults
+    pub fn http_options(&self) -> HttpOptions {
+        let mut options = HttpOptions::default();
+        if let Some(secs) = self.http_timeout_secs {
+            options.connect_timeout = Duration::from_secs(secs);
+            options.read_timeout = Duration::from_secs(secs);
+        }
+        if let Some(retries) = self.http_retries {
+            options.max_retries = retries;
+        }
+        if let Some(jitter) = self.retry_jitter {
+            options.retry_jitter = jitter;
+        }
+        if let Some(max) = self.max_concurrent_requests {
+            options.max_concurrent_requests = max;
+        }
+        if let Some(rate) = self.requests_per_second.filter(|rate| *rate > 0.0) {
+            options.min_request_interval = Duration::from_secs_f64(1.0 / rate);
+        }
+        if let Some(minutes) = self.config_ttl_minutes {
+            options.config_ttl = Duration::from_secs(minutes * 60);
+        }
+        if let Some(secs) = self.epg_ttl_secs {
+            options.epg_ttl = Duration::from_secs(secs);
+        }
+        if let Some(ref user_agent) = self.user_agent {
+            options.user_agent = user_agent.clone();
+        }
+        options.proxy = self.proxy.clone();
+        options.ca_cert = self.ca_cert.clone();
+        options.headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        options
+    }
+
+    /// How long a silent stream goes unreported, or `None` when turned off
+    pub fn silence_alert(&self) -> Option<Duration> {
+        match self
+            .silence_alert_secs
+            .unwrap_or(DEFAULT_SILENCE_ALERT_SECS)
+        {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// Override settings with `NHK_RADIO_*` environment variables, so they
+    /// win over the config file and lose to command-line flags
+    ///
+    /// Settings that are also flags, like the area, channel, and theme, are
+    /// read by the flags themselves. Never save a config after this, or the
+    /// environment ends up in the file.
+    pub fn apply_env(&mut self) -> Result<()> {
+        self.apply_vars(|name| std::env::var(name).ok())
+    }
+
+    fn apply_vars(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        let get = |key: &str| var(&format!("{}{}", ENV_PREFIX, key)).filter(|v| !v.is_empty());
+        fn parse<T: FromStr>(key: &str, value: &str) -> Result<T>
+        where
+            T::Err: Display,
+        {
+            value
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid {}{}: {}", ENV_PREFIX, key, e))
+        }
+
+        if let Some(device) = get("DEVICE") {
+            self.device = Some(device);
+        }
+        if let Some(value) = get("EQ") {
+            let preset = EqPreset::from_str(&value, true)
+                .map_err(|e| anyhow::anyhow!("Invalid {}EQ: {}", ENV_PREFIX, e))?;
+            self.equalizer = Some(preset);
+        }
+        if let Some(value) = get("TARGET_LUFS") {
+            self.target_lufs = Some(parse("TARGET_LUFS", &value)?);
+        }
+        if let Some(level) = get("LOG_LEVEL") {
+            self.log_level = Some(level);
+        }
+        if let Some(value) = get("BUFFER_SECS") {
+            self.buffer_secs = Some(parse("BUFFER_SECS", &value)?);
+        }
+        if let Some(value) = get("TIMESHIFT_MINUTES") {
+            self.timeshift_minutes = Some(parse("TIMESHIFT_MINUTES", &value)?);
+        }
+        if let Some(value) = get("SILENCE_ALERT_SECS") {
+            self.silence_alert_secs = Some(parse("SILENCE_ALERT_SECS", &value)?);
+        }
+        if let Some(dir) = get("RECORD_DIR") {
+            self.record_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(value) = get("HTTP_TIMEOUT_SECS") {
+            self.http_timeout_secs = Some(parse("HTTP_TIMEOUT_SECS", &value)?);
+        }
+        if let Some(value) = get("HTTP_RETRIES") {
+            self.http_retries = Some(parse("HTTP_RETRIES", &value)?);
+        }
+        if let Some(user_agent) = get("USER_AGENT") {
+            self.user_agent = Some(user_agent);
+        }
+        if let Some(path) = get("CA_CERT") {
+            self.ca_cert = Some(PathBuf::from(path));
+        }
+        Ok(())
+    }
+
+    /// Location of the config file, honoring `XDG_CONFIG_HOME`
+    pub fn path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|home| home.join(".config")))?;
+        Some(base.join("nhk-radio-player").join("config.toml"))
+    }
+
+    pub fn exists() -> bool {
+        Self::path().is_some_and(|path| path.exists())
+    }
+
+    /// Load the config file, returning defaults when it does not exist
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path)?;
+        toml::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("No config directory available"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_roundtrip() {
+        let config = Config {
+            area: Some("tokyo".to_string()),
+            channel: Some("fm".to_string()),
+            ..Default::default()
+        };
+        let text = toml::to_string_pretty(&config).unwrap();
+        let parsed: Config = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.area.as_deref(), Some("tokyo"));
+        assert_eq!(parsed.channel.as_deref(), Some("fm"));
+        assert!(parsed.theme.is_none());
+    }
+
+    #[test]
+    fn test_config_theme_colors() {
+        let parsed: Config =
+            toml::from_str("theme = \"light\"\n\n[colors]\naccent = \"#005f87\"\n").unwrap();
+        assert_eq!(parsed.theme.as_deref(), Some("light"));
+        assert_eq!(parsed.colors.accent.as_deref(), Some("#005f87"));
+        // Unset colors are left out when saving
+        let text = toml::to_string_pretty(&Config::default()).unwrap();
+        assert!(!text.contains("[colors]"));
+    }
+
+    #[test]
+    fn test_config_keys() {
+        let parsed: Config =
+            toml::from_str("[keys]\nquit = \"x\"\nnext_channel = [\"l\", \"Tab\"]\n").unwrap();
+        assert_eq!(parsed.keys["quit"], KeyList::One("x".to_string()));
+        assert_eq!(
+            parsed.keys["next_channel"],
+            KeyList::Many(vec!["l".to_string(), "Tab".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_config_hooks() {
+        let parsed: Config =
+            toml::from_str("[hooks]\non_error = \"notify-send \\\"$NHK_ERROR\\\"\"\n").unwrap();
+        assert_eq!(
+            parsed.hooks.on_error.as_deref(),
+            Some("notify-send \"$NHK_ERROR\"")
+        );
+        assert!(parsed.hooks.on_program_change.is_none());
+        let text = toml::to_string_pretty(&Config::default()).unwrap();
+        assert!(!text.contains("[hooks]"));
+    }
+
+    #[test]
+    fn test_config_ttl() {
+        let parsed: Config = toml::from_str("config_ttl_minutes = 90\n").unwrap();
+        assert_eq!(
+            parsed.http_options().config_ttl,
+            Duration::from_secs(90 * 60)
+        );
+        let defaults = Config::default().http_options();
+        assert_eq!(defaults.config_ttl, crate::client::DEFAULT_CONFIG_TTL);
+        assert_eq!(defaults.epg_ttl, crate::client::DEFAULT_EPG_TTL);
+        let parsed: Config = toml::from_str("epg_ttl_secs = 0\n").unwrap();
+        assert!(parsed.http_options().epg_ttl.is_zero());
+    }
+
+    #[test]
+    fn test_config_politeness() {
+        let parsed: Config =
+            toml::from_str("max_concurrent_requests = 2\nrequests_per_second = 4\n").unwrap();
+        let options = parsed.http_options();
+        assert_eq!(options.max_concurrent_requests, 2);
+        assert_eq!(options.min_request_interval, Duration::from_millis(250));
+        let defaults = Config::default().http_options();
+        assert_eq!(defaults.min_request_interval, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_config_silence_alert() {
+        assert_eq!(
+            Config::default().silence_alert(),
+            Some(Duration::from_secs(DEFAULT_SILENCE_ALERT_SECS))
+        );
+        let parsed: Config = toml::from_str("silence_alert_secs = 0\n").unwrap();
+        assert_eq!(parsed.silence_alert(), None);
+    }
+
+    #[test]
+    fn test_config_env() {
+        let mut config: Config =
+            toml::from_str("device = \"USB\"\nbuffer_secs = 10\nhttp_retries = 2\n").unwrap();
+        let vars = |name: &str| match name {
+            "NHK_RADIO_DEVICE" => Some("HDMI".to_string()),
+            "NHK_RADIO_EQ" => Some("bass-cut".to_string()),
+            "NHK_RADIO_BUFFER_SECS" => Some("4".to_string()),
+            "NHK_RADIO_HTTP_RETRIES" => Some(String::new()),
+            _ => None,
+        };
+        config.apply_vars(vars).unwrap();
+        assert_eq!(config.device.as_deref(), Some("HDMI"));
+        assert_eq!(config.equalizer, Some(EqPreset::BassCut));
+        assert_eq!(config.buffer_secs, Some(4));
+        // Empty variables are ignored
+        assert_eq!(config.http_retries, Some(2));
+
+        let error = config
+            .apply_vars(|name| (name == "NHK_RADIO_BUFFER_SECS").then(|| "soon".to_string()))
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Invalid NHK_RADIO_BUFFER_SECS")
+        );
+    }
+
+    #[test]
+    fn test_config_empty_file() {
+        let parsed: Config = toml::from_str("").unwrap();
+        assert!(parsed.area.is_none());
+    }
+}
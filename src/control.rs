@@ -0,0 +1,369 @@
+//! Remote control of a running daemon.
+//!
+//! Requests and responses are single JSON lines exchanged over a Unix socket
+//! (a named pipe on Windows). [`Controller::dispatch`] is the one place
+//! commands are applied, whichever transport they arrived on.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{broadcast, watch};
+use unicode_width::UnicodeWidthStr;
+
+#[cfg(unix)]
+use crate::cache;
+use crate::client::NhkRadioClient;
+use crate::engine::PlaybackEngine;
+use crate::events::DaemonEvent;
+use crate::i18n::tr;
+use crate::ondemand::format_position;
+use crate::output::{ProgramSummary, Report};
+use crate::player::ChannelKind;
+
+#[cfg(windows)]
+pub const PIPE_NAME: &str = r"\\.\pipe\nhk-radio-player";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlCommand {
+    Channel { channel: String },
+    Pause,
+    Resume,
+    TogglePause,
+    Volume { volume: u8 },
+    Status,
+    Stats,
+    Stop,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<DaemonStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<DaemonStats>,
+}
+
+impl ControlResponse {
+    fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            status: None,
+            stats: None,
+        }
+    }
+
+    fn error(message: impl ToString) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.to_string()),
+            status: None,
+            stats: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub area: String,
+    pub channel: String,
+    pub paused: bool,
+    pub volume: u8,
+    pub program: Option<ProgramSummary>,
+    /// HTTP requests retried since the daemon started
+    #[serde(default)]
+    pub http_retries: u64,
+}
+
+impl Report for DaemonStatus {
+    fn print_text(&self) {
+        let state = if self.paused {
+            tr().paused
+        } else {
+            tr().playing
+        };
+        println!(
+            "{} ({})  {}  🔊 {}%",
+            self.channel, self.area, state, self.volume
+        );
+        if let Some(ref program) = self.program {
+            println!("♪ {}", program.name);
+        }
+        if self.http_retries > 0 {
+            println!("{}", (tr().http_retries)(self.http_retries));
+        }
+    }
+}
+
+/// Counters of the daemon's playback since it started, for monitoring
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DaemonStats {
+    pub uptime_secs: u64,
+    /// Bandwidth of the variant being fetched in bits/s, when known
+    pub bitrate: Option<u64>,
+    pub segments_fetched: u64,
+    pub segments_failed: u64,
+    pub segments_missed: u64,
+    pub bytes_downloaded: u64,
+    pub decode_errors: u64,
+    pub underruns: u64,
+    pub reconnects: u64,
+    /// Seconds of audio discarded because playback fell behind
+    pub dropped_secs: f64,
+    pub http_retries: u64,
+}
+
+impl DaemonStats {
+    /// Label and value of each line of the text output
+    fn rows(&self) -> Vec<(&'static str, String)> {
+        let t = tr();
+        vec![
+            (t.stats_uptime, format_position(self.uptime_secs as f64)),
+            (
+                t.stats_bitrate,
+                self.bitrate
+                    .map_or("—".to_string(), |bps| format!("{} kbps", bps / 1000)),
+            ),
+            (t.stats_fetched, self.segments_fetched.to_string()),
+            (t.stats_failed, self.segments_failed.to_string()),
+            (t.stats_missed, self.segments_missed.to_string()),
+            (
+                t.stats_downloaded,
+                format!("{:.1} MB", self.bytes_downloaded as f64 / 1_000_000.0),
+            ),
+            (t.stats_decode_errors, self.decode_errors.to_string()),
+            (t.stats_underruns, self.underruns.to_string()),
+            (t.stats_reconnects, self.reconnects.to_string()),
+            (t.stats_dropped, format!("{:.1} s", self.dropped_secs)),
+        ]
+    }
+}
+
+impl Report for DaemonStats {
+    fn print_text(&self) {
+        let rows = self.rows();
+        let label_width = rows
+            .iter()
+            .map(|(label, _)| label.width())
+            .max()
+            .unwrap_or(0);
+        for (label, value) in rows {
+            println!(
+                "{}{}  {}",
+                label,
+                " ".repeat(label_width - label.width()),
+                value
+            );
+        }
+        println!("{}", (tr().http_retries)(self.http_retries));
+    }
+}
+
+pub struct Controller {
+    pub client: Arc<NhkRadioClient>,
+    pub area: String,
+    pub program_url: String,
+    pub engine: PlaybackEngine,
+    pub shutdown_tx: watch::Sender<bool>,
+    /// Changes pushed to `/ws` clients
+    pub events: broadcast::Sender<DaemonEvent>,
+}
+
+impl Controller {
+    pub async fn dispatch(&self, command: ControlCommand) -> ControlResponse {
+        match command {
+            ControlCommand::Channel { channel } => match channel.parse::<ChannelKind>() {
+                Ok(kind) => {
+                    self.engine.set_channel(kind);
+                }
+                Err(e) => return ControlResponse::error(e),
+            },
+            ControlCommand::Pause => {
+                self.engine.set_paused(true);
+            }
+            ControlCommand::Resume => {
+                self.engine.set_paused(false);
+            }
+            ControlCommand::TogglePause => {
+                self.engine.toggle_pause();
+            }
+            ControlCommand::Volume { volume } => {
+                self.engine.set_volume(volume.min(100) as f32 / 100.0);
+            }
+            ControlCommand::Status => {
+                return ControlResponse {
+                    status: Some(self.status().await),
+                    ..ControlResponse::ok()
+                };
+            }
+            ControlCommand::Stats => {
+                return ControlResponse {
+                    stats: Some(self.stats()),
+                    ..ControlResponse::ok()
+                };
+            }
+            ControlCommand::Stop => {
+                self.shutdown_tx.send_replace(true);
+            }
+        }
+        ControlResponse::ok()
+    }
+
+    pub async fn status(&self) -> DaemonStatus {
+        let channel = self.engine.channel();
+        let program = match self.client.fetch_program(&self.program_url).await {
+            Ok(root) => {
+                let present = match channel {
+                    ChannelKind::R1 => root.r1.present,
+                    ChannelKind::R2 => root.r2.present,
+                    ChannelKind::Fm => root.r3.present,
+                };
+                present.as_ref().map(ProgramSummary::new)
+            }
+            Err(e) => {
+                log::warn!("Failed to fetch program info: {}", e);
+                None
+            }
+        };
+        DaemonStatus {
+            area: self.area.clone(),
+            channel: channel.short_name().to_string(),
+            paused: self.engine.is_paused(),
+            volume: self.volume(),
+            program,
+            http_retries: self.client.retry_count(),
+        }
+    }
+
+    /// Volume in percent
+    pub fn volume(&self) -> u8 {
+        (self.engine.volume() * 100.0).round() as u8
+    }
+
+    /// Send `event` to every `/ws` client, if there are any
+    pub fn publish(&self, event: DaemonEvent) {
+        let _ = self.events.send(event);
+    }
+
+    pub fn stats(&self) -> DaemonStats {
+        let stream = self.engine.stats();
+        DaemonStats {
+            uptime_secs: self.engine.uptime().as_secs(),
+            bitrate: stream.bitrate,
+            segments_fetched: stream.segments_fetched,
+            segments_failed: stream.segments_failed,
+            segments_missed: stream.segments_missed,
+            bytes_downloaded: stream.bytes_downloaded,
+            decode_errors: stream.decode_errors,
+            underruns: stream.underruns,
+            reconnects: stream.reconnects,
+            dropped_secs: stream.dropped_secs,
+            http_retries: self.client.retry_count(),
+        }
+    }
+}
+
+/// Unix socket the daemon listens on
+#[cfg(unix)]
+pub fn socket_path() -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .map(|dir| dir.join("nhk-radio-player.sock"))
+        .or_else(|| cache::cache_dir().map(|dir| dir.join("control.sock")))
+}
+
+/// Answer requests on one connection until the client hangs up
+pub async fn serve_connection<S>(stream: S, controller: Arc<Controller>) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<ControlCommand>(&line) {
+            Ok(command) => controller.dispatch(command).await,
+            Err(e) => ControlResponse::error(format!("Invalid request: {}", e)),
+        };
+        let mut bytes = serde_json::to_vec(&response)?;
+        bytes.push(b'\n');
+        writer.write_all(&bytes).await?;
+    }
+    Ok(())
+}
+
+/// Send one command to the running daemon
+pub async fn send_command(command: &ControlCommand) -> Result<ControlResponse> {
+    #[cfg(unix)]
+    let stream = {
+        let path =
+            socket_path().ok_or_else(|| anyhow::anyhow!("No runtime directory available"))?;
+        tokio::net::UnixStream::connect(&path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Daemon is not running ({}): {}", path.display(), e))?
+    };
+    #[cfg(windows)]
+    let stream = tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(PIPE_NAME)
+        .map_err(|e| anyhow::anyhow!("Daemon is not running ({}): {}", PIPE_NAME, e))?;
+
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut request = serde_json::to_vec(command)?;
+    request.push(b'\n');
+    writer.write_all(&request).await?;
+
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Daemon closed the connection"))?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_wire_format() {
+        let json = serde_json::to_string(&ControlCommand::Channel {
+            channel: "fm".to_string(),
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"command":"channel","channel":"fm"}"#);
+
+        let command: ControlCommand =
+            serde_json::from_str(r#"{"command":"toggle_pause"}"#).unwrap();
+        assert!(matches!(command, ControlCommand::TogglePause));
+    }
+
+    #[test]
+    fn test_stats_rows() {
+        let stats = DaemonStats {
+            uptime_secs: 3_725,
+            bitrate: Some(48_000),
+            bytes_downloaded: 12_345_678,
+            underruns: 2,
+            ..Default::default()
+        };
+        let rows = stats.rows();
+        assert_eq!(rows[0], (tr().stats_uptime, "1:02:05".to_string()));
+        assert_eq!(rows[1].1, "48 kbps");
+        assert_eq!(rows[5].1, "12.3 MB");
+        assert_eq!(rows[7].1, "2");
+
+        let response = ControlResponse {
+            stats: Some(stats.clone()),
+            ..ControlResponse::ok()
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(!json.contains("status"));
+        let parsed: ControlResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.stats, Some(stats));
+    }
+}
@@ -0,0 +1,248 @@
+//! Cue sheets marking programs and pieces of music in a recording.
+//!
+//! Next to `evening.aac` goes `evening.cue`, with a track for each program
+//! that airs while recording and, where the program's music list has them,
+//! for each piece. Times come from the timetable, counted from the broadcast
+//! time of the first audio recorded. Pieces are laid out back to back like
+//! for scrobbling, so their marks are estimates. The sheet is rewritten
+//! whenever a track is added, so it is there even if the player is killed.
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::program_info::MusicPiece;
+use crate::recorder::{RecordFormat, RecordingStatus};
+use crate::scrobble::timeline;
+
+/// One `TRACK` of a sheet
+#[derive(Debug, Clone, PartialEq)]
+struct Track {
+    /// Start of the program the track belongs to, to replace its tracks by
+    program: Option<DateTime<FixedOffset>>,
+    start: DateTime<FixedOffset>,
+    title: String,
+    performer: String,
+}
+
+/// The cue sheet of one recording
+#[derive(Debug)]
+pub struct CueSheet {
+    recording: PathBuf,
+    format: RecordFormat,
+    /// Broadcast time of the start of the recording
+    origin: DateTime<FixedOffset>,
+    tracks: Vec<Track>,
+}
+
+impl CueSheet {
+    pub fn new(recording: &Path, format: RecordFormat, origin: DateTime<FixedOffset>) -> Self {
+        CueSheet {
+            recording: recording.to_path_buf(),
+            format,
+            origin,
+            tracks: Vec::new(),
+        }
+    }
+
+    /// The sheet of `recording`, started afresh for a new file and dropped
+    /// when recording stops
+    ///
+    /// `live_edge` is the broadcast time of the audio being recorded now.
+    pub fn follow<'a>(
+        sheet: &'a mut Option<CueSheet>,
+        recording: Option<&RecordingStatus>,
+        live_edge: Option<DateTime<FixedOffset>>,
+    ) -> Option<&'a mut CueSheet> {
+        let Some(recording) = recording else {
+            *sheet = None;
+            return None;
+        };
+        if sheet
+            .as_ref()
+            .is_none_or(|sheet| sheet.recording != recording.path)
+        {
+            let now = live_edge.unwrap_or_else(|| Local::now().fixed_offset());
+            let elapsed = chrono::Duration::from_std(recording.elapsed).unwrap_or_default();
+            *sheet = Some(CueSheet::new(
+                &recording.path,
+                recording.format,
+                now - elapsed,
+            ));
+        }
+        sheet.as_mut()
+    }
+
+    /// Mark the program `title` on `station`, airing during `airs`, and the
+    /// pieces of `music` in it, saving the sheet when that changes it
+    pub fn mark_program(
+        &mut self,
+        title: &str,
+        station: &str,
+        airs: Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)>,
+        music: &[MusicPiece],
+    ) -> Result<()> {
+        let program = airs.map(|(start, _)| start);
+        let mut tracks: Vec<Track> = self
+            .tracks
+            .iter()
+            .filter(|track| track.program != program)
+            .cloned()
+            .collect();
+        tracks.push(Track {
+            program,
+            start: program.map_or(self.origin, |start| start.max(self.origin)),
+            title: title.to_string(),
+            performer: station.to_string(),
+        });
+        if let Some(airs) = airs {
+            for (piece, (start, length)) in music.iter().zip(timeline(music, airs)) {
+                if start < self.origin || start >= airs.1 || length <= chrono::Duration::zero() {
+                    continue;
+                }
+                tracks.push(Track {
+                    program,
+                    start,
+                    title: piece.title.clone(),
+                    performer: piece.artist(),
+                });
+            }
+        }
+        // A piece starting with its program leaves the mark to the program
+        tracks.sort_by_key(|track| track.start);
+        tracks.dedup_by_key(|track| track.start);
+        if tracks == self.tracks {
+            return Ok(());
+        }
+        self.tracks = tracks;
+        self.save()
+    }
+
+    /// Where the sheet is written: the recording with a `.cue` extension
+    pub fn path(&self) -> PathBuf {
+        self.recording.with_extension("cue")
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = self.path();
+        fs::write(&path, self.render())
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))
+    }
+
+    fn render(&self) -> String {
+        let file_name = self
+            .recording
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // Players take any compressed audio as MP3 and lossless as WAVE
+        let file_type = match self.format {
+            RecordFormat::Flac | RecordFormat::Wav => "WAVE",
+            _ => "MP3",
+        };
+        let mut text = String::new();
+        let _ = writeln!(text, "TITLE {}", quote(&file_name));
+        let _ = writeln!(text, "FILE {} {}", quote(&file_name), file_type);
+        for (number, track) in self.tracks.iter().enumerate() {
+            let _ = writeln!(text, "  TRACK {:02} AUDIO", number + 1);
+            let _ = writeln!(text, "    TITLE {}", quote(&track.title));
+            if !track.performer.is_empty() {
+                let _ = writeln!(text, "    PERFORMER {}", quote(&track.performer));
+            }
+            let _ = writeln!(text, "    INDEX 01 {}", cue_time(track.start - self.origin));
+        }
+        text
+    }
+}
+
+/// A cue sheet string, which cannot hold double quotes
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "'"))
+}
+
+/// `MM:SS:FF` with 75 frames a second; minutes go past 99 if need be
+fn cue_time(offset: chrono::Duration) -> String {
+    let millis = offset.num_milliseconds().max(0);
+    let secs = millis / 1000;
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 60,
+        secs % 60,
+        millis % 1000 * 75 / 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(time: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(&format!("2025-11-25T{}+09:00", time)).unwrap()
+    }
+
+    fn piece(title: &str, minutes: u64) -> MusicPiece {
+        MusicPiece {
+            title: title.to_string(),
+            composer: "バッハ".to_string(),
+            performers: vec![],
+            artists: vec![],
+            duration: Some(Duration::from_secs(minutes * 60)),
+        }
+    }
+
+    #[test]
+    fn test_cue_time() {
+        assert_eq!(cue_time(chrono::Duration::zero()), "00:00:00");
+        assert_eq!(cue_time(chrono::Duration::milliseconds(61_500)), "01:01:37");
+        assert_eq!(cue_time(chrono::Duration::hours(2)), "120:00:00");
+    }
+
+    #[test]
+    fn test_mark_program() {
+        let dir = std::env::temp_dir().join(format!("nhk-cue-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut sheet = CueSheet::new(&dir.join("evening.aac"), RecordFormat::Raw, at("20:30:00"));
+
+        // Started mid-program: its mark goes at the start of the file
+        sheet
+            .mark_program(
+                "ニュース",
+                "NHK FM",
+                Some((at("20:00:00"), at("21:00:00"))),
+                &[],
+            )
+            .unwrap();
+        let music = [piece("G線上のアリア", 5), piece("\"トッカータ\"", 10)];
+        sheet
+            .mark_program(
+                "クラシック音楽館",
+                "NHK FM",
+                Some((at("21:00:00"), at("23:00:00"))),
+                &music,
+            )
+            .unwrap();
+        // Marking it again changes nothing
+        sheet
+            .mark_program(
+                "クラシック音楽館",
+                "NHK FM",
+                Some((at("21:00:00"), at("23:00:00"))),
+                &music,
+            )
+            .unwrap();
+
+        let text = fs::read_to_string(dir.join("evening.cue")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            text,
+            "TITLE \"evening.aac\"\n\
+             FILE \"evening.aac\" MP3\n  \
+             TRACK 01 AUDIO\n    TITLE \"ニュース\"\n    PERFORMER \"NHK FM\"\n    INDEX 01 00:00:00\n  \
+             TRACK 02 AUDIO\n    TITLE \"クラシック音楽館\"\n    PERFORMER \"NHK FM\"\n    INDEX 01 30:00:00\n  \
+             TRACK 03 AUDIO\n    TITLE \"'トッカータ'\"\n    PERFORMER \"バッハ\"\n    INDEX 01 35:00:00\n"
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! Headless playback controlled through [`crate::control`].
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch};
+
+use crate::autorecord::{AutoRecorder, RecordRule};
+use crate::client::NhkRadioClient;
+use crate::control::{Controller, serve_connection};
+use crate::engine::PlaybackEngine;
+use crate::events::EVENT_CAPACITY;
+use crate::headless::shutdown_signal;
+use crate::http::serve_http;
+use crate::player::{ChannelKind, PlayerOptions};
+use crate::recorder::RecordFormat;
+use crate::systemd;
+
+/// Where the daemon's recording rules come from and write to
+pub struct AutoRecordOptions {
+    pub rules: Vec<RecordRule>,
+    pub dir: PathBuf,
+    pub format: RecordFormat,
+}
+
+/// Play `initial_kind` without a UI until a `stop` command, Ctrl-C, or
+/// SIGTERM
+///
+/// With `http_addr`, the HTTP API and its `/ws` event stream are served
+/// alongside the control socket.
+/// Programs matching the recording rules are recorded as they air. Under
+/// systemd, readiness and watchdog pings are reported as described in
+/// [`crate::systemd`].
+pub async fn run_daemon(
+    area: String,
+    initial_kind: ChannelKind,
+    options: PlayerOptions,
+    http_addr: Option<SocketAddr>,
+    auto_record: AutoRecordOptions,
+) -> Result<()> {
+    let client = Arc::new(NhkRadioClient::with_options(options.http.clone())?);
+    let config = client.fetch_config().await?;
+    let (stream_data, program_url) = config.find_area(&area)?;
+    let auto_recorder = AutoRecorder::new(
+        client.clone(),
+        &config,
+        &auto_record.rules,
+        &area,
+        auto_record.dir,
+        auto_record.format,
+    )?;
+
+    // Claim the socket first so a second daemon fails before it starts playing
+    let listener = bind().await?;
+
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+    let engine = PlaybackEngine::start(client.clone(), stream_data, initial_kind, options);
+
+    let controller = Arc::new(Controller {
+        client,
+        area,
+        program_url,
+        engine,
+        shutdown_tx,
+        events: broadcast::channel(EVENT_CAPACITY).0,
+    });
+
+    let http = async {
+        match http_addr {
+            Some(addr) => serve_http(addr, controller.clone()).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    log::info!("Daemon started");
+    tokio::select! {
+        result = serve(listener, controller.clone()) => result?,
+        result = http => result?,
+        result = auto_recorder.run() => result?,
+        _ = systemd::supervise(&controller.engine) => {}
+        _ = shutdown_rx.wait_for(|stop| *stop) => {
+            log::info!("Stop requested");
+            // Let the reply to `stop` reach the client before exiting
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        _ = shutdown_signal() => log::info!("Interrupted"),
+    }
+    systemd::notify("STOPPING=1");
+
+    #[cfg(unix)]
+    if let Some(path) = crate::control::socket_path() {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn bind() -> Result<tokio::net::UnixListener> {
+    let path = crate::control::socket_path()
+        .ok_or_else(|| anyhow::anyhow!("No runtime directory available"))?;
+    if tokio::net::UnixStream::connect(&path).await.is_ok() {
+        anyhow::bail!("A daemon is already listening on {}", path.display());
+    }
+    // Left behind by a daemon that did not shut down cleanly
+    let _ = std::fs::remove_file(&path);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let listener = tokio::net::UnixListener::bind(&path)?;
+    log::info!("Listening on {}", path.display());
+    Ok(listener)
+}
+
+#[cfg(unix)]
+async fn serve(listener: tokio::net::UnixListener, controller: Arc<Controller>) -> Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let controller = controller.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_connection(stream, controller).await {
+                log::warn!("Control connection failed: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(windows)]
+async fn bind() -> Result<tokio::net::windows::named_pipe::NamedPipeServer> {
+    use crate::control::PIPE_NAME;
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(PIPE_NAME)
+        .map_err(|e| anyhow::anyhow!("A daemon may already be running ({}): {}", PIPE_NAME, e))?;
+    log::info!("Listening on {}", PIPE_NAME);
+    Ok(server)
+}
+
+#[cfg(windows)]
+async fn serve(
+    mut server: tokio::net::windows::named_pipe::NamedPipeServer,
+    controller: Arc<Controller>,
+) -> Result<()> {
+    use crate::control::PIPE_NAME;
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    loop {
+        server.connect().await?;
+        // Open the next instance before handing this one off
+        let connected = std::mem::replace(&mut server, ServerOptions::new().create(PIPE_NAME)?);
+        let controller = controller.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_connection(connected, controller).await {
+                log::warn!("Control connection failed: {}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,266 @@
+//! Discord Rich Presence: "Listening to" the program on the user's profile.
+//!
+//! Presence goes over the local IPC socket of the Discord desktop app, as
+//! length-prefixed JSON frames. Discord needs an application to show the
+//! presence as, so `client_id` in the `[discord]` table names one created in
+//! its developer portal; the application's name is what "Listening to" shows.
+//! When Discord is not running, the connection is retried in the background.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::watch;
+
+use crate::now_playing::NowPlayingRecord;
+
+/// How long to wait before connecting to Discord again
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Activity type shown as "Listening to"
+const LISTENING: u8 = 2;
+
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+const OP_CLOSE: u32 = 2;
+
+/// Settings from the `[discord]` table
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DiscordConfig {
+    /// Show what is playing on the Discord profile
+    pub enabled: bool,
+    /// Application ID from https://discord.com/developers/applications
+    pub client_id: Option<String>,
+}
+
+impl DiscordConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == DiscordConfig::default()
+    }
+}
+
+/// The presence to show, kept up to date by a background task
+pub struct DiscordPresence {
+    tx: watch::Sender<Option<serde_json::Value>>,
+}
+
+impl DiscordPresence {
+    /// Start publishing presence, or `None` when it is turned off
+    pub fn start(config: &DiscordConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let Some(client_id) = config.client_id.clone().filter(|id| !id.is_empty()) else {
+            log::warn!("Discord presence needs client_id under [discord] in the config file");
+            return None;
+        };
+        let (tx, rx) = watch::channel(None);
+        tokio::spawn(publish(client_id, rx));
+        Some(DiscordPresence { tx })
+    }
+
+    /// Show `record` with the station's `image`, or clear the presence
+    pub fn update(&self, record: Option<&NowPlayingRecord>, image: Option<&str>) {
+        let activity = record.map(|record| activity(record, image));
+        self.tx.send_if_modified(|current| {
+            let changed = *current != activity;
+            *current = activity;
+            changed
+        });
+    }
+}
+
+/// The activity for `record`, with the elapsed time counting from its start
+fn activity(record: &NowPlayingRecord, image: Option<&str>) -> serde_json::Value {
+    let mut activity = serde_json::json!({
+        "type": LISTENING,
+        "details": record.title,
+        "state": format!("{} ({})", record.station, record.area),
+    });
+    if let Some(start) = record
+        .start
+        .as_deref()
+        .and_then(|start| chrono::DateTime::parse_from_rfc3339(start).ok())
+    {
+        activity["timestamps"] = serde_json::json!({ "start": start.timestamp() });
+    }
+    if let Some(image) = image {
+        activity["assets"] = serde_json::json!({
+            "large_image": image,
+            "large_text": record.station,
+        });
+    }
+    activity
+}
+
+/// Keep Discord showing what `rx` holds, reconnecting whenever it goes away
+async fn publish(client_id: String, mut rx: watch::Receiver<Option<serde_json::Value>>) {
+    loop {
+        match connect().await {
+            Ok(stream) => {
+                if let Err(e) = session(stream, &client_id, &mut rx).await {
+                    log::debug!("Discord connection ended: {}", e);
+                }
+            }
+            Err(e) => log::debug!("Discord is not reachable: {}", e),
+        }
+        // The player has quit
+        if rx.has_changed().is_err() {
+            return;
+        }
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+    }
+}
+
+async fn session<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    client_id: &str,
+    rx: &mut watch::Receiver<Option<serde_json::Value>>,
+) -> Result<()> {
+    let handshake = serde_json::json!({ "v": 1, "client_id": client_id });
+    write_frame(&mut stream, OP_HANDSHAKE, &handshake).await?;
+    // READY, or an error for an unknown application
+    read_frame(&mut stream).await?;
+    log::info!("Connected to Discord");
+
+    let mut nonce = 0u64;
+    loop {
+        let activity = rx.borrow_and_update().clone();
+        nonce += 1;
+        let command = serde_json::json!({
+            "cmd": "SET_ACTIVITY",
+            "args": { "pid": std::process::id(), "activity": activity },
+            "nonce": nonce.to_string(),
+        });
+        write_frame(&mut stream, OP_FRAME, &command).await?;
+        let reply = read_frame(&mut stream).await?;
+        if reply["evt"] == "ERROR" {
+            log::warn!(
+                "Discord rejected the presence: {}",
+                reply["data"]["message"]
+            );
+        }
+        rx.changed().await?;
+    }
+}
+
+/// One frame: opcode and length as little-endian u32, then the JSON
+fn encode_frame(op: u32, payload: &serde_json::Value) -> Vec<u8> {
+    let json = payload.to_string();
+    let mut frame = Vec::with_capacity(8 + json.len());
+    frame.extend_from_slice(&op.to_le_bytes());
+    frame.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    frame.extend_from_slice(json.as_bytes());
+    frame
+}
+
+async fn write_frame(
+    stream: &mut (impl AsyncWrite + Unpin),
+    op: u32,
+    payload: &serde_json::Value,
+) -> Result<()> {
+    stream.write_all(&encode_frame(op, payload)).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_frame(stream: &mut (impl AsyncRead + Unpin)) -> Result<serde_json::Value> {
+    let mut header = [0u8; 8];
+    stream.read_exact(&mut header).await?;
+    let op = u32::from_le_bytes(header[..4].try_into()?);
+    let len = u32::from_le_bytes(header[4..].try_into()?) as usize;
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).await?;
+    let payload: serde_json::Value = serde_json::from_slice(&payload)?;
+    if op == OP_CLOSE {
+        anyhow::bail!("Discord closed the connection: {}", payload["message"]);
+    }
+    Ok(payload)
+}
+
+/// Connect to the first Discord IPC socket that answers, looking in the
+/// runtime and temporary directories, including Flatpak and Snap installs
+#[cfg(unix)]
+async fn connect() -> Result<tokio::net::UnixStream> {
+    let dirs: Vec<std::path::PathBuf> = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .into_iter()
+        .filter_map(std::env::var_os)
+        .map(std::path::PathBuf::from)
+        .chain([std::path::PathBuf::from("/tmp")])
+        .collect();
+    for dir in &dirs {
+        for sub in ["", "app/com.discordapp.Discord", "snap.discord"] {
+            for i in 0..10 {
+                let path = dir.join(sub).join(format!("discord-ipc-{}", i));
+                if let Ok(stream) = tokio::net::UnixStream::connect(&path).await {
+                    return Ok(stream);
+                }
+            }
+        }
+    }
+    anyhow::bail!("No Discord IPC socket found")
+}
+
+#[cfg(windows)]
+async fn connect() -> Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    for i in 0..10 {
+        if let Ok(pipe) = ClientOptions::new().open(format!(r"\\.\pipe\discord-ipc-{}", i)) {
+            return Ok(pipe);
+        }
+    }
+    anyhow::bail!("No Discord IPC pipe found")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::ChannelKind;
+
+    #[test]
+    fn test_activity() {
+        let start = chrono::DateTime::parse_from_rfc3339("2025-11-25T19:30:00+09:00").unwrap();
+        let record = NowPlayingRecord::new(
+            ChannelKind::Fm,
+            "東京",
+            "ベストオブクラシック",
+            Some(start),
+            None,
+        );
+
+        let activity = activity(&record, Some("https://example.com/fm.png"));
+        assert_eq!(activity["type"], 2);
+        assert_eq!(activity["details"], "ベストオブクラシック");
+        assert_eq!(activity["state"], "NHK FM (東京)");
+        assert_eq!(activity["timestamps"]["start"], start.timestamp());
+        assert_eq!(
+            activity["assets"]["large_image"],
+            "https://example.com/fm.png"
+        );
+
+        let bare = super::activity(
+            &NowPlayingRecord::new(ChannelKind::R1, "東京", "ニュース", None, None),
+            None,
+        );
+        assert!(bare.get("timestamps").is_none());
+        assert!(bare.get("assets").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_frames() {
+        let payload = serde_json::json!({ "v": 1, "client_id": "123" });
+        let frame = encode_frame(OP_HANDSHAKE, &payload);
+        assert_eq!(&frame[..4], &[0, 0, 0, 0]);
+        assert_eq!(frame.len(), 8 + payload.to_string().len());
+        assert_eq!(read_frame(&mut frame.as_slice()).await.unwrap(), payload);
+
+        let close = encode_frame(
+            OP_CLOSE,
+            &serde_json::json!({ "code": 4000, "message": "Invalid Client ID" }),
+        );
+        let error = read_frame(&mut close.as_slice()).await.unwrap_err();
+        assert!(error.to_string().contains("Invalid Client ID"));
+    }
+}
@@ -0,0 +1,584 @@
+//! Playing live radio on a DLNA/UPnP media renderer.
+//!
+//! Renderers are found with SSDP and pointed at a local [`Relay`] through
+//! their AVTransport service. [`DlnaPlayback`] offers the same controls as
+//! [`PlaybackEngine`](crate::engine::PlaybackEngine), so the TUI can drive
+//! a renderer like the local output: channel changes retune the relay, and
+//! pause and volume become Stop/Play and SetVolume calls.
+
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+use crate::client::NhkRadioClient;
+use crate::device::find_device;
+use crate::engine::PlaybackState;
+use crate::i18n::{area_label, tr};
+use crate::player::{ChannelKind, PlaybackEvent};
+use crate::relay::{Relay, local_ip_towards};
+use crate::stream::{SegmentData, StreamOptions, segment_stream};
+use crate::types::StreamData;
+
+const SSDP_ADDR: &str = "239.255.255.250:1900";
+const RENDERER_TYPE: &str = "urn:schemas-upnp-org:device:MediaRenderer:1";
+const DISCOVERY_TIME: Duration = Duration::from_secs(3);
+const SOAP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A control endpoint of a renderer
+#[derive(Debug, Clone, PartialEq)]
+struct Service {
+    service_type: String,
+    control_url: String,
+}
+
+/// A media renderer found on the network
+#[derive(Debug, Clone)]
+pub struct DlnaRenderer {
+    pub name: String,
+    av_transport: Service,
+    rendering_control: Option<Service>,
+    addr: SocketAddr,
+}
+
+/// Renderers answering an SSDP search within a few seconds
+pub async fn discover_renderers() -> Result<Vec<DlnaRenderer>> {
+    let socket = tokio::net::UdpSocket::bind("0.0.0.0:0").await?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: {}\r\n\r\n",
+        SSDP_ADDR, RENDERER_TYPE
+    );
+    socket.send_to(search.as_bytes(), SSDP_ADDR).await?;
+
+    let mut locations = Vec::new();
+    let mut buf = vec![0u8; 2048];
+    let deadline = tokio::time::Instant::now() + DISCOVERY_TIME;
+    while let Ok(Ok((n, _))) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        if let Some(location) = ssdp_location(&String::from_utf8_lossy(&buf[..n]))
+            && !locations.contains(&location)
+        {
+            locations.push(location);
+        }
+    }
+
+    let http = soap_client()?;
+    let mut renderers = Vec::new();
+    for location in locations {
+        match describe(&http, &location).await {
+            Ok(renderer) => renderers.push(renderer),
+            Err(e) => log::debug!("Skipping renderer at {}: {}", location, e),
+        }
+    }
+    Ok(renderers)
+}
+
+/// The renderer named by `query`, or an unambiguous part of its name
+pub async fn find_renderer(query: &str) -> Result<DlnaRenderer> {
+    let renderers = discover_renderers().await?;
+    if renderers.is_empty() {
+        anyhow::bail!("No DLNA renderers found on the network");
+    }
+    let names: Vec<String> = renderers.iter().map(|r| r.name.clone()).collect();
+    Ok(renderers[find_device(&names, query)?].clone())
+}
+
+/// The LOCATION header of an SSDP response
+fn ssdp_location(response: &str) -> Option<String> {
+    response.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("location")
+            .then(|| value.trim().to_string())
+    })
+}
+
+async fn describe(http: &reqwest::Client, location: &str) -> Result<DlnaRenderer> {
+    let base = reqwest::Url::parse(location)?;
+    let xml = http.get(base.clone()).send().await?.text().await?;
+    parse_description(&xml, &base)
+}
+
+fn parse_description(xml: &str, location: &reqwest::Url) -> Result<DlnaRenderer> {
+    let base = match tag_text(xml, "URLBase") {
+        Some(url_base) => reqwest::Url::parse(&url_base)?,
+        None => location.clone(),
+    };
+    let addr = base
+        .socket_addrs(|| Some(80))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Renderer has no address"))?;
+
+    let services: Vec<Service> = xml
+        .split("<service>")
+        .skip(1)
+        .filter_map(|block| {
+            Some(Service {
+                service_type: tag_text(block, "serviceType")?,
+                control_url: base.join(&tag_text(block, "controlURL")?).ok()?.into(),
+            })
+        })
+        .collect();
+    let find = |kind: &str| {
+        services
+            .iter()
+            .find(|s| s.service_type.contains(kind))
+            .cloned()
+    };
+
+    Ok(DlnaRenderer {
+        name: tag_text(xml, "friendlyName")
+            .ok_or_else(|| anyhow::anyhow!("Renderer has no friendlyName"))?,
+        av_transport: find(":AVTransport:")
+            .ok_or_else(|| anyhow::anyhow!("Renderer has no AVTransport service"))?,
+        rendering_control: find(":RenderingControl:"),
+        addr,
+    })
+}
+
+/// Unescaped text of the first `<tag>` element in `xml`
+fn tag_text(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(
+        xml[start..end]
+            .trim()
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn soap_client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder().timeout(SOAP_TIMEOUT).build()?)
+}
+
+fn soap_envelope(service_type: &str, action: &str, args: &[(&str, &str)]) -> String {
+    let args: String = args
+        .iter()
+        .map(|(name, value)| format!("<{0}>{1}</{0}>", name, escape_xml(value)))
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:{1} xmlns:u=\"{0}\">{2}</u:{1}></s:Body></s:Envelope>",
+        service_type, action, args
+    )
+}
+
+/// DIDL-Lite metadata describing the relayed stream as a broadcast
+fn didl_metadata(url: &str, title: &str) -> String {
+    format!(
+        "<DIDL-Lite xmlns=\"urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+         xmlns:upnp=\"urn:schemas-upnp-org:metadata-1-0/upnp/\">\
+         <item id=\"0\" parentID=\"-1\" restricted=\"1\">\
+         <dc:title>{}</dc:title>\
+         <upnp:class>object.item.audioItem.audioBroadcast</upnp:class>\
+         <res protocolInfo=\"http-get:*:application/vnd.apple.mpegurl:*\">{}</res>\
+         </item></DIDL-Lite>",
+        escape_xml(title),
+        escape_xml(url)
+    )
+}
+
+impl DlnaRenderer {
+    async fn call(
+        &self,
+        http: &reqwest::Client,
+        service: &Service,
+        action: &str,
+        args: &[(&str, &str)],
+    ) -> Result<String> {
+        let response = http
+            .post(&service.control_url)
+            .header("Content-Type", "text/xml; charset=\"utf-8\"")
+            .header(
+                "SOAPACTION",
+                format!("\"{}#{}\"", service.service_type, action),
+            )
+            .body(soap_envelope(&service.service_type, action, args))
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            let reason = tag_text(&body, "errorDescription").unwrap_or_else(|| status.to_string());
+            anyhow::bail!("{} failed on {}: {}", action, self.name, reason);
+        }
+        Ok(body)
+    }
+
+    async fn play_url(&self, http: &reqwest::Client, url: &str, title: &str) -> Result<()> {
+        let metadata = didl_metadata(url, title);
+        let transport = &self.av_transport;
+        self.call(
+            http,
+            transport,
+            "SetAVTransportURI",
+            &[
+                ("InstanceID", "0"),
+                ("CurrentURI", url),
+                ("CurrentURIMetaData", &metadata),
+            ],
+        )
+        .await?;
+        self.call(
+            http,
+            transport,
+            "Play",
+            &[("InstanceID", "0"), ("Speed", "1")],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn stop(&self, http: &reqwest::Client) -> Result<()> {
+        self.call(http, &self.av_transport, "Stop", &[("InstanceID", "0")])
+            .await?;
+        Ok(())
+    }
+
+    /// Current volume from 0 to 100, when the renderer exposes it
+    async fn volume(&self, http: &reqwest::Client) -> Option<u8> {
+        let service = self.rendering_control.as_ref()?;
+        let args = [("InstanceID", "0"), ("Channel", "Master")];
+        let body = self.call(http, service, "GetVolume", &args).await.ok()?;
+        tag_text(&body, "CurrentVolume")?.parse().ok()
+    }
+
+    async fn set_volume(&self, http: &reqwest::Client, volume: u8) -> Result<()> {
+        let Some(ref service) = self.rendering_control else {
+            return Ok(());
+        };
+        let volume = volume.to_string();
+        let args = [
+            ("InstanceID", "0"),
+            ("Channel", "Master"),
+            ("DesiredVolume", volume.as_str()),
+        ];
+        self.call(http, service, "SetVolume", &args).await?;
+        Ok(())
+    }
+}
+
+enum Command {
+    Paused(bool),
+    Volume(u8),
+    Area(StreamData),
+}
+
+/// Live playback on a renderer, controlled like a
+/// [`PlaybackEngine`](crate::engine::PlaybackEngine)
+pub struct DlnaPlayback {
+    pub renderer_name: String,
+    channel_tx: watch::Sender<ChannelKind>,
+    paused_tx: watch::Sender<bool>,
+    volume_tx: watch::Sender<f32>,
+    command_tx: mpsc::UnboundedSender<Command>,
+    event_rx: std::sync::Mutex<Receiver<PlaybackEvent>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl DlnaPlayback {
+    /// Serve `channel` on the relay and start it on `renderer`
+    pub async fn start(
+        client: Arc<NhkRadioClient>,
+        stream_data: StreamData,
+        channel: ChannelKind,
+        renderer: DlnaRenderer,
+        stream: StreamOptions,
+    ) -> Result<Self> {
+        let local_ip = local_ip_towards(renderer.addr.ip())?;
+        let listener = tokio::net::TcpListener::bind(SocketAddr::new(local_ip, 0)).await?;
+        let url = format!("http://{}/live.m3u8", listener.local_addr()?);
+        let relay = Relay::default();
+        let router = relay.router();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                log::error!("Relay stopped: {}", e);
+            }
+        });
+
+        let renderer_name = renderer.name.clone();
+        let http = soap_client()?;
+        let volume = renderer.volume(&http).await.unwrap_or(50);
+        let (channel_tx, channel_rx) = watch::channel(channel);
+        let (paused_tx, _) = watch::channel(false);
+        let (volume_tx, _) = watch::channel(volume as f32 / 100.0);
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
+        let (event_tx, event_rx) = std::sync::mpsc::channel();
+
+        let feed = Feed {
+            client,
+            stream_data,
+            stream,
+            renderer,
+            http,
+            relay,
+            url,
+            events: event_tx,
+        };
+        let task = tokio::spawn(feed.run(channel_rx, command_rx));
+
+        Ok(DlnaPlayback {
+            renderer_name,
+            channel_tx,
+            paused_tx,
+            volume_tx,
+            command_tx,
+            event_rx: std::sync::Mutex::new(event_rx),
+            task,
+        })
+    }
+
+    pub fn channel(&self) -> ChannelKind {
+        *self.channel_tx.borrow()
+    }
+
+    /// Retune the relay and restart the renderer on `channel`
+    pub fn set_channel(&self, channel: ChannelKind) {
+        self.channel_tx.send_if_modified(|current| {
+            let changed = *current != channel;
+            *current = channel;
+            changed
+        });
+    }
+
+    /// Retune the relay to the same channel in another area
+    pub fn set_area(&self, stream_data: StreamData) {
+        let _ = self.command_tx.send(Command::Area(stream_data));
+    }
+
+    /// Stop the renderer, or start it again at the live edge
+    pub fn set_paused(&self, paused: bool) {
+        self.paused_tx.send_replace(paused);
+        let _ = self.command_tx.send(Command::Paused(paused));
+    }
+
+    /// Set the renderer's volume from 0.0 to 1.0
+    pub fn set_volume(&self, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        self.volume_tx.send_replace(volume);
+        let _ = self
+            .command_tx
+            .send(Command::Volume((volume * 100.0).round() as u8));
+    }
+
+    /// Volume the renderer reported when playback started, from 0 to 100
+    pub fn initial_volume(&self) -> u8 {
+        (*self.volume_tx.borrow() * 100.0).round() as u8
+    }
+
+    pub fn state(&self) -> PlaybackState {
+        PlaybackState {
+            channel: self.channel(),
+            paused: *self.paused_tx.borrow(),
+            volume: *self.volume_tx.borrow(),
+            timeshift_secs: 0.0,
+            queued_secs: 0.0,
+            levels: [0.0; 2],
+            live_edge: None,
+        }
+    }
+
+    /// The next pending event, without waiting
+    pub fn try_event(&self) -> Option<PlaybackEvent> {
+        self.event_rx
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .try_recv()
+            .ok()
+    }
+
+    /// Stop the renderer and the relay
+    pub async fn shutdown(self) {
+        drop(self.command_tx);
+        let _ = tokio::time::timeout(SOAP_TIMEOUT, self.task).await;
+    }
+}
+
+/// The background side of [`DlnaPlayback`]: fetches segments into the relay
+/// and makes the SOAP calls
+struct Feed {
+    client: Arc<NhkRadioClient>,
+    stream_data: StreamData,
+    stream: StreamOptions,
+    renderer: DlnaRenderer,
+    http: reqwest::Client,
+    relay: Relay,
+    url: String,
+    events: Sender<PlaybackEvent>,
+}
+
+impl Feed {
+    async fn run(
+        mut self,
+        mut channel_rx: watch::Receiver<ChannelKind>,
+        mut commands: mpsc::UnboundedReceiver<Command>,
+    ) {
+        let mut channel = *channel_rx.borrow_and_update();
+        let mut segments = Box::pin(self.segments(channel));
+        let mut paused = false;
+        let mut started = false;
+
+        loop {
+            tokio::select! {
+                Some(segment) = segments.next() => {
+                    self.relay.push(segment);
+                    if !started && !paused {
+                        started = true;
+                        self.report(self.play(channel).await, PlaybackEvent::Started);
+                    }
+                }
+                Ok(()) = channel_rx.changed() => {
+                    channel = *channel_rx.borrow_and_update();
+                    self.relay.reset();
+                    segments = Box::pin(self.segments(channel));
+                    started = false;
+                }
+                command = commands.recv() => match command {
+                    Some(Command::Paused(true)) => {
+                        paused = true;
+                        started = false;
+                        self.report_error(self.renderer.stop(&self.http).await);
+                    }
+                    Some(Command::Paused(false)) => {
+                        paused = false;
+                        // Otherwise the next segment starts it
+                        if self.relay.is_ready() {
+                            started = true;
+                            self.report(self.play(channel).await, PlaybackEvent::Started);
+                        }
+                    }
+                    Some(Command::Volume(volume)) => {
+                        self.report_error(self.renderer.set_volume(&self.http, volume).await);
+                    }
+                    Some(Command::Area(stream_data)) => {
+                        self.stream_data = stream_data;
+                        self.relay.reset();
+                        segments = Box::pin(self.segments(channel));
+                        started = false;
+                    }
+                    None => {
+                        if let Err(e) = self.renderer.stop(&self.http).await {
+                            log::warn!("Failed to stop {}: {}", self.renderer.name, e);
+                        }
+                        return;
+                    }
+                },
+            }
+        }
+    }
+
+    fn segments(&self, channel: ChannelKind) -> impl Stream<Item = SegmentData> + use<> {
+        segment_stream(
+            self.client.clone(),
+            self.stream_data.clone(),
+            channel,
+            self.stream,
+        )
+    }
+
+    async fn play(&self, channel: ChannelKind) -> Result<()> {
+        let title = format!(
+            "NHK {} ({})",
+            tr().channel_name(channel),
+            area_label(&self.stream_data)
+        );
+        self.renderer.play_url(&self.http, &self.url, &title).await
+    }
+
+    fn report(&self, result: Result<()>, ok: PlaybackEvent) {
+        let event = match result {
+            Ok(()) => ok,
+            Err(e) => PlaybackEvent::Error(e.to_string()),
+        };
+        let _ = self.events.send(event);
+    }
+
+    fn report_error(&self, result: Result<()>) {
+        if let Err(e) = result {
+            let _ = self.events.send(PlaybackEvent::Error(e.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:MediaRenderer:1</deviceType>
+    <friendlyName>Living Room &amp; Kitchen</friendlyName>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:RenderingControl:1</serviceType>
+        <controlURL>/RenderingControl/ctrl</controlURL>
+      </service>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:AVTransport:1</serviceType>
+        <controlURL>AVTransport/ctrl</controlURL>
+      </service>
+    </serviceList>
+  </device>
+</root>"#;
+
+    #[test]
+    fn test_parse_description() {
+        let location = reqwest::Url::parse("http://192.168.1.20:1400/xml/device.xml").unwrap();
+        let renderer = parse_description(DESCRIPTION, &location).unwrap();
+        assert_eq!(renderer.name, "Living Room & Kitchen");
+        assert_eq!(
+            renderer.av_transport.control_url,
+            "http://192.168.1.20:1400/xml/AVTransport/ctrl"
+        );
+        assert_eq!(
+            renderer.rendering_control.unwrap().control_url,
+            "http://192.168.1.20:1400/RenderingControl/ctrl"
+        );
+        assert_eq!(renderer.addr, "192.168.1.20:1400".parse().unwrap());
+    }
+
+    #[test]
+    fn test_ssdp_location() {
+        let response = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\n\
+                        Location: http://192.168.1.20:1400/xml/device.xml\r\n\r\n";
+        assert_eq!(
+            ssdp_location(response).as_deref(),
+            Some("http://192.168.1.20:1400/xml/device.xml")
+        );
+        assert!(ssdp_location("HTTP/1.1 200 OK\r\n\r\n").is_none());
+    }
+
+    #[test]
+    fn test_soap_envelope_escapes_arguments() {
+        let metadata = didl_metadata("http://10.0.0.2:8000/live.m3u8", "NHK FM (東京)");
+        let envelope = soap_envelope(
+            "urn:schemas-upnp-org:service:AVTransport:1",
+            "SetAVTransportURI",
+            &[("CurrentURIMetaData", &metadata)],
+        );
+        assert!(envelope.contains(
+            "<u:SetAVTransportURI xmlns:u=\"urn:schemas-upnp-org:service:AVTransport:1\">"
+        ));
+        // The DIDL-Lite document travels as text inside the envelope
+        assert!(envelope.contains("<CurrentURIMetaData>&lt;DIDL-Lite"));
+        assert!(tag_text(&envelope, "CurrentURIMetaData").unwrap() == metadata);
+    }
+}
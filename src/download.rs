@@ -0,0 +1,454 @@
+//! Download of whole 聴き逃し episodes to a tagged audio file.
+//!
+//! Segments are fetched in parallel but written in playlist order, so the
+//! output is a plain ADTS AAC stream with an ID3v2 tag in front. Other
+//! formats are produced by re-encoding that file (`transcode` feature).
+//!
+//! Until it is complete the stream is kept in `<file>.part.aac`, with how far
+//! it got in `<file>.part.json`; running the same download again picks up
+//! after the last segment written. Each segment is checked to be whole
+//! before it is written, and fetched again if not.
+
+use anyhow::{Context, Result};
+use futures::{StreamExt, TryStreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::client::NhkRadioClient;
+use crate::crypto::decrypt_segment;
+use crate::ondemand::VodPlaylist;
+use crate::recorder::{RecordFormat, RecordingTags};
+use crate::types::{OndemandEpisode, OndemandSeries};
+
+/// Number of segments fetched concurrently by default
+pub const DEFAULT_JOBS: usize = 4;
+
+/// Times a segment that arrives damaged is fetched before giving up
+const SEGMENT_ATTEMPTS: u32 = 3;
+
+/// Size of an MPEG-TS packet
+const TS_PACKET: usize = 188;
+
+/// Tag values taken from the series and episode metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackInfo {
+    pub title: String,
+    pub series: String,
+    pub artist: String,
+    pub date: String,
+    pub description: String,
+}
+
+impl TrackInfo {
+    pub fn new(series: &OndemandSeries, episode: &OndemandEpisode) -> Self {
+        let description = if episode.program_sub_title.is_empty() {
+            series.series_description.clone()
+        } else {
+            episode.program_sub_title.clone()
+        };
+        Self {
+            title: episode.program_title.clone(),
+            series: series.title.clone(),
+            artist: format!("NHK {}", series.radio_broadcast).trim().to_string(),
+            date: episode.onair_date.clone(),
+            description,
+        }
+    }
+
+    fn tags(&self) -> RecordingTags {
+        RecordingTags {
+            title: self.title.clone(),
+            album: self.series.clone(),
+            artist: self.artist.clone(),
+            date: self.date.clone(),
+            comment: self.description.clone(),
+        }
+    }
+
+    /// File name derived from the series title and air date
+    pub fn file_name(&self, format: RecordFormat) -> String {
+        let stem = format!("{} {}", self.series, self.date);
+        format!("{}.{}", sanitize_file_name(stem.trim()), format.extension())
+    }
+}
+
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// How far a download got, saved next to the partial stream
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct DownloadProgress {
+    /// URL of the first segment, to tell the episode's playlist apart
+    first_segment: String,
+    /// Segments in the playlist
+    segments: usize,
+    /// Segments written to the partial stream, in playlist order
+    done: usize,
+    /// Length of the partial stream after them
+    bytes: u64,
+}
+
+impl DownloadProgress {
+    fn new(playlist: &VodPlaylist) -> Self {
+        DownloadProgress {
+            first_segment: playlist
+                .segments
+                .first()
+                .map(|s| s.url.clone())
+                .unwrap_or_default(),
+            segments: playlist.segments.len(),
+            done: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Progress saved at `path` by an earlier download of `playlist` into
+    /// a partial stream now `partial_len` bytes long
+    fn load(path: &Path, playlist: &VodPlaylist, partial_len: u64) -> Option<Self> {
+        let json = std::fs::read_to_string(path).ok()?;
+        let saved: DownloadProgress = serde_json::from_str(&json).ok()?;
+        let fresh = DownloadProgress::new(playlist);
+        let same = saved.first_segment == fresh.first_segment && saved.segments == fresh.segments;
+        (same && saved.done <= saved.segments && saved.bytes <= partial_len).then_some(saved)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Download `playlist` into `path` as `format`, returning the size of the
+/// AAC stream, tag included
+///
+/// An interrupted download of the same playlist into the same `path` is
+/// resumed.
+pub async fn download_episode(
+    client: Arc<NhkRadioClient>,
+    playlist: &VodPlaylist,
+    info: &TrackInfo,
+    path: &Path,
+    format: RecordFormat,
+    jobs: usize,
+) -> Result<u64> {
+    if format != RecordFormat::Raw && !cfg!(feature = "transcode") {
+        anyhow::bail!(
+            "Downloading as {:?} requires building with `--features transcode`",
+            format
+        );
+    }
+
+    // Keys are shared by many segments, so fetch each one once up front
+    let mut keys: HashMap<String, Vec<u8>> = HashMap::new();
+    for key_url in playlist.segments.iter().filter_map(|s| s.key_url.as_ref()) {
+        if !keys.contains_key(key_url) {
+            keys.insert(key_url.clone(), client.fetch_key(key_url).await?);
+        }
+    }
+    let keys = Arc::new(keys);
+
+    // fMP4 fragments are only playable after the initialization section
+    let init = match playlist.segments.first().and_then(|s| s.map_url.as_ref()) {
+        Some(map_url) => Some(client.fetch_segment(map_url, None).await?),
+        None => None,
+    };
+
+    let aac_path = partial_path(path);
+    let state_path = progress_path(path);
+    let partial_len = std::fs::metadata(&aac_path)
+        .map(|meta| meta.len())
+        .unwrap_or_default();
+    let mut state = match DownloadProgress::load(&state_path, playlist, partial_len) {
+        Some(state) => {
+            log::info!(
+                "Resuming {} after segment {}/{}",
+                path.display(),
+                state.done,
+                state.segments
+            );
+            state
+        }
+        None => DownloadProgress::new(playlist),
+    };
+
+    let mut writer = if state.bytes > 0 {
+        // Anything after the last segment recorded may be cut short
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(&aac_path)
+            .with_context(|| format!("Failed to open {}", aac_path.display()))?;
+        file.set_len(state.bytes)?;
+        file.seek(SeekFrom::End(0))?;
+        BufWriter::new(file)
+    } else {
+        let mut writer = BufWriter::new(
+            File::create(&aac_path)
+                .with_context(|| format!("Failed to create {}", aac_path.display()))?,
+        );
+        match init {
+            Some(ref init) => writer.write_all(init)?,
+            // An ID3 header would hide an MP4 file's signature, so only tag ADTS output
+            None if format == RecordFormat::Raw => info
+                .tags()
+                .to_id3()
+                .write_to(&mut writer, id3::Version::Id3v24)?,
+            None => {}
+        }
+        writer.flush()?;
+        state.bytes = writer.get_ref().metadata()?.len();
+        state.save(&state_path)?;
+        writer
+    };
+
+    let progress = ProgressBar::new(playlist.segments.len() as u64);
+    progress.set_position(state.done as u64);
+    progress.set_style(
+        ProgressStyle::with_template(
+            "{spinner} [{bar:40.cyan/blue}] {pos}/{len} segments ({eta})",
+        )?
+        .progress_chars("=> "),
+    );
+
+    let mut segments = futures::stream::iter(playlist.segments.iter().skip(state.done).cloned())
+        .map(|segment| {
+            let client = client.clone();
+            let keys = keys.clone();
+            async move {
+                let mut attempt = 1;
+                loop {
+                    let data = client
+                        .fetch_segment(&segment.url, segment.byte_range)
+                        .await?;
+                    let checked = match segment.key_url {
+                        Some(ref key_url) => decrypt_segment(
+                            &data,
+                            &keys[key_url],
+                            segment.iv.as_deref(),
+                            segment.seq_no,
+                            segment.key_method,
+                        ),
+                        None => Ok(data),
+                    }
+                    .and_then(|data| check_segment(&data).map(|_| data));
+                    match checked {
+                        Ok(data) => {
+                            if segment.key_url.is_some() {
+                                client.dump_decrypted(&segment.url, segment.seq_no, &data);
+                            }
+                            return Ok::<_, anyhow::Error>(data);
+                        }
+                        Err(e) if attempt < SEGMENT_ATTEMPTS => {
+                            log::warn!("Fetching segment {} again: {}", segment.seq_no, e);
+                            attempt += 1;
+                        }
+                        Err(e) => {
+                            return Err(e.context(format!("Segment {} is damaged", segment.seq_no)));
+                        }
+                    }
+                }
+            }
+        })
+        .buffered(jobs.max(1));
+
+    while let Some(data) = segments.try_next().await? {
+        writer.write_all(&data)?;
+        // Record the segment only once it is on disk
+        writer.flush()?;
+        state.done += 1;
+        state.bytes += data.len() as u64;
+        state.save(&state_path)?;
+        progress.inc(1);
+    }
+    drop(writer);
+    progress.finish_and_clear();
+
+    if format == RecordFormat::Raw {
+        std::fs::rename(&aac_path, path).with_context(|| {
+            format!(
+                "Failed to move {} to {}",
+                aac_path.display(),
+                path.display()
+            )
+        })?;
+    }
+    #[cfg(feature = "transcode")]
+    if format != RecordFormat::Raw {
+        crate::encoder::transcode_file(&aac_path, path, format, &info.tags().metadata())?;
+        let _ = std::fs::remove_file(&aac_path);
+    }
+    let _ = std::fs::remove_file(&state_path);
+
+    Ok(state.bytes)
+}
+
+/// Check that `data` is a whole segment: MPEG-TS packets, ADTS frames
+/// after any ID3 tag, or MP4 boxes, none of them cut short
+///
+/// Data in none of these formats is let through unchecked.
+fn check_segment(data: &[u8]) -> Result<()> {
+    if data.is_empty() {
+        anyhow::bail!("Segment is empty");
+    }
+    if data[0] == 0x47 {
+        let whole = data.len().is_multiple_of(TS_PACKET)
+            && data.chunks(TS_PACKET).all(|packet| packet[0] == 0x47);
+        anyhow::ensure!(whole, "MPEG-TS packets are out of sync");
+        return Ok(());
+    }
+
+    let mut pos = 0;
+    // Timestamp tag in front of packed audio
+    if data.starts_with(b"ID3") && data.len() >= 10 {
+        let size = data[6..10]
+            .iter()
+            .fold(0usize, |size, &b| (size << 7) | (b & 0x7f) as usize);
+        pos = 10 + size;
+    }
+    if data.get(pos) == Some(&0xff) && data.get(pos + 1).is_some_and(|b| b & 0xf6 == 0xf0) {
+        while pos < data.len() {
+            let header = data
+                .get(pos..pos + 7)
+                .filter(|h| h[0] == 0xff && h[1] & 0xf6 == 0xf0)
+                .ok_or_else(|| anyhow::anyhow!("ADTS frame at byte {} is damaged", pos))?;
+            let len = ((header[3] as usize & 0x03) << 11)
+                | ((header[4] as usize) << 3)
+                | (header[5] as usize >> 5);
+            anyhow::ensure!(len >= 7, "ADTS frame at byte {} is damaged", pos);
+            pos += len;
+        }
+        anyhow::ensure!(pos == data.len(), "Last ADTS frame is cut short");
+        return Ok(());
+    }
+
+    if data.len() >= 8 && matches!(&data[4..8], b"styp" | b"moof" | b"sidx" | b"ftyp") {
+        let mut pos = 0;
+        while pos < data.len() {
+            let header = data
+                .get(pos..pos + 8)
+                .ok_or_else(|| anyhow::anyhow!("MP4 box at byte {} is cut short", pos))?;
+            let size = match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+                0 => data.len() - pos,
+                1 => {
+                    let large = data
+                        .get(pos + 8..pos + 16)
+                        .ok_or_else(|| anyhow::anyhow!("MP4 box at byte {} is cut short", pos))?;
+                    u64::from_be_bytes(large.try_into()?) as usize
+                }
+                size => size as usize,
+            };
+            anyhow::ensure!(size >= 8, "MP4 box at byte {} is damaged", pos);
+            pos = pos.saturating_add(size);
+        }
+        anyhow::ensure!(pos == data.len(), "Last MP4 box is cut short");
+    }
+    Ok(())
+}
+
+/// Scratch file holding the AAC stream until it is complete
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part.aac");
+    path.with_file_name(name)
+}
+
+/// Where how far the download into `path` got is kept
+fn progress_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part.json");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Segment;
+
+    #[test]
+    fn test_file_name_is_sanitized() {
+        let info = TrackInfo {
+            title: "第1回".to_string(),
+            series: "英会話/入門".to_string(),
+            artist: "NHK R2".to_string(),
+            date: "2024年4月1日(月)".to_string(),
+            description: String::new(),
+        };
+        assert_eq!(
+            info.file_name(RecordFormat::Raw),
+            "英会話_入門 2024年4月1日(月).aac"
+        );
+        assert_eq!(
+            info.file_name(RecordFormat::Flac),
+            "英会話_入門 2024年4月1日(月).flac"
+        );
+    }
+
+    /// One silent ADTS frame of AAC-LC
+    const FRAME: [u8; 11] = [
+        0xff, 0xf1, 0x4c, 0x40, 0x01, 0x7f, 0xfc, 0x00, 0xc8, 0x00, 0x07,
+    ];
+
+    #[test]
+    fn test_check_segment() {
+        let adts = FRAME.repeat(3);
+        assert!(check_segment(&adts).is_ok());
+        assert!(check_segment(&adts[..adts.len() - 1]).is_err());
+        assert!(check_segment(&[]).is_err());
+
+        let mut tagged = b"ID3\x04\x00\x00\x00\x00\x00\x02ab".to_vec();
+        tagged.extend_from_slice(&adts);
+        assert!(check_segment(&tagged).is_ok());
+
+        let mut ts = vec![0u8; TS_PACKET * 2];
+        ts[0] = 0x47;
+        ts[TS_PACKET] = 0x47;
+        assert!(check_segment(&ts).is_ok());
+        assert!(check_segment(&ts[..TS_PACKET + 10]).is_err());
+
+        let mut mp4 = vec![0, 0, 0, 8];
+        mp4.extend_from_slice(b"styp");
+        mp4.extend_from_slice(&[0, 0, 0, 12]);
+        mp4.extend_from_slice(b"mdat\x01\x02\x03\x04");
+        assert!(check_segment(&mp4).is_ok());
+        assert!(check_segment(&mp4[..mp4.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn test_progress_resumes_same_playlist() {
+        let segments = (0..4)
+            .map(|n| Segment {
+                url: format!("https://example.com/seg{}.aac", n),
+                seq_no: n,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let playlist = VodPlaylist::new(segments.clone());
+        let path = std::env::temp_dir().join(format!("nhk-download-{}.json", std::process::id()));
+
+        let mut progress = DownloadProgress::new(&playlist);
+        progress.done = 2;
+        progress.bytes = 100;
+        progress.save(&path).unwrap();
+        assert_eq!(
+            DownloadProgress::load(&path, &playlist, 120),
+            Some(progress)
+        );
+        // The partial stream lost what was recorded
+        assert_eq!(DownloadProgress::load(&path, &playlist, 50), None);
+        // Another episode in its place
+        let other = VodPlaylist::new(segments[1..].to_vec());
+        assert_eq!(DownloadProgress::load(&path, &other, 120), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,237 @@
+//! Live status of the daemon, pushed to `/ws` clients of the HTTP API.
+//!
+//! [`watch_events`] follows the program on air on the channel being played,
+//! the pause and volume controls, and the engine's [`PlaybackEvent`]s, and
+//! broadcasts a [`DaemonEvent`] for each change. Each event is one JSON
+//! object tagged with its kind, e.g.
+//! `{"event":"channel_change","channel":"fm"}`.
+
+use futures::StreamExt;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::control::{Controller, DaemonStatus};
+use crate::i18n::{area_label, tr};
+use crate::now_playing::NowPlayingRecord;
+use crate::player::{ChannelKind, PlaybackEvent};
+use crate::radio::now_playing_stream;
+
+/// Events kept for a client that falls behind before it misses some
+pub const EVENT_CAPACITY: usize = 64;
+
+/// How often the engine and controls are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Something a dashboard showing the daemon needs to know
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DaemonEvent {
+    /// Everything at once, sent to each client as it connects and again if
+    /// it fell behind
+    Status(DaemonStatus),
+    /// A new program started on the channel being played
+    ProgramChange(NowPlayingRecord),
+    /// Another channel was tuned in; `r1`, `r2`, or `fm`
+    ChannelChange { channel: String },
+    /// Audio started or stopped, or the volume changed
+    PlaybackState { state: PlayState, volume: u8 },
+    /// Playback failed, or the broadcast went silent
+    Error { message: String },
+}
+
+/// Whether audio is being heard, and if not why
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayState {
+    /// Waiting for audio after starting or a channel change
+    Buffering,
+    Playing,
+    Paused,
+    /// The output device went away and is being reopened
+    OutputLost,
+}
+
+/// What clients were last told, to report only changes
+#[derive(Debug)]
+struct Tracker {
+    channel: ChannelKind,
+    /// State of the audio output, whether paused or not
+    output: PlayState,
+    paused: bool,
+    volume: u8,
+}
+
+impl Tracker {
+    fn new(channel: ChannelKind, paused: bool, volume: u8) -> Self {
+        Tracker {
+            channel,
+            output: PlayState::Buffering,
+            paused,
+            volume,
+        }
+    }
+
+    fn state(&self) -> PlayState {
+        if self.paused {
+            PlayState::Paused
+        } else {
+            self.output
+        }
+    }
+
+    fn playback_state(&self) -> DaemonEvent {
+        DaemonEvent::PlaybackState {
+            state: self.state(),
+            volume: self.volume,
+        }
+    }
+
+    /// What clients are told of `event` from the engine
+    fn playback(&mut self, event: PlaybackEvent) -> Vec<DaemonEvent> {
+        match event {
+            PlaybackEvent::ChannelChanged(channel) => {
+                self.channel = channel;
+                self.output = PlayState::Buffering;
+                vec![
+                    DaemonEvent::ChannelChange {
+                        channel: channel.short_name().to_lowercase(),
+                    },
+                    self.playback_state(),
+                ]
+            }
+            PlaybackEvent::Started | PlaybackEvent::OutputRestored => {
+                self.set_output(PlayState::Playing)
+            }
+            PlaybackEvent::OutputLost => self.set_output(PlayState::OutputLost),
+            PlaybackEvent::Silence => vec![DaemonEvent::Error {
+                message: tr().silence.to_string(),
+            }],
+            PlaybackEvent::SoundReturned => vec![],
+            PlaybackEvent::Error(message) => vec![DaemonEvent::Error { message }],
+        }
+    }
+
+    fn set_output(&mut self, output: PlayState) -> Vec<DaemonEvent> {
+        let before = self.state();
+        self.output = output;
+        if self.state() == before {
+            vec![]
+        } else {
+            vec![self.playback_state()]
+        }
+    }
+
+    /// An event when pausing or the volume changed since last time
+    fn controls(&mut self, paused: bool, volume: u8) -> Option<DaemonEvent> {
+        if (paused, volume) == (self.paused, self.volume) {
+            return None;
+        }
+        self.paused = paused;
+        self.volume = volume;
+        Some(self.playback_state())
+    }
+}
+
+/// Broadcast the daemon's changes on [`Controller::events`] until the task
+/// is dropped
+pub async fn watch_events(controller: Arc<Controller>) {
+    let engine = &controller.engine;
+    let mut tracker = Tracker::new(engine.channel(), engine.is_paused(), controller.volume());
+    let follow = |channel| {
+        Box::pin(now_playing_stream(
+            controller.client.clone(),
+            controller.program_url.clone(),
+            channel,
+        ))
+    };
+    let mut programs = follow(tracker.channel);
+    let mut tick = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            Some(program) = programs.next() => {
+                let record = NowPlayingRecord::new(
+                    program.channel,
+                    &area_label(&engine.area()),
+                    &program.title,
+                    program.start,
+                    program.end,
+                );
+                controller.publish(DaemonEvent::ProgramChange(record));
+            }
+            _ = tick.tick() => {
+                while let Some(event) = engine.try_event() {
+                    let channel = tracker.channel;
+                    for event in tracker.playback(event) {
+                        controller.publish(event);
+                    }
+                    if tracker.channel != channel {
+                        programs = follow(tracker.channel);
+                    }
+                }
+                if let Some(event) = tracker.controls(engine.is_paused(), controller.volume()) {
+                    controller.publish(event);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(events: &[DaemonEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| serde_json::to_string(event).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_tracker() {
+        let mut tracker = Tracker::new(ChannelKind::R1, false, 80);
+
+        assert_eq!(
+            json(&tracker.playback(PlaybackEvent::Started)),
+            [r#"{"event":"playback_state","state":"playing","volume":80}"#]
+        );
+        assert!(tracker.playback(PlaybackEvent::Started).is_empty());
+        assert_eq!(
+            json(&tracker.playback(PlaybackEvent::ChannelChanged(ChannelKind::Fm))),
+            [
+                r#"{"event":"channel_change","channel":"fm"}"#,
+                r#"{"event":"playback_state","state":"buffering","volume":80}"#
+            ]
+        );
+        assert_eq!(tracker.channel, ChannelKind::Fm);
+
+        assert!(tracker.controls(false, 80).is_none());
+        assert_eq!(
+            json(&[tracker.controls(true, 50).unwrap()]),
+            [r#"{"event":"playback_state","state":"paused","volume":50}"#]
+        );
+        // Audio coming back is not news while paused
+        assert!(tracker.playback(PlaybackEvent::Started).is_empty());
+        assert_eq!(
+            json(&[tracker.controls(false, 50).unwrap()]),
+            [r#"{"event":"playback_state","state":"playing","volume":50}"#]
+        );
+
+        assert_eq!(
+            json(&tracker.playback(PlaybackEvent::Error("Stream lost".to_string()))),
+            [r#"{"event":"error","message":"Stream lost"}"#]
+        );
+        assert!(tracker.playback(PlaybackEvent::SoundReturned).is_empty());
+    }
+
+    #[test]
+    fn test_program_change_wire_format() {
+        let record = NowPlayingRecord::new(ChannelKind::Fm, "東京", "クラシックの庭", None, None);
+        let value = serde_json::to_value(DaemonEvent::ProgramChange(record)).unwrap();
+        assert_eq!(value["event"], "program_change");
+        assert_eq!(value["channel"], "fm");
+        assert_eq!(value["title"], "クラシックの庭");
+    }
+}
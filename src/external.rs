@@ -0,0 +1,188 @@
+//! Live playback through an external player such as mpv.
+//!
+//! The player either gets the resolved variant URL and fetches the stream
+//! itself, or, when its command line has no `{url}`, reads the decrypted
+//! segments on stdin. Either way rodio and the decoder are not involved.
+
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, ChildStdin, Command};
+
+use crate::client::{HttpOptions, NhkRadioClient};
+use crate::headless::program_line;
+use crate::i18n::{area_label, tr};
+use crate::player::ChannelKind;
+use crate::radio::now_playing_stream;
+use crate::stream::{SegmentData, StreamOptions, segment_stream, variant_url};
+
+/// Placeholder replaced with the stream URL in a player command line
+const URL_PLACEHOLDER: &str = "{url}";
+
+/// Command lines for the players `--player` knows by name
+const PRESETS: &[(&str, &str)] = &[
+    ("mpv", "mpv --no-video --force-window=no {url}"),
+    ("vlc", "vlc --intf dummy {url}"),
+    ("ffplay", "ffplay -nodisp -loglevel error {url}"),
+    ("mplayer", "mplayer -really-quiet {url}"),
+];
+
+/// An external player command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalPlayer {
+    program: String,
+    args: Vec<String>,
+}
+
+impl ExternalPlayer {
+    /// One of the known players: mpv, vlc, ffplay, or mplayer
+    pub fn preset(name: &str) -> Result<Self> {
+        let (_, template) = PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown player: {} (known: {}; use --exec-player for others)",
+                    name,
+                    PRESETS
+                        .iter()
+                        .map(|(preset, _)| *preset)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+        Self::from_template(template)
+    }
+
+    /// A whitespace-separated command line, with `{url}` where the stream URL
+    /// goes; without it the decrypted stream is written to stdin
+    pub fn from_template(template: &str) -> Result<Self> {
+        let mut words = template.split_whitespace().map(str::to_string);
+        let program = words
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Empty player command"))?;
+        Ok(ExternalPlayer {
+            program,
+            args: words.collect(),
+        })
+    }
+
+    /// Whether the player reads the stream on stdin instead of fetching a URL
+    pub fn reads_stdin(&self) -> bool {
+        !self.args.iter().any(|arg| arg.contains(URL_PLACEHOLDER))
+    }
+
+    fn command(&self, url: &str) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(
+                self.args
+                    .iter()
+                    .map(|arg| arg.replace(URL_PLACEHOLDER, url)),
+            )
+            .kill_on_drop(true);
+        command
+    }
+}
+
+/// Play `kind` in `area` with `player` until it exits or Ctrl-C, printing a
+/// line whenever the program changes
+pub async fn run_external_player(
+    area: String,
+    kind: ChannelKind,
+    player: ExternalPlayer,
+    http: HttpOptions,
+    stream: StreamOptions,
+) -> Result<()> {
+    let client = Arc::new(NhkRadioClient::with_options(http)?);
+    let (stream_data, program_url) = client.fetch_config().await?.find_area(&area)?;
+
+    let mut child = if player.reads_stdin() {
+        let mut child = spawn(player.command("").stdin(Stdio::piped()), &player)?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let segments = segment_stream(client.clone(), stream_data.clone(), kind, stream);
+        tokio::spawn(feed_segments(segments, stdin));
+        child
+    } else {
+        let url = variant_url(client.as_ref(), &stream_data, kind, stream).await?;
+        log::info!("Handing {} to {}", url, player.program);
+        spawn(player.command(&url).stdin(Stdio::null()), &player)?
+    };
+
+    println!("▶ {} {}", area_label(&stream_data), tr().channel_name(kind));
+    let mut programs = std::pin::pin!(now_playing_stream(client, program_url, kind));
+
+    loop {
+        tokio::select! {
+            Some(program) = programs.next() => println!("{}", program_line(&program)),
+            status = child.wait() => return check_status(status?, &player),
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    child.kill().await?;
+    Ok(())
+}
+
+fn spawn(command: &mut Command, player: &ExternalPlayer) -> Result<Child> {
+    command
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to start {}: {}", player.program, e))
+}
+
+fn check_status(status: ExitStatus, player: &ExternalPlayer) -> Result<()> {
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", player.program, status);
+    }
+    Ok(())
+}
+
+/// Write each segment to the player's stdin, preceded by its fMP4
+/// initialization section whenever that changes
+async fn feed_segments(segments: impl Stream<Item = SegmentData>, mut stdin: ChildStdin) {
+    let mut segments = std::pin::pin!(segments);
+    let mut last_init = None;
+    while let Some(segment) = segments.next().await {
+        let mut result = Ok(());
+        if let Some(init) = segment.init.as_ref()
+            && last_init
+                .as_ref()
+                .is_none_or(|last| !Arc::ptr_eq(last, init))
+        {
+            result = stdin.write_all(init).await;
+            last_init = Some(init.clone());
+        }
+        if result.is_ok() {
+            result = stdin.write_all(&segment.data).await;
+        }
+        // A closed pipe means the player has quit; its exit is handled above
+        if let Err(e) = result {
+            log::info!("External player stopped reading: {}", e);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_player_templates() {
+        let mpv = ExternalPlayer::preset("MPV").unwrap();
+        assert_eq!(mpv.program, "mpv");
+        assert!(!mpv.reads_stdin());
+
+        let piped = ExternalPlayer::from_template("ffplay -nodisp -i -").unwrap();
+        assert_eq!(piped.program, "ffplay");
+        assert_eq!(piped.args, ["-nodisp", "-i", "-"]);
+        assert!(piped.reads_stdin());
+
+        let custom = ExternalPlayer::from_template("player --url={url}").unwrap();
+        assert!(!custom.reads_stdin());
+
+        assert!(ExternalPlayer::preset("winamp").is_err());
+        assert!(ExternalPlayer::from_template("  ").is_err());
+    }
+}
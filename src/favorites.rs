@@ -0,0 +1,292 @@
+//! Favorite series, and reminders shortly before they air.
+//!
+//! Favorites are kept in `favorites.toml` beside the config file, as a list of
+//! `[[favorite]]` entries. An entry matches a series by its `radioSeriesId`,
+//! or any program whose title contains a pattern.
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+use crate::player::ChannelKind;
+use crate::types::ScheduledProgram;
+
+/// Minutes before a favorite airs that it is announced, by default
+pub const DEFAULT_REMINDER_MINUTES: u32 = 5;
+
+/// A series, or programs with a title pattern, to be reminded of
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Favorite {
+    /// `radioSeriesId` of the series
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series_id: Option<String>,
+    /// Text the program title contains, ignoring case
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Name to list the favorite by
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+}
+
+impl Favorite {
+    pub fn matches(&self, series_id: &str, title: &str) -> bool {
+        let by_series = self
+            .series_id
+            .as_deref()
+            .is_some_and(|id| !id.is_empty() && id == series_id);
+        let by_title = self
+            .title
+            .as_deref()
+            .is_some_and(|pattern| title.to_lowercase().contains(&pattern.to_lowercase()));
+        by_series || by_title
+    }
+
+    /// The name, else the pattern or ID it matches by
+    pub fn label(&self) -> &str {
+        [self.name.as_str()]
+            .into_iter()
+            .chain(self.title.as_deref())
+            .chain(self.series_id.as_deref())
+            .find(|label| !label.is_empty())
+            .unwrap_or_default()
+    }
+}
+
+/// Every favorite, as saved in `favorites.toml`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Favorites {
+    #[serde(rename = "favorite")]
+    pub entries: Vec<Favorite>,
+}
+
+impl Favorites {
+    pub fn path() -> Option<PathBuf> {
+        Config::path().map(|path| path.with_file_name("favorites.toml"))
+    }
+
+    /// Load the favorites, or none when the file does not exist
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path().filter(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+        let text = fs::read_to_string(&path)?;
+        toml::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("No config directory available"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn matches(&self, series_id: &str, title: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|favorite| favorite.matches(series_id, title))
+    }
+
+    /// Add `favorite` unless the same entry is there already
+    pub fn add(&mut self, favorite: Favorite) -> bool {
+        if self.entries.contains(&favorite) {
+            return false;
+        }
+        self.entries.push(favorite);
+        true
+    }
+
+    /// Remove the favorites named, or matching by series ID or pattern, `query`
+    pub fn remove(&mut self, query: &str) -> Vec<Favorite> {
+        let (removed, kept) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|favorite| {
+                favorite.name == query
+                    || favorite.series_id.as_deref() == Some(query)
+                    || favorite.title.as_deref() == Some(query)
+            });
+        self.entries = kept;
+        removed
+    }
+
+    /// Make a program's series a favorite, or remove the favorites matching
+    /// the program; true when it was added
+    ///
+    /// Programs without a series ID are added by their full title.
+    pub fn toggle(&mut self, series_id: &str, series_name: &str, title: &str) -> bool {
+        if self.matches(series_id, title) {
+            self.entries
+                .retain(|favorite| !favorite.matches(series_id, title));
+            return false;
+        }
+        self.entries.push(Favorite {
+            series_id: (!series_id.is_empty()).then(|| series_id.to_string()),
+            title: series_id.is_empty().then(|| title.to_string()),
+            name: series_name.to_string(),
+        });
+        true
+    }
+
+    /// Favorite programs in `schedule` starting after `now`, within `lead`
+    pub fn upcoming(
+        &self,
+        schedule: &[(ChannelKind, ScheduledProgram)],
+        now: DateTime<FixedOffset>,
+        lead: chrono::Duration,
+    ) -> Vec<Reminder> {
+        schedule
+            .iter()
+            .filter(|(_, program)| {
+                self.matches(&program.identifier_group.radio_series_id, &program.name)
+            })
+            .filter_map(|(channel, program)| {
+                let start = DateTime::parse_from_rfc3339(&program.start_date).ok()?;
+                (start > now && start - now <= lead).then(|| Reminder {
+                    channel: *channel,
+                    title: program.name.clone(),
+                    start,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A favorite program about to start
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reminder {
+    pub channel: ChannelKind,
+    pub title: String,
+    pub start: DateTime<FixedOffset>,
+}
+
+/// Show a desktop notification with notify-send, or osascript on macOS
+pub fn notify_desktop(summary: &str, body: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {:?} with title {:?}",
+            body, summary
+        ));
+        command
+    } else if cfg!(windows) {
+        return;
+    } else {
+        let mut command = Command::new("notify-send");
+        command.arg(summary).arg(body);
+        command
+    };
+    match command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => log::debug!("No desktop notification: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduled(series_id: &str, name: &str, start: &str) -> ScheduledProgram {
+        let mut program = ScheduledProgram::named(name, start, "");
+        program.identifier_group.radio_series_id = series_id.to_string();
+        program
+    }
+
+    #[test]
+    fn test_matches() {
+        let series = Favorite {
+            series_id: Some("Z9L1V2M24L".to_string()),
+            ..Default::default()
+        };
+        assert!(series.matches("Z9L1V2M24L", "クラシック音楽館"));
+        assert!(!series.matches("", "クラシック音楽館"));
+
+        let pattern = Favorite {
+            title: Some("jazz".to_string()),
+            ..Default::default()
+        };
+        assert!(pattern.matches("", "ジャズ・トゥナイト JAZZ Tonight"));
+        assert!(!pattern.matches("", "クラシックの庭"));
+        assert_eq!(pattern.label(), "jazz");
+    }
+
+    #[test]
+    fn test_toggle_and_remove() {
+        let mut favorites = Favorites::default();
+        assert!(favorites.toggle("Z9L1V2M24L", "クラシック音楽館", "第1回"));
+        assert!(favorites.matches("Z9L1V2M24L", "第2回"));
+        assert!(!favorites.toggle("Z9L1V2M24L", "クラシック音楽館", "第2回"));
+        assert!(favorites.is_empty());
+
+        // Without a series ID the title stands in
+        assert!(favorites.toggle("", "", "ラジオ深夜便"));
+        assert_eq!(favorites.entries[0].title.as_deref(), Some("ラジオ深夜便"));
+
+        let text = toml::to_string_pretty(&favorites).unwrap();
+        assert!(text.contains("[[favorite]]"));
+        assert_eq!(toml::from_str::<Favorites>(&text).unwrap(), favorites);
+
+        assert_eq!(favorites.remove("ラジオ深夜便").len(), 1);
+        assert!(favorites.remove("ラジオ深夜便").is_empty());
+    }
+
+    #[test]
+    fn test_upcoming() {
+        let favorites = Favorites {
+            entries: vec![Favorite {
+                title: Some("音楽館".to_string()),
+                ..Default::default()
+            }],
+        };
+        let schedule = vec![
+            (
+                ChannelKind::Fm,
+                scheduled("", "クラシック音楽館", "2025-11-25T21:00:00+09:00"),
+            ),
+            (
+                ChannelKind::R1,
+                scheduled("", "ニュース", "2025-11-25T21:00:00+09:00"),
+            ),
+        ];
+        let at = |time: &str| DateTime::parse_from_rfc3339(time).unwrap();
+        let lead = chrono::Duration::minutes(5);
+
+        let reminders = favorites.upcoming(&schedule, at("2025-11-25T20:56:00+09:00"), lead);
+        assert_eq!(
+            reminders,
+            [Reminder {
+                channel: ChannelKind::Fm,
+                title: "クラシック音楽館".to_string(),
+                start: at("2025-11-25T21:00:00+09:00"),
+            }]
+        );
+        assert!(
+            favorites
+                .upcoming(&schedule, at("2025-11-25T20:50:00+09:00"), lead)
+                .is_empty()
+        );
+        // Once it has started there is nothing to remind of
+        assert!(
+            favorites
+                .upcoming(&schedule, at("2025-11-25T21:00:00+09:00"), lead)
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Readable names for NHK's genre IDs, and `--genre` matching.
+//!
+//! Genre IDs are four digits: two for the major genre (`04` music) and two
+//! for the genre within it (`0402` classical and opera), following the
+//! ARIB program genre table. Filters take an ID, a word such as `news`,
+//! `classical`, or `language`, or part of a name in Japanese or English.
+
+use crate::types::Genre;
+
+/// An NHK genre ID and what it is called
+struct GenreName {
+    /// Two digits for a major genre, four for one within it
+    id: &'static str,
+    name: &'static str,
+    /// Word that selects the genre in filters, if any
+    key: &'static str,
+}
+
+const fn genre(id: &'static str, name: &'static str, key: &'static str) -> GenreName {
+    GenreName { id, name, key }
+}
+
+const GENRES: &[GenreName] = &[
+    genre("00", "News", "news"),
+    genre("0000", "Regular news", ""),
+    genre("0001", "Weather", "weather"),
+    genre("0002", "Features", ""),
+    genre("0003", "Politics", "politics"),
+    genre("0004", "Economy and markets", "economy"),
+    genre("0005", "International", ""),
+    genre("0006", "Commentary", ""),
+    genre("0007", "Discussion", ""),
+    genre("0008", "Special reports", ""),
+    genre("0009", "Local news", ""),
+    genre("0010", "Traffic", "traffic"),
+    genre("01", "Sports", "sports"),
+    genre("0101", "Baseball", "baseball"),
+    genre("0102", "Soccer", "soccer"),
+    genre("0105", "Sumo and martial arts", "sumo"),
+    genre("02", "Information", "information"),
+    genre("03", "Drama", "drama"),
+    genre("04", "Music", "music"),
+    genre("0400", "Japanese pop", "jpop"),
+    genre("0401", "Western pop", "pop"),
+    genre("0402", "Classical and opera", "classical"),
+    genre("0403", "Jazz and fusion", "jazz"),
+    genre("0404", "Kayōkyoku and enka", "enka"),
+    genre("0405", "Live and concerts", "live"),
+    genre("0406", "Charts and requests", "requests"),
+    genre("0407", "Karaoke and amateur singing", ""),
+    genre("0408", "Folk and traditional Japanese", "hogaku"),
+    genre("0409", "Children's songs", ""),
+    genre("0410", "World music", "world"),
+    genre("05", "Variety", "variety"),
+    genre("06", "Film", "film"),
+    genre("07", "Animation", "anime"),
+    genre("08", "Documentary and culture", "documentary"),
+    genre("0800", "Society and current affairs", ""),
+    genre("0801", "History and travel", "history"),
+    genre("0802", "Nature and environment", "nature"),
+    genre("0803", "Science and medicine", "science"),
+    genre("0804", "Culture and tradition", ""),
+    genre("0805", "Literature and arts", "literature"),
+    genre("0808", "Interviews", ""),
+    genre("09", "Theater and performance", "theater"),
+    genre("0903", "Rakugo and manzai", "rakugo"),
+    genre("0904", "Kabuki and classical theater", "kabuki"),
+    genre("10", "Hobbies and education", "education"),
+    genre("1003", "Go and shogi", "shogi"),
+    genre("1007", "Language courses", "language"),
+    genre("1008", "For children", "kids"),
+    genre("1011", "Lifelong learning", ""),
+    genre("11", "Welfare", "welfare"),
+    genre("15", "Other", ""),
+];
+
+fn lookup(id: &str) -> Option<&'static GenreName> {
+    GENRES.iter().find(|genre| genre.id == id)
+}
+
+/// e.g. "Music: Classical and opera", falling back to NHK's own names for
+/// IDs not in the table
+pub fn label(genre: &Genre) -> String {
+    let major = genre.id.get(..2).and_then(lookup);
+    match (major, lookup(&genre.id)) {
+        (Some(major), Some(minor)) if minor.id != major.id => {
+            format!("{}: {}", major.name, minor.name)
+        }
+        (Some(major), _) if genre.name2.is_empty() => major.name.to_string(),
+        (Some(major), _) => format!("{}: {}", major.name, genre.name2),
+        (None, _) if genre.name2.is_empty() => genre.name1.clone(),
+        (None, _) => format!("{} / {}", genre.name1, genre.name2),
+    }
+}
+
+/// [`label`] of each of `genres`
+pub fn labels(genres: &[Genre]) -> Vec<String> {
+    genres.iter().map(label).collect()
+}
+
+/// Whether `genre` is what `query` asks for: its ID or its major genre's, a
+/// genre word such as "classical" covering it, or text in its Japanese or
+/// English name
+pub fn matches(genre: &Genre, query: &str) -> bool {
+    let query = query.to_lowercase();
+    if !query.is_empty() && query.bytes().all(|b| b.is_ascii_digit()) {
+        return genre.id.starts_with(&query);
+    }
+    let by_key: Vec<_> = GENRES.iter().filter(|g| g.key == query).collect();
+    let ids = if by_key.is_empty() {
+        GENRES
+            .iter()
+            .filter(|g| g.name.to_lowercase().contains(&query))
+            .collect()
+    } else {
+        by_key
+    };
+    ids.iter().any(|g| genre.id.starts_with(g.id))
+        || genre.name1.to_lowercase().contains(&query)
+        || genre.name2.to_lowercase().contains(&query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn genre(id: &str, name1: &str, name2: &str) -> Genre {
+        Genre {
+            id: id.to_string(),
+            name1: name1.to_string(),
+            name2: name2.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_label() {
+        assert_eq!(
+            label(&genre("0402", "音楽", "クラシック・オペラ")),
+            "Music: Classical and opera"
+        );
+        assert_eq!(label(&genre("0415", "音楽", "その他")), "Music: その他");
+        assert_eq!(label(&genre("9999", "未分類", "")), "未分類");
+    }
+
+    #[test]
+    fn test_matches() {
+        let classical = genre("0402", "音楽", "クラシック・オペラ");
+        let news = genre("0000", "ニュース／報道", "定時・総合");
+        let sports_news = genre("0100", "スポーツ", "スポーツニュース");
+        let english = genre("1007", "趣味／教育", "会話・語学");
+
+        assert!(matches(&classical, "classical"));
+        assert!(matches(&classical, "Music"));
+        assert!(matches(&classical, "オペラ"));
+        assert!(matches(&classical, "0402"));
+        assert!(matches(&classical, "04"));
+        assert!(!matches(&classical, "0403"));
+        assert!(!matches(&classical, "jazz"));
+
+        assert!(matches(&news, "news"));
+        // A genre word picks its genres, not every name with the word in it
+        assert!(!matches(&sports_news, "news"));
+        assert!(matches(&english, "language"));
+        assert!(matches(&english, "education"));
+    }
+}
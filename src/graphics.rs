@@ -0,0 +1,213 @@
+//! Inline images for terminals with a graphics protocol.
+//!
+//! The TUI draws text through ratatui; pictures are written straight to the
+//! terminal afterwards, on cells the frame leaves blank. Which protocol to
+//! speak is judged from the environment, since querying the terminal would
+//! race with the event loop reading its input.
+
+use anyhow::Result;
+use base64::Engine;
+use image::{DynamicImage, ImageFormat, imageops::FilterType};
+use std::io::Cursor;
+
+/// A terminal graphics protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// kitty's graphics protocol, also spoken by Ghostty and WezTerm
+    Kitty,
+    /// iTerm2's inline images
+    Iterm2,
+    /// DEC sixel, e.g. foot, mlterm, and xterm built with it
+    Sixel,
+}
+
+/// Pixel size of a cell when the terminal does not report it
+const DEFAULT_CELL_SIZE: (u16, u16) = (10, 20);
+
+/// Payload bytes per kitty escape sequence, as the protocol requires
+const KITTY_CHUNK: usize = 4096;
+
+impl Protocol {
+    /// The protocol the terminal supports, or `None` when it has none or
+    /// cannot be told apart
+    pub fn detect() -> Option<Self> {
+        Self::from_env(|name| std::env::var(name).ok())
+    }
+
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        // Multiplexers do not pass images through unless told to
+        if var("TMUX").is_some() || var("STY").is_some() {
+            return None;
+        }
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        if var("KITTY_WINDOW_ID").is_some()
+            || term == "xterm-kitty"
+            || term == "xterm-ghostty"
+            || program == "ghostty"
+        {
+            Some(Protocol::Kitty)
+        } else if program == "iTerm.app"
+            || program == "WezTerm"
+            || var("LC_TERMINAL").as_deref() == Some("iTerm2")
+        {
+            Some(Protocol::Iterm2)
+        } else if term.starts_with("foot") || term.starts_with("mlterm") || term.contains("sixel") {
+            Some(Protocol::Sixel)
+        } else {
+            None
+        }
+    }
+
+    /// Escape sequence removing every picture this protocol has placed,
+    /// where the protocol keeps them apart from the text
+    pub fn clear(self) -> &'static str {
+        match self {
+            Protocol::Kitty => "\x1b_Ga=d,d=a,q=2\x1b\\",
+            // Pictures live in the cells and go when they are redrawn
+            Protocol::Iterm2 | Protocol::Sixel => "",
+        }
+    }
+}
+
+/// Pixel size of one terminal cell
+pub fn cell_size() -> (u16, u16) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => {
+            (size.width / size.columns, size.height / size.rows)
+        }
+        _ => DEFAULT_CELL_SIZE,
+    }
+}
+
+/// A decoded picture, scaled to fit wherever it is shown
+pub struct Picture {
+    image: DynamicImage,
+}
+
+impl Picture {
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        Ok(Picture {
+            image: image::load_from_memory(bytes)?,
+        })
+    }
+
+    /// Escape sequence drawing the picture at the cursor, as large as fits
+    /// in `columns` by `rows` cells of `cell` pixels without distorting it
+    pub fn encode(
+        &self,
+        protocol: Protocol,
+        columns: u16,
+        rows: u16,
+        cell: (u16, u16),
+    ) -> Result<String> {
+        let width = u32::from(columns) * u32::from(cell.0);
+        let height = u32::from(rows) * u32::from(cell.1);
+        if width == 0 || height == 0 {
+            return Ok(String::new());
+        }
+        let image = self.image.resize(width, height, FilterType::Triangle);
+
+        Ok(match protocol {
+            Protocol::Kitty => {
+                let png = base64::engine::general_purpose::STANDARD.encode(png(&image)?);
+                let chunks: Vec<&str> = png
+                    .as_bytes()
+                    .chunks(KITTY_CHUNK)
+                    .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+                    .collect();
+                let mut out = String::new();
+                for (i, chunk) in chunks.iter().enumerate() {
+                    let more = u8::from(i + 1 < chunks.len());
+                    if i == 0 {
+                        // Transmit and show in one go, leaving the cursor put
+                        out.push_str(&format!("\x1b_Gf=100,a=T,q=2,C=1,m={};", more));
+                    } else {
+                        out.push_str(&format!("\x1b_Gm={};", more));
+                    }
+                    out.push_str(chunk);
+                    out.push_str("\x1b\\");
+                }
+                out
+            }
+            Protocol::Iterm2 => {
+                let png = png(&image)?;
+                format!(
+                    "\x1b]1337;File=inline=1;size={};width={}px;height={}px;preserveAspectRatio=1:{}\x07",
+                    png.len(),
+                    image.width(),
+                    image.height(),
+                    base64::engine::general_purpose::STANDARD.encode(&png)
+                )
+            }
+            Protocol::Sixel => {
+                let rgb = image.to_rgb8();
+                icy_sixel::sixel_string(
+                    rgb.as_raw(),
+                    rgb.width() as i32,
+                    rgb.height() as i32,
+                    icy_sixel::PixelFormat::RGB888,
+                    icy_sixel::DiffusionMethod::Stucki,
+                    icy_sixel::MethodForLargest::Auto,
+                    icy_sixel::MethodForRep::Auto,
+                    icy_sixel::Quality::HIGH,
+                )
+                .map_err(|e| anyhow::anyhow!("Failed to encode sixel: {}", e))?
+            }
+        })
+    }
+}
+
+fn png(image: &DynamicImage) -> Result<Vec<u8>> {
+    let mut out = Cursor::new(Vec::new());
+    image.write_to(&mut out, ImageFormat::Png)?;
+    Ok(out.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)]) -> Option<Protocol> {
+        Protocol::from_env(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(&[("TERM", "xterm-kitty")]), Some(Protocol::Kitty));
+        assert_eq!(
+            detect(&[("TERM", "xterm-256color"), ("TERM_PROGRAM", "iTerm.app")]),
+            Some(Protocol::Iterm2)
+        );
+        assert_eq!(detect(&[("TERM", "foot")]), Some(Protocol::Sixel));
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), None);
+        assert_eq!(
+            detect(&[("TERM", "xterm-kitty"), ("TMUX", "/tmp/tmux-1000/default")]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let picture = Picture {
+            image: DynamicImage::new_rgb8(64, 32),
+        };
+        let kitty = picture.encode(Protocol::Kitty, 4, 4, (10, 20)).unwrap();
+        assert!(kitty.starts_with("\x1b_Gf=100,a=T,q=2,C=1,m=0;"));
+        // 64x32 scaled into 40x80 keeps its 2:1 shape
+        let iterm = picture.encode(Protocol::Iterm2, 4, 4, (10, 20)).unwrap();
+        assert!(iterm.contains(";width=40px;height=20px;"));
+        let sixel = picture.encode(Protocol::Sixel, 4, 4, (10, 20)).unwrap();
+        assert!(sixel.starts_with("\x1bP"));
+        assert!(
+            picture
+                .encode(Protocol::Kitty, 0, 4, (10, 20))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
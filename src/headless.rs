@@ -0,0 +1,277 @@
+//! Foreground playback without the TUI, for pipes, logs, and dumb terminals.
+
+use anyhow::Result;
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::client::NhkRadioClient;
+use crate::cue::CueSheet;
+use crate::device::{default_device, output_devices};
+use crate::discord::{DiscordConfig, DiscordPresence};
+use crate::engine::PlaybackEngine;
+use crate::error::ErrorKind;
+use crate::hooks::{HookEvent, Hooks};
+use crate::i18n::{area_label, tr};
+use crate::now_playing::{NowPlayingFile, NowPlayingRecord};
+use crate::ondemand::format_position;
+use crate::player::{ChannelKind, PlaybackEvent, PlayerOptions};
+use crate::radio::{NowPlaying, now_playing_stream};
+use crate::split::ProgramSplit;
+use crate::state::SessionState;
+use crate::title::TerminalTitle;
+
+/// Settings of headless playback, beside those of the player
+pub struct HeadlessOptions {
+    /// File kept up to date with what is playing
+    pub now_playing: Option<NowPlayingFile>,
+    pub hooks: Hooks,
+    pub discord: DiscordConfig,
+    /// Stop after this long
+    pub duration: Option<Duration>,
+    /// Print nothing but log records, e.g. under systemd
+    pub quiet: bool,
+    /// Start a new recording file whenever the program changes
+    pub split: bool,
+    /// Write a cue sheet of the programs next to the recording
+    pub cue: bool,
+    /// Show what is playing in the terminal's title
+    pub terminal_title: bool,
+}
+
+/// Play `initial_kind` until Ctrl-C or for `duration`, printing a line
+/// whenever the program changes, keeping `now_playing` and Discord presence
+/// up to date, and running `hooks`
+pub async fn run_headless_player(
+    area: String,
+    initial_kind: ChannelKind,
+    options: PlayerOptions,
+    headless: HeadlessOptions,
+) -> Result<()> {
+    let HeadlessOptions {
+        mut now_playing,
+        hooks,
+        discord,
+        duration,
+        quiet,
+        split,
+        cue,
+        terminal_title,
+    } = headless;
+    // With nothing to play on, fail now with an exit code of its own rather
+    // than keep retrying the output in the background
+    if options.speaker
+        && default_device().is_none()
+        && output_devices().unwrap_or_default().is_empty()
+    {
+        return Err(ErrorKind::AudioDevice.error("No audio output device found"));
+    }
+    let client = Arc::new(NhkRadioClient::with_options(options.http.clone())?);
+    let (stream_data, program_url) = client.fetch_config().await?.find_area(&area)?;
+
+    // Keep stdout clean when the audio itself goes there
+    let to_stderr = options.pipes.iter().any(|pipe| pipe.is_stdout());
+    // Quiet, the lines become log records at info level
+    let say = move |line: String| {
+        if quiet {
+            log::info!("{}", line);
+        } else if to_stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    };
+
+    let area_name = area_label(&stream_data);
+    say(format!(
+        "▶ {} {}",
+        area_name,
+        tr().channel_name(initial_kind)
+    ));
+    let engine = PlaybackEngine::start(client.clone(), stream_data, initial_kind, options);
+    let mut programs = std::pin::pin!(now_playing_stream(client, program_url, initial_kind));
+    let mut tick = tokio::time::interval(Duration::from_secs(1));
+    let mut heard: Option<NowPlayingRecord> = None;
+    let mut program_split = ProgramSplit::default();
+    let mut cue_sheet: Option<CueSheet> = None;
+    let presence = DiscordPresence::start(&discord);
+    let mut title = if terminal_title && !quiet {
+        TerminalTitle::start()
+    } else {
+        None
+    };
+    let deadline = async {
+        match duration {
+            Some(duration) => tokio::time::sleep(duration).await,
+            None => std::future::pending().await,
+        }
+    };
+    let mut deadline = std::pin::pin!(deadline);
+    let mut shutdown = std::pin::pin!(shutdown_signal());
+
+    loop {
+        tokio::select! {
+            Some(program) = programs.next() => {
+                say(program_line(&program));
+                let recording = engine.recording();
+                if split
+                    && let Some(recorder) =
+                        program_split.next_file(recording.as_ref(), &area, Some(&program))
+                {
+                    if let Some(recording) = recording {
+                        say((tr().recording_saved)(
+                            &recording.path.display().to_string(),
+                            &format_megabytes(recording.bytes_written),
+                        ));
+                    }
+                    engine.start_recording(recorder);
+                }
+                if cue
+                    && let Some(sheet) = CueSheet::follow(
+                        &mut cue_sheet,
+                        engine.recording().as_ref(),
+                        engine.state().live_edge,
+                    )
+                    && let Err(e) = sheet.mark_program(
+                        &program.title,
+                        tr().channel_name(program.channel),
+                        program.start.zip(program.end),
+                        &[],
+                    )
+                {
+                    log::warn!("{}", e);
+                }
+                let record = NowPlayingRecord::new(
+                    program.channel,
+                    &area_name,
+                    &program.title,
+                    program.start,
+                    program.end,
+                );
+                if let Some(event) = HookEvent::between(heard.as_ref(), &record) {
+                    hooks.run(&event);
+                }
+                if let Some(file) = now_playing.as_mut()
+                    && let Err(e) = file.update(record.clone())
+                {
+                    log::warn!("{}", e);
+                }
+                if let Some(presence) = presence.as_ref() {
+                    presence.update(Some(&record), None);
+                }
+                if let Some(title) = title.as_mut() {
+                    title.update(Some(&record), false);
+                }
+                heard = Some(record);
+            }
+            _ = tick.tick() => {
+                while let Some(event) = engine.try_event() {
+                    match event {
+                        PlaybackEvent::Error(e) => {
+                            if quiet {
+                                log::error!("{}: {}", tr().playback_error, e);
+                            } else {
+                                eprintln!("{}: {}", tr().playback_error, e);
+                            }
+                            hooks.run(&HookEvent::Error { message: e });
+                        }
+                        PlaybackEvent::OutputLost if quiet => log::warn!("{}", tr().output_lost),
+                        PlaybackEvent::OutputLost => eprintln!("{}", tr().output_lost),
+                        PlaybackEvent::OutputRestored if quiet => {
+                            log::info!("{}", tr().playback_resumed)
+                        }
+                        PlaybackEvent::OutputRestored => eprintln!("{}", tr().playback_resumed),
+                        PlaybackEvent::Silence => {
+                            if quiet {
+                                log::warn!("{}", tr().silence);
+                            } else {
+                                eprintln!("{}", tr().silence);
+                            }
+                            hooks.run(&HookEvent::Error {
+                                message: tr().silence.to_string(),
+                            });
+                        }
+                        PlaybackEvent::SoundReturned if quiet => log::info!("{}", tr().sound_returned),
+                        PlaybackEvent::SoundReturned => eprintln!("{}", tr().sound_returned),
+                        _ => {}
+                    }
+                }
+                // Nothing more will play once the stream loop has given up
+                if !engine.is_streaming() {
+                    break;
+                }
+            }
+            _ = &mut deadline => break,
+            _ = &mut shutdown => break,
+        }
+    }
+
+    let stream_error = engine.take_stream_error();
+    let listened = engine.uptime();
+    let downloaded = engine.stats().bytes_downloaded;
+    let recording = engine.recording();
+    // Finish the file now rather than whenever the stream task winds down,
+    // then stop fetching
+    engine.stop_recording();
+    drop(engine);
+    if let Some(file) = now_playing {
+        file.remove();
+    }
+    let mut session = SessionState::load();
+    session.set_station(&area, initial_kind);
+    if let Err(e) = session.save() {
+        log::warn!("Failed to save session state: {}", e);
+    }
+    say((tr().listened)(
+        &format_position(listened.as_secs_f64()),
+        &format_megabytes(downloaded),
+    ));
+    if let Some(recording) = recording {
+        say((tr().recording_saved)(
+            &recording.path.display().to_string(),
+            &format_megabytes(recording.bytes_written),
+        ));
+    }
+    // Stopping because the stream gave up is a failure, reported with the
+    // exit code of what went wrong
+    match stream_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Wait for Ctrl-C, or on Unix also for SIGTERM from `kill` or systemd, so
+/// playback stops cleanly instead of the process dying mid-write
+pub(crate) async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => log::warn!("Cannot listen for SIGTERM: {}", e),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+pub(crate) fn format_megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+}
+
+pub(crate) fn program_line(program: &NowPlaying) -> String {
+    let time = |t: Option<chrono::DateTime<chrono::FixedOffset>>| {
+        t.map(|t| t.format("%H:%M").to_string()).unwrap_or_default()
+    };
+    format!(
+        "♪ {}-{} {}",
+        time(program.start),
+        time(program.end),
+        program.title
+    )
+}
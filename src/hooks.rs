@@ -0,0 +1,193 @@
+//! User commands run on playback events, for notifications and home
+//! automation.
+//!
+//! Each hook is a shell command from the config file's `[hooks]` table. It
+//! runs in the background with the event in `NHK_*` environment variables and
+//! as JSON on stdin; the player neither waits for it nor shows its output.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::now_playing::NowPlayingRecord;
+
+/// Commands from the `[hooks]` table
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Hooks {
+    /// Run when a new program starts on the station being heard
+    pub on_program_change: Option<String>,
+    /// Run when another channel or area is tuned in
+    pub on_channel_switch: Option<String>,
+    /// Run when playback fails
+    pub on_error: Option<String>,
+}
+
+/// Something a hook can be run for
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HookEvent {
+    ProgramChange(NowPlayingRecord),
+    ChannelSwitch(NowPlayingRecord),
+    Error { message: String },
+}
+
+impl HookEvent {
+    /// What hearing `current` after `previous` amounts to, if anything
+    pub fn between(
+        previous: Option<&NowPlayingRecord>,
+        current: &NowPlayingRecord,
+    ) -> Option<Self> {
+        match previous {
+            Some(previous) if previous == current => None,
+            Some(previous)
+                if previous.channel != current.channel || previous.area != current.area =>
+            {
+                Some(HookEvent::ChannelSwitch(current.clone()))
+            }
+            _ => Some(HookEvent::ProgramChange(current.clone())),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            HookEvent::ProgramChange(_) => "program_change",
+            HookEvent::ChannelSwitch(_) => "channel_switch",
+            HookEvent::Error { .. } => "error",
+        }
+    }
+
+    /// The event as `NHK_*` environment variables; unknown times are empty
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![("NHK_EVENT", self.name().to_string())];
+        match self {
+            HookEvent::ProgramChange(record) | HookEvent::ChannelSwitch(record) => {
+                env.extend([
+                    ("NHK_STATION", record.station.clone()),
+                    ("NHK_CHANNEL", record.channel.clone()),
+                    ("NHK_AREA", record.area.clone()),
+                    ("NHK_TITLE", record.title.clone()),
+                    ("NHK_START", record.start.clone().unwrap_or_default()),
+                    ("NHK_END", record.end.clone().unwrap_or_default()),
+                ]);
+            }
+            HookEvent::Error { message } => env.push(("NHK_ERROR", message.clone())),
+        }
+        env
+    }
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        *self == Hooks::default()
+    }
+
+    /// Start the hook for `event` in the background, if one is configured
+    pub fn run(&self, event: &HookEvent) {
+        let command = match event {
+            HookEvent::ProgramChange(_) => &self.on_program_change,
+            HookEvent::ChannelSwitch(_) => &self.on_channel_switch,
+            HookEvent::Error { .. } => &self.on_error,
+        };
+        if let Some(command) = command
+            && let Err(e) = spawn(command, event)
+        {
+            log::warn!("Failed to run the on_{} hook: {}", event.name(), e);
+        }
+    }
+}
+
+fn spawn(command: &str, event: &HookEvent) -> Result<()> {
+    let json = serde_json::to_string(event)?;
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    // Output would draw over the TUI
+    let mut child = shell
+        .arg(command)
+        .envs(event.env())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let name = event.name();
+    std::thread::spawn(move || {
+        // A hook that ignores stdin may exit before reading it
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(json.as_bytes());
+        }
+        match child.wait() {
+            Ok(status) if !status.success() => {
+                log::warn!("The on_{} hook exited with {}", name, status);
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("The on_{} hook failed: {}", name, e),
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::ChannelKind;
+
+    fn record(channel: ChannelKind, title: &str) -> NowPlayingRecord {
+        NowPlayingRecord::new(channel, "東京", title, None, None)
+    }
+
+    #[test]
+    fn test_between() {
+        let news = record(ChannelKind::R1, "ニュース");
+        let weather = record(ChannelKind::R1, "気象情報");
+        let fm = record(ChannelKind::Fm, "クラシックの庭");
+
+        assert_eq!(
+            HookEvent::between(None, &news),
+            Some(HookEvent::ProgramChange(news.clone()))
+        );
+        assert_eq!(HookEvent::between(Some(&news), &news), None);
+        assert_eq!(
+            HookEvent::between(Some(&news), &weather),
+            Some(HookEvent::ProgramChange(weather.clone()))
+        );
+        assert_eq!(
+            HookEvent::between(Some(&weather), &fm),
+            Some(HookEvent::ChannelSwitch(fm.clone()))
+        );
+    }
+
+    #[test]
+    fn test_event_details() {
+        let event = HookEvent::ChannelSwitch(record(ChannelKind::Fm, "クラシックの庭"));
+        let json: serde_json::Value = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "channel_switch");
+        assert_eq!(json["title"], "クラシックの庭");
+        let env = event.env();
+        assert!(env.contains(&("NHK_CHANNEL", "fm".to_string())));
+        assert!(env.contains(&("NHK_START", String::new())));
+
+        let error = HookEvent::Error {
+            message: "playlist failed".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_string(&error).unwrap(),
+            r#"{"event":"error","message":"playlist failed"}"#
+        );
+        assert_eq!(
+            error.env(),
+            [
+                ("NHK_EVENT", "error".to_string()),
+                ("NHK_ERROR", "playlist failed".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Optional HTTP API for the daemon.
+//!
+//! Every request is turned into a [`ControlCommand`] and applied through the
+//! same [`Controller::dispatch`] as the control socket. `/ws` is a WebSocket
+//! that pushes [`DaemonEvent`]s instead, starting with the status.
+
+use anyhow::Result;
+use axum::{
+    Json, Router,
+    extract::{
+        State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::control::{ControlCommand, ControlResponse, Controller};
+use crate::events::{DaemonEvent, watch_events};
+
+#[derive(Deserialize)]
+struct ChannelRequest {
+    channel: String,
+}
+
+#[derive(Deserialize)]
+struct VolumeRequest {
+    volume: u8,
+}
+
+/// Serve the API on `addr`, watching for the events `/ws` pushes, until the
+/// task is dropped
+pub async fn serve_http(addr: SocketAddr, controller: Arc<Controller>) -> Result<()> {
+    let app = Router::new()
+        .route("/status", get(status))
+        .route("/nowplaying", get(now_playing))
+        .route("/stats", get(stats))
+        .route("/channel", get(status).put(set_channel).post(set_channel))
+        .route("/volume", get(status).put(set_volume).post(set_volume))
+        .route("/ws", get(events))
+        .with_state(controller.clone());
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    log::info!("HTTP API listening on http://{}", listener.local_addr()?);
+    tokio::select! {
+        result = axum::serve(listener, app) => result?,
+        _ = watch_events(controller) => {}
+    }
+    Ok(())
+}
+
+async fn status(State(controller): State<Arc<Controller>>) -> Response {
+    reply(controller.dispatch(ControlCommand::Status).await)
+}
+
+async fn now_playing(State(controller): State<Arc<Controller>>) -> Response {
+    Json(controller.status().await.program).into_response()
+}
+
+async fn stats(State(controller): State<Arc<Controller>>) -> Response {
+    Json(controller.stats()).into_response()
+}
+
+async fn set_channel(
+    State(controller): State<Arc<Controller>>,
+    Json(request): Json<ChannelRequest>,
+) -> Response {
+    let command = ControlCommand::Channel {
+        channel: request.channel,
+    };
+    reply(controller.dispatch(command).await)
+}
+
+async fn set_volume(
+    State(controller): State<Arc<Controller>>,
+    Json(request): Json<VolumeRequest>,
+) -> Response {
+    let command = ControlCommand::Volume {
+        volume: request.volume,
+    };
+    reply(controller.dispatch(command).await)
+}
+
+async fn events(ws: WebSocketUpgrade, State(controller): State<Arc<Controller>>) -> Response {
+    ws.on_upgrade(move |socket| push_events(socket, controller))
+}
+
+/// Send the status, then every event as it happens, until the client hangs up
+async fn push_events(mut socket: WebSocket, controller: Arc<Controller>) {
+    // Subscribe first so nothing between the status and the events is lost
+    let mut events = controller.events.subscribe();
+    let mut next = Some(DaemonEvent::Status(controller.status().await));
+    loop {
+        if let Some(event) = next.take()
+            && let Err(e) = send_event(&mut socket, &event).await
+        {
+            log::debug!("WebSocket client gone: {}", e);
+            return;
+        }
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => next = Some(event),
+                // Too slow to keep up: start it over from the current status
+                Err(RecvError::Lagged(_)) => {
+                    next = Some(DaemonEvent::Status(controller.status().await));
+                }
+                Err(RecvError::Closed) => return,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                // Pings are answered for us, and there is nothing to say
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn send_event(socket: &mut WebSocket, event: &DaemonEvent) -> Result<()> {
+    let text = serde_json::to_string(event)?;
+    socket.send(Message::Text(text.into())).await?;
+    Ok(())
+}
+
+fn reply(response: ControlResponse) -> Response {
+    let code = if response.ok {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_REQUEST
+    };
+    (code, Json(response)).into_response()
+}
@@ -0,0 +1,528 @@
+//! Interface language for the TUI and status output.
+//!
+//! Every user-facing string has one entry in a [`Strings`] table per
+//! language. The language is set once at startup from `--lang` or `lang` in
+//! the config file, and [`tr`] returns the table to use.
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Timelike};
+use std::sync::OnceLock;
+
+use crate::equalizer::EqPreset;
+use crate::keymap::Action;
+use crate::player::ChannelKind;
+use crate::types::StreamData;
+use crate::world;
+
+/// A supported interface language
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Lang {
+    /// 日本語
+    #[default]
+    Ja,
+    /// English
+    En,
+}
+
+impl Lang {
+    /// Parse the config file's `lang` value
+    pub fn from_code(code: &str) -> Result<Self> {
+        <Lang as clap::ValueEnum>::from_str(code, true)
+            .map_err(|_| anyhow::anyhow!("Unknown language: {} (ja or en)", code))
+    }
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Choose the interface language; only the first call has an effect
+pub fn set_lang(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+/// Strings in the chosen language, Japanese until [`set_lang`] is called
+pub fn tr() -> &'static Strings {
+    match LANG.get().copied().unwrap_or_default() {
+        Lang::Ja => &JA,
+        Lang::En => &EN,
+    }
+}
+
+/// Name of an area as shown to the listener
+pub fn area_label(stream_data: &StreamData) -> String {
+    match LANG.get().copied().unwrap_or_default() {
+        Lang::Ja => stream_data.areajp.clone(),
+        Lang::En => {
+            if let Some(language) = world::language_name(&stream_data.area) {
+                return format!("NHK WORLD {}", language);
+            }
+            let mut chars = stream_data.area.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// What to press for one row of the `?` overlay
+#[derive(Debug, PartialEq, Eq)]
+pub enum HelpKeys {
+    /// Whatever keys these actions are bound to
+    Actions(&'static [Action]),
+    /// A mouse gesture, named in words
+    Mouse(&'static str),
+}
+
+pub struct Strings {
+    /// R1, R2, and FM
+    channel_names: [&'static str; 3],
+    pub loading: &'static str,
+    pub program_loading: &'static str,
+    pub schedule_loading: &'static str,
+    pub switching: &'static str,
+    pub output_lost: &'static str,
+    pub playback_resumed: &'static str,
+    /// The broadcast went quiet while the stream is still arriving
+    pub silence: &'static str,
+    pub sound_returned: &'static str,
+    pub playback_error: &'static str,
+    pub paused: &'static str,
+    pub playing: &'static str,
+    pub muted: &'static str,
+    pub next_label: &'static str,
+    pub previous_label: &'static str,
+    pub minutes_left: fn(i64) -> String,
+    pub behind_live: fn(f64) -> String,
+    pub schedule_title: fn(&str) -> String,
+    /// Date and time a program starts
+    pub air_time: fn(DateTime<FixedOffset>) -> String,
+    pub keys_title: &'static str,
+    pub keys_dismiss: &'static str,
+    /// Every key the live player handles, as shown by the `?` overlay
+    pub key_bindings: &'static [(HelpKeys, &'static str)],
+    pub help_channel: &'static str,
+    pub help_switch: &'static str,
+    pub help_pause: &'static str,
+    pub help_volume: &'static str,
+    pub help_help: &'static str,
+    pub help_quit: &'static str,
+    pub credits_title: &'static str,
+    pub cast_heading: &'static str,
+    pub music_heading: &'static str,
+    pub composer: &'static str,
+    pub no_credits: &'static str,
+    pub stats_title: &'static str,
+    pub no_stats: &'static str,
+    pub stats_bitrate: &'static str,
+    pub stats_buffered: &'static str,
+    pub stats_fetched: &'static str,
+    pub stats_failed: &'static str,
+    pub stats_missed: &'static str,
+    pub stats_decode_errors: &'static str,
+    pub stats_dropped: &'static str,
+    pub stats_refreshed: &'static str,
+    pub stats_downloaded: &'static str,
+    pub stats_underruns: &'static str,
+    pub stats_reconnects: &'static str,
+    pub stats_uptime: &'static str,
+    pub secs_ago: fn(u64) -> String,
+    pub log_title: &'static str,
+    pub copied: &'static str,
+    /// Notices for switching to each equalizer preset, in the order of
+    /// [`EqPreset::ALL`]
+    pub eq_presets: [&'static str; 4],
+    pub favorite_added: &'static str,
+    pub favorite_removed: &'static str,
+    /// A favorite starting at a time on a channel
+    pub reminder: fn(&str, &str, &str) -> String,
+    pub no_log: &'static str,
+    pub area_picker_title: &'static str,
+    pub no_matching_area: &'static str,
+    pub ondemand_title: &'static str,
+    pub ondemand_help_corners: &'static str,
+    pub ondemand_help_episodes: &'static str,
+    pub ondemand_help_playback: &'static str,
+    pub cannot_play: &'static str,
+    pub cannot_fetch: &'static str,
+    pub available_until: &'static str,
+    pub serving: &'static str,
+    pub http_retries: fn(u64) -> String,
+    pub casting_to: fn(&str) -> String,
+    pub cast_controls: &'static str,
+    pub cast_ended: fn(&str) -> String,
+    /// Time listened and data downloaded, on stopping
+    pub listened: fn(&str, &str) -> String,
+    /// Recording file and its size, once it is finished
+    pub recording_saved: fn(&str, &str) -> String,
+    /// Podcast feed written, with the number of episodes new in it
+    pub podcast_updated: fn(&str, usize) -> String,
+}
+
+impl Strings {
+    pub fn channel_name(&self, kind: ChannelKind) -> &'static str {
+        match kind {
+            ChannelKind::R1 => self.channel_names[0],
+            ChannelKind::R2 => self.channel_names[1],
+            ChannelKind::Fm => self.channel_names[2],
+        }
+    }
+
+    pub fn eq_preset(&self, preset: EqPreset) -> &'static str {
+        let index = EqPreset::ALL.iter().position(|&p| p == preset);
+        self.eq_presets[index.unwrap_or(0)]
+    }
+}
+
+const JA: Strings = Strings {
+    channel_names: ["ラジオ第1", "ラジオ第2", "FM"],
+    loading: "読み込み中...",
+    program_loading: "番組情報を取得中...",
+    schedule_loading: "番組表を取得中...",
+    switching: "切替中...",
+    output_lost: "出力デバイスを再接続中...",
+    playback_resumed: "再生を再開しました",
+    silence: "放送が無音です (受信は継続中)",
+    sound_returned: "放送の音声が戻りました",
+    playback_error: "再生エラー",
+    paused: "⏸ 一時停止中",
+    playing: "▶ 再生中",
+    muted: "🔇 ミュート",
+    next_label: "次",
+    previous_label: "前",
+    minutes_left: |minutes| format!("あと{}分", minutes),
+    behind_live: |secs| format!("⏪ ライブ -{:.0}秒", secs),
+    schedule_title: |channel| format!(" 📅 NHK {} 番組表 ", channel),
+    air_time: |time| {
+        let (pm, hour) = time.hour12();
+        format!(
+            "{} {}{:02}:{:02}",
+            time.format("%Y年%-m月%-d日"),
+            if pm { "午後" } else { "午前" },
+            hour,
+            time.minute()
+        )
+    },
+    keys_title: " キー操作 ",
+    keys_dismiss: " 何かキーを押すと閉じます ",
+    key_bindings: &[
+        (
+            HelpKeys::Actions(&[Action::R1, Action::R2, Action::Fm]),
+            "R1 / R2 / FM に切替",
+        ),
+        (
+            HelpKeys::Actions(&[Action::PrevChannel, Action::NextChannel]),
+            "前後のチャンネルに切替",
+        ),
+        (
+            HelpKeys::Actions(&[Action::LastChannel]),
+            "直前のチャンネルに戻る",
+        ),
+        (HelpKeys::Actions(&[Action::Pause]), "一時停止 / 再開"),
+        (
+            HelpKeys::Actions(&[Action::VolumeUp, Action::VolumeDown]),
+            "音量を上げる / 下げる",
+        ),
+        (HelpKeys::Actions(&[Action::Mute]), "ミュート"),
+        (HelpKeys::Actions(&[Action::Rewind]), "30秒戻る"),
+        (HelpKeys::Actions(&[Action::Live]), "ライブに戻る"),
+        (HelpKeys::Actions(&[Action::Device]), "出力デバイスを切替"),
+        (
+            HelpKeys::Actions(&[Action::Equalizer]),
+            "イコライザを切替 (フラット / 音声 / 音楽 / 低音カット)",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Schedule]),
+            "番組表の表示 / 非表示",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Credits]),
+            "出演・曲目の表示 / 非表示",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Stats]),
+            "ストリーム情報の表示 / 非表示",
+        ),
+        (HelpKeys::Actions(&[Action::Log]), "ログの表示 / 非表示"),
+        (
+            HelpKeys::Actions(&[Action::OpenPage]),
+            "番組のページをブラウザで開く",
+        ),
+        (
+            HelpKeys::Actions(&[Action::SearchHashtags]),
+            "ハッシュタグを検索",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Copy]),
+            "番組情報をクリップボードにコピー",
+        ),
+        (HelpKeys::Actions(&[Action::Record]), "録音の開始 / 停止"),
+        (
+            HelpKeys::Actions(&[Action::Sleep]),
+            "スリープタイマー (15/30/60/90分 / オフ)",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Favorite]),
+            "お気に入りに追加 / 削除",
+        ),
+        (
+            HelpKeys::Actions(&[Action::TuneReminder]),
+            "お知らせ中のお気に入り番組に切替",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Up, Action::Down]),
+            "スクロール / 番組表の選択",
+        ),
+        (HelpKeys::Actions(&[Action::Area]), "地域を切替"),
+        (HelpKeys::Actions(&[Action::Help]), "このヘルプ"),
+        (HelpKeys::Actions(&[Action::Quit]), "終了"),
+        (
+            HelpKeys::Mouse("クリック"),
+            "チャンネル / 番組表の番組を選択",
+        ),
+        (HelpKeys::Mouse("ホイール"), "音量"),
+    ],
+    help_channel: "チャンネル",
+    help_switch: "切替",
+    help_pause: "一時停止",
+    help_volume: "音量",
+    help_help: "ヘルプ",
+    help_quit: "終了",
+    credits_title: " 🎼 出演・曲目 ",
+    cast_heading: "出演",
+    music_heading: "曲目",
+    composer: "作曲",
+    no_credits: "出演者・曲目の情報はありません",
+    stats_title: " 📶 ストリーム情報 ",
+    no_stats: "この出力ではストリーム情報はありません",
+    stats_bitrate: "ビットレート",
+    stats_buffered: "バッファ",
+    stats_fetched: "取得セグメント",
+    stats_failed: "取得失敗",
+    stats_missed: "取りこぼし",
+    stats_decode_errors: "デコードエラー",
+    stats_dropped: "破棄した音声",
+    stats_refreshed: "プレイリスト更新",
+    stats_downloaded: "ダウンロード量",
+    stats_underruns: "音切れ",
+    stats_reconnects: "再接続",
+    stats_uptime: "稼働時間",
+    secs_ago: |secs| format!("{}秒前", secs),
+    log_title: " 📝 ログ ",
+    copied: "📋 コピーしました",
+    eq_presets: [
+        "🎚 イコライザ: フラット",
+        "🎚 イコライザ: 音声",
+        "🎚 イコライザ: 音楽",
+        "🎚 イコライザ: 低音カット",
+    ],
+    favorite_added: "★ お気に入りに追加しました",
+    favorite_removed: "☆ お気に入りから外しました",
+    reminder: |time, channel, title| format!("⏰ {} から {}「{}」", time, channel, title),
+    no_log: "警告やエラーはありません",
+    area_picker_title: " 地域を選択 ",
+    no_matching_area: "該当する地域がありません",
+    ondemand_title: " 📻 聴き逃し 新着 ",
+    ondemand_help_corners: "[↑/↓] 選択  [Enter] 開く  [q] 終了",
+    ondemand_help_episodes: "[↑/↓] 選択  [Enter] 再生  [Esc] 戻る  [q] 終了",
+    ondemand_help_playback: "[Space] 一時停止  [←/→] 30秒移動  [+/-] 音量  [Esc] 戻る  [q] 終了",
+    cannot_play: "再生できません",
+    cannot_fetch: "取得できません",
+    available_until: "配信終了",
+    serving: "配信中",
+    http_retries: |count| format!("HTTP再試行: {}回", count),
+    casting_to: |device| format!("📺 {} にキャスト中", device),
+    cast_controls: "操作: r1 / r2 / fm でチャンネル切替、+ / - で音量、q で終了",
+    cast_ended: |device| format!("{} での再生が終了しました", device),
+    listened: |time, size| format!("■ 再生時間 {} (受信 {})", time, size),
+    recording_saved: |path, size| format!("録音を保存しました: {} ({})", path, size),
+    podcast_updated: |feed, added| format!("フィードを更新しました: {} (新着 {}件)", feed, added),
+};
+
+const EN: Strings = Strings {
+    channel_names: ["Radio 1", "Radio 2", "FM"],
+    loading: "Loading...",
+    program_loading: "Loading program info...",
+    schedule_loading: "Loading schedule...",
+    switching: "Switching...",
+    output_lost: "Reconnecting output device...",
+    playback_resumed: "Playback resumed",
+    silence: "Broadcast is silent (stream still arriving)",
+    sound_returned: "Broadcast sound is back",
+    playback_error: "Playback error",
+    paused: "⏸ Paused",
+    playing: "▶ Playing",
+    muted: "🔇 Muted",
+    next_label: "Next",
+    previous_label: "Prev",
+    minutes_left: |minutes| format!("{} min left", minutes),
+    behind_live: |secs| format!("⏪ Live -{:.0}s", secs),
+    schedule_title: |channel| format!(" 📅 NHK {} Schedule ", channel),
+    air_time: |time| time.format("%b %-d, %Y %-I:%M %p").to_string(),
+    keys_title: " Keys ",
+    keys_dismiss: " Press any key to close ",
+    key_bindings: &[
+        (
+            HelpKeys::Actions(&[Action::R1, Action::R2, Action::Fm]),
+            "Switch to R1 / R2 / FM",
+        ),
+        (
+            HelpKeys::Actions(&[Action::PrevChannel, Action::NextChannel]),
+            "Previous / next channel",
+        ),
+        (
+            HelpKeys::Actions(&[Action::LastChannel]),
+            "Back to the last channel",
+        ),
+        (HelpKeys::Actions(&[Action::Pause]), "Pause / resume"),
+        (
+            HelpKeys::Actions(&[Action::VolumeUp, Action::VolumeDown]),
+            "Volume up / down",
+        ),
+        (HelpKeys::Actions(&[Action::Mute]), "Mute"),
+        (HelpKeys::Actions(&[Action::Rewind]), "Back 30 seconds"),
+        (HelpKeys::Actions(&[Action::Live]), "Back to live"),
+        (HelpKeys::Actions(&[Action::Device]), "Switch output device"),
+        (
+            HelpKeys::Actions(&[Action::Equalizer]),
+            "Equalizer (flat / speech / music / bass cut)",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Schedule]),
+            "Show / hide the schedule",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Credits]),
+            "Show / hide cast and music",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Stats]),
+            "Show / hide stream stats",
+        ),
+        (HelpKeys::Actions(&[Action::Log]), "Show / hide the log"),
+        (
+            HelpKeys::Actions(&[Action::OpenPage]),
+            "Open the program page in a browser",
+        ),
+        (
+            HelpKeys::Actions(&[Action::SearchHashtags]),
+            "Search the program's hashtags",
+        ),
+        (HelpKeys::Actions(&[Action::Copy]), "Copy what is playing"),
+        (
+            HelpKeys::Actions(&[Action::Record]),
+            "Start / stop recording",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Sleep]),
+            "Sleep timer (15/30/60/90 min / off)",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Favorite]),
+            "Add to / remove from favorites",
+        ),
+        (
+            HelpKeys::Actions(&[Action::TuneReminder]),
+            "Tune to the favorite being announced",
+        ),
+        (
+            HelpKeys::Actions(&[Action::Up, Action::Down]),
+            "Scroll / select in the schedule",
+        ),
+        (HelpKeys::Actions(&[Action::Area]), "Switch area"),
+        (HelpKeys::Actions(&[Action::Help]), "This help"),
+        (HelpKeys::Actions(&[Action::Quit]), "Quit"),
+        (
+            HelpKeys::Mouse("Click"),
+            "Select a channel / scheduled program",
+        ),
+        (HelpKeys::Mouse("Wheel"), "Volume"),
+    ],
+    help_channel: "Channel",
+    help_switch: "Switch",
+    help_pause: "Pause",
+    help_volume: "Volume",
+    help_help: "Help",
+    help_quit: "Quit",
+    credits_title: " 🎼 Cast & Music ",
+    cast_heading: "Cast",
+    music_heading: "Music",
+    composer: "Composer",
+    no_credits: "No cast or music listed",
+    stats_title: " 📶 Stream Stats ",
+    no_stats: "No stream stats for this output",
+    stats_bitrate: "Bitrate",
+    stats_buffered: "Buffered",
+    stats_fetched: "Segments fetched",
+    stats_failed: "Fetches failed",
+    stats_missed: "Segments missed",
+    stats_decode_errors: "Decode errors",
+    stats_dropped: "Audio dropped",
+    stats_refreshed: "Playlist loaded",
+    stats_downloaded: "Downloaded",
+    stats_underruns: "Underruns",
+    stats_reconnects: "Reconnects",
+    stats_uptime: "Uptime",
+    secs_ago: |secs| format!("{}s ago", secs),
+    log_title: " 📝 Log ",
+    copied: "📋 Copied",
+    eq_presets: [
+        "🎚 EQ: flat",
+        "🎚 EQ: speech",
+        "🎚 EQ: music",
+        "🎚 EQ: bass cut",
+    ],
+    favorite_added: "★ Added to favorites",
+    favorite_removed: "☆ Removed from favorites",
+    reminder: |time, channel, title| format!("⏰ {} on {}: {}", time, channel, title),
+    no_log: "No warnings or errors",
+    area_picker_title: " Choose area ",
+    no_matching_area: "No matching area",
+    ondemand_title: " 📻 On-demand: New ",
+    ondemand_help_corners: "[↑/↓] Select  [Enter] Open  [q] Quit",
+    ondemand_help_episodes: "[↑/↓] Select  [Enter] Play  [Esc] Back  [q] Quit",
+    ondemand_help_playback: "[Space] Pause  [←/→] Seek 30s  [+/-] Volume  [Esc] Back  [q] Quit",
+    cannot_play: "Cannot play",
+    cannot_fetch: "Cannot fetch",
+    available_until: "Available until",
+    serving: "Serving",
+    http_retries: |count| format!("HTTP retries: {}", count),
+    casting_to: |device| format!("📺 Casting to {}", device),
+    cast_controls: "Controls: r1 / r2 / fm to switch channel, + / - for volume, q to quit",
+    cast_ended: |device| format!("Playback on {} ended", device),
+    listened: |time, size| format!("■ Listened for {} ({} downloaded)", time, size),
+    recording_saved: |path, size| format!("Recording saved to {} ({})", path, size),
+    podcast_updated: |feed, added| format!("Feed updated: {} ({} new)", feed, added),
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lang_codes() {
+        assert_eq!(Lang::from_code("en").unwrap(), Lang::En);
+        assert_eq!(Lang::from_code("JA").unwrap(), Lang::Ja);
+        assert!(Lang::from_code("fr").is_err());
+    }
+
+    #[test]
+    fn test_tables_match() {
+        // Both languages list the same keys in the same order; only the
+        // mouse rows name their gesture in words
+        assert_eq!(JA.key_bindings.len(), EN.key_bindings.len());
+        for ((ja, _), (en, _)) in JA.key_bindings.iter().zip(EN.key_bindings) {
+            if let HelpKeys::Actions(_) = ja {
+                assert_eq!(ja, en);
+            }
+        }
+    }
+
+    #[test]
+    fn test_air_time() {
+        let time = DateTime::parse_from_rfc3339("2025-11-25T23:00:00+09:00").unwrap();
+        assert_eq!((JA.air_time)(time), "2025年11月25日 午後11:00");
+        assert_eq!((EN.air_time)(time), "Nov 25, 2025 11:00 PM");
+        assert_eq!(EN.channel_name(ChannelKind::R2), "Radio 2");
+        assert_eq!((EN.minutes_left)(12), "12 min left");
+    }
+}
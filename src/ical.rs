@@ -0,0 +1,152 @@
+//! iCalendar (RFC 5545) export of timetables, for `timetable --ical`.
+
+use chrono::{DateTime, Utc};
+
+use crate::i18n::tr;
+use crate::player::ChannelKind;
+use crate::types::ScheduledProgram;
+
+/// Content lines are folded after this many octets
+const LINE_LIMIT: usize = 75;
+
+/// A calendar with one event per program in `programs`, broadcast in `area`
+///
+/// Programs whose air time cannot be parsed are left out.
+pub fn calendar(programs: &[(ChannelKind, ScheduledProgram)], area: &str) -> String {
+    let stamp = utc_time(Utc::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!(
+            "PRODID:-//nhk-radio-player//{}//EN",
+            env!("CARGO_PKG_VERSION")
+        ),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", escape(&format!("NHK Radio {}", area))),
+    ];
+    for (channel, program) in programs {
+        let (Ok(start), Ok(end)) = (
+            DateTime::parse_from_rfc3339(&program.start_date),
+            DateTime::parse_from_rfc3339(&program.end_date),
+        ) else {
+            continue;
+        };
+        let station = format!("NHK {}", tr().channel_name(*channel));
+        let uid = match program.identifier_group.broadcast_event_id.as_str() {
+            "" => format!(
+                "{}-{}-{}@nhk-radio-player",
+                area,
+                channel.short_name().to_lowercase(),
+                start.timestamp()
+            ),
+            id => format!("{}@nhk-radio-player", id),
+        };
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", escape(&uid)));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART:{}", utc_time(start.with_timezone(&Utc))));
+        lines.push(format!("DTEND:{}", utc_time(end.with_timezone(&Utc))));
+        lines.push(format!("SUMMARY:{}", escape(&program.name)));
+        lines.push(format!(
+            "LOCATION:{}",
+            escape(&format!("{} ({})", station, area))
+        ));
+        if !program.description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&program.description)));
+        }
+        if let Some(url) = program.page_url() {
+            lines.push(format!("URL:{}", url));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+fn utc_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a TEXT value
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Split a content line into lines of at most [`LINE_LIMIT`] octets, each
+/// continuation starting with a space, without breaking a character
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calendar() {
+        let program: ScheduledProgram = serde_json::from_value(serde_json::json!({
+            "name": "クラシック音楽館",
+            "description": "N響定期公演, 指揮; 鈴木花子\n第2000回",
+            "startDate": "2025-11-25T21:00:00+09:00",
+            "endDate": "2025-11-25T23:00:00+09:00",
+            "identifierGroup": { "broadcastEventId": "r3-130-2025112521000" },
+            "about": {
+                "partOfSeries": { "canonical": "https://www.nhk.jp/p/ts/69WR9WJKM4/" }
+            }
+        }))
+        .unwrap();
+        let broken = ScheduledProgram {
+            start_date: "unknown".to_string(),
+            ..program.clone()
+        };
+
+        let ics = calendar(
+            &[(ChannelKind::Fm, program), (ChannelKind::Fm, broken)],
+            "東京",
+        );
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+        assert!(ics.contains("\r\nUID:r3-130-2025112521000@nhk-radio-player\r\n"));
+        assert!(ics.contains("\r\nDTSTART:20251125T120000Z\r\n"));
+        assert!(ics.contains("\r\nDTEND:20251125T140000Z\r\n"));
+        assert!(ics.contains("\r\nSUMMARY:クラシック音楽館\r\n"));
+        assert!(ics.contains("\r\nDESCRIPTION:N響定期公演\\, 指揮\\; 鈴木花子\\n第2000回\r\n"));
+        assert!(ics.contains("\r\nURL:https://www.nhk.jp/p/ts/69WR9WJKM4/\r\n"));
+    }
+
+    #[test]
+    fn test_fold() {
+        let line = format!("SUMMARY:{}", "あ".repeat(30));
+        let folded = fold(&line);
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= LINE_LIMIT));
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(lines.concat().replacen(' ', "", 1), line);
+        assert_eq!(fold("VERSION:2.0"), "VERSION:2.0");
+    }
+}
@@ -0,0 +1,316 @@
+//! Icecast-compatible re-streaming with ICY metadata (`transcode` feature).
+//!
+//! The live channel is decoded, re-encoded to MP3 or AAC by ffmpeg, and
+//! served as one endless HTTP response per listener. Clients that send
+//! `Icy-MetaData: 1` also get the present program's title as `StreamTitle`
+//! every [`META_INTERVAL`] bytes, which is what older internet-radio
+//! hardware shows on its display.
+
+use anyhow::Result;
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    extract::State,
+    http::{HeaderMap, HeaderValue, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use futures::StreamExt;
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch};
+
+use crate::client::{HttpOptions, NhkRadioClient};
+use crate::encoder::spawn_stream;
+use crate::headless::program_line;
+use crate::i18n::{area_label, tr};
+use crate::player::{CHANNELS, ChannelKind, SAMPLE_RATE};
+use crate::radio::now_playing_stream;
+use crate::stream::{StreamOptions, pcm_stream, segment_stream};
+
+/// Audio bytes between ICY metadata blocks
+const META_INTERVAL: usize = 16_000;
+
+/// Encoded chunks a slow listener may fall behind before skipping ahead
+const LISTENER_BACKLOG: usize = 256;
+
+/// Codec the stream is re-encoded to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum IcyCodec {
+    Mp3,
+    Aac,
+}
+
+impl IcyCodec {
+    fn content_type(self) -> &'static str {
+        match self {
+            IcyCodec::Mp3 => "audio/mpeg",
+            IcyCodec::Aac => "audio/aac",
+        }
+    }
+
+    fn ffmpeg_args(self, kbps: u32) -> Vec<String> {
+        let (codec, container) = match self {
+            IcyCodec::Mp3 => ("libmp3lame", "mp3"),
+            IcyCodec::Aac => ("aac", "adts"),
+        };
+        [
+            "-c:a",
+            codec,
+            "-b:a",
+            &format!("{}k", kbps),
+            "-f",
+            container,
+        ]
+        .map(str::to_string)
+        .to_vec()
+    }
+}
+
+/// Settings for [`run_icecast`]
+#[derive(Debug, Clone)]
+pub struct IcecastOptions {
+    pub addr: SocketAddr,
+    pub codec: IcyCodec,
+    /// Encoded bitrate in kbit/s
+    pub kbps: u32,
+    pub http: HttpOptions,
+    pub stream: StreamOptions,
+}
+
+#[derive(Clone)]
+struct Station {
+    name: String,
+    codec: IcyCodec,
+    kbps: u32,
+    audio: broadcast::Sender<Bytes>,
+    title: watch::Receiver<String>,
+}
+
+/// Re-stream `kind` in `area` until Ctrl-C, printing a line whenever the
+/// program changes
+pub async fn run_icecast(area: String, kind: ChannelKind, options: IcecastOptions) -> Result<()> {
+    let client = Arc::new(NhkRadioClient::with_options(options.http)?);
+    let (stream_data, program_url) = client.fetch_config().await?.find_area(&area)?;
+
+    let mut encoder = spawn_stream(
+        &options.codec.ffmpeg_args(options.kbps),
+        SAMPLE_RATE,
+        CHANNELS,
+    )?;
+    let mut encoder_in = encoder.stdin.take().expect("stdin is piped");
+    let mut encoder_out = encoder.stdout.take().expect("stdout is piped");
+
+    let (audio_tx, _) = broadcast::channel(LISTENER_BACKLOG);
+    let (title_tx, title_rx) = watch::channel(String::new());
+    let station = Station {
+        name: format!(
+            "NHK {} ({})",
+            tr().channel_name(kind),
+            area_label(&stream_data)
+        ),
+        codec: options.codec,
+        kbps: options.kbps,
+        audio: audio_tx.clone(),
+        title: title_rx,
+    };
+
+    // ffmpeg's pipes are blocking, so each end gets its own thread
+    std::thread::spawn(move || {
+        let mut buf = vec![0u8; 4096];
+        while let Ok(n) = encoder_out.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            // No listeners is fine; the audio is simply dropped
+            let _ = audio_tx.send(Bytes::copy_from_slice(&buf[..n]));
+        }
+    });
+    let (pcm_tx, pcm_rx) = std::sync::mpsc::channel::<Vec<i16>>();
+    std::thread::spawn(move || {
+        for samples in pcm_rx {
+            let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+            if let Err(e) = encoder_in.write_all(&bytes) {
+                log::error!("Encoder stopped: {}", e);
+                break;
+            }
+        }
+    });
+
+    let app = Router::new()
+        .route("/", get(listen))
+        .route("/stream", get(listen))
+        .with_state(station);
+    let listener = tokio::net::TcpListener::bind(options.addr).await?;
+    let local_addr = listener.local_addr().unwrap_or(options.addr);
+
+    println!("▶ {} {}", area_label(&stream_data), tr().channel_name(kind));
+    println!("{}: http://{}/stream", tr().serving, local_addr);
+
+    let segments = segment_stream(client.clone(), stream_data, kind, options.stream);
+    let mut pcm = std::pin::pin!(pcm_stream(segments));
+    let mut programs = std::pin::pin!(now_playing_stream(client, program_url, kind));
+    let mut server = std::pin::pin!(axum::serve(listener, app).into_future());
+
+    loop {
+        tokio::select! {
+            Some(chunk) = pcm.next() => {
+                if pcm_tx.send(chunk.to_playback()).is_err() {
+                    anyhow::bail!("Encoder exited");
+                }
+            }
+            Some(program) = programs.next() => {
+                println!("{}", program_line(&program));
+                title_tx.send_replace(program.title);
+            }
+            result = &mut server => return Ok(result?),
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    let _ = encoder.kill();
+    Ok(())
+}
+
+async fn listen(State(station): State<Station>, request: HeaderMap) -> Response {
+    let wants_metadata = request
+        .get("icy-metadata")
+        .is_some_and(|v| v.as_bytes() == b"1");
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(station.codec.content_type()),
+    );
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    if let Ok(name) = HeaderValue::from_bytes(station.name.as_bytes()) {
+        response_headers.insert("icy-name", name);
+    }
+    response_headers.insert("icy-br", HeaderValue::from(station.kbps));
+    if wants_metadata {
+        response_headers.insert("icy-metaint", HeaderValue::from(META_INTERVAL));
+    }
+
+    let listener = (
+        station.audio.subscribe(),
+        station.title,
+        wants_metadata.then(|| IcyInterleaver::new(META_INTERVAL)),
+    );
+    let body =
+        futures::stream::unfold(listener, |(mut audio, title, mut interleaver)| async move {
+            let chunk = loop {
+                match audio.recv().await {
+                    Ok(chunk) => break chunk,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::debug!("Listener fell behind by {} chunks", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            };
+            let chunk = match interleaver {
+                Some(ref mut interleaver) => interleaver.interleave(&chunk, &title.borrow()),
+                None => chunk,
+            };
+            Some((Ok::<_, std::io::Error>(chunk), (audio, title, interleaver)))
+        });
+    (response_headers, Body::from_stream(body)).into_response()
+}
+
+/// Splices ICY metadata blocks into a listener's audio
+struct IcyInterleaver {
+    interval: usize,
+    /// Audio bytes left before the next metadata block
+    until_meta: usize,
+    /// Title last sent, so unchanged titles cost a single byte
+    sent_title: Option<String>,
+}
+
+impl IcyInterleaver {
+    fn new(interval: usize) -> Self {
+        IcyInterleaver {
+            interval,
+            until_meta: interval,
+            sent_title: None,
+        }
+    }
+
+    fn interleave(&mut self, mut audio: &[u8], title: &str) -> Bytes {
+        let mut out = Vec::with_capacity(audio.len() + 64);
+        while audio.len() >= self.until_meta {
+            let (head, rest) = audio.split_at(self.until_meta);
+            out.extend_from_slice(head);
+            out.extend_from_slice(&self.metadata_block(title));
+            audio = rest;
+            self.until_meta = self.interval;
+        }
+        out.extend_from_slice(audio);
+        self.until_meta -= audio.len();
+        Bytes::from(out)
+    }
+
+    /// A length byte in 16-byte units followed by the padded metadata, or a
+    /// lone zero when the title has not changed
+    fn metadata_block(&mut self, title: &str) -> Vec<u8> {
+        if self.sent_title.as_deref() == Some(title) {
+            return vec![0];
+        }
+        self.sent_title = Some(title.to_string());
+
+        // A quote would end the value early, and players have no escape for it
+        let title = title.replace('\'', "’");
+        // The length byte caps a block at 255 * 16 bytes; cut the title, not
+        // the closing `';`, and only between characters
+        let room = 255 * 16 - "StreamTitle='';".len();
+        let mut end = title.len().min(room);
+        while !title.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut meta = format!("StreamTitle='{}';", &title[..end]).into_bytes();
+        let blocks = meta.len().div_ceil(16);
+        meta.resize(blocks * 16, 0);
+        let mut block = Vec::with_capacity(meta.len() + 1);
+        block.push(blocks as u8);
+        block.extend_from_slice(&meta);
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_block() {
+        let mut icy = IcyInterleaver::new(16);
+        let block = icy.metadata_block("ラジオ深夜便");
+        assert_eq!(block.len(), 1 + block[0] as usize * 16);
+        assert!(block[1..].starts_with("StreamTitle='ラジオ深夜便';".as_bytes()));
+        // Unchanged titles are not resent
+        assert_eq!(icy.metadata_block("ラジオ深夜便"), [0]);
+        assert_ne!(icy.metadata_block("NHKジャーナル"), [0]);
+
+        let block = icy.metadata_block("Rock'n'Roll");
+        assert!(block[1..].starts_with("StreamTitle='Rock’n’Roll';".as_bytes()));
+        // Long titles are cut between characters and keep the closing quote
+        let block = icy.metadata_block(&format!("#{}", "深夜便".repeat(1500)));
+        assert_eq!(block[0], 255);
+        let meta = String::from_utf8(block[1..].to_vec()).unwrap();
+        assert!(meta.trim_end_matches('\0').ends_with("';"));
+    }
+
+    #[test]
+    fn test_interleave_positions() {
+        let mut icy = IcyInterleaver::new(4);
+        let first = icy.interleave(b"abcdef", "");
+        // Four audio bytes, a metadata block, then the rest
+        assert_eq!(&first[..4], b"abcd");
+        let meta_len = 1 + first[4] as usize * 16;
+        assert_eq!(&first[4 + meta_len..], b"ef");
+
+        // The count carries over between chunks
+        let second = icy.interleave(b"ghij", "");
+        assert_eq!(&second[..2], b"gh");
+        assert_eq!(second[2], 0);
+        assert_eq!(&second[3..], b"ij");
+    }
+}
@@ -0,0 +1,338 @@
+//! Key bindings for the live player.
+//!
+//! Every key the player reacts to is bound to an [`Action`]. The defaults
+//! can be replaced per action in the config file's `[keys]` table, and a key
+//! bound to two actions is rejected at startup rather than shadowing one.
+
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Something a key does in the live player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Pause,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    Rewind,
+    Live,
+    Device,
+    /// Cycle through the equalizer presets
+    Equalizer,
+    Help,
+    Area,
+    Schedule,
+    Credits,
+    Stats,
+    Log,
+    /// Open the program's web page in the browser
+    OpenPage,
+    /// Search the program's hashtags in the browser
+    SearchHashtags,
+    /// Copy what is playing to the clipboard
+    Copy,
+    /// Start or stop recording what is playing
+    Record,
+    /// Cycle through the sleep timer presets
+    Sleep,
+    /// Add the program's series to the favorites, or remove it
+    Favorite,
+    /// Tune to the favorite a reminder is shown for
+    TuneReminder,
+    /// Scroll up, or select the previous program in the schedule
+    Up,
+    /// Scroll down, or select the next program in the schedule
+    Down,
+    R1,
+    R2,
+    Fm,
+    PrevChannel,
+    NextChannel,
+    /// Go back to the channel played before this one
+    LastChannel,
+}
+
+/// Each action's name in `[keys]` and the keys it has by default
+const DEFAULTS: &[(Action, &str, &[&str])] = &[
+    (Action::Quit, "quit", &["q", "Esc"]),
+    (Action::Pause, "pause", &["p", "Space"]),
+    (Action::VolumeUp, "volume_up", &["+", "="]),
+    (Action::VolumeDown, "volume_down", &["-"]),
+    (Action::Mute, "mute", &["m"]),
+    (Action::Rewind, "rewind", &["b"]),
+    (Action::Live, "live", &["n"]),
+    (Action::Device, "device", &["o"]),
+    (Action::Equalizer, "equalizer", &["e"]),
+    (Action::Help, "help", &["?"]),
+    (Action::Area, "area", &["a"]),
+    (Action::Schedule, "schedule", &["t"]),
+    (Action::Credits, "credits", &["c"]),
+    (Action::Stats, "stats", &["i"]),
+    // `l` is next_channel, so the log pane takes the capital letter
+    (Action::Log, "log", &["L"]),
+    // `o` is the output device picker, so the program page opens on `w` (web)
+    (Action::OpenPage, "open_page", &["w"]),
+    (Action::SearchHashtags, "search_hashtags", &["#"]),
+    (Action::Copy, "copy", &["y"]),
+    (Action::Record, "record", &["r"]),
+    (Action::Sleep, "sleep", &["s"]),
+    (Action::Favorite, "favorite", &["f"]),
+    (Action::TuneReminder, "tune_reminder", &["g"]),
+    (Action::Up, "up", &["Up", "k"]),
+    (Action::Down, "down", &["Down", "j"]),
+    (Action::R1, "r1", &["1"]),
+    (Action::R2, "r2", &["2"]),
+    (Action::Fm, "fm", &["3"]),
+    (Action::PrevChannel, "prev_channel", &["Left", "h"]),
+    (Action::NextChannel, "next_channel", &["Right", "l"]),
+    (Action::LastChannel, "last_channel", &["Tab", "0"]),
+];
+
+/// Keys for one action in the config file: `"x"` or `["x", "Tab"]`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    fn names(&self) -> &[String] {
+        match self {
+            KeyList::One(name) => std::slice::from_ref(name),
+            KeyList::Many(names) => names,
+        }
+    }
+}
+
+/// Which action each key triggers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    actions: HashMap<KeyCode, Action>,
+    /// Keys of each action in the order given, for the help overlay
+    keys: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::load(&BTreeMap::new()).expect("default key bindings are valid")
+    }
+}
+
+impl Keymap {
+    /// The default bindings with the actions named in `overrides` rebound
+    pub fn load(overrides: &BTreeMap<String, KeyList>) -> Result<Self> {
+        if let Some(name) = overrides
+            .keys()
+            .find(|name| !DEFAULTS.iter().any(|(_, known, _)| known == name))
+        {
+            anyhow::bail!("Unknown action in [keys]: {}", name);
+        }
+
+        let mut keymap = Keymap {
+            actions: HashMap::new(),
+            keys: HashMap::new(),
+        };
+        for &(action, name, defaults) in DEFAULTS {
+            let names: Vec<&str> = match overrides.get(name) {
+                Some(list) => list.names().iter().map(String::as_str).collect(),
+                None => defaults.to_vec(),
+            };
+            let mut codes = Vec::new();
+            for key in names {
+                let code = parse_key(key)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid key for {}: {}", name, key))?;
+                if let Some(other) = keymap.actions.insert(code, action)
+                    && other != action
+                {
+                    anyhow::bail!(
+                        "Key {} is bound to both {} and {}",
+                        key_name(code),
+                        action_name(other),
+                        name
+                    );
+                }
+                codes.push(code);
+            }
+            keymap.keys.insert(action, codes);
+        }
+        Ok(keymap)
+    }
+
+    /// The action bound to `code`, if any
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        self.actions.get(&code).copied()
+    }
+
+    /// Every key bound to `action`, e.g. `"q / Esc"`
+    pub fn describe(&self, action: Action) -> String {
+        self.key_names(action).join(" / ")
+    }
+
+    /// The keys of several actions, e.g. `"← h / → l"`
+    pub fn describe_all(&self, actions: &[Action]) -> String {
+        match actions {
+            [action] => self.describe(*action),
+            _ => actions
+                .iter()
+                .map(|&action| self.key_names(action).join(" "))
+                .collect::<Vec<_>>()
+                .join(" / "),
+        }
+    }
+
+    /// The first key of each action, for the one-line hint, e.g. `"←/→"`
+    pub fn hint(&self, actions: &[Action]) -> String {
+        actions
+            .iter()
+            .map(|&action| {
+                self.key_names(action)
+                    .into_iter()
+                    .next()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn key_names(&self, action: Action) -> Vec<String> {
+        self.keys
+            .get(&action)
+            .map(|codes| codes.iter().map(|&code| key_name(code)).collect())
+            .unwrap_or_default()
+    }
+}
+
+fn action_name(action: Action) -> &'static str {
+    DEFAULTS
+        .iter()
+        .find(|(a, _, _)| *a == action)
+        .map(|(_, name, _)| *name)
+        .unwrap_or_default()
+}
+
+/// Parse a key as written in `[keys]`: a single character, or a name such
+/// as `Space`, `Esc`, `Enter`, `Tab`, `Backspace`, `Left`, or `F1`
+fn parse_key(key: &str) -> Option<KeyCode> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(match c {
+            '←' => KeyCode::Left,
+            '→' => KeyCode::Right,
+            '↑' => KeyCode::Up,
+            '↓' => KeyCode::Down,
+            c => KeyCode::Char(c),
+        });
+    }
+    let code = match key.to_ascii_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "esc" | "escape" => KeyCode::Esc,
+        "enter" | "return" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        name => KeyCode::F(
+            name.strip_prefix('f')?
+                .parse()
+                .ok()
+                .filter(|n| (1..=12).contains(n))?,
+        ),
+    };
+    Some(code)
+}
+
+/// How a key is shown in the help
+fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Left => "←".to_string(),
+        KeyCode::Right => "→".to_string(),
+        KeyCode::Up => "↑".to_string(),
+        KeyCode::Down => "↓".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(entries: &[(&str, KeyList)]) -> BTreeMap<String, KeyList> {
+        entries
+            .iter()
+            .map(|(name, keys)| (name.to_string(), keys.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.action(KeyCode::Char('q')), Some(Action::Quit));
+        assert_eq!(keymap.action(KeyCode::Esc), Some(Action::Quit));
+        assert_eq!(keymap.action(KeyCode::Char(' ')), Some(Action::Pause));
+        assert_eq!(keymap.action(KeyCode::Char('x')), None);
+        assert_eq!(keymap.describe(Action::Quit), "q / Esc");
+        assert_eq!(
+            keymap.describe_all(&[Action::PrevChannel, Action::NextChannel]),
+            "← h / → l"
+        );
+        assert_eq!(
+            keymap.describe_all(&[Action::R1, Action::R2, Action::Fm]),
+            "1 / 2 / 3"
+        );
+        assert_eq!(keymap.hint(&[Action::VolumeUp, Action::VolumeDown]), "+/-");
+        assert_eq!(keymap.action(KeyCode::Tab), Some(Action::LastChannel));
+        assert_eq!(keymap.action(KeyCode::Char('0')), Some(Action::LastChannel));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let keymap = Keymap::load(&overrides(&[
+            ("quit", KeyList::One("x".to_string())),
+            ("r1", KeyList::Many(vec!["F1".to_string(), "!".to_string()])),
+        ]))
+        .unwrap();
+        assert_eq!(keymap.action(KeyCode::Char('x')), Some(Action::Quit));
+        // Rebinding replaces the defaults
+        assert_eq!(keymap.action(KeyCode::Char('q')), None);
+        assert_eq!(keymap.action(KeyCode::F(1)), Some(Action::R1));
+        assert_eq!(keymap.describe(Action::R1), "F1 / !");
+    }
+
+    #[test]
+    fn test_conflicts_and_typos() {
+        let conflict = Keymap::load(&overrides(&[("mute", KeyList::One("q".to_string()))]));
+        assert_eq!(
+            conflict.unwrap_err().to_string(),
+            "Key q is bound to both quit and mute"
+        );
+        assert!(Keymap::load(&overrides(&[("qiut", KeyList::One("x".to_string()))])).is_err());
+        assert!(Keymap::load(&overrides(&[("quit", KeyList::One("Ctrl".to_string()))])).is_err());
+        // The same key twice for one action is harmless
+        let twice = KeyList::Many(vec!["Esc".to_string(), "Esc".to_string()]);
+        assert!(Keymap::load(&overrides(&[("quit", twice)])).is_ok());
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("space"), Some(KeyCode::Char(' ')));
+        assert_eq!(parse_key("F12"), Some(KeyCode::F(12)));
+        assert_eq!(parse_key("f13"), None);
+        assert_eq!(parse_key("→"), Some(KeyCode::Right));
+        assert_eq!(parse_key("PageDown"), Some(KeyCode::PageDown));
+        assert_eq!(parse_key(""), None);
+    }
+}
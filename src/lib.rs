@@ -0,0 +1,62 @@
+//! The `nhk-radio-player` application: CLI, TUI, daemon, and downloads.
+//!
+//! Playback itself lives in the [`nhk_radio_core`] crate. Its modules are
+//! re-exported here so the application code can keep referring to them as
+//! `crate::client`, `crate::player`, and so on.
+
+#[cfg(feature = "transcode")]
+pub use nhk_radio_core::encoder;
+pub use nhk_radio_core::{
+    area, audio_queue, cache, client, crypto, decoder, device, dsp, dump, engine, equalizer, error,
+    loudness, m3u8, mock, ondemand, pipe, player, radio, recorder, resample, stats, stream,
+    timeshift, types, world,
+};
+
+pub mod autorecord;
+pub mod browser;
+pub mod capture;
+pub mod cast;
+pub mod cli;
+pub mod config;
+pub mod control;
+pub mod cue;
+pub mod daemon;
+pub mod discord;
+pub mod dlna;
+pub mod download;
+pub mod events;
+pub mod external;
+pub mod favorites;
+pub mod genre;
+pub mod graphics;
+pub mod headless;
+pub mod hooks;
+pub mod http;
+pub mod i18n;
+pub mod ical;
+#[cfg(feature = "transcode")]
+pub mod icecast;
+pub mod keymap;
+pub mod logging;
+pub mod media_controls;
+pub mod now_playing;
+pub mod ondemand_browser;
+pub mod output;
+pub mod play_url;
+pub mod podcast;
+pub mod program_info;
+pub mod relay;
+pub mod scrobble;
+pub mod search;
+pub mod split;
+pub mod state;
+pub mod systemd;
+pub mod theme;
+pub mod title;
+pub mod tui;
+pub mod wizard;
+
+pub use nhk_radio_core::{
+    ChannelKind, NhkRadio, NowPlaying, PcmChunk, PcmSpec, SegmentData, StreamOptions, live_audio,
+    live_segments,
+};
@@ -0,0 +1,292 @@
+//! Logging that stays visible inside the TUI.
+//!
+//! Records go to stderr through env_logger as before, except while a
+//! full-screen view is up and stderr would scribble over it. Warnings and
+//! errors, along with anything the configured level lets through, are also
+//! kept in a small ring buffer for the TUI's log pane.
+//!
+//! With a log file, records go there instead of stderr, also while the TUI
+//! is up. The file is rotated when it grows too large, keeping a few older
+//! ones beside it as `<path>.1`, `<path>.2`, and so on.
+
+use chrono::{DateTime, Local};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// Records kept for the log pane
+const CAPACITY: usize = 200;
+
+/// Size a log file grows to before it is rotated
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Rotated log files kept beside the current one
+const KEEP_FILES: u32 = 3;
+
+/// One logged record, as the log pane shows it
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub message: String,
+}
+
+struct BufferedLogger {
+    inner: env_logger::Logger,
+    /// Set while a full-screen view owns the terminal
+    quiet: AtomicBool,
+    /// Whether `inner` writes to a log file, which the terminal never shows
+    to_file: bool,
+    entries: Mutex<VecDeque<LogEntry>>,
+}
+
+impl BufferedLogger {
+    fn new(inner: env_logger::Logger, to_file: bool) -> Self {
+        BufferedLogger {
+            inner,
+            quiet: AtomicBool::new(false),
+            to_file,
+            entries: Mutex::new(VecDeque::with_capacity(CAPACITY)),
+        }
+    }
+
+    fn recent(&self, count: usize) -> Vec<LogEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let skip = entries.len().saturating_sub(count);
+        entries.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Log for BufferedLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        let wanted = self.inner.matches(record);
+        if !wanted && record.level() > Level::Warn {
+            return;
+        }
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            if entries.len() == CAPACITY {
+                entries.pop_front();
+            }
+            entries.push_back(LogEntry {
+                time: Local::now(),
+                level: record.level(),
+                message: record.args().to_string(),
+            });
+        }
+        if wanted && (self.to_file || !self.quiet.load(Ordering::Relaxed)) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+static LOGGER: OnceLock<BufferedLogger> = OnceLock::new();
+
+/// A log file that moves itself aside once it reaches its size limit
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+}
+
+impl RotatingFile {
+    /// Append to the file at `path`, creating it if needed
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        Self::with_limit(path, MAX_FILE_BYTES)
+    }
+
+    fn with_limit(path: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path,
+            file,
+            size,
+            max_bytes,
+        })
+    }
+
+    /// Shift `<path>.1` to `<path>.2` and so on, dropping the oldest, move
+    /// the current file to `<path>.1`, and start an empty one
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..KEEP_FILES).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Log filter for `-v` given `count` times, or `None` without it
+pub fn verbosity_level(count: u8) -> Option<&'static str> {
+    match count {
+        0 => None,
+        1 => Some("info"),
+        2 => Some("debug"),
+        _ => Some("trace"),
+    }
+}
+
+/// Install the logger, printing `default_level` and above to stderr, or to
+/// `file` when given, unless `RUST_LOG` says otherwise; without `color`,
+/// never with ANSI colors
+pub fn init(default_level: &str, color: bool, file: Option<RotatingFile>) {
+    let mut builder =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(default_level));
+    if !color || file.is_some() {
+        builder.write_style(env_logger::WriteStyle::Never);
+    }
+    let to_file = file.is_some();
+    if let Some(file) = file {
+        builder.target(env_logger::Target::Pipe(Box::new(file)));
+    }
+    let inner = builder.build();
+    // Warnings are always kept for the log pane, whatever reaches stderr
+    let max_level = inner.filter().max(LevelFilter::Warn);
+    let logger = LOGGER.get_or_init(|| BufferedLogger::new(inner, to_file));
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Hold back stderr output while a full-screen view is shown; records are
+/// still kept for the log pane
+pub fn set_quiet(quiet: bool) {
+    if let Some(logger) = LOGGER.get() {
+        logger.quiet.store(quiet, Ordering::Relaxed);
+    }
+}
+
+/// Up to `count` of the newest records, oldest first
+pub fn recent(count: usize) -> Vec<LogEntry> {
+    LOGGER
+        .get()
+        .map(|logger| logger.recent(count))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(logger: &BufferedLogger, level: Level, message: &str) {
+        logger.log(
+            &Record::builder()
+                .level(level)
+                .target("nhk_radio_player")
+                .args(format_args!("{}", message))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn test_keeps_warnings_and_enabled_records() {
+        let logger = BufferedLogger::new(
+            env_logger::Builder::new().parse_filters("error").build(),
+            false,
+        );
+        logger.quiet.store(true, Ordering::Relaxed);
+        log(&logger, Level::Info, "tuned in");
+        log(&logger, Level::Warn, "segment missed");
+        log(&logger, Level::Error, "playlist failed");
+
+        let entries = logger.recent(10);
+        let messages: Vec<&str> = entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["segment missed", "playlist failed"]);
+        assert_eq!(logger.recent(1)[0].level, Level::Error);
+
+        for i in 0..CAPACITY {
+            log(&logger, Level::Warn, &i.to_string());
+        }
+        let entries = logger.recent(CAPACITY * 2);
+        assert_eq!(entries.len(), CAPACITY);
+        assert_eq!(entries[0].message, "0");
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let dir = std::env::temp_dir().join(format!("nhk-log-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("player.log");
+
+        let mut file = RotatingFile::with_limit(path.clone(), 10).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n", "fifth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fifth\n");
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 1)).unwrap(),
+            "fourth\n"
+        );
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 3)).unwrap(),
+            "second\n"
+        );
+        // Only KEEP_FILES old files are kept
+        assert!(!rotated_path(&path, 4).exists());
+
+        // Reopening appends, counting what is already there
+        let mut file = RotatingFile::with_limit(path.clone(), 10).unwrap();
+        file.write_all(b"sixth\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "sixth\n");
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 1)).unwrap(),
+            "fifth\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verbosity_level() {
+        assert_eq!(verbosity_level(0), None);
+        assert_eq!(verbosity_level(1), Some("info"));
+        assert_eq!(verbosity_level(2), Some("debug"));
+        assert_eq!(verbosity_level(5), Some("trace"));
+    }
+}
@@ -1,16 +1,12 @@
-mod cli;
-mod client;
-mod crypto;
-mod decoder;
-mod m3u8;
-mod player;
-mod tui;
-mod types;
+use clap::Parser;
+use nhk_radio_player::cli::{self, Cli};
+use nhk_radio_player::output::report_error;
 
 #[tokio::main]
 async fn main() {
-    if let Err(e) = cli::run_cli().await {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+    let cli = Cli::parse();
+    let errors = cli.errors;
+    if let Err(e) = cli::run_cli(cli).await {
+        std::process::exit(report_error(&e, errors));
     }
 }
@@ -0,0 +1,319 @@
+//! The system's media controls, for the TUI.
+//!
+//! The program being heard is shown where the system shows what is playing,
+//! and the keyboard's play/pause, next, and previous keys pause and switch
+//! channels:
+//!
+//! - On Windows the player registers with the System Media Transport
+//!   Controls, shown in the volume flyout and on the lock screen. They belong
+//!   to a `MediaPlayer` that never plays anything itself; its own command
+//!   handling is turned off so every button press comes here.
+//! - On macOS it publishes to `MPNowPlayingInfoCenter`, shown in Control
+//!   Center, and takes commands from `MPRemoteCommandCenter`. Commands arrive
+//!   on the main thread's run loop, which the TUI's loop runs on, so it is
+//!   given a turn whenever keys are polled.
+//!
+//! Elsewhere there are no controls and [`MediaControls::start`] returns `None`.
+
+use std::sync::mpsc::Receiver;
+
+use crate::now_playing::NowPlayingRecord;
+
+/// A button pressed on the system's media controls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKey {
+    /// Play or pause, whichever is not the case now
+    Toggle,
+    Play,
+    Pause,
+    Next,
+    Previous,
+}
+
+/// Registration with the system's media controls
+pub struct MediaControls {
+    #[cfg(any(windows, target_os = "macos"))]
+    system: System,
+    keys: Receiver<MediaKey>,
+    /// What the controls show now, so unchanged updates are skipped
+    shown: Option<(Option<NowPlayingRecord>, bool)>,
+}
+
+impl MediaControls {
+    /// Register with the system's media controls, or `None` where there are
+    /// none or registering fails
+    pub fn start() -> Option<Self> {
+        #[cfg(any(windows, target_os = "macos"))]
+        {
+            let (tx, keys) = std::sync::mpsc::channel();
+            match System::new(tx) {
+                Ok(system) => {
+                    return Some(MediaControls {
+                        system,
+                        keys,
+                        shown: None,
+                    });
+                }
+                Err(e) => log::warn!("Cannot register with the media controls: {}", e),
+            }
+        }
+        None
+    }
+
+    /// Show `record`, or nothing, as playing or paused
+    pub fn update(&mut self, record: Option<&NowPlayingRecord>, paused: bool) {
+        let shown = (record.cloned(), paused);
+        if self.shown.as_ref() == Some(&shown) {
+            return;
+        }
+        #[cfg(any(windows, target_os = "macos"))]
+        if let Err(e) = self.system.show(shown.0.as_ref(), paused) {
+            log::warn!("Failed to update the media controls: {}", e);
+        }
+        self.shown = Some(shown);
+    }
+
+    /// The next button pressed, without waiting
+    pub fn try_key(&self) -> Option<MediaKey> {
+        #[cfg(target_os = "macos")]
+        self.system.run_pending();
+        self.keys.try_recv().ok()
+    }
+}
+
+#[cfg(windows)]
+use smtc::Smtc as System;
+
+#[cfg(target_os = "macos")]
+use macos::NowPlayingCenter as System;
+
+#[cfg(windows)]
+mod smtc {
+    use std::sync::mpsc::Sender;
+    use windows::Foundation::{EventRegistrationToken, TypedEventHandler};
+    use windows::Media::Playback::MediaPlayer;
+    use windows::Media::{
+        MediaPlaybackStatus, MediaPlaybackType, SystemMediaTransportControls,
+        SystemMediaTransportControlsButton, SystemMediaTransportControlsButtonPressedEventArgs,
+    };
+    use windows::core::{HSTRING, Result};
+
+    use super::MediaKey;
+    use crate::now_playing::NowPlayingRecord;
+
+    pub(super) struct Smtc {
+        player: MediaPlayer,
+        controls: SystemMediaTransportControls,
+        token: EventRegistrationToken,
+    }
+
+    impl Smtc {
+        /// Enable the controls, sending the buttons pressed to `keys`
+        pub(super) fn new(keys: Sender<MediaKey>) -> Result<Self> {
+            let player = MediaPlayer::new()?;
+            player.CommandManager()?.SetIsEnabled(false)?;
+            let controls = player.SystemMediaTransportControls()?;
+            controls.SetIsEnabled(true)?;
+            controls.SetIsPlayEnabled(true)?;
+            controls.SetIsPauseEnabled(true)?;
+            controls.SetIsNextEnabled(true)?;
+            controls.SetIsPreviousEnabled(true)?;
+
+            let handler = TypedEventHandler::<
+                SystemMediaTransportControls,
+                SystemMediaTransportControlsButtonPressedEventArgs,
+            >::new(move |_, args| {
+                let Some(args) = args.as_ref() else {
+                    return Ok(());
+                };
+                let key = match args.Button()? {
+                    SystemMediaTransportControlsButton::Play => MediaKey::Play,
+                    SystemMediaTransportControlsButton::Pause => MediaKey::Pause,
+                    SystemMediaTransportControlsButton::Next => MediaKey::Next,
+                    SystemMediaTransportControlsButton::Previous => MediaKey::Previous,
+                    _ => return Ok(()),
+                };
+                let _ = keys.send(key);
+                Ok(())
+            });
+            let token = controls.ButtonPressed(&handler)?;
+            Ok(Smtc {
+                player,
+                controls,
+                token,
+            })
+        }
+
+        /// Show the program and station of `record`, or clear the display
+        pub(super) fn show(&self, record: Option<&NowPlayingRecord>, paused: bool) -> Result<()> {
+            let updater = self.controls.DisplayUpdater()?;
+            match record {
+                Some(record) => {
+                    updater.SetType(MediaPlaybackType::Music)?;
+                    let music = updater.MusicProperties()?;
+                    music.SetTitle(&HSTRING::from(record.title.as_str()))?;
+                    music.SetArtist(&HSTRING::from(format!(
+                        "{} ({})",
+                        record.station, record.area
+                    )))?;
+                }
+                None => updater.ClearAll()?,
+            }
+            updater.Update()?;
+            self.controls.SetPlaybackStatus(if paused {
+                MediaPlaybackStatus::Paused
+            } else {
+                MediaPlaybackStatus::Playing
+            })
+        }
+    }
+
+    impl Drop for Smtc {
+        fn drop(&mut self) {
+            let _ = self.controls.RemoveButtonPressed(self.token);
+            let _ = self.controls.SetIsEnabled(false);
+            let _ = self.player.Close();
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use anyhow::Result;
+    use block2::RcBlock;
+    use objc2::msg_send;
+    use objc2::rc::Retained;
+    use objc2::runtime::{AnyClass, AnyObject};
+    use objc2_foundation::{NSDictionary, NSNumber, NSString};
+    use std::ffi::{CStr, c_void};
+    use std::ptr::NonNull;
+    use std::sync::mpsc::Sender;
+
+    use super::MediaKey;
+    use crate::now_playing::NowPlayingRecord;
+
+    /// `MPNowPlayingPlaybackState` values
+    const PLAYING: usize = 1;
+    const PAUSED: usize = 2;
+
+    /// `MPRemoteCommandHandlerStatusSuccess`
+    const HANDLED: isize = 0;
+
+    #[link(name = "MediaPlayer", kind = "framework")]
+    unsafe extern "C" {
+        static MPMediaItemPropertyTitle: &'static NSString;
+        static MPMediaItemPropertyArtist: &'static NSString;
+        static MPNowPlayingInfoPropertyIsLiveStream: &'static NSString;
+        static MPNowPlayingInfoPropertyPlaybackRate: &'static NSString;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    unsafe extern "C" {
+        static kCFRunLoopDefaultMode: *const c_void;
+        fn CFRunLoopRunInMode(mode: *const c_void, seconds: f64, return_after_handled: u8) -> i32;
+    }
+
+    fn class(name: &CStr) -> Result<&'static AnyClass> {
+        AnyClass::get(name)
+            .ok_or_else(|| anyhow::anyhow!("{} is not available", name.to_string_lossy()))
+    }
+
+    pub(super) struct NowPlayingCenter {
+        info_center: Retained<AnyObject>,
+        /// Each remote command with the handler added to it
+        targets: Vec<(Retained<AnyObject>, Retained<AnyObject>)>,
+    }
+
+    impl NowPlayingCenter {
+        /// Handle the remote commands, sending the buttons pressed to `keys`
+        pub(super) fn new(keys: Sender<MediaKey>) -> Result<Self> {
+            let info_center: Retained<AnyObject> =
+                unsafe { msg_send![class(c"MPNowPlayingInfoCenter")?, defaultCenter] };
+            let commands: Retained<AnyObject> =
+                unsafe { msg_send![class(c"MPRemoteCommandCenter")?, sharedCommandCenter] };
+            let buttons: [(MediaKey, Retained<AnyObject>); 5] = unsafe {
+                [
+                    (
+                        MediaKey::Toggle,
+                        msg_send![&commands, togglePlayPauseCommand],
+                    ),
+                    (MediaKey::Play, msg_send![&commands, playCommand]),
+                    (MediaKey::Pause, msg_send![&commands, pauseCommand]),
+                    (MediaKey::Next, msg_send![&commands, nextTrackCommand]),
+                    (
+                        MediaKey::Previous,
+                        msg_send![&commands, previousTrackCommand],
+                    ),
+                ]
+            };
+
+            let mut targets = Vec::new();
+            for (key, command) in buttons {
+                let keys = keys.clone();
+                let handler = RcBlock::new(move |_event: NonNull<AnyObject>| -> isize {
+                    let _ = keys.send(key);
+                    HANDLED
+                });
+                let target: Retained<AnyObject> =
+                    unsafe { msg_send![&command, addTargetWithHandler: &*handler] };
+                targets.push((command, target));
+            }
+            Ok(NowPlayingCenter {
+                info_center,
+                targets,
+            })
+        }
+
+        /// Show the program and station of `record`, or clear the display
+        pub(super) fn show(&self, record: Option<&NowPlayingRecord>, paused: bool) -> Result<()> {
+            let info = record.map(|record| {
+                let title = NSString::from_str(&record.title);
+                let artist = NSString::from_str(&format!("{} ({})", record.station, record.area));
+                let live = NSNumber::new_bool(true);
+                let rate = NSNumber::new_f64(if paused { 0.0 } else { 1.0 });
+                let keys = unsafe {
+                    [
+                        MPMediaItemPropertyTitle,
+                        MPMediaItemPropertyArtist,
+                        MPNowPlayingInfoPropertyIsLiveStream,
+                        MPNowPlayingInfoPropertyPlaybackRate,
+                    ]
+                };
+                let values: [&AnyObject; 4] = [
+                    AsRef::<AnyObject>::as_ref(&*title),
+                    AsRef::<AnyObject>::as_ref(&*artist),
+                    AsRef::<AnyObject>::as_ref(&*live),
+                    AsRef::<AnyObject>::as_ref(&*rate),
+                ];
+                NSDictionary::<NSString, AnyObject>::from_slices(&keys, &values)
+            });
+            let state = if paused { PAUSED } else { PLAYING };
+            unsafe {
+                let _: () = msg_send![&self.info_center, setNowPlayingInfo: info.as_deref()];
+                let _: () = msg_send![&self.info_center, setPlaybackState: state];
+            }
+            Ok(())
+        }
+
+        /// Deliver the commands waiting on the main run loop, without blocking
+        pub(super) fn run_pending(&self) {
+            unsafe {
+                CFRunLoopRunInMode(kCFRunLoopDefaultMode, 0.0, 1);
+            }
+        }
+    }
+
+    impl Drop for NowPlayingCenter {
+        fn drop(&mut self) {
+            unsafe {
+                for (command, target) in &self.targets {
+                    let _: () = msg_send![command, removeTarget: &**target];
+                }
+                let _: () = msg_send![
+                    &self.info_center,
+                    setNowPlayingInfo: None::<&NSDictionary<NSString, AnyObject>>
+                ];
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! A file kept up to date with what is playing, for OBS text sources and
+//! status bars such as waybar or i3blocks.
+//!
+//! The file is rewritten whenever the station or program changes, through a
+//! temporary file and a rename so readers never see half of it. A path ending
+//! in `.json` gets a JSON object; anything else gets a single line of text.
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::i18n::tr;
+use crate::player::ChannelKind;
+
+/// What the file says is playing
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NowPlayingRecord {
+    /// e.g. "NHK FM"
+    pub station: String,
+    /// `r1`, `r2`, or `fm`
+    pub channel: String,
+    pub area: String,
+    pub title: String,
+    /// RFC 3339 start of the program, when known
+    pub start: Option<String>,
+    /// RFC 3339 end of the program, when known
+    pub end: Option<String>,
+}
+
+impl NowPlayingRecord {
+    pub fn new(
+        channel: ChannelKind,
+        area: &str,
+        title: &str,
+        start: Option<DateTime<FixedOffset>>,
+        end: Option<DateTime<FixedOffset>>,
+    ) -> Self {
+        NowPlayingRecord {
+            station: format!("NHK {}", tr().channel_name(channel)),
+            channel: channel.short_name().to_lowercase(),
+            area: area.to_string(),
+            title: title.to_string(),
+            start: start.map(|t| t.to_rfc3339()),
+            end: end.map(|t| t.to_rfc3339()),
+        }
+    }
+
+    /// One line, e.g. "NHK FM — クラシック音楽館 (21:00-23:00)"
+    fn text(&self) -> String {
+        let clock = |time: &Option<String>| {
+            time.as_deref()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.format("%H:%M").to_string())
+        };
+        match (clock(&self.start), clock(&self.end)) {
+            (Some(start), Some(end)) => {
+                format!("{} — {} ({}-{})", self.station, self.title, start, end)
+            }
+            _ => format!("{} — {}", self.station, self.title),
+        }
+    }
+}
+
+/// The file given with `--now-playing-file`
+pub struct NowPlayingFile {
+    path: PathBuf,
+    json: bool,
+    /// What was last written, so unchanged records are not written again
+    last: Option<NowPlayingRecord>,
+}
+
+impl NowPlayingFile {
+    pub fn new(path: PathBuf) -> Self {
+        let json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        NowPlayingFile {
+            path,
+            json,
+            last: None,
+        }
+    }
+
+    /// Rewrite the file if `record` differs from what it holds
+    ///
+    /// A failed write is not retried until the record changes again.
+    pub fn update(&mut self, record: NowPlayingRecord) -> Result<()> {
+        if self.last.as_ref() == Some(&record) {
+            return Ok(());
+        }
+        let contents = if self.json {
+            serde_json::to_string_pretty(&record)? + "\n"
+        } else {
+            record.text() + "\n"
+        };
+        self.last = Some(record);
+        write_atomically(&self.path, &contents)
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", self.path.display(), e))
+    }
+
+    /// Delete the file once nothing is playing any more
+    pub fn remove(self) {
+        if self.last.is_some()
+            && let Err(e) = fs::remove_file(&self.path)
+        {
+            log::warn!("Failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Replace `path` with `contents` in one step, through a temporary file
+/// beside it
+fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(start: Option<&str>) -> NowPlayingRecord {
+        let time = |t: &str| DateTime::parse_from_rfc3339(t).unwrap();
+        NowPlayingRecord::new(
+            ChannelKind::Fm,
+            "東京",
+            "クラシック音楽館",
+            start.map(time),
+            Some(time("2025-11-25T23:00:00+09:00")),
+        )
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(
+            record(Some("2025-11-25T21:00:00+09:00")).text(),
+            "NHK FM — クラシック音楽館 (21:00-23:00)"
+        );
+        assert_eq!(record(None).text(), "NHK FM — クラシック音楽館");
+    }
+
+    #[test]
+    fn test_update() {
+        let dir = std::env::temp_dir().join(format!("nhk-now-playing-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut file = NowPlayingFile::new(dir.join("now.json"));
+        file.update(record(Some("2025-11-25T21:00:00+09:00")))
+            .unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("now.json")).unwrap()).unwrap();
+        assert_eq!(json["channel"], "fm");
+        assert_eq!(json["title"], "クラシック音楽館");
+        assert_eq!(json["start"], "2025-11-25T21:00:00+09:00");
+        // Only the file itself is left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        file.remove();
+        assert!(!dir.join("now.json").exists());
+
+        let mut file = NowPlayingFile::new(dir.join("now.txt"));
+        file.update(record(None)).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("now.txt")).unwrap(),
+            "NHK FM — クラシック音楽館\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
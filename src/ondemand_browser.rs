@@ -0,0 +1,435 @@
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph},
+};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+use crate::audio_queue::audio_queue;
+use crate::client::NhkRadioClient;
+use crate::i18n::tr;
+use crate::ondemand::{PREFETCH_SECS, VodPlaylist, format_position, run_vod_loop};
+use crate::player::{AudioControls, run_audio_thread};
+use crate::theme::Theme;
+use crate::tui::{Tui, VOLUME_STEP, truncate_str, volume_gauge};
+use crate::types::{OndemandCorner, OndemandEpisode, OndemandSeries};
+
+const SEEK_STEP: f64 = 30.0;
+
+/// An episode being played, with the handles needed to control it
+struct Playback {
+    title: String,
+    subtitle: String,
+    duration: f64,
+    is_paused: bool,
+    seek_tx: watch::Sender<f64>,
+    paused_tx: watch::Sender<bool>,
+    volume_tx: watch::Sender<f32>,
+    position_rx: watch::Receiver<f64>,
+    task: tokio::task::JoinHandle<Result<()>>,
+}
+
+impl Playback {
+    async fn start(
+        client: Arc<NhkRadioClient>,
+        series: &OndemandSeries,
+        episode: &OndemandEpisode,
+        start: f64,
+        volume: u8,
+        device: Option<String>,
+    ) -> Result<Self> {
+        let playlist = Arc::new(VodPlaylist::load(client.as_ref(), &episode.stream_url).await?);
+        let duration = playlist.duration;
+
+        let (seek_tx, seek_rx) = watch::channel(start);
+        let (paused_tx, paused_rx) = watch::channel(false);
+        let (volume_tx, volume_rx) = watch::channel(volume as f32 / 100.0);
+        let (_device_tx, device_rx) = watch::channel(device);
+        let (position_tx, position_rx) = watch::channel(start);
+        let (_command_tx, command_rx) = std::sync::mpsc::channel();
+        let (timeshift_tx, _) = watch::channel(0.0);
+        let (queued_tx, _) = watch::channel(0.0);
+        let (levels_tx, _) = watch::channel([0.0; 2]);
+        // Room for everything the VOD loop prefetches, so nothing is dropped
+        let (audio_tx, audio_rx) = audio_queue(PREFETCH_SECS * 2.0);
+        let (events_tx, _events_rx) = std::sync::mpsc::channel();
+
+        let controls = AudioControls {
+            paused_rx: paused_rx.clone(),
+            volume_rx,
+            device_rx,
+            command_rx,
+            timeshift_tx,
+            queued_tx,
+            levels_tx,
+            stats: Arc::default(),
+        };
+        // The thread exits once the VOD task drops `audio_tx`
+        std::thread::spawn(move || run_audio_thread(audio_rx, controls, 0, events_tx));
+
+        let task = tokio::spawn(run_vod_loop(
+            client,
+            playlist,
+            seek_rx,
+            paused_rx,
+            audio_tx,
+            position_tx,
+        ));
+
+        Ok(Self {
+            title: series.title.clone(),
+            subtitle: episode.program_title.clone(),
+            duration,
+            is_paused: false,
+            seek_tx,
+            paused_tx,
+            volume_tx,
+            position_rx,
+            task,
+        })
+    }
+
+    fn position(&self) -> f64 {
+        *self.position_rx.borrow()
+    }
+
+    fn seek_by(&self, delta: f64) {
+        let target = (self.position() + delta).clamp(0.0, self.duration);
+        let _ = self.seek_tx.send(target);
+    }
+}
+
+impl Drop for Playback {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct BrowserState {
+    corners: Vec<OndemandCorner>,
+    corner_list: ListState,
+    series: Option<OndemandSeries>,
+    episode_list: ListState,
+    playback: Option<Playback>,
+    volume: u8,
+    device: Option<String>,
+    message: String,
+    theme: Theme,
+}
+
+impl BrowserState {
+    fn selected_corner(&self) -> Option<&OndemandCorner> {
+        self.corner_list
+            .selected()
+            .and_then(|i| self.corners.get(i))
+    }
+
+    fn selected_episode(&self) -> Option<&OndemandEpisode> {
+        let series = self.series.as_ref()?;
+        self.episode_list
+            .selected()
+            .and_then(|i| series.episodes.get(i))
+    }
+}
+
+/// Where the browser should open
+pub enum BrowserStart {
+    /// The list of recently added corners
+    Corners,
+    /// Directly play an episode, returning to its series on Esc
+    Episode {
+        series_site_id: String,
+        corner_site_id: String,
+        episode: usize,
+        start: f64,
+    },
+}
+
+/// Interactive 聴き逃し browser: corners → episodes → playback with seeking
+pub async fn run_ondemand_browser(
+    client: Arc<NhkRadioClient>,
+    start: BrowserStart,
+    volume: u8,
+    device: Option<String>,
+    theme: Theme,
+) -> Result<()> {
+    let mut state = BrowserState {
+        corners: client.fetch_ondemand_corners().await?.corners,
+        corner_list: ListState::default().with_selected(Some(0)),
+        series: None,
+        episode_list: ListState::default(),
+        playback: None,
+        volume,
+        device: device.clone(),
+        message: String::new(),
+        theme,
+    };
+
+    if let BrowserStart::Episode {
+        series_site_id,
+        corner_site_id,
+        episode,
+        start,
+    } = start
+    {
+        let series = client
+            .fetch_ondemand_series(&series_site_id, &corner_site_id)
+            .await?;
+        let ep = series
+            .episodes
+            .get(episode)
+            .ok_or_else(|| anyhow::anyhow!("Episode {} not found", episode))?;
+        state.playback =
+            Some(Playback::start(client.clone(), &series, ep, start, volume, device).await?);
+        state.episode_list.select(Some(episode));
+        state.series = Some(series);
+    }
+
+    let mut tui = Tui::new()?;
+
+    loop {
+        tui.draw_with(|f| render_browser(f, &mut state))?;
+
+        if !event::poll(std::time::Duration::from_millis(100))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        if let Some(ref mut playback) = state.playback {
+            match key.code {
+                KeyCode::Char('q') => break,
+                KeyCode::Esc | KeyCode::Backspace => state.playback = None,
+                KeyCode::Char('p') | KeyCode::Char(' ') => {
+                    playback.is_paused = !playback.is_paused;
+                    let _ = playback.paused_tx.send(playback.is_paused);
+                }
+                KeyCode::Left | KeyCode::Char('h') => playback.seek_by(-SEEK_STEP),
+                KeyCode::Right | KeyCode::Char('l') => playback.seek_by(SEEK_STEP),
+                KeyCode::Char('+') | KeyCode::Char('=') => {
+                    state.volume = (state.volume + VOLUME_STEP).min(100);
+                    let _ = playback.volume_tx.send(state.volume as f32 / 100.0);
+                }
+                KeyCode::Char('-') => {
+                    state.volume = state.volume.saturating_sub(VOLUME_STEP);
+                    let _ = playback.volume_tx.send(state.volume as f32 / 100.0);
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let in_episodes = state.series.is_some();
+        let list_len = match state.series {
+            Some(ref series) => series.episodes.len(),
+            None => state.corners.len(),
+        };
+        let list = if in_episodes {
+            &mut state.episode_list
+        } else {
+            &mut state.corner_list
+        };
+
+        match key.code {
+            KeyCode::Char('q') => break,
+            KeyCode::Esc | KeyCode::Backspace => {
+                if in_episodes {
+                    state.series = None;
+                } else {
+                    break;
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') if list_len > 0 => {
+                let next = list.selected().map_or(0, |i| (i + 1).min(list_len - 1));
+                list.select(Some(next));
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                let prev = list.selected().map_or(0, |i| i.saturating_sub(1));
+                list.select(Some(prev));
+            }
+            KeyCode::Enter => {
+                state.message = tr().loading.to_string();
+                tui.draw_with(|f| render_browser(f, &mut state))?;
+
+                if in_episodes {
+                    let result = match (state.series.as_ref(), state.selected_episode()) {
+                        (Some(series), Some(episode)) => Some(
+                            Playback::start(
+                                client.clone(),
+                                series,
+                                episode,
+                                0.0,
+                                state.volume,
+                                state.device.clone(),
+                            )
+                            .await,
+                        ),
+                        _ => None,
+                    };
+                    match result {
+                        Some(Ok(playback)) => {
+                            state.playback = Some(playback);
+                            state.message.clear();
+                        }
+                        Some(Err(e)) => state.message = format!("{}: {}", tr().cannot_play, e),
+                        None => state.message.clear(),
+                    }
+                } else if let Some(corner) = state.selected_corner().cloned() {
+                    match client
+                        .fetch_ondemand_series(&corner.series_site_id, &corner.corner_site_id)
+                        .await
+                    {
+                        Ok(series) => {
+                            state.episode_list.select(Some(0));
+                            state.series = Some(series);
+                            state.message.clear();
+                        }
+                        Err(e) => state.message = format!("{}: {}", tr().cannot_fetch, e),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    state.playback = None;
+    drop(tui);
+    Ok(())
+}
+
+fn render_browser(f: &mut Frame, state: &mut BrowserState) {
+    let theme = state.theme;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Min(5),    // Content
+            Constraint::Length(1), // Message
+            Constraint::Length(1), // Help
+        ])
+        .split(f.area());
+
+    let help = if let Some(ref playback) = state.playback {
+        render_playback(f, chunks[0], playback, state.volume, &theme);
+        tr().ondemand_help_playback
+    } else if let Some(ref series) = state.series {
+        let items: Vec<ListItem> = series
+            .episodes
+            .iter()
+            .map(|ep| ListItem::new(format!("{}  {}", ep.onair_date, ep.program_title)))
+            .collect();
+        let list = List::new(items)
+            .block(list_block(format!(" {} ", series.title), &theme))
+            .highlight_style(theme.selected())
+            .highlight_symbol("▶ ");
+        f.render_stateful_widget(list, chunks[0], &mut state.episode_list);
+        tr().ondemand_help_episodes
+    } else {
+        let width = chunks[0].width.saturating_sub(8) as usize;
+        let items: Vec<ListItem> = state
+            .corners
+            .iter()
+            .map(|c| {
+                let name = if c.corner_name.is_empty() {
+                    c.title.clone()
+                } else {
+                    format!("{} {}", c.title, c.corner_name)
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("[{}] ", c.radio_broadcast),
+                        Style::default().fg(theme.info),
+                    ),
+                    Span::raw(truncate_str(&name, width)),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(list_block(tr().ondemand_title.to_string(), &theme))
+            .highlight_style(theme.selected())
+            .highlight_symbol("▶ ");
+        f.render_stateful_widget(list, chunks[0], &mut state.corner_list);
+        tr().ondemand_help_corners
+    };
+
+    f.render_widget(
+        Paragraph::new(state.message.as_str()).style(Style::default().fg(theme.highlight)),
+        chunks[1],
+    );
+    f.render_widget(
+        Paragraph::new(help)
+            .style(Style::default().fg(theme.muted))
+            .alignment(ratatui::layout::Alignment::Center),
+        chunks[2],
+    );
+}
+
+fn render_playback(f: &mut Frame, area: Rect, playback: &Playback, volume: u8, theme: &Theme) {
+    let block = list_block(format!(" 📻 {} ", playback.title), theme);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2), // Episode title
+            Constraint::Length(1), // Gauge
+            Constraint::Length(1), // Status
+            Constraint::Min(0),
+        ])
+        .split(inner);
+
+    f.render_widget(
+        Paragraph::new(Span::styled(
+            format!("♪ {}", playback.subtitle),
+            Style::default()
+                .fg(theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        )),
+        rows[0],
+    );
+
+    let position = playback.position();
+    let ratio = if playback.duration > 0.0 {
+        (position / playback.duration).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let gauge = Gauge::default()
+        .gauge_style(Style::default().fg(theme.accent))
+        .ratio(ratio)
+        .label(format!(
+            "{} / {}",
+            format_position(position),
+            format_position(playback.duration)
+        ));
+    f.render_widget(gauge, rows[1]);
+
+    let status = if playback.is_paused {
+        tr().paused
+    } else {
+        tr().playing
+    };
+    f.render_widget(
+        Paragraph::new(format!("{}   {}", status, volume_gauge(volume, false)))
+            .style(Style::default().fg(theme.info)),
+        rows[2],
+    );
+}
+
+fn list_block(title: String, theme: &Theme) -> Block<'static> {
+    Block::default()
+        .title(title)
+        .title_style(theme.title())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent))
+}
@@ -0,0 +1,670 @@
+//! Results of the informational commands, printable as text, JSON, or YAML.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+use unicode_width::UnicodeWidthStr;
+
+use crate::device::{default_device, output_devices};
+use crate::error::ErrorKind;
+use crate::favorites::Favorite;
+use crate::genre;
+use crate::player::ChannelKind;
+use crate::types::{BroadcastEvent, Channel, RadiruConfig, ScheduledProgram};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable tables
+    #[default]
+    Text,
+    Json,
+    Yaml,
+}
+
+/// How a failed command reports its error on stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorFormat {
+    /// `Error: ` and the message
+    #[default]
+    Text,
+    /// One JSON object with the error's kind, message, and exit code
+    Json,
+}
+
+/// The JSON form of `error`
+fn error_json(error: &anyhow::Error) -> serde_json::Value {
+    let kind = ErrorKind::of(error);
+    serde_json::json!({
+        "error": kind,
+        "message": error.to_string(),
+        "exit_code": kind.exit_code(),
+    })
+}
+
+/// Report `error` in `format`, returning the exit code for its kind
+pub fn report_error(error: &anyhow::Error, format: ErrorFormat) -> i32 {
+    match format {
+        ErrorFormat::Text => eprintln!("Error: {}", error),
+        ErrorFormat::Json => eprintln!("{}", error_json(error)),
+    }
+    ErrorKind::of(error).exit_code()
+}
+
+/// A command result that also knows its human-readable form
+pub trait Report: Serialize {
+    fn print_text(&self);
+}
+
+/// Print `report` in the requested format
+pub fn print<T: Report>(report: &T, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Text => report.print_text(),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(report)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(report)?),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct AreaEntry {
+    pub code: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct AreaList(pub Vec<AreaEntry>);
+
+impl AreaList {
+    pub fn new(config: &RadiruConfig) -> Self {
+        Self(
+            config
+                .stream_url
+                .data
+                .iter()
+                .map(|d| AreaEntry {
+                    code: d.area.clone(),
+                    name: d.areajp.clone(),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Report for AreaList {
+    fn print_text(&self) {
+        println!("Available areas:");
+        println!("{:<10} Area Name", "Area Code");
+        println!("{}", "-".repeat(40));
+        for area in &self.0 {
+            println!("{:<10} {}", area.code, area.name);
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StreamEntry {
+    pub area: String,
+    pub name: String,
+    pub r1: String,
+    pub r2: String,
+    pub fm: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct StreamList(pub Vec<StreamEntry>);
+
+impl StreamList {
+    pub fn new(config: &RadiruConfig) -> Self {
+        Self(
+            config
+                .stream_url
+                .data
+                .iter()
+                .map(|d| StreamEntry {
+                    area: d.area.clone(),
+                    name: d.areajp.clone(),
+                    r1: d.r1hls.clone(),
+                    r2: d.r2hls.clone(),
+                    fm: d.fmhls.clone(),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Report for StreamList {
+    fn print_text(&self) {
+        println!("Available streams:");
+        println!();
+        for stream in &self.0 {
+            println!("Area: {} ({})", stream.area, stream.name);
+            println!("  R1 HLS: {}", stream.r1);
+            println!("  R2 HLS: {}", stream.r2);
+            println!("  FM HLS: {}", stream.fm);
+            println!();
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceEntry {
+    pub name: String,
+    pub default: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct DeviceList(pub Vec<DeviceEntry>);
+
+impl DeviceList {
+    pub fn new() -> Result<Self> {
+        let default = default_device();
+        Ok(Self(
+            output_devices()?
+                .into_iter()
+                .map(|name| DeviceEntry {
+                    default: default.as_ref() == Some(&name),
+                    name,
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl Report for DeviceList {
+    fn print_text(&self) {
+        println!("Audio output devices:");
+        for device in &self.0 {
+            let marker = if device.default { "*" } else { " " };
+            println!("{} {}", marker, device.name);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgramSummary {
+    pub id: String,
+    /// `radioSeriesId`, for `favorite add --series`
+    #[serde(default)]
+    pub series_id: String,
+    /// Name of the series the program is an episode of
+    #[serde(default)]
+    pub series: String,
+    pub name: String,
+    pub description: String,
+    pub start: String,
+    pub end: String,
+    /// Readable names of the program's genres
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+}
+
+impl ProgramSummary {
+    pub fn new(event: &BroadcastEvent) -> Self {
+        // Prefer the series-level info when the event carries it
+        let (id, name, description) = match event.about {
+            Some(ref about) => (&about.id, &about.name, &about.description),
+            None => (&event.id, &event.name, &event.description),
+        };
+        let series = match event.about {
+            Some(ref about) => &about.part_of_series.name,
+            None => &event.identifier_group.radio_series_name,
+        };
+        Self {
+            id: id.clone(),
+            series_id: event.identifier_group.radio_series_id.clone(),
+            series: series.clone(),
+            name: name.clone(),
+            description: description.clone(),
+            start: event.start_date.clone(),
+            end: event.end_date.clone(),
+            genres: genre::labels(&event.identifier_group.genre),
+        }
+    }
+}
+
+/// Which of the three programs NHK lists for a channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Slot {
+    /// The program before the one on air
+    Previous,
+    /// The program on air
+    #[default]
+    Present,
+    /// The next program
+    Following,
+}
+
+impl Slot {
+    /// Name of the slot in NHK's listing and in our JSON
+    fn key(self) -> &'static str {
+        match self {
+            Slot::Previous => "previous",
+            Slot::Present => "present",
+            Slot::Following => "following",
+        }
+    }
+
+    fn heading(self) -> &'static str {
+        match self {
+            Slot::Previous => "Previous Program",
+            Slot::Present => "Current Program",
+            Slot::Following => "Next Program",
+        }
+    }
+}
+
+/// One program of a channel, serialized under its slot's name, e.g.
+/// `{"channel":"R1","present":{...}}`
+#[derive(Debug)]
+pub struct NowPlaying {
+    pub channel: String,
+    pub slot: Slot,
+    pub program: Option<ProgramSummary>,
+}
+
+impl NowPlaying {
+    pub fn new(kind: ChannelKind, channel: &Channel, slot: Slot) -> Self {
+        let event = match slot {
+            Slot::Previous => &channel.previous,
+            Slot::Present => &channel.present,
+            Slot::Following => &channel.following,
+        };
+        Self {
+            channel: kind.short_name().to_string(),
+            slot,
+            program: event.as_ref().map(ProgramSummary::new),
+        }
+    }
+}
+
+impl Serialize for NowPlaying {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("channel", &self.channel)?;
+        map.serialize_entry(self.slot.key(), &self.program)?;
+        map.end()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProgramReport {
+    pub area: String,
+    pub channels: Vec<NowPlaying>,
+}
+
+impl Report for ProgramReport {
+    fn print_text(&self) {
+        for channel in &self.channels {
+            println!("\n=== {} {} ===", channel.channel, channel.slot.heading());
+            match channel.program {
+                Some(ref program) => {
+                    println!("ID: {}", program.id);
+                    if !program.series.is_empty() {
+                        println!("Series: {}", program.series);
+                    }
+                    if !program.series_id.is_empty() {
+                        println!("Series ID: {}", program.series_id);
+                    }
+                    println!("Name: {}", program.name);
+                    println!(
+                        "Time: {} {}-{}",
+                        program.start.get(..10).unwrap_or_default(),
+                        clock_time(&program.start),
+                        clock_time(&program.end)
+                    );
+                    if !program.genres.is_empty() {
+                        println!("Genre: {}", program.genres.join(", "));
+                    }
+                    println!("Description: {}", program.description);
+                }
+                None => println!("No {}", channel.slot.heading().to_lowercase()),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NowEntry {
+    pub channel: String,
+    pub title: Option<String>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub next: Option<TimetableEntry>,
+}
+
+impl NowEntry {
+    pub fn new(kind: ChannelKind, channel: &Channel) -> Self {
+        let present = channel.present.as_ref();
+        Self {
+            channel: kind.short_name().to_string(),
+            title: present.map(|p| p.name.clone()),
+            start: present.map(|p| p.start_date.clone()),
+            end: present.map(|p| p.end_date.clone()),
+            next: channel.following.as_ref().map(|p| TimetableEntry {
+                start: p.start_date.clone(),
+                end: p.end_date.clone(),
+                title: p.name.clone(),
+                genres: genre::labels(&p.identifier_group.genre),
+            }),
+        }
+    }
+}
+
+/// One row per channel, for `now`
+#[derive(Debug, Serialize)]
+pub struct NowTable {
+    pub area: String,
+    pub channels: Vec<NowEntry>,
+}
+
+impl Report for NowTable {
+    fn print_text(&self) {
+        let titles: Vec<&str> = self
+            .channels
+            .iter()
+            .map(|c| c.title.as_deref().unwrap_or("-"))
+            .collect();
+        let width = titles.iter().map(|t| t.width()).max().unwrap_or(0);
+        for (channel, title) in self.channels.iter().zip(titles) {
+            let time = match (channel.start.as_deref(), channel.end.as_deref()) {
+                (Some(start), Some(end)) => format!("{}-{}", clock_time(start), clock_time(end)),
+                _ => "-".to_string(),
+            };
+            let next = channel
+                .next
+                .as_ref()
+                .map(|next| format!("{} {}", clock_time(&next.start), next.title))
+                .unwrap_or_default();
+            println!(
+                "{:<3} {:<11}  {}{}  → {}",
+                channel.channel,
+                time,
+                title,
+                " ".repeat(width - title.width()),
+                next
+            );
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimetableEntry {
+    pub start: String,
+    pub end: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelSchedule {
+    pub channel: String,
+    pub programs: Vec<TimetableEntry>,
+}
+
+impl ChannelSchedule {
+    /// The schedule of `programs` that `keep` accepts
+    pub fn new(
+        kind: ChannelKind,
+        programs: &[ScheduledProgram],
+        keep: impl Fn(&ScheduledProgram) -> bool,
+    ) -> Self {
+        Self {
+            channel: kind.short_name().to_string(),
+            programs: programs
+                .iter()
+                .filter(|p| keep(p))
+                .map(|p| TimetableEntry {
+                    start: p.start_date.clone(),
+                    end: p.end_date.clone(),
+                    title: p.name.clone(),
+                    genres: genre::labels(&p.identifier_group.genre),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Timetable {
+    pub area: String,
+    pub date: String,
+    pub channels: Vec<ChannelSchedule>,
+}
+
+impl Report for Timetable {
+    fn print_text(&self) {
+        for channel in &self.channels {
+            println!("\n=== {} {} ===", channel.channel, self.date);
+            if channel.programs.is_empty() {
+                println!("No programs found");
+            }
+            for program in &channel.programs {
+                println!(
+                    "{}-{}  {}{}",
+                    clock_time(&program.start),
+                    clock_time(&program.end),
+                    program.title,
+                    genre_suffix(&program.genres)
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpcomingEntry {
+    pub start: String,
+    pub end: String,
+    pub title: String,
+    /// Whole minutes from now until the start
+    pub minutes_until: Option<i64>,
+}
+
+impl UpcomingEntry {
+    pub fn new(start: &str, end: &str, title: &str, now: DateTime<Utc>) -> Self {
+        Self {
+            start: start.to_string(),
+            end: end.to_string(),
+            title: title.to_string(),
+            minutes_until: DateTime::parse_from_rfc3339(start)
+                .ok()
+                .map(|start| (start.with_timezone(&Utc) - now).num_minutes().max(0)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelUpcoming {
+    pub channel: String,
+    pub programs: Vec<UpcomingEntry>,
+}
+
+/// The next programs on each channel, for `upcoming`
+#[derive(Debug, Serialize)]
+pub struct UpcomingList {
+    pub area: String,
+    pub channels: Vec<ChannelUpcoming>,
+}
+
+impl Report for UpcomingList {
+    fn print_text(&self) {
+        for channel in &self.channels {
+            if channel.programs.is_empty() {
+                println!("{:<3} No upcoming programs", channel.channel);
+            }
+            for (i, program) in channel.programs.iter().enumerate() {
+                let until = program
+                    .minutes_until
+                    .map(|minutes| format!("in {} min", minutes))
+                    .unwrap_or_default();
+                println!(
+                    "{:<3} {}-{}  {:>11}  {}",
+                    if i == 0 { channel.channel.as_str() } else { "" },
+                    clock_time(&program.start),
+                    clock_time(&program.end),
+                    until,
+                    program.title
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub area: String,
+    pub channel: String,
+    pub start: String,
+    pub end: String,
+    pub title: String,
+    pub series_id: String,
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+}
+
+impl SearchHit {
+    pub fn new(area: &str, kind: ChannelKind, program: &ScheduledProgram) -> Self {
+        Self {
+            area: area.to_string(),
+            channel: kind.short_name().to_string(),
+            start: program.start_date.clone(),
+            end: program.end_date.clone(),
+            title: program.name.clone(),
+            series_id: program.identifier_group.radio_series_id.clone(),
+            url: program.page_url().map(str::to_string),
+            genres: genre::labels(&program.identifier_group.genre),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct SearchResults(pub Vec<SearchHit>);
+
+impl Report for SearchResults {
+    fn print_text(&self) {
+        if self.0.is_empty() {
+            println!("No programs found");
+        }
+        for hit in &self.0 {
+            println!(
+                "{} {}-{}  {:<2} {}  {}{}",
+                hit.start.get(..10).unwrap_or_default(),
+                clock_time(&hit.start),
+                clock_time(&hit.end),
+                hit.channel,
+                hit.area,
+                hit.title,
+                genre_suffix(&hit.genres)
+            );
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct FavoriteList(pub Vec<Favorite>);
+
+impl Report for FavoriteList {
+    fn print_text(&self) {
+        if self.0.is_empty() {
+            println!("No favorites");
+        }
+        for favorite in &self.0 {
+            let mut matched_by = Vec::new();
+            if let Some(ref id) = favorite.series_id {
+                matched_by.push(format!("series {}", id));
+            }
+            if let Some(ref title) = favorite.title {
+                matched_by.push(format!("title contains \"{}\"", title));
+            }
+            println!("★ {}  ({})", favorite.label(), matched_by.join(", "));
+        }
+    }
+}
+
+/// `HH:MM` part of an ISO timestamp like "2025-11-25T23:00:00+09:00"
+/// e.g. "  (Music: Jazz and fusion)" after a title, or nothing
+fn genre_suffix(genres: &[String]) -> String {
+    if genres.is_empty() {
+        String::new()
+    } else {
+        format!("  ({})", genres.join(", "))
+    }
+}
+
+pub(crate) fn clock_time(iso_time: &str) -> &str {
+    iso_time.get(11..16).unwrap_or(iso_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_time() {
+        assert_eq!(clock_time("2025-11-25T23:00:00+09:00"), "23:00");
+        assert_eq!(clock_time("bad"), "bad");
+    }
+
+    #[test]
+    fn test_upcoming_entry() {
+        let now = DateTime::parse_from_rfc3339("2025-11-25T19:48:30+09:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let entry = UpcomingEntry::new(
+            "2025-11-25T20:00:00+09:00",
+            "2025-11-25T20:55:00+09:00",
+            "NHKジャーナル",
+            now,
+        );
+        assert_eq!(entry.minutes_until, Some(11));
+        assert_eq!(UpcomingEntry::new("", "", "", now).minutes_until, None);
+    }
+
+    #[test]
+    fn test_error_json() {
+        let error = ErrorKind::AreaNotFound.error("Area not found: atlantis");
+        assert_eq!(
+            error_json(&error),
+            serde_json::json!({
+                "error": "area-not-found",
+                "message": "Area not found: atlantis",
+                "exit_code": 4,
+            })
+        );
+    }
+
+    #[test]
+    fn test_area_list_serializes_as_array() {
+        let areas = AreaList(vec![AreaEntry {
+            code: "tokyo".to_string(),
+            name: "東京".to_string(),
+        }]);
+        assert_eq!(
+            serde_json::to_string(&areas).unwrap(),
+            r#"[{"code":"tokyo","name":"東京"}]"#
+        );
+    }
+
+    #[test]
+    fn test_now_playing_keyed_by_slot() {
+        let channel = Channel::default();
+        let present = NowPlaying::new(ChannelKind::R1, &channel, Slot::default());
+        assert_eq!(
+            serde_json::to_string(&present).unwrap(),
+            r#"{"channel":"R1","present":null}"#
+        );
+        let next = NowPlaying::new(ChannelKind::Fm, &channel, Slot::Following);
+        assert_eq!(
+            serde_json::to_string(&next).unwrap(),
+            r#"{"channel":"FM","following":null}"#
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! `play-url`: any HLS audio stream through the same pipeline as the radio.
+//!
+//! A live playlist is followed like an NHK channel, with master playlist
+//! resolution, key fetching, and decoding all done by the playback engine.
+//! A playlist that ends with EXT-X-ENDLIST is played once from the start,
+//! like a 聴き逃し episode.
+
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::audio_queue::audio_queue;
+use crate::client::{NhkRadioClient, RadioHttp};
+use crate::engine::PlaybackEngine;
+use crate::i18n::tr;
+use crate::m3u8::{ParsedPlaylist, parse_media_m3u8, parse_playlist, select_variant};
+use crate::ondemand::{PREFETCH_SECS, VodPlaylist, format_position, run_vod_loop};
+use crate::player::{AudioControls, ChannelKind, PlaybackEvent, PlayerOptions, run_audio_thread};
+use crate::types::StreamData;
+
+/// Play the HLS stream at `url` until it ends or Ctrl-C
+pub async fn run_url_player(url: String, options: PlayerOptions) -> Result<()> {
+    let client = Arc::new(NhkRadioClient::with_options(options.http.clone())?);
+    if is_on_demand(client.as_ref(), &url, options.stream.max_bitrate).await? {
+        play_on_demand(client, &url, options).await
+    } else {
+        play_live(client, url, options).await
+    }
+}
+
+/// Whether the playlist at `url`, or the variant that would be played, is
+/// complete rather than live
+async fn is_on_demand(
+    client: &impl RadioHttp,
+    url: &str,
+    max_bitrate: Option<u64>,
+) -> Result<bool> {
+    let content = client.fetch_m3u8(url).await?;
+    let (media_url, content) = match parse_playlist(&content, url)? {
+        ParsedPlaylist::Media(_) => (url.to_string(), content),
+        ParsedPlaylist::Master(variants) => {
+            let media_url = variants[select_variant(&variants, max_bitrate)].url.clone();
+            let content = client.fetch_m3u8(&media_url).await?;
+            (media_url, content)
+        }
+    };
+    Ok(parse_media_m3u8(&content, &media_url)?.end_list)
+}
+
+async fn play_live(client: Arc<NhkRadioClient>, url: String, options: PlayerOptions) -> Result<()> {
+    println!("▶ {}", url);
+    // The engine plays channels of an area, so make one whose every channel is `url`
+    let stream_data = StreamData {
+        areajp: String::new(),
+        area: "url".to_string(),
+        apikey: String::new(),
+        areakey: String::new(),
+        r1hls: url.clone(),
+        r2hls: url.clone(),
+        fmhls: url,
+    };
+    let engine = PlaybackEngine::start(client, stream_data, ChannelKind::R1, options);
+    let mut tick = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {
+                while let Some(event) = engine.try_event() {
+                    match event {
+                        PlaybackEvent::Error(e) => eprintln!("{}: {}", tr().playback_error, e),
+                        PlaybackEvent::OutputLost => eprintln!("{}", tr().output_lost),
+                        PlaybackEvent::OutputRestored => eprintln!("{}", tr().playback_resumed),
+                        PlaybackEvent::Silence => eprintln!("{}", tr().silence),
+                        PlaybackEvent::SoundReturned => eprintln!("{}", tr().sound_returned),
+                        _ => {}
+                    }
+                }
+            }
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+async fn play_on_demand(
+    client: Arc<NhkRadioClient>,
+    url: &str,
+    options: PlayerOptions,
+) -> Result<()> {
+    let playlist = Arc::new(VodPlaylist::load(client.as_ref(), url).await?);
+    let duration = playlist.duration;
+    println!("▶ {} ({})", url, format_position(duration));
+
+    let (_seek_tx, seek_rx) = watch::channel(0.0);
+    let (_paused_tx, paused_rx) = watch::channel(false);
+    let (_volume_tx, volume_rx) = watch::channel(options.volume as f32 / 100.0);
+    let (_device_tx, device_rx) = watch::channel(options.device);
+    let (position_tx, position_rx) = watch::channel(0.0);
+    let (_command_tx, command_rx) = std::sync::mpsc::channel();
+    let (timeshift_tx, _) = watch::channel(0.0);
+    let (queued_tx, queued_rx) = watch::channel(0.0);
+    let (levels_tx, _) = watch::channel([0.0; 2]);
+    // Room for everything the VOD loop prefetches, so nothing is dropped
+    let (audio_tx, audio_rx) = audio_queue(PREFETCH_SECS * 2.0);
+    let (events_tx, _events_rx) = std::sync::mpsc::channel();
+
+    let controls = AudioControls {
+        paused_rx: paused_rx.clone(),
+        volume_rx,
+        device_rx,
+        command_rx,
+        timeshift_tx,
+        queued_tx,
+        levels_tx,
+        stats: Arc::default(),
+    };
+    // The thread exits once the VOD task drops `audio_tx`
+    std::thread::spawn(move || run_audio_thread(audio_rx, controls, 0, events_tx));
+    let task = tokio::spawn(run_vod_loop(
+        client,
+        playlist,
+        seek_rx,
+        paused_rx,
+        audio_tx,
+        position_tx,
+    ));
+
+    let mut tick = tokio::time::interval(Duration::from_millis(500));
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {
+                if *position_rx.borrow() >= duration && *queued_rx.borrow() <= 0.0 {
+                    break;
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    task.abort();
+    Ok(())
+}
@@ -0,0 +1,415 @@
+//! 聴き逃し series kept as private podcasts.
+//!
+//! For each series followed under `[podcast]` in the config file, `podcast`
+//! downloads the episodes NHK offers that are not on disk yet into a
+//! directory of their own, and writes `feed.xml` there: an RSS 2.0 feed with
+//! iTunes tags whose enclosures point at the files under the URL the
+//! directories are served at. What is known of each episode is kept in
+//! `episodes.json`, so episodes stay in the feed after NHK stops offering
+//! them, for as long as their files are kept.
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::client::NhkRadioClient;
+use crate::download::{TrackInfo, download_episode, sanitize_file_name};
+use crate::ondemand::VodPlaylist;
+use crate::recorder::RecordFormat;
+use crate::types::OndemandSeries;
+
+/// Settings from the `[podcast]` table
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PodcastConfig {
+    /// Directory the series' directories are kept in
+    pub dir: Option<PathBuf>,
+    /// URL `dir` is served at, for the enclosure links
+    pub base_url: Option<String>,
+    /// Series to keep up to date, from `[[podcast.series]]`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub series: Vec<FollowedSeries>,
+}
+
+impl PodcastConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == PodcastConfig::default()
+    }
+}
+
+/// A series by the site IDs `ondemand list` shows
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FollowedSeries {
+    pub series: String,
+    pub corner: String,
+}
+
+/// One downloaded episode, as the feed lists it
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct FeedEpisode {
+    /// Name of the audio file in the series' directory
+    file: String,
+    title: String,
+    description: String,
+    /// RFC 3339 time it aired, or was downloaded when NHK's date is unclear
+    published: String,
+    duration_secs: u64,
+    bytes: u64,
+}
+
+/// A series' directory and the feed written into it
+#[derive(Debug)]
+struct SeriesFeed {
+    dir: PathBuf,
+    /// URL `dir` is served at, without a trailing slash
+    url: String,
+    title: String,
+    description: String,
+    author: String,
+    episodes: Vec<FeedEpisode>,
+}
+
+impl SeriesFeed {
+    /// The feed of `series` in its directory under `root`, with the episodes
+    /// downloaded before whose files are still there
+    fn open(root: &Path, base_url: &str, series: &OndemandSeries) -> Result<Self> {
+        let name = sanitize_file_name(format!("{} {}", series.title, series.corner_name).trim());
+        let dir = root.join(&name);
+        fs::create_dir_all(&dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", dir.display(), e))?;
+        let mut episodes: Vec<FeedEpisode> = fs::read_to_string(dir.join("episodes.json"))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        episodes.retain(|episode| dir.join(&episode.file).exists());
+        Ok(SeriesFeed {
+            dir,
+            url: format!("{}/{}", base_url.trim_end_matches('/'), url_encode(&name)),
+            title: series.title.clone(),
+            description: series.series_description.clone(),
+            author: format!("NHK {}", series.radio_broadcast).trim().to_string(),
+            episodes,
+        })
+    }
+
+    fn has(&self, file: &str) -> bool {
+        self.episodes.iter().any(|episode| episode.file == file)
+    }
+
+    /// List an episode, newest first
+    fn add(&mut self, episode: FeedEpisode) {
+        self.episodes.retain(|listed| listed.file != episode.file);
+        self.episodes.push(episode);
+        self.episodes.sort_by(|a, b| b.published.cmp(&a.published));
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join("feed.xml")
+    }
+
+    fn save(&self) -> Result<()> {
+        fs::write(
+            self.dir.join("episodes.json"),
+            serde_json::to_string_pretty(&self.episodes)?,
+        )?;
+        let path = self.path();
+        fs::write(&path, self.render())
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))
+    }
+
+    fn render(&self) -> String {
+        let mut xml = String::new();
+        let _ = writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        let _ = writeln!(
+            xml,
+            r#"<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">"#
+        );
+        let _ = writeln!(xml, "<channel>");
+        let _ = writeln!(xml, "  <title>{}</title>", escape(&self.title));
+        let _ = writeln!(xml, "  <link>{}/feed.xml</link>", escape(&self.url));
+        let _ = writeln!(
+            xml,
+            "  <description>{}</description>",
+            escape(&self.description)
+        );
+        let _ = writeln!(xml, "  <language>ja</language>");
+        let _ = writeln!(
+            xml,
+            "  <itunes:author>{}</itunes:author>",
+            escape(&self.author)
+        );
+        for episode in &self.episodes {
+            let published = DateTime::parse_from_rfc3339(&episode.published)
+                .map(|time| time.to_rfc2822())
+                .unwrap_or_default();
+            let url = format!("{}/{}", self.url, url_encode(&episode.file));
+            let mime = mime_type(RecordFormat::from_path(Path::new(&episode.file)));
+            let _ = writeln!(xml, "  <item>");
+            let _ = writeln!(xml, "    <title>{}</title>", escape(&episode.title));
+            let _ = writeln!(
+                xml,
+                "    <description>{}</description>",
+                escape(&episode.description)
+            );
+            let _ = writeln!(xml, "    <pubDate>{}</pubDate>", published);
+            let _ = writeln!(
+                xml,
+                r#"    <guid isPermaLink="false">{}</guid>"#,
+                escape(&episode.file)
+            );
+            let _ = writeln!(
+                xml,
+                r#"    <enclosure url="{}" length="{}" type="{}"/>"#,
+                escape(&url),
+                episode.bytes,
+                mime
+            );
+            let _ = writeln!(
+                xml,
+                "    <itunes:duration>{}</itunes:duration>",
+                episode.duration_secs
+            );
+            let _ = writeln!(xml, "  </item>");
+        }
+        let _ = writeln!(xml, "</channel>");
+        let _ = writeln!(xml, "</rss>");
+        xml
+    }
+}
+
+/// Download what is new of `followed` into its directory under `root` and
+/// rewrite its feed, returning the feed's path and the episodes added
+///
+/// An episode that fails to download is skipped until the next run, which
+/// resumes it.
+pub async fn update_series(
+    client: Arc<NhkRadioClient>,
+    followed: &FollowedSeries,
+    root: &Path,
+    base_url: &str,
+    format: RecordFormat,
+    jobs: usize,
+) -> Result<(PathBuf, usize)> {
+    let series = client
+        .fetch_ondemand_series(&followed.series, &followed.corner)
+        .await?;
+    let mut feed = SeriesFeed::open(root, base_url, &series)?;
+    let mut added = 0;
+    for episode in &series.episodes {
+        let info = TrackInfo::new(&series, episode);
+        let file = info.file_name(format);
+        let path = feed.dir.join(&file);
+        // A file the feed does not list is left from an interrupted run
+        if feed.has(&file) {
+            continue;
+        }
+        println!("{} - {}", info.series, info.title);
+        let result = async {
+            let playlist = VodPlaylist::load(client.as_ref(), &episode.stream_url).await?;
+            download_episode(client.clone(), &playlist, &info, &path, format, jobs).await?;
+            Ok::<_, anyhow::Error>(playlist.duration)
+        }
+        .await;
+        let duration = match result {
+            Ok(duration) => duration,
+            Err(e) => {
+                log::warn!("Failed to download {}: {}", file, e);
+                continue;
+            }
+        };
+        let now = Local::now().fixed_offset();
+        feed.add(FeedEpisode {
+            bytes: fs::metadata(&path)
+                .map(|meta| meta.len())
+                .unwrap_or_default(),
+            file,
+            title: info.title,
+            description: info.description,
+            published: air_time(&episode.onair_date, now)
+                .unwrap_or(now)
+                .to_rfc3339(),
+            duration_secs: duration.round() as u64,
+        });
+        added += 1;
+        // Saved as it goes, so an interrupted run keeps what it fetched
+        feed.save()?;
+    }
+    feed.save()?;
+    Ok((feed.path(), added))
+}
+
+/// When an episode aired, from NHK's `onair_date` such as
+/// "2025年11月25日(火)午後9:00放送" or "11月25日(火)午後9:00放送"
+///
+/// Without a year the latest one not after `now` is taken; without a time,
+/// midnight. Times are Japan time.
+fn air_time(onair_date: &str, now: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    // Each number with the character after it
+    let text: String = onair_date
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+            '：' => ':',
+            c => c,
+        })
+        .collect();
+    let mut numbers: Vec<(u32, Option<char>)> = Vec::new();
+    let mut digits = String::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if c.is_ascii_digit() {
+            digits.push(c);
+        } else if !digits.is_empty() {
+            numbers.push((digits.parse().ok()?, Some(c)));
+            digits.clear();
+        }
+    }
+    let find = |unit: char| {
+        numbers
+            .iter()
+            .find(|(_, after)| *after == Some(unit))
+            .map(|(n, _)| *n)
+    };
+    let month = find('月')?;
+    let day = find('日')?;
+    let (mut hour, minute) = numbers
+        .windows(2)
+        .find(|pair| pair[0].1 == Some(':'))
+        .map_or((0, 0), |pair| (pair[0].0, pair[1].0));
+    if text.contains("午後") && hour < 12 {
+        hour += 12;
+    }
+
+    let jst = FixedOffset::east_opt(9 * 3600)?;
+    let at = |year: i32| {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .and_then(|date| date.and_hms_opt(hour, minute, 0))
+            .and_then(|time| jst.from_local_datetime(&time).single())
+    };
+    match find('年') {
+        Some(year) => at(year as i32),
+        None => {
+            let year = now.with_timezone(&jst).year();
+            at(year)
+                .filter(|time| *time <= now + chrono::Duration::days(1))
+                .or_else(|| at(year - 1))
+        }
+    }
+}
+
+fn mime_type(format: RecordFormat) -> &'static str {
+    match format {
+        RecordFormat::Raw => "audio/aac",
+        RecordFormat::Mp3 => "audio/mpeg",
+        RecordFormat::Opus => "audio/ogg",
+        RecordFormat::Flac => "audio/flac",
+        RecordFormat::Wav => "audio/wav",
+    }
+}
+
+/// `text` with the characters XML gives meaning to replaced
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// One URL path segment holding `name`
+fn url_encode(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(time).unwrap()
+    }
+
+    #[test]
+    fn test_air_time() {
+        let now = at("2026-01-10T12:00:00+09:00");
+        assert_eq!(
+            air_time("2025年11月25日(火)午後9:00放送", now),
+            Some(at("2025-11-25T21:00:00+09:00"))
+        );
+        // December is last year's, January this year's
+        assert_eq!(
+            air_time("12月30日(火)午前5:30放送", now),
+            Some(at("2025-12-30T05:30:00+09:00"))
+        );
+        assert_eq!(
+            air_time("１月９日(金)午後０：１５放送", now),
+            Some(at("2026-01-09T12:15:00+09:00"))
+        );
+        assert_eq!(
+            air_time("1月3日", now),
+            Some(at("2026-01-03T00:00:00+09:00"))
+        );
+        assert_eq!(air_time("放送日未定", now), None);
+    }
+
+    #[test]
+    fn test_feed() {
+        let root = std::env::temp_dir().join(format!("nhk-podcast-{}", std::process::id()));
+        let series = OndemandSeries {
+            title: "ラジオ英会話".to_string(),
+            radio_broadcast: "R2".to_string(),
+            schedule: String::new(),
+            corner_name: String::new(),
+            series_description: "Q&A <毎日>".to_string(),
+            series_site_id: "XYZ".to_string(),
+            corner_site_id: "01".to_string(),
+            episodes: vec![],
+        };
+        let mut feed = SeriesFeed::open(&root, "http://nas.local:8000/", &series).unwrap();
+        let episode = |file: &str, published: &str| FeedEpisode {
+            file: file.to_string(),
+            title: file.to_string(),
+            description: String::new(),
+            published: published.to_string(),
+            duration_secs: 900,
+            bytes: 1234,
+        };
+        fs::write(feed.dir.join("a 11.aac"), b"a").unwrap();
+        fs::write(feed.dir.join("b.mp3"), b"b").unwrap();
+        feed.add(episode("a 11.aac", "2025-11-24T06:15:00+09:00"));
+        feed.add(episode("b.mp3", "2025-11-25T06:15:00+09:00"));
+        feed.save().unwrap();
+        let xml = fs::read_to_string(feed.path()).unwrap();
+
+        // Reopened, an episode whose file is gone drops out
+        fs::remove_file(feed.dir.join("b.mp3")).unwrap();
+        let reopened = SeriesFeed::open(&root, "http://nas.local:8000", &series).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            reopened.episodes,
+            [episode("a 11.aac", "2025-11-24T06:15:00+09:00")]
+        );
+
+        assert!(xml.contains("<description>Q&amp;A &lt;毎日&gt;</description>"));
+        assert!(xml.contains("<itunes:author>NHK R2</itunes:author>"));
+        // Newest first, enclosures escaped for URLs
+        let newest = xml.find("<title>b.mp3</title>").unwrap();
+        assert!(newest < xml.find("<title>a 11.aac</title>").unwrap());
+        let dir = "%E3%83%A9%E3%82%B8%E3%82%AA%E8%8B%B1%E4%BC%9A%E8%A9%B1";
+        assert!(xml.contains(&format!(
+            r#"<enclosure url="http://nas.local:8000/{}/a%2011.aac" length="1234" type="audio/aac"/>"#,
+            dir
+        )));
+        assert!(xml.contains("<pubDate>Tue, 25 Nov 2025 06:15:00 +0900</pubDate>"));
+    }
+}
@@ -0,0 +1,363 @@
+//! What the player knows about the program on air.
+//!
+//! [`ProgramInfo`] gathers what NHK's listing says about one channel's
+//! program: its title and air time, the programs either side, the cast, the
+//! music list, and links. The TUI shows it, and scrobbling and cue sheets
+//! work from its music list.
+
+use chrono::{DateTime, FixedOffset};
+use std::time::Duration;
+
+use crate::i18n::tr;
+use crate::output::clock_time;
+use crate::player::ChannelKind;
+use crate::types::{About, BroadcastEvent, Images, Music, Root};
+
+/// What is known about the program on air, as the player shows it
+pub struct ProgramInfo {
+    pub station_name: String,
+    pub area_name: String,
+    pub program_title: String,
+    pub start_time: String,
+    pub description: String,
+    /// Air time of the program shown, for the progress gauge
+    pub airs: Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)>,
+    /// The program before the one shown, when the EPG has it
+    pub previous: Option<ProgramSlot>,
+    /// The program after the one shown, when the EPG has it
+    pub next: Option<ProgramSlot>,
+    /// Hosts and guests, e.g. "司会: 山田太郎"
+    pub cast: Vec<String>,
+    /// Pieces played, in broadcast order
+    pub music: Vec<MusicPiece>,
+    /// Picture of the program or its series
+    pub eyecatch_url: Option<String>,
+    /// Logo of the station, for Discord presence
+    pub station_logo_url: Option<String>,
+    /// Web page of the episode, else of its series
+    pub page_url: Option<String>,
+    /// Hashtags of the episode, else of its series, each starting with `#`
+    pub hashtags: Vec<String>,
+    /// `radioSeriesId` of the program, for favorites
+    pub series_id: String,
+    /// Name of the program's series, else its title
+    pub series_name: String,
+}
+
+/// Start time and title of a neighbouring program
+pub struct ProgramSlot {
+    /// `HH:MM`
+    pub start_time: String,
+    pub title: String,
+}
+
+impl ProgramSlot {
+    fn new(event: &BroadcastEvent) -> Self {
+        ProgramSlot {
+            start_time: clock_time(&event.start_date).to_string(),
+            title: event_title(event),
+        }
+    }
+}
+
+/// One entry of a program's music list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MusicPiece {
+    pub title: String,
+    pub composer: String,
+    /// Performers with their part, e.g. "管弦楽: NHK交響楽団"
+    pub performers: Vec<String>,
+    /// Performers' names alone
+    pub artists: Vec<String>,
+    /// Length of the piece, when the list gives it
+    pub duration: Option<Duration>,
+}
+
+impl MusicPiece {
+    fn new(music: &Music) -> Self {
+        MusicPiece {
+            title: music.name.clone(),
+            composer: music.composer.clone(),
+            artists: music
+                .by_artist
+                .iter()
+                .map(|artist| artist.name.clone())
+                .filter(|name| !name.is_empty())
+                .collect(),
+            duration: parse_piece_duration(&music.duration),
+            performers: music
+                .by_artist
+                .iter()
+                .map(|artist| {
+                    let part = [&artist.part, &artist.role]
+                        .into_iter()
+                        .find(|part| !part.is_empty());
+                    credit(part.map(String::as_str), &artist.name)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl MusicPiece {
+    /// Who the piece is by, for scrobbling: the composer, else the performers
+    pub fn artist(&self) -> String {
+        if self.composer.is_empty() {
+            self.artists.join(", ")
+        } else {
+            self.composer.clone()
+        }
+    }
+}
+
+/// A music list duration such as "PT4M30S", "04:30", "1:02:03", or "4分30秒"
+fn parse_piece_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let secs = if let Some(iso) = text.strip_prefix("PT") {
+        let mut secs = 0;
+        let mut number = String::new();
+        for c in iso.chars() {
+            match c {
+                'H' | 'M' | 'S' => {
+                    let unit = match c {
+                        'H' => 3600,
+                        'M' => 60,
+                        _ => 1,
+                    };
+                    secs += number.parse::<f64>().ok()? as u64 * unit;
+                    number.clear();
+                }
+                c => number.push(c),
+            }
+        }
+        secs
+    } else if text.contains(':') {
+        text.split(':')
+            .try_fold(0, |secs, part| Some(secs * 60 + part.parse::<u64>().ok()?))?
+    } else {
+        let minutes_secs = text.strip_suffix('秒').unwrap_or(text);
+        let (minutes, secs) = minutes_secs.split_once('分').unwrap_or(("0", minutes_secs));
+        let secs = if secs.is_empty() { "0" } else { secs };
+        minutes.parse::<u64>().ok()? * 60 + secs.parse::<u64>().ok()?
+    };
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// "role: name", or just the name when the role is not given
+pub(crate) fn credit(role: Option<&str>, name: &str) -> String {
+    match role.filter(|role| !role.is_empty()) {
+        Some(role) => format!("{}: {}", role, name),
+        None => name.to_string(),
+    }
+}
+
+impl ProgramInfo {
+    pub fn from_program(program: &Option<Root>, kind: ChannelKind, area_name: &str) -> Self {
+        Self::from_program_at(program, kind, area_name, None)
+    }
+
+    /// Info for the program airing at `at`, e.g. while listening behind live
+    ///
+    /// Falls back to the present program when `at` is unknown or outside the
+    /// previous/present/following window.
+    pub fn from_program_at(
+        program: &Option<Root>,
+        kind: ChannelKind,
+        area_name: &str,
+        at: Option<DateTime<FixedOffset>>,
+    ) -> Self {
+        let listing = program.as_ref().map(|p| match kind {
+            ChannelKind::R1 => &p.r1,
+            ChannelKind::R2 => &p.r2,
+            ChannelKind::Fm => &p.r3,
+        });
+        let events = listing
+            .map(|channel| {
+                [
+                    channel.previous.as_ref(),
+                    channel.present.as_ref(),
+                    channel.following.as_ref(),
+                ]
+            })
+            .unwrap_or_default();
+        let airing = at
+            .and_then(|at| {
+                events
+                    .iter()
+                    .position(|event| event.is_some_and(|event| airs_at(event, at)))
+            })
+            .or(events[1].is_some().then_some(1));
+        let slot = |index: Option<usize>| {
+            index
+                .and_then(|i| events.get(i).copied().flatten())
+                .map(ProgramSlot::new)
+        };
+
+        let present = airing.and_then(|i| events[i]);
+        let airs = present.and_then(|event| {
+            Some((
+                DateTime::parse_from_rfc3339(&event.start_date).ok()?,
+                DateTime::parse_from_rfc3339(&event.end_date).ok()?,
+            ))
+        });
+        let (program_title, description, start_time) = present
+            .map(|present| {
+                let desc = present
+                    .about
+                    .as_ref()
+                    .map(|a| a.description.clone())
+                    .unwrap_or_default();
+                let time = format_time(&present.start_date);
+                (event_title(present), desc, time)
+            })
+            .unwrap_or_else(|| {
+                (
+                    tr().program_loading.to_string(),
+                    String::new(),
+                    String::new(),
+                )
+            });
+
+        ProgramInfo {
+            station_name: tr().channel_name(kind).to_string(),
+            area_name: area_name.to_string(),
+            program_title,
+            start_time,
+            description,
+            airs,
+            previous: slot(airing.and_then(|i| i.checked_sub(1))),
+            next: slot(airing.map(|i| i + 1)),
+            cast: present
+                .map(|event| {
+                    event
+                        .misc
+                        .act_list
+                        .iter()
+                        .map(|act| credit(act.role.as_deref(), &act.name))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            music: present
+                .map(|event| event.misc.music_list.iter().map(MusicPiece::new).collect())
+                .unwrap_or_default(),
+            eyecatch_url: present.and_then(eyecatch_url),
+            station_logo_url: listing
+                .and_then(|channel| channel.published_on.as_ref())
+                .and_then(|service| service.logo.thumbnail_url()),
+            page_url: present
+                .and_then(|event| event.about.as_ref())
+                .and_then(page_url),
+            hashtags: present
+                .and_then(|event| event.about.as_ref())
+                .map(hashtags)
+                .unwrap_or_default(),
+            series_id: present
+                .map(|event| event.identifier_group.radio_series_id.clone())
+                .unwrap_or_default(),
+            series_name: present
+                .map(|event| event.identifier_group.radio_series_name.clone())
+                .filter(|name| !name.is_empty())
+                .or_else(|| present.map(event_title))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The episode's own picture, else its series', else any the event lists
+fn eyecatch_url(event: &BroadcastEvent) -> Option<String> {
+    let about = event.about.as_ref();
+    about
+        .and_then(|about| about.eyecatch.as_ref())
+        .and_then(Images::thumbnail_url)
+        .or_else(|| about.and_then(|about| about.part_of_series.eyecatch.thumbnail_url()))
+        .or_else(|| event.eyecatch_list.iter().find_map(Images::thumbnail_url))
+}
+
+fn page_url(about: &About) -> Option<String> {
+    [&about.canonical, &about.part_of_series.canonical]
+        .into_iter()
+        .flatten()
+        .find(|url| !url.is_empty())
+        .cloned()
+}
+
+fn hashtags(about: &About) -> Vec<String> {
+    let episode = &about.identifier_group.hashtag;
+    let tags = if episode.is_empty() {
+        &about.part_of_series.identifier_group.hashtag
+    } else {
+        episode
+    };
+    tags.iter().filter_map(|tag| hashtag(tag)).collect()
+}
+
+/// `tag` with exactly one leading `#`, whichever way the API wrote it
+fn hashtag(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches(['#', '＃']);
+    (!tag.is_empty()).then(|| format!("#{}", tag))
+}
+
+fn event_title(event: &BroadcastEvent) -> String {
+    event
+        .about
+        .as_ref()
+        .map(|a| a.name.clone())
+        .unwrap_or_else(|| event.name.clone())
+}
+
+fn airs_at(event: &BroadcastEvent, at: DateTime<FixedOffset>) -> bool {
+    match (
+        DateTime::parse_from_rfc3339(&event.start_date),
+        DateTime::parse_from_rfc3339(&event.end_date),
+    ) {
+        (Ok(start), Ok(end)) => start <= at && at < end,
+        _ => false,
+    }
+}
+
+/// Start date and time in the interface language, e.g. "2025年11月25日 午後11:00"
+fn format_time(iso_time: &str) -> String {
+    match DateTime::parse_from_rfc3339(iso_time) {
+        Ok(time) => (tr().air_time)(time),
+        Err(_) => iso_time.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashtag() {
+        assert_eq!(hashtag("らじる").as_deref(), Some("#らじる"));
+        assert_eq!(hashtag(" #nhkfm ").as_deref(), Some("#nhkfm"));
+        assert_eq!(hashtag("＃まいあさ").as_deref(), Some("#まいあさ"));
+        assert_eq!(hashtag("#"), None);
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(
+            format_time("2025-11-25T23:00:00+09:00"),
+            "2025年11月25日 午後11:00"
+        );
+        assert_eq!(
+            format_time("2025-11-26T00:05:00+09:00"),
+            "2025年11月26日 午前12:05"
+        );
+        assert_eq!(format_time("bad"), "bad");
+    }
+
+    #[test]
+    fn test_parse_piece_duration() {
+        let secs = |s: u64| Some(Duration::from_secs(s));
+        assert_eq!(parse_piece_duration("PT4M30S"), secs(270));
+        assert_eq!(parse_piece_duration("PT1H2M"), secs(3720));
+        assert_eq!(parse_piece_duration("04:30"), secs(270));
+        assert_eq!(parse_piece_duration("1:02:03"), secs(3723));
+        assert_eq!(parse_piece_duration("4分30秒"), secs(270));
+        assert_eq!(parse_piece_duration("12分"), secs(720));
+        assert_eq!(parse_piece_duration(""), None);
+        assert_eq!(parse_piece_duration("不明"), None);
+    }
+}
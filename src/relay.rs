@@ -0,0 +1,344 @@
+//! Re-publishing a live channel as plain HLS for other players on the LAN.
+//!
+//! Segments come from [`segment_stream`] already decrypted. The relay keeps
+//! the newest few and serves a media playlist listing them, so smart
+//! speakers and other HLS clients can play without NHK's keys.
+
+use anyhow::Result;
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use futures::StreamExt;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+
+use crate::client::{HttpOptions, NhkRadioClient};
+use crate::headless::program_line;
+use crate::i18n::{area_label, tr};
+use crate::player::ChannelKind;
+use crate::radio::now_playing_stream;
+use crate::stream::{SegmentData, StreamOptions, segment_stream};
+
+/// Segments listed in the relayed playlist
+const WINDOW_SEGMENTS: usize = 6;
+
+struct RelaySegment {
+    segment: SegmentData,
+    /// Whether the segment does not continue the one before it
+    discontinuity: bool,
+}
+
+/// The newest segments of the relayed stream
+#[derive(Default)]
+struct RelayWindow {
+    segments: VecDeque<RelaySegment>,
+    /// Discontinuities that have scrolled out of the window
+    discontinuity_sequence: u64,
+}
+
+impl RelayWindow {
+    fn push(&mut self, segment: SegmentData) {
+        let discontinuity = self.segments.back().is_some_and(|last| {
+            segment.seq_no != last.segment.seq_no + 1 || !same_init(&last.segment, &segment)
+        });
+        self.segments.push_back(RelaySegment {
+            segment,
+            discontinuity,
+        });
+        while self.segments.len() > WINDOW_SEGMENTS {
+            if let Some(old) = self.segments.pop_front()
+                && old.discontinuity
+            {
+                self.discontinuity_sequence += 1;
+            }
+        }
+    }
+
+    fn get(&self, seq_no: u64) -> Option<&SegmentData> {
+        self.segments
+            .iter()
+            .map(|s| &s.segment)
+            .find(|s| s.seq_no == seq_no)
+    }
+
+    /// Media playlist for the window, or `None` before the first segment
+    fn playlist(&self) -> Option<String> {
+        let first = self.segments.front()?;
+        let target = self
+            .segments
+            .iter()
+            .map(|s| s.segment.duration.ceil() as u64)
+            .max()
+            .unwrap_or(1);
+        let has_init = self.segments.iter().any(|s| s.segment.init.is_some());
+
+        let mut out = String::new();
+        let _ = writeln!(out, "#EXTM3U");
+        let _ = writeln!(out, "#EXT-X-VERSION:{}", if has_init { 6 } else { 3 });
+        let _ = writeln!(out, "#EXT-X-TARGETDURATION:{}", target);
+        let _ = writeln!(out, "#EXT-X-MEDIA-SEQUENCE:{}", first.segment.seq_no);
+        let _ = writeln!(
+            out,
+            "#EXT-X-DISCONTINUITY-SEQUENCE:{}",
+            self.discontinuity_sequence
+        );
+
+        // Each initialization section is named after the first listed segment
+        // using it; a new one always comes with a discontinuity
+        for (i, relay) in self.segments.iter().enumerate() {
+            let segment = &relay.segment;
+            if relay.discontinuity {
+                let _ = writeln!(out, "#EXT-X-DISCONTINUITY");
+            }
+            if segment.init.is_some() && (i == 0 || relay.discontinuity) {
+                let _ = writeln!(out, "#EXT-X-MAP:URI=\"init/{}.mp4\"", segment.seq_no);
+            }
+            if let Some(time) = segment.program_date_time {
+                let _ = writeln!(out, "#EXT-X-PROGRAM-DATE-TIME:{}", time.to_rfc3339());
+            }
+            let _ = writeln!(out, "#EXTINF:{:.3},", segment.duration);
+            let _ = writeln!(out, "segment/{}.{}", segment.seq_no, extension(segment));
+        }
+        Some(out)
+    }
+}
+
+fn same_init(a: &SegmentData, b: &SegmentData) -> bool {
+    match (&a.init, &b.init) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// File extension of the upstream segment, defaulting to AAC
+fn extension(segment: &SegmentData) -> &'static str {
+    if segment.init.is_some() {
+        return "m4s";
+    }
+    let path = segment.url.split(['?', '#']).next().unwrap_or_default();
+    if path.ends_with(".ts") { "ts" } else { "aac" }
+}
+
+fn content_type(extension: &str) -> &'static str {
+    match extension {
+        "ts" => "video/mp2t",
+        "m4s" | "mp4" => "audio/mp4",
+        _ => "audio/aac",
+    }
+}
+
+/// The relay's HTTP side, fed with segments by its owner
+#[derive(Clone, Default)]
+pub struct Relay {
+    window: Arc<RwLock<RelayWindow>>,
+}
+
+impl Relay {
+    /// Routes serving `/live.m3u8` and the segments it lists
+    ///
+    /// Responses allow any origin, since web-based receivers such as
+    /// Chromecast fetch the playlist from script.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/live.m3u8", get(playlist))
+            .route("/segment/{file}", get(segment))
+            .route("/init/{file}", get(init))
+            .with_state(self.clone())
+    }
+
+    pub fn push(&self, segment: SegmentData) {
+        self.window
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(segment);
+    }
+
+    /// Drop every segment, e.g. before relaying another channel
+    pub fn reset(&self) {
+        *self.window.write().unwrap_or_else(|e| e.into_inner()) = RelayWindow::default();
+    }
+
+    /// Whether a playlist can be served yet
+    pub fn is_ready(&self) -> bool {
+        !self.read().segments.is_empty()
+    }
+
+    /// HLS segment format of the newest segment: `aac`, `ts`, or `fmp4`
+    pub fn segment_format(&self) -> Option<&'static str> {
+        self.read()
+            .segments
+            .back()
+            .map(|s| match extension(&s.segment) {
+                "m4s" => "fmp4",
+                ext => ext,
+            })
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, RelayWindow> {
+        self.window.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Local address of the interface used to reach `peer`, for handing the
+/// relay's URL to a device on the network
+pub fn local_ip_towards(peer: IpAddr) -> Result<IpAddr> {
+    let any: IpAddr = if peer.is_ipv4() {
+        [0, 0, 0, 0].into()
+    } else {
+        [0u16; 8].into()
+    };
+    // Connecting a UDP socket sends nothing but picks the route
+    let socket = std::net::UdpSocket::bind((any, 0))?;
+    socket.connect((peer, 9))?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Relay `kind` in `area` as plain HLS on `addr` until Ctrl-C, printing a
+/// line whenever the program changes
+pub async fn run_relay(
+    area: String,
+    kind: ChannelKind,
+    addr: SocketAddr,
+    http: HttpOptions,
+    stream: StreamOptions,
+) -> Result<()> {
+    let client = Arc::new(NhkRadioClient::with_options(http)?);
+    let (stream_data, program_url) = client.fetch_config().await?.find_area(&area)?;
+
+    let relay = Relay::default();
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+
+    println!("▶ {} {}", area_label(&stream_data), tr().channel_name(kind));
+    println!(
+        "{}: http://{}/live.m3u8",
+        tr().serving,
+        listener.local_addr().unwrap_or(addr)
+    );
+
+    let mut segments = std::pin::pin!(segment_stream(client.clone(), stream_data, kind, stream));
+    let mut programs = std::pin::pin!(now_playing_stream(client, program_url, kind));
+    let mut server = std::pin::pin!(axum::serve(listener, relay.router()).into_future());
+
+    loop {
+        tokio::select! {
+            Some(segment) = segments.next() => relay.push(segment),
+            Some(program) = programs.next() => println!("{}", program_line(&program)),
+            result = &mut server => return Ok(result?),
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    Ok(())
+}
+
+async fn playlist(State(relay): State<Relay>) -> Response {
+    let playlist = relay.read().playlist();
+    match playlist {
+        Some(body) => (
+            [
+                (header::CONTENT_TYPE, "application/vnd.apple.mpegurl"),
+                (header::CACHE_CONTROL, "no-cache"),
+                (header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+            ],
+            body,
+        )
+            .into_response(),
+        // Not started yet; clients retry
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn segment(State(relay): State<Relay>, Path(file): Path<String>) -> Response {
+    let window = relay.read();
+    let Some((segment, ext)) = file
+        .split_once('.')
+        .and_then(|(seq, ext)| Some((window.get(seq.parse().ok()?)?, ext)))
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    (
+        [
+            (header::CONTENT_TYPE, content_type(ext)),
+            (header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+        ],
+        segment.data.clone(),
+    )
+        .into_response()
+}
+
+async fn init(State(relay): State<Relay>, Path(file): Path<String>) -> Response {
+    let window = relay.read();
+    let Some(init) = file
+        .strip_suffix(".mp4")
+        .and_then(|seq| window.get(seq.parse().ok()?))
+        .and_then(|segment| segment.init.clone())
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    (
+        [
+            (header::CONTENT_TYPE, "audio/mp4"),
+            (header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+        ],
+        init.to_vec(),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(seq_no: u64) -> SegmentData {
+        SegmentData {
+            url: format!("https://example.com/fm/{}.aac?token=x", seq_no),
+            seq_no,
+            duration: 5.0,
+            program_date_time: None,
+            init: None,
+            data: vec![seq_no as u8],
+        }
+    }
+
+    #[test]
+    fn test_window_playlist() {
+        let mut window = RelayWindow::default();
+        assert!(window.playlist().is_none());
+
+        for seq_no in 1..=8 {
+            window.push(segment(seq_no));
+        }
+        let playlist = window.playlist().unwrap();
+        assert!(playlist.contains("#EXT-X-MEDIA-SEQUENCE:3\n"));
+        assert!(playlist.contains("#EXT-X-TARGETDURATION:5\n"));
+        assert!(playlist.contains("segment/3.aac\n"));
+        assert!(playlist.contains("segment/8.aac\n"));
+        assert!(!playlist.contains("segment/2.aac"));
+        assert!(!playlist.contains("#EXT-X-DISCONTINUITY\n"));
+        assert!(window.get(2).is_none());
+        assert_eq!(window.get(8).unwrap().data, [8]);
+    }
+
+    #[test]
+    fn test_window_marks_gaps() {
+        let mut window = RelayWindow::default();
+        window.push(segment(1));
+        window.push(segment(5));
+        let playlist = window.playlist().unwrap();
+        assert!(playlist.contains("segment/1.aac\n#EXT-X-DISCONTINUITY\n#EXTINF"));
+
+        // Once the gap scrolls out it is counted instead
+        for seq_no in 6..=11 {
+            window.push(segment(seq_no));
+        }
+        let playlist = window.playlist().unwrap();
+        assert!(playlist.contains("#EXT-X-DISCONTINUITY-SEQUENCE:1\n"));
+        assert!(!playlist.contains("#EXT-X-DISCONTINUITY\n"));
+    }
+}
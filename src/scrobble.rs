@@ -0,0 +1,531 @@
+//! Scrobbling the music NHK lists for a program to Last.fm and ListenBrainz.
+//!
+//! The program's `musicList` gives pieces but not when each one airs, so the
+//! pieces are laid out back to back from the start of the program using their
+//! durations. A piece is scrobbled once it has ended, if at least half of it
+//! (or four minutes) was heard. Scrobbles wait in a queue file until a
+//! service accepts them, so they survive being offline or quitting.
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::client::HttpOptions;
+use crate::config::Config;
+use crate::program_info::{MusicPiece, ProgramInfo};
+
+const LASTFM_API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+const LASTFM_AUTH_URL: &str = "https://www.last.fm/api/auth/";
+const LISTENBRAINZ_SUBMIT_URL: &str = "https://api.listenbrainz.org/1/submit-listens";
+
+/// How often scrobbles that could not be submitted are retried
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Longest a submission may take, so a hung request cannot stall the queue
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most scrobbles Last.fm takes in one request
+const LASTFM_BATCH: usize = 50;
+
+/// A jump in broadcast time bigger than this means a skip, not listening
+const SKIP_THRESHOLD: chrono::Duration = chrono::Duration::seconds(30);
+
+/// Accounts from the `[scrobble]` table
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScrobbleConfig {
+    /// Key of a Last.fm API account (https://www.last.fm/api/account/create)
+    pub lastfm_api_key: Option<String>,
+    /// Shared secret of the Last.fm API account
+    pub lastfm_api_secret: Option<String>,
+    /// Session saved by `scrobble login`
+    pub lastfm_session_key: Option<String>,
+    /// ListenBrainz user token (https://listenbrainz.org/settings/)
+    pub listenbrainz_token: Option<String>,
+}
+
+impl ScrobbleConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == ScrobbleConfig::default()
+    }
+
+    fn lastfm(&self) -> Option<LastFm> {
+        Some(LastFm {
+            api_key: self.lastfm_api_key.clone()?,
+            secret: self.lastfm_api_secret.clone()?,
+            session_key: self.lastfm_session_key.clone()?,
+        })
+    }
+}
+
+/// One piece heard, as both services take it
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Scrobble {
+    pub artist: String,
+    pub track: String,
+    /// The program, standing in for the album
+    pub album: String,
+    /// Unix time the piece started
+    pub timestamp: i64,
+    pub duration_secs: Option<u64>,
+}
+
+/// When each piece of `music` airs, laid out from `start` to `end`
+///
+/// Pieces without a duration share the time the others leave over.
+pub(crate) fn timeline(
+    music: &[MusicPiece],
+    (start, end): (DateTime<FixedOffset>, DateTime<FixedOffset>),
+) -> Vec<(DateTime<FixedOffset>, chrono::Duration)> {
+    let known: chrono::Duration = music
+        .iter()
+        .filter_map(|piece| piece.duration)
+        .map(|d| chrono::Duration::from_std(d).unwrap_or_default())
+        .sum();
+    let unknown = music
+        .iter()
+        .filter(|piece| piece.duration.is_none())
+        .count();
+    let share = match unknown {
+        0 => chrono::Duration::zero(),
+        n => ((end - start - known) / n as i32).max(chrono::Duration::zero()),
+    };
+
+    let mut at = start;
+    music
+        .iter()
+        .map(|piece| {
+            let length = piece
+                .duration
+                .and_then(|d| chrono::Duration::from_std(d).ok())
+                .unwrap_or(share);
+            let slot = (at, length);
+            at += length;
+            slot
+        })
+        .collect()
+}
+
+/// Pieces of `info`'s program that ended by `at` after being heard since `since`
+pub fn due(
+    info: &ProgramInfo,
+    since: DateTime<FixedOffset>,
+    at: DateTime<FixedOffset>,
+) -> Vec<Scrobble> {
+    let Some(airs) = info.airs else {
+        return Vec::new();
+    };
+    info.music
+        .iter()
+        .zip(timeline(&info.music, airs))
+        .filter(|(piece, (start, length))| {
+            let end = *start + *length;
+            let heard = end - since.max(*start);
+            !piece.artist().is_empty()
+                && *length > chrono::Duration::zero()
+                && end <= at
+                && (heard >= *length / 2 || heard >= chrono::Duration::minutes(4))
+        })
+        .map(|(piece, (start, length))| Scrobble {
+            artist: piece.artist(),
+            track: piece.title.clone(),
+            album: info.program_title.clone(),
+            timestamp: start.timestamp(),
+            duration_secs: Some(length.num_seconds() as u64),
+        })
+        .collect()
+}
+
+/// Follows what is heard and hands finished pieces to the submitting task
+pub struct Scrobbler {
+    tx: mpsc::UnboundedSender<Scrobble>,
+    /// Broadcast time listening started from, and the last time seen
+    listening: Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)>,
+    sent: HashSet<(String, i64)>,
+}
+
+impl Scrobbler {
+    /// Start submitting in the background through a client configured by
+    /// `http`, or `None` without any account
+    pub fn start(config: &ScrobbleConfig, http: &HttpOptions) -> Option<Self> {
+        let lastfm = config.lastfm();
+        let listenbrainz = config.listenbrainz_token.clone();
+        if lastfm.is_none() && listenbrainz.is_none() {
+            if config.lastfm_api_key.is_some() {
+                log::warn!("Not scrobbling to Last.fm until `scrobble login` is run");
+            }
+            return None;
+        }
+        let http = match http_client(http) {
+            Ok(http) => http,
+            Err(e) => {
+                log::warn!("Not scrobbling: {}", e);
+                return None;
+            }
+        };
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(submit_queue(http, lastfm, listenbrainz, rx));
+        Some(Scrobbler {
+            tx,
+            listening: None,
+            sent: HashSet::new(),
+        })
+    }
+
+    /// Note that `info`'s program is heard at broadcast time `at`, or that
+    /// nothing is heard when `at` is `None`
+    pub fn observe(&mut self, info: &ProgramInfo, at: Option<DateTime<FixedOffset>>) {
+        let Some(at) = at else {
+            self.listening = None;
+            return;
+        };
+        let since = match self.listening {
+            Some((since, last)) if at >= last && at - last <= SKIP_THRESHOLD => since,
+            _ => at,
+        };
+        self.listening = Some((since, at));
+
+        for scrobble in due(info, since, at) {
+            if self
+                .sent
+                .insert((scrobble.track.clone(), scrobble.timestamp))
+            {
+                log::info!("Scrobbling {} — {}", scrobble.artist, scrobble.track);
+                let _ = self.tx.send(scrobble);
+            }
+        }
+    }
+}
+
+/// Scrobbles each service has yet to accept, kept in the state directory
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct ScrobbleQueue {
+    lastfm: Vec<Scrobble>,
+    listenbrainz: Vec<Scrobble>,
+}
+
+impl ScrobbleQueue {
+    fn path() -> Option<PathBuf> {
+        let base = dirs::state_dir().or_else(dirs::cache_dir)?;
+        Some(base.join("nhk-radio-player").join("scrobbles.json"))
+    }
+
+    fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, serde_json::to_string(self).unwrap_or_default()));
+        if let Err(e) = result {
+            log::warn!("Failed to save {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Client for the scrobbling services, with the proxy and certificate of
+/// `options` and a limit on each request
+fn http_client(options: &HttpOptions) -> Result<reqwest::Client> {
+    Ok(options.client_builder()?.timeout(REQUEST_TIMEOUT).build()?)
+}
+
+/// Queue what arrives on `rx` and submit it, retrying what fails
+async fn submit_queue(
+    http: reqwest::Client,
+    lastfm: Option<LastFm>,
+    listenbrainz: Option<String>,
+    mut rx: mpsc::UnboundedReceiver<Scrobble>,
+) {
+    let mut queue = ScrobbleQueue::load();
+    let mut retry = tokio::time::interval(RETRY_INTERVAL);
+    loop {
+        tokio::select! {
+            scrobble = rx.recv() => match scrobble {
+                Some(scrobble) => {
+                    if lastfm.is_some() {
+                        queue.lastfm.push(scrobble.clone());
+                    }
+                    if listenbrainz.is_some() {
+                        queue.listenbrainz.push(scrobble);
+                    }
+                    queue.save();
+                }
+                None => return,
+            },
+            _ = retry.tick() => {}
+        }
+
+        if let Some(ref lastfm) = lastfm
+            && let Err(e) = lastfm.scrobble(&http, &mut queue).await
+        {
+            log::warn!("Failed to scrobble to Last.fm: {}", e);
+        }
+        if let Some(ref token) = listenbrainz
+            && !queue.listenbrainz.is_empty()
+        {
+            match submit_listens(&http, token, &queue.listenbrainz).await {
+                Ok(()) => queue.listenbrainz.clear(),
+                Err(e) => log::warn!("Failed to submit listens to ListenBrainz: {}", e),
+            }
+        }
+        queue.save();
+    }
+}
+
+struct LastFm {
+    api_key: String,
+    secret: String,
+    session_key: String,
+}
+
+impl LastFm {
+    /// Submit the queue's Last.fm scrobbles a batch at a time, taking each
+    /// batch off the queue as soon as it is accepted so that a later failure
+    /// does not send it again
+    async fn scrobble(&self, http: &reqwest::Client, queue: &mut ScrobbleQueue) -> Result<()> {
+        while !queue.lastfm.is_empty() {
+            let count = queue.lastfm.len().min(LASTFM_BATCH);
+            let batch = &queue.lastfm[..count];
+            let mut params = BTreeMap::from([
+                ("method".to_string(), "track.scrobble".to_string()),
+                ("api_key".to_string(), self.api_key.clone()),
+                ("sk".to_string(), self.session_key.clone()),
+            ]);
+            for (i, scrobble) in batch.iter().enumerate() {
+                params.insert(format!("artist[{}]", i), scrobble.artist.clone());
+                params.insert(format!("track[{}]", i), scrobble.track.clone());
+                params.insert(format!("album[{}]", i), scrobble.album.clone());
+                params.insert(format!("timestamp[{}]", i), scrobble.timestamp.to_string());
+                if let Some(secs) = scrobble.duration_secs {
+                    params.insert(format!("duration[{}]", i), secs.to_string());
+                }
+            }
+            lastfm_call(http, &self.secret, params).await?;
+            queue.lastfm.drain(..count);
+            queue.save();
+        }
+        Ok(())
+    }
+}
+
+/// `api_sig` for Last.fm: the MD5 of the sorted parameters and the secret
+fn lastfm_signature(params: &BTreeMap<String, String>, secret: &str) -> String {
+    let mut hasher = Md5::new();
+    for (name, value) in params {
+        hasher.update(name.as_bytes());
+        hasher.update(value.as_bytes());
+    }
+    hasher.update(secret.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Make a signed Last.fm API call, returning the JSON response
+async fn lastfm_call(
+    http: &reqwest::Client,
+    secret: &str,
+    mut params: BTreeMap<String, String>,
+) -> Result<serde_json::Value> {
+    let signature = lastfm_signature(&params, secret);
+    params.insert("api_sig".to_string(), signature);
+    params.insert("format".to_string(), "json".to_string());
+
+    let text = http
+        .post(LASTFM_API_URL)
+        .form(&params)
+        .send()
+        .await?
+        .text()
+        .await?;
+    let response: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|e| anyhow::anyhow!("Unexpected response from Last.fm: {}", e))?;
+    if let Some(code) = response.get("error") {
+        anyhow::bail!(
+            "Last.fm error {}: {}",
+            code,
+            response["message"].as_str().unwrap_or_default()
+        );
+    }
+    Ok(response)
+}
+
+async fn submit_listens(http: &reqwest::Client, token: &str, scrobbles: &[Scrobble]) -> Result<()> {
+    let body = serde_json::json!({
+        "listen_type": if scrobbles.len() == 1 { "single" } else { "import" },
+        "payload": scrobbles.iter().map(listen).collect::<Vec<_>>(),
+    });
+    http.post(LISTENBRAINZ_SUBMIT_URL)
+        .header("Authorization", format!("Token {}", token))
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+fn listen(scrobble: &Scrobble) -> serde_json::Value {
+    let mut info = serde_json::json!({
+        "media_player": "nhk-radio-player",
+        "submission_client": "nhk-radio-player",
+        "submission_client_version": env!("CARGO_PKG_VERSION"),
+    });
+    if let Some(secs) = scrobble.duration_secs {
+        info["duration"] = secs.into();
+    }
+    serde_json::json!({
+        "listened_at": scrobble.timestamp,
+        "track_metadata": {
+            "artist_name": scrobble.artist,
+            "track_name": scrobble.track,
+            "release_name": scrobble.album,
+            "additional_info": info,
+        },
+    })
+}
+
+/// Authorize this player with Last.fm and save the session in the config file
+pub async fn login(http: &HttpOptions) -> Result<()> {
+    let mut config = Config::load()?;
+    let (Some(api_key), Some(secret)) = (
+        config.scrobble.lastfm_api_key.clone(),
+        config.scrobble.lastfm_api_secret.clone(),
+    ) else {
+        anyhow::bail!(
+            "Set lastfm_api_key and lastfm_api_secret under [scrobble] in the config file first"
+        );
+    };
+    let http = http_client(http)?;
+
+    let params = |method: &str| {
+        BTreeMap::from([
+            ("method".to_string(), method.to_string()),
+            ("api_key".to_string(), api_key.clone()),
+        ])
+    };
+    let response = lastfm_call(&http, &secret, params("auth.getToken")).await?;
+    let token = response["token"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Last.fm sent no token"))?
+        .to_string();
+
+    let url = format!("{}?api_key={}&token={}", LASTFM_AUTH_URL, api_key, token);
+    println!("Allow access in the browser, then press Enter:\n{}", url);
+    if let Err(e) = crate::browser::open_url(&url) {
+        log::debug!("{}", e);
+    }
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+
+    let mut params = params("auth.getSession");
+    params.insert("token".to_string(), token);
+    let response = lastfm_call(&http, &secret, params).await?;
+    let session = &response["session"];
+    let key = session["key"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Last.fm sent no session"))?;
+
+    config.scrobble.lastfm_session_key = Some(key.to_string());
+    config.save()?;
+    println!(
+        "Scrobbling to Last.fm as {}",
+        session["name"].as_str().unwrap_or_default()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::ChannelKind;
+
+    fn at(time: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(time).unwrap()
+    }
+
+    fn piece(title: &str, minutes: Option<u64>) -> MusicPiece {
+        MusicPiece {
+            title: title.to_string(),
+            composer: "ベートーベン".to_string(),
+            performers: Vec::new(),
+            artists: Vec::new(),
+            duration: minutes.map(|m| Duration::from_secs(m * 60)),
+        }
+    }
+
+    fn program(music: Vec<MusicPiece>) -> ProgramInfo {
+        let mut info = ProgramInfo::from_program(&None, ChannelKind::Fm, "東京");
+        info.program_title = "クラシックの庭".to_string();
+        info.airs = Some((
+            at("2025-11-25T14:00:00+09:00"),
+            at("2025-11-25T15:00:00+09:00"),
+        ));
+        info.music = music;
+        info
+    }
+
+    #[test]
+    fn test_due() {
+        // 14:00-14:20, then the remaining 40 minutes split 14:20-14:40-15:00
+        let info = program(vec![
+            piece("交響曲第5番", Some(20)),
+            piece("ロマンス第1番", None),
+            piece("ロマンス第2番", None),
+        ]);
+        let since = at("2025-11-25T14:05:00+09:00");
+
+        assert!(due(&info, since, at("2025-11-25T14:19:00+09:00")).is_empty());
+        let done = due(&info, since, at("2025-11-25T14:45:00+09:00"));
+        assert_eq!(done.len(), 2);
+        assert_eq!(done[0].track, "交響曲第5番");
+        assert_eq!(done[0].artist, "ベートーベン");
+        assert_eq!(done[0].album, "クラシックの庭");
+        assert_eq!(done[0].duration_secs, Some(20 * 60));
+        assert_eq!(
+            done[1].timestamp,
+            at("2025-11-25T14:20:00+09:00").timestamp()
+        );
+
+        // Tuning in for the last few minutes of a piece is not enough
+        assert_eq!(
+            due(
+                &info,
+                at("2025-11-25T14:37:00+09:00"),
+                at("2025-11-25T15:00:00+09:00")
+            )
+            .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_lastfm_signature() {
+        let params = BTreeMap::from([
+            ("method".to_string(), "auth.getToken".to_string()),
+            ("api_key".to_string(), "key".to_string()),
+        ]);
+        // md5("api_keykeymethodauth.getTokensecret")
+        assert_eq!(
+            lastfm_signature(&params, "secret"),
+            "b4705499705a550b07ca058a15bde9b0"
+        );
+    }
+}
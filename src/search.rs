@@ -0,0 +1,67 @@
+//! Matching programs in the day schedules, for `search` and the record rules.
+
+use crate::genre;
+use crate::types::ScheduledProgram;
+
+/// Whether the title, description, series, or cast of `program` contains
+/// `text`, ignoring case
+pub fn mentions(program: &ScheduledProgram, text: &str) -> bool {
+    let details = &program.detailed_description;
+    [
+        &program.name,
+        &program.description,
+        &program.identifier_group.radio_series_name,
+        &program.identifier_group.radio_episode_name,
+        &details.epg200,
+        &details.epg_information,
+    ]
+    .into_iter()
+    .any(|field| contains(field, text))
+}
+
+/// Whether `program` is in a genre `query` asks for, by ID, genre word, or
+/// name (see [`genre::matches`])
+pub fn in_genre(program: &ScheduledProgram, query: &str) -> bool {
+    program
+        .identifier_group
+        .genre
+        .iter()
+        .any(|g| genre::matches(g, query))
+}
+
+fn contains(text: &str, pattern: &str) -> bool {
+    text.to_lowercase().contains(&pattern.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Genre;
+
+    #[test]
+    fn test_mentions() {
+        let mut program = ScheduledProgram::named("ジャズ・トゥナイト", "", "");
+        program.description = "Live at Blue Note".to_string();
+        program.detailed_description.epg_information = "【出演】大友良英".to_string();
+
+        assert!(mentions(&program, "ジャズ"));
+        assert!(mentions(&program, "blue note"));
+        assert!(mentions(&program, "大友良英"));
+        assert!(!mentions(&program, "クラシック"));
+    }
+
+    #[test]
+    fn test_in_genre() {
+        let mut program = ScheduledProgram::named("", "", "");
+        program.identifier_group.genre = vec![Genre {
+            id: "0409".to_string(),
+            name1: "音楽".to_string(),
+            name2: "ジャズ・フュージョン".to_string(),
+        }];
+
+        assert!(in_genre(&program, "0409"));
+        assert!(in_genre(&program, "ジャズ"));
+        assert!(in_genre(&program, "music"));
+        assert!(!in_genre(&program, "クラシック"));
+    }
+}
@@ -0,0 +1,155 @@
+//! Continuous recordings split into one file per program.
+//!
+//! While a recording is in progress, [`ProgramSplit`] watches the program on
+//! air on the channel being recorded. When it changes, a new file is started
+//! next to the current one, named after the program and its start time and
+//! tagged with its title, station, and description. The stream is tapped at
+//! the live edge, so the files change with the clock, not with what a
+//! rewound player is playing.
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local};
+use std::path::Path;
+
+use crate::autorecord::program_file_name;
+use crate::radio::NowPlaying;
+use crate::recorder::{Recorder, RecordingStatus, RecordingTags};
+
+/// Which program the recording in progress holds
+#[derive(Debug, Default)]
+pub struct ProgramSplit {
+    /// Title and start of the program the current file began with
+    recorded: Option<(String, Option<DateTime<FixedOffset>>)>,
+}
+
+impl ProgramSplit {
+    /// A recorder for the next file, when `on_air` has taken over from the
+    /// program `recording` holds
+    ///
+    /// The first program seen in a recording is the one it started in, so
+    /// it is only noted.
+    pub fn next_file(
+        &mut self,
+        recording: Option<&RecordingStatus>,
+        area: &str,
+        on_air: Option<&NowPlaying>,
+    ) -> Option<Recorder> {
+        let Some(recording) = recording else {
+            self.recorded = None;
+            return None;
+        };
+        let on_air = on_air?;
+        let program = (on_air.title.clone(), on_air.start);
+        if self
+            .recorded
+            .replace(program.clone())
+            .is_none_or(|recorded| recorded == program)
+        {
+            return None;
+        }
+        let dir = recording.path.parent().unwrap_or(Path::new("."));
+        match program_recorder(dir, recording, area, on_air) {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                log::warn!("{}", e);
+                None
+            }
+        }
+    }
+}
+
+/// A tagged recording of `program` in `dir`, in the format of `recording`
+fn program_recorder(
+    dir: &Path,
+    recording: &RecordingStatus,
+    area: &str,
+    program: &NowPlaying,
+) -> Result<Recorder> {
+    let start = program.start.unwrap_or_else(|| Local::now().fixed_offset());
+    let name = program_file_name(
+        area,
+        program.channel,
+        start,
+        &program.title,
+        recording.format,
+    );
+    Recorder::with_tags(dir.join(name), recording.format, Some(&tags(program)))
+}
+
+/// Tags for a recording of `program`
+fn tags(program: &NowPlaying) -> RecordingTags {
+    RecordingTags {
+        title: program.title.clone(),
+        artist: format!("NHK {}", program.channel.display_name()),
+        date: program
+            .start
+            .map(|start| start.format("%Y-%m-%d").to_string())
+            .unwrap_or_default(),
+        comment: program.description.clone(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::ChannelKind;
+    use crate::recorder::RecordFormat;
+    use std::time::Duration;
+
+    fn program(title: &str, start: &str) -> NowPlaying {
+        NowPlaying {
+            channel: ChannelKind::Fm,
+            title: title.to_string(),
+            description: String::new(),
+            start: DateTime::parse_from_rfc3339(start).ok(),
+            end: None,
+        }
+    }
+
+    #[test]
+    fn test_next_file() {
+        let dir = std::env::temp_dir().join(format!("nhk-split-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let recording = RecordingStatus {
+            path: dir.join("evening.aac"),
+            format: RecordFormat::Raw,
+            elapsed: Duration::ZERO,
+            bytes_written: 0,
+        };
+        let first = program("ラジオ深夜便", "2025-11-25T23:05:00+09:00");
+        let second = program("クラシックの庭", "2025-11-26T14:00:00+09:00");
+        let mut split = ProgramSplit::default();
+
+        // The program the recording started in stays in its file
+        assert!(
+            split
+                .next_file(Some(&recording), "tokyo", Some(&first))
+                .is_none()
+        );
+        assert!(
+            split
+                .next_file(Some(&recording), "tokyo", Some(&first))
+                .is_none()
+        );
+        assert!(split.next_file(Some(&recording), "tokyo", None).is_none());
+
+        let recorder = split
+            .next_file(Some(&recording), "tokyo", Some(&second))
+            .unwrap();
+        assert_eq!(
+            recorder.path(),
+            dir.join("tokyo-fm-20251126-1400 クラシックの庭.aac")
+        );
+        drop(recorder);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // A new recording starts over
+        assert!(split.next_file(None, "tokyo", Some(&second)).is_none());
+        assert!(
+            split
+                .next_file(Some(&recording), "tokyo", Some(&first))
+                .is_none()
+        );
+    }
+}
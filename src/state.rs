@@ -0,0 +1,70 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::player::ChannelKind;
+
+/// Runtime state remembered between runs (not meant for hand editing)
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SessionState {
+    /// Last volume level in percent (0-100)
+    pub volume: Option<u8>,
+    /// Area code last listened to
+    pub area: Option<String>,
+    /// Channel last listened to: r1, r2, or fm
+    pub channel: Option<String>,
+}
+
+impl SessionState {
+    pub fn path() -> Option<PathBuf> {
+        let base = dirs::state_dir().or_else(dirs::cache_dir)?;
+        Some(base.join("nhk-radio-player").join("state.toml"))
+    }
+
+    /// Remember `area` and `channel` as what `resume` plays
+    pub fn set_station(&mut self, area: &str, channel: ChannelKind) {
+        self.area = Some(area.to_string());
+        self.channel = Some(channel.short_name().to_lowercase());
+    }
+
+    /// Load saved state, falling back to defaults if missing or unreadable
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("No state directory available"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_station() {
+        let mut session = SessionState {
+            volume: Some(40),
+            ..Default::default()
+        };
+        session.set_station("osaka", ChannelKind::Fm);
+        let text = toml::to_string_pretty(&session).unwrap();
+        let parsed: SessionState = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.area.as_deref(), Some("osaka"));
+        assert_eq!(parsed.channel.as_deref(), Some("fm"));
+        assert_eq!(parsed.volume, Some(40));
+        // State saved before the station was remembered still loads
+        let old: SessionState = toml::from_str("volume = 70\n").unwrap();
+        assert!(old.area.is_none());
+    }
+}
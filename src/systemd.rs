@@ -0,0 +1,189 @@
+//! Running the daemon as a systemd service.
+//!
+//! Under a `Type=notify` unit the daemon reports ready once the first audio
+//! reaches the output, and with `WatchdogSec=` set it pings the watchdog for
+//! as long as the stream loop keeps loading the playlist, so a daemon stuck
+//! without a stream is restarted. Notifications go to `$NOTIFY_SOCKET` and
+//! are skipped when it is unset, e.g. when run from a terminal.
+//! [`unit_file`] writes a user service for `daemon --generate-unit`.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::engine::PlaybackEngine;
+
+/// How often readiness is checked before the first audio plays
+const READY_POLL: Duration = Duration::from_millis(250);
+
+/// Send `state`, e.g. `READY=1`, to the service manager; does nothing
+/// outside systemd
+pub fn notify(state: &str) {
+    #[cfg(target_os = "linux")]
+    if let Some(socket) = std::env::var_os("NOTIFY_SOCKET")
+        && let Err(e) = send(&socket, state)
+    {
+        log::warn!("Failed to notify systemd: {}", e);
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = state;
+}
+
+#[cfg(target_os = "linux")]
+fn send(socket: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let path = socket.as_bytes();
+    // A leading @ names a socket in the abstract namespace
+    let addr = match path.strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(Path::new(socket))?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+/// How often the watchdog expects a ping, when this process has one
+fn watchdog_timeout() -> Option<Duration> {
+    parse_watchdog(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    // The variables are inherited by children, which must not ping for us
+    if let Some(pid) = pid
+        && pid.parse::<u32>().ok() != Some(own_pid)
+    {
+        return None;
+    }
+    let usec: u64 = usec?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Whether the stream loop has shown signs of life within `timeout`: it is
+/// still running and loaded the playlist lately, or has only just started
+fn stream_alive(engine: &PlaybackEngine, timeout: Duration, now: Instant) -> bool {
+    engine.is_streaming()
+        && match engine.stats().last_refresh {
+            Some(refreshed) => now.duration_since(refreshed) < timeout,
+            None => engine.uptime() < timeout,
+        }
+}
+
+/// Report ready once audio plays, then ping the watchdog while the stream
+/// is alive; never returns
+pub async fn supervise(engine: &PlaybackEngine) {
+    while engine.stats().played_secs == 0.0 {
+        tokio::time::sleep(READY_POLL).await;
+    }
+    log::info!("Audio is playing, reporting ready");
+    notify("READY=1\nSTATUS=Playing");
+
+    let Some(timeout) = watchdog_timeout() else {
+        return std::future::pending().await;
+    };
+    let mut tick = tokio::time::interval(timeout / 2);
+    loop {
+        tick.tick().await;
+        if stream_alive(engine, timeout, Instant::now()) {
+            notify("WATCHDOG=1");
+        } else {
+            log::warn!("The stream loop has stalled; leaving the systemd watchdog to restart us");
+        }
+    }
+}
+
+/// A user service running `exe` with `args`, e.g. `daemon tokyo fm`
+pub fn unit_file(exe: &Path, args: &[String]) -> String {
+    let command = std::iter::once(exe.display().to_string())
+        .chain(args.iter().cloned())
+        .map(|arg| quote(&arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "[Unit]
+Description=NHK radio player
+After=network-online.target sound.target pipewire.service pulseaudio.service
+
+[Service]
+Type=notify
+ExecStart={}
+Restart=on-failure
+RestartSec=10
+WatchdogSec=60
+
+[Install]
+WantedBy=default.target
+",
+        command
+    )
+}
+
+/// `arg` quoted for an `ExecStart=` line when it needs to be
+fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| !c.is_whitespace() && !matches!(c, '"' | '\'' | '\\' | '$' | '%' | ';'));
+    if plain {
+        return arg.to_string();
+    }
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "$$")
+        .replace('%', "%%");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_watchdog() {
+        assert_eq!(
+            parse_watchdog(Some("30000000"), None, 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(30))
+        );
+        // Meant for another process
+        assert_eq!(parse_watchdog(Some("30000000"), Some("7"), 42), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog(None, None, 42), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_send() {
+        let dir = std::env::temp_dir().join(format!("nhk-notify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify.sock");
+        let _ = std::fs::remove_file(&path);
+        let socket = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        send(path.as_os_str(), "READY=1").unwrap();
+        let mut buf = [0; 64];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unit_file() {
+        let args = ["daemon", "tokyo", "fm", "--device", "USB Audio"].map(String::from);
+        let unit = unit_file(Path::new("/usr/local/bin/nhk-radio-player"), &args);
+        assert!(unit.contains("Type=notify\n"));
+        assert!(unit.contains(
+            "ExecStart=/usr/local/bin/nhk-radio-player daemon tokyo fm --device \"USB Audio\"\n"
+        ));
+        assert_eq!(quote("50%"), "\"50%%\"");
+    }
+}
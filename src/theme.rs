@@ -0,0 +1,192 @@
+//! Color themes for the TUI.
+//!
+//! A theme gives colors to roles rather than to widgets, so the live player
+//! and the 聴き逃し browser stay consistent. A preset is picked with
+//! `--theme` or `theme` in the config file, and single roles can be
+//! overridden in the config file's `[colors]` table.
+
+use anyhow::Result;
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Names accepted by `--theme`
+pub const PRESETS: &[&str] = &["default", "light", "monochrome"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Borders, titles, key hints, and the selected channel
+    pub accent: Color,
+    /// The program title and transient states such as loading
+    pub highlight: Color,
+    /// Times and the playing status
+    pub info: Color,
+    /// Body text such as descriptions
+    pub text: Color,
+    /// Secondary text such as unselected channels
+    pub subtle: Color,
+    /// Inactive borders, placeholders, and hints
+    pub muted: Color,
+    /// The behind-live indicator
+    pub timeshift: Color,
+    /// Recording and quitting
+    pub alert: Color,
+    /// Behind popups
+    pub background: Color,
+}
+
+/// For dark terminal backgrounds
+const DEFAULT: Theme = Theme {
+    accent: Color::Cyan,
+    highlight: Color::Yellow,
+    info: Color::Green,
+    text: Color::White,
+    subtle: Color::Gray,
+    muted: Color::DarkGray,
+    timeshift: Color::Magenta,
+    alert: Color::Red,
+    background: Color::Black,
+};
+
+/// For light terminal backgrounds, where yellow and cyan wash out
+const LIGHT: Theme = Theme {
+    accent: Color::Blue,
+    highlight: Color::Magenta,
+    info: Color::Green,
+    text: Color::Reset,
+    subtle: Color::DarkGray,
+    muted: Color::DarkGray,
+    timeshift: Color::Blue,
+    alert: Color::Red,
+    background: Color::Reset,
+};
+
+/// The terminal's own colors only; emphasis comes from bold and reverse video
+const MONOCHROME: Theme = Theme {
+    accent: Color::Reset,
+    highlight: Color::Reset,
+    info: Color::Reset,
+    text: Color::Reset,
+    subtle: Color::Reset,
+    muted: Color::Reset,
+    timeshift: Color::Reset,
+    alert: Color::Reset,
+    background: Color::Reset,
+};
+
+impl Default for Theme {
+    fn default() -> Self {
+        DEFAULT
+    }
+}
+
+impl Theme {
+    /// The preset called `name` (the default one for `None`) with `colors`
+    /// applied on top
+    pub fn load(name: Option<&str>, colors: &ThemeColors) -> Result<Self> {
+        let mut theme = match name.unwrap_or("default") {
+            "default" => DEFAULT,
+            "light" => LIGHT,
+            "monochrome" | "mono" => MONOCHROME,
+            other => anyhow::bail!(
+                "Unknown theme: {} (available: {})",
+                other,
+                PRESETS.join(", ")
+            ),
+        };
+
+        let overrides = [
+            ("accent", &colors.accent, &mut theme.accent),
+            ("highlight", &colors.highlight, &mut theme.highlight),
+            ("info", &colors.info, &mut theme.info),
+            ("text", &colors.text, &mut theme.text),
+            ("subtle", &colors.subtle, &mut theme.subtle),
+            ("muted", &colors.muted, &mut theme.muted),
+            ("timeshift", &colors.timeshift, &mut theme.timeshift),
+            ("alert", &colors.alert, &mut theme.alert),
+            ("background", &colors.background, &mut theme.background),
+        ];
+        for (role, value, color) in overrides {
+            if let Some(value) = value {
+                *color = Color::from_str(value)
+                    .map_err(|_| anyhow::anyhow!("Invalid color for {}: {}", role, value))?;
+            }
+        }
+        Ok(theme)
+    }
+
+    /// Block titles
+    pub fn title(&self) -> Style {
+        Style::default()
+            .fg(self.accent)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    /// The chosen item among several, e.g. the playing channel
+    pub fn selected(&self) -> Style {
+        Style::default()
+            .fg(self.accent)
+            .add_modifier(Modifier::REVERSED | Modifier::BOLD)
+    }
+}
+
+/// Per-role overrides from the config file's `[colors]` table: color names
+/// (`lightblue`), `#rrggbb`, or 256-color indexes
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ThemeColors {
+    pub accent: Option<String>,
+    pub highlight: Option<String>,
+    pub info: Option<String>,
+    pub text: Option<String>,
+    pub subtle: Option<String>,
+    pub muted: Option<String>,
+    pub timeshift: Option<String>,
+    pub alert: Option<String>,
+    pub background: Option<String>,
+}
+
+impl ThemeColors {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        let none = ThemeColors::default();
+        assert_eq!(Theme::load(None, &none).unwrap(), Theme::default());
+        assert_eq!(
+            Theme::load(Some("light"), &none).unwrap().accent,
+            Color::Blue
+        );
+        assert_eq!(
+            Theme::load(Some("mono"), &none).unwrap(),
+            Theme::load(Some("monochrome"), &none).unwrap()
+        );
+        assert!(Theme::load(Some("solarized"), &none).is_err());
+    }
+
+    #[test]
+    fn test_color_overrides() {
+        let colors = ThemeColors {
+            accent: Some("lightblue".to_string()),
+            highlight: Some("#ff8800".to_string()),
+            ..Default::default()
+        };
+        let theme = Theme::load(Some("light"), &colors).unwrap();
+        assert_eq!(theme.accent, Color::LightBlue);
+        assert_eq!(theme.highlight, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(theme.alert, Color::Red);
+
+        let bad = ThemeColors {
+            text: Some("not-a-color".to_string()),
+            ..Default::default()
+        };
+        assert!(Theme::load(None, &bad).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+//! The terminal's window title, kept to what is playing.
+//!
+//! The title is set with OSC 2 to e.g. "NHK FM — クラシックの庭". The title
+//! it replaces is pushed onto the terminal's title stack and popped back when
+//! [`TerminalTitle`] is dropped; terminals without the stack keep the last
+//! title shown. Under tmux this is the pane title, which `set-titles` passes
+//! on to the terminal tmux runs in.
+
+use std::io::{IsTerminal, Write};
+
+use crate::now_playing::NowPlayingRecord;
+
+/// Save the window title on the title stack (XTWINOPS 22)
+const PUSH_TITLE: &str = "\x1b[22;0t";
+/// Restore the window title saved by [`PUSH_TITLE`] (XTWINOPS 23)
+const POP_TITLE: &str = "\x1b[23;0t";
+
+/// Which of our outputs is the terminal
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Stdout,
+    Stderr,
+}
+
+/// The window title while playing, restored when dropped
+#[derive(Debug)]
+pub struct TerminalTitle {
+    target: Target,
+    /// What the title was last set to
+    shown: Option<String>,
+}
+
+impl TerminalTitle {
+    /// Take over the title of the terminal on stdout, or on stderr when
+    /// stdout is not one; `None` when neither is a terminal
+    pub fn start() -> Option<Self> {
+        let target = if std::io::stdout().is_terminal() {
+            Target::Stdout
+        } else if std::io::stderr().is_terminal() {
+            Target::Stderr
+        } else {
+            return None;
+        };
+        let title = TerminalTitle {
+            target,
+            shown: None,
+        };
+        title.write(PUSH_TITLE);
+        Some(title)
+    }
+
+    /// Show `record` as the title, marked when playback is paused
+    pub fn update(&mut self, record: Option<&NowPlayingRecord>, paused: bool) {
+        let Some(record) = record else {
+            return;
+        };
+        let text = title_text(record, paused);
+        if self.shown.as_ref() != Some(&text) {
+            self.write(&set_title(&text));
+            self.shown = Some(text);
+        }
+    }
+
+    fn write(&self, sequence: &str) {
+        let result = match self.target {
+            Target::Stdout => {
+                let mut out = std::io::stdout().lock();
+                out.write_all(sequence.as_bytes()).and_then(|_| out.flush())
+            }
+            Target::Stderr => std::io::stderr().lock().write_all(sequence.as_bytes()),
+        };
+        if let Err(e) = result {
+            log::debug!("Failed to set the terminal title: {}", e);
+        }
+    }
+}
+
+impl Drop for TerminalTitle {
+    fn drop(&mut self) {
+        self.write(POP_TITLE);
+    }
+}
+
+/// e.g. "NHK FM — クラシックの庭", or "⏸ NHK FM — クラシックの庭" when paused
+fn title_text(record: &NowPlayingRecord, paused: bool) -> String {
+    let pause = if paused { "⏸ " } else { "" };
+    format!("{}{} — {}", pause, record.station, record.title)
+}
+
+/// OSC 2 setting the title to `text`, with anything that would end the
+/// sequence early dropped
+fn set_title(text: &str) -> String {
+    let text: String = text.chars().filter(|c| !c.is_control()).collect();
+    format!("\x1b]2;{}\x07", text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::ChannelKind;
+
+    #[test]
+    fn test_title() {
+        let record =
+            NowPlayingRecord::new(ChannelKind::Fm, "東京", "クラシック\x07の庭\n", None, None);
+        let playing = title_text(&record, false);
+        assert!(playing.ends_with(" — クラシック\x07の庭\n"));
+        assert!(title_text(&record, true).starts_with("⏸ "));
+        assert_eq!(
+            set_title(&playing),
+            format!("\x1b]2;{} — クラシックの庭\x07", record.station)
+        );
+    }
+}
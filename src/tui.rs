@@ -1,119 +1,383 @@
 use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local, Utc};
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseButton,
+        MouseEventKind,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use ratatui::{
     Frame, Terminal,
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    layout::{Constraint, Direction, Layout, Position, Rect},
+    style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{
+        Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, Wrap,
+    },
 };
-use std::io::{self, Stdout};
+use std::collections::HashSet;
+use std::io::{self, Stdout, Write};
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::watch;
-use unicode_width::UnicodeWidthStr;
+use std::time::{Duration, Instant};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+use crate::area::area_matches;
+use crate::browser;
+use crate::cache;
 use crate::client::NhkRadioClient;
-use crate::player::{ChannelKind, run_audio_thread, run_stream_loop};
-use crate::types::Root;
+use crate::cue::CueSheet;
+use crate::device::{next_device, output_devices};
+use crate::discord::{DiscordConfig, DiscordPresence};
+use crate::dlna::{DlnaPlayback, DlnaRenderer};
+use crate::engine::{PlaybackEngine, PlaybackState};
+use crate::favorites::{self, Favorites, Reminder};
+use crate::graphics::{self, Picture, Protocol};
+use crate::hooks::{HookEvent, Hooks};
+use crate::i18n::{HelpKeys, area_label, tr};
+use crate::keymap::{Action, Keymap};
+use crate::logging::{self, LogEntry};
+use crate::media_controls::{MediaControls, MediaKey};
+use crate::now_playing::{NowPlayingFile, NowPlayingRecord};
+use crate::output::clock_time;
+use crate::player::{ChannelKind, PlaybackEvent, PlayerOptions};
+use crate::program_info::{ProgramInfo, credit};
+use crate::radio::{NowPlaying, program_day_url, today_jst};
+use crate::recorder::{RecordFormat, Recorder, RecordingStatus};
+use crate::scrobble::{ScrobbleConfig, Scrobbler};
+use crate::split::ProgramSplit;
+use crate::state::SessionState;
+use crate::stats::StreamStatsSnapshot;
+use crate::theme::Theme;
+use crate::title::TerminalTitle;
+use crate::types::{DaySchedule, Root, ScheduledProgram, StreamData};
+
+pub(crate) const VOLUME_STEP: u8 = 5;
+const REWIND_STEP: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Width of the program picture, in columns
+const EYECATCH_COLUMNS: u16 = 24;
+/// Narrowest now-playing text that still leaves room for the picture
+const EYECATCH_MIN_WIDTH: u16 = 60;
+
+/// Channels in the order the selector shows them
+const CHANNEL_ORDER: [ChannelKind; 3] = [ChannelKind::R1, ChannelKind::R2, ChannelKind::Fm];
+
+/// Wait after a program's end before refetching, so the listing has moved on
+const PROGRAM_END_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+/// Soonest refetch, for when the listing still shows an ended program
+const PROGRAM_REFRESH_MIN: std::time::Duration = std::time::Duration::from_secs(15);
+/// Latest refetch, in case a program runs over or the end time is missing
+const PROGRAM_REFRESH_MAX: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Time until the program listing should be refetched: just after the first
+/// present program on any channel ends
+fn next_program_refresh(program: &Root, now: DateTime<FixedOffset>) -> std::time::Duration {
+    [&program.r1, &program.r2, &program.r3]
+        .into_iter()
+        .filter_map(|channel| channel.present.as_ref())
+        .filter_map(|event| DateTime::parse_from_rfc3339(&event.end_date).ok())
+        .min()
+        .map(|end| (end - now).to_std().unwrap_or_default() + PROGRAM_END_GRACE)
+        .unwrap_or(PROGRAM_REFRESH_MAX)
+        .clamp(PROGRAM_REFRESH_MIN, PROGRAM_REFRESH_MAX)
+}
 
-pub struct ProgramInfo {
-    pub station_name: String,
-    pub area_name: String,
-    pub program_title: String,
-    pub start_time: String,
-    pub description: String,
+/// Fetch the program listing now and again whenever a program ends, until
+/// the receiver is dropped
+async fn refresh_program(
+    client: Arc<NhkRadioClient>,
+    program_url: String,
+    program_tx: tokio::sync::mpsc::UnboundedSender<Root>,
+) {
+    loop {
+        let wait = match client.fetch_program(&program_url).await {
+            Ok(root) => {
+                let wait = next_program_refresh(&root, Utc::now().fixed_offset());
+                if program_tx.send(root).is_err() {
+                    return;
+                }
+                wait
+            }
+            Err(e) => {
+                log::warn!("Failed to fetch program info: {}", e);
+                PROGRAM_REFRESH_MIN
+            }
+        };
+        tokio::time::sleep(wait).await;
+    }
 }
 
-impl ProgramInfo {
-    pub fn from_program(program: &Option<Root>, kind: ChannelKind, area_name: &str) -> Self {
-        let (program_title, description, start_time) = program
-            .as_ref()
-            .and_then(|p| {
-                let channel = match kind {
-                    ChannelKind::R1 => &p.r1,
-                    ChannelKind::R2 => &p.r2,
-                    ChannelKind::Fm => &p.r3,
-                };
-                channel.present.as_ref().map(|present| {
-                    let title = present
-                        .about
-                        .as_ref()
-                        .map(|a| a.name.clone())
-                        .unwrap_or_else(|| present.name.clone());
-                    let desc = present
-                        .about
-                        .as_ref()
-                        .map(|a| a.description.clone())
-                        .unwrap_or_default();
-                    let time = format_time(&present.start_date);
-                    (title, desc, time)
-                })
-            })
-            .unwrap_or_else(|| {
-                (
-                    "番組情報を取得中...".to_string(),
-                    String::new(),
-                    String::new(),
-                )
-            });
+/// Fraction of the program aired at `now` and whole minutes left, rounded up
+fn program_progress(
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    now: DateTime<FixedOffset>,
+) -> (f64, i64) {
+    let total = (end - start).num_seconds().max(1);
+    let elapsed = (now - start).num_seconds().clamp(0, total);
+    let left = total - elapsed;
+    (elapsed as f64 / total as f64, (left + 59) / 60)
+}
 
-        ProgramInfo {
-            station_name: kind.display_name().to_string(),
-            area_name: area_name.to_string(),
-            program_title,
-            start_time,
-            description,
-        }
-    }
-}
-
-fn format_time(iso_time: &str) -> String {
-    // Parse ISO format like "2025-11-25T23:00:00+09:00"
-    if iso_time.len() >= 16 {
-        let date_part = &iso_time[0..10];
-        let time_part = &iso_time[11..16];
-
-        // Convert to Japanese format
-        let parts: Vec<&str> = date_part.split('-').collect();
-        if parts.len() == 3 {
-            let month = parts[1].parse::<u32>().unwrap_or(0);
-            let day = parts[2].parse::<u32>().unwrap_or(0);
-
-            // Convert 24h time to AM/PM Japanese
-            let time_parts: Vec<&str> = time_part.split(':').collect();
-            if time_parts.len() == 2 {
-                let hour = time_parts[0].parse::<u32>().unwrap_or(0);
-                let minute = time_parts[1];
-                let (period, display_hour) = if hour < 12 {
-                    ("午前", if hour == 0 { 12 } else { hour })
-                } else {
-                    ("午後", if hour == 12 { 12 } else { hour - 12 })
-                };
-                return format!(
-                    "{}年{}月{}日 {}{:02}:{}",
-                    parts[0], month, day, period, display_hour, minute
-                );
-            }
+/// The day's timetable for one channel, opened with `t`
+pub struct ScheduleView {
+    pub channel: ChannelKind,
+    pub programs: Vec<ScheduledProgram>,
+    /// Program whose description is shown
+    pub selected: usize,
+}
+
+impl ScheduleView {
+    /// Timetable with the program airing at `at` selected
+    pub fn new(
+        channel: ChannelKind,
+        programs: Vec<ScheduledProgram>,
+        at: DateTime<FixedOffset>,
+    ) -> Self {
+        let selected = on_air_index(&programs, at).unwrap_or(0);
+        ScheduleView {
+            channel,
+            programs,
+            selected,
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.programs.len() {
+            self.selected += 1;
+        }
+    }
+}
+
+/// Index of the program airing at `at`
+fn on_air_index(programs: &[ScheduledProgram], at: DateTime<FixedOffset>) -> Option<usize> {
+    programs.iter().position(|program| {
+        match (
+            DateTime::parse_from_rfc3339(&program.start_date),
+            DateTime::parse_from_rfc3339(&program.end_date),
+        ) {
+            (Ok(start), Ok(end)) => start <= at && at < end,
+            _ => false,
+        }
+    })
+}
+
+/// Popup for retuning to another area, opened with `a`
+pub struct AreaPicker {
+    pub areas: Vec<StreamData>,
+    /// Typed so far; areas are matched fuzzily against it
+    pub filter: String,
+    /// Index into [`AreaPicker::matches`]
+    pub selected: usize,
+}
+
+impl AreaPicker {
+    /// Picker over `areas` with `current` selected
+    pub fn new(areas: Vec<StreamData>, current: &str) -> Self {
+        let selected = areas.iter().position(|d| d.area == current).unwrap_or(0);
+        AreaPicker {
+            areas,
+            filter: String::new(),
+            selected,
+        }
+    }
+
+    pub fn matches(&self) -> Vec<&StreamData> {
+        self.areas
+            .iter()
+            .filter(|d| area_matches(&self.filter, d))
+            .collect()
+    }
+
+    pub fn chosen(&self) -> Option<&StreamData> {
+        self.matches().get(self.selected).copied()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.filter.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.filter.pop();
+        self.selected = 0;
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches().len() {
+            self.selected += 1;
         }
     }
-    iso_time.to_string()
 }
 
 pub struct AppState {
     pub current_channel: ChannelKind,
+    /// Channel played before the current one, for `Tab`
+    pub previous_channel: Option<ChannelKind>,
     pub program_info: ProgramInfo,
     pub is_loading: bool,
     pub is_switching: bool,
+    pub is_paused: bool,
+    pub volume: u8,
+    pub is_muted: bool,
+    /// The recording in progress, from `--record` or `r`
+    pub recording: Option<RecordingStatus>,
+    /// When the player quits on its own, if a sleep timer is set
+    pub sleep_timer: Option<SleepTimer>,
+    pub favorites: Favorites,
+    /// A favorite about to air, until it starts or is tuned to
+    pub reminder: Option<Reminder>,
+    /// Output device chosen with `o`, or `None` for the default
+    pub device: Option<String>,
+    /// Set while the output device is being reopened
+    pub output_lost: bool,
+    /// Set while the broadcast has gone silent though its stream still arrives
+    pub silent: bool,
+    /// DLNA renderer playing instead of the local output
+    pub renderer: Option<String>,
+    /// Seconds behind live after rewinding
+    pub timeshift_secs: f64,
+    /// Seconds between the broadcast and what is heard, when the stream carries timestamps
+    pub latency_secs: Option<f64>,
+    /// RMS level of the left and right channel being heard, for the meter
+    pub levels: [f32; 2],
+    /// Lines of the program description scrolled past with `j`/`k`
+    pub description_scroll: usize,
+    /// Picture of the program shown, once downloaded
+    pub eyecatch: Option<Eyecatch>,
+    /// Whether the cast and music list is shown beside the program
+    pub show_credits: bool,
+    /// Lines of the cast and music list scrolled past with `j`/`k`
+    pub credits_scroll: usize,
+    /// Whether stream stats are shown beside the program instead
+    pub show_stats: bool,
+    /// Counters from the stream loop, when playing locally
+    pub stats: Option<StreamStatsSnapshot>,
+    /// Seconds of received audio not yet heard
+    pub buffered_secs: f64,
+    /// Whether recent log records are shown under the program
+    pub show_log: bool,
+    /// The records shown in the log pane, oldest first
+    pub log_entries: Vec<LogEntry>,
+    /// Brief confirmation in the status bar, and when it appeared
+    pub notice: Option<(&'static str, Instant)>,
+    /// Whether the day's timetable replaces the now-playing panel
+    pub show_schedule: bool,
+    /// Timetable last fetched, possibly for another channel
+    pub schedule: Option<ScheduleView>,
+    /// Open while choosing another area
+    pub area_picker: Option<AreaPicker>,
+    /// Whether the key binding overlay is shown
+    pub show_help: bool,
+    pub theme: Theme,
+    pub keymap: Keymap,
     pub animation_frame: usize,
 }
 
+impl AppState {
+    /// Show another program, from the top of its description and credits
+    pub fn set_program_info(&mut self, info: ProgramInfo) {
+        if info.program_title != self.program_info.program_title
+            || info.description != self.program_info.description
+        {
+            self.description_scroll = 0;
+            self.credits_scroll = 0;
+        }
+        if self.eyecatch.as_ref().map(|e| &e.url) != info.eyecatch_url.as_ref() {
+            self.eyecatch = None;
+        }
+        self.program_info = info;
+    }
+
+    /// Wall-clock broadcast time of the audio being heard
+    pub fn playback_time(&self) -> Option<DateTime<FixedOffset>> {
+        self.latency_secs.map(|latency| {
+            (Utc::now() - chrono::Duration::milliseconds((latency * 1000.0) as i64)).fixed_offset()
+        })
+    }
+
+    /// Whether the program shown is one of the favorites
+    pub fn is_favorite(&self) -> bool {
+        self.program_info.airs.is_some()
+            && self.favorites.matches(
+                &self.program_info.series_id,
+                &self.program_info.program_title,
+            )
+    }
+
+    /// Volume to apply to the sink, taking mute into account; the sleep
+    /// timer's fade-out is applied on top of this
+    pub fn effective_volume(&self) -> f32 {
+        if self.is_muted {
+            0.0
+        } else {
+            self.volume as f32 / 100.0
+        }
+    }
+}
+
+/// Minutes the sleep timer cycles through, before turning off again
+const SLEEP_PRESETS: [u64; 4] = [15, 30, 60, 90];
+/// How long playback fades out before the sleep timer quits
+const SLEEP_FADE: Duration = Duration::from_secs(10);
+
+/// Quits the player once one of the preset times has passed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SleepTimer {
+    pub minutes: u64,
+    pub ends: Instant,
+}
+
+impl SleepTimer {
+    /// The preset after `current`, counted from `now`; off after the longest
+    fn next(current: Option<&SleepTimer>, now: Instant) -> Option<Self> {
+        let index = match current {
+            Some(timer) => SLEEP_PRESETS.iter().position(|&m| m == timer.minutes)? + 1,
+            None => 0,
+        };
+        let minutes = *SLEEP_PRESETS.get(index)?;
+        Some(SleepTimer {
+            minutes,
+            ends: now + Duration::from_secs(minutes * 60),
+        })
+    }
+
+    fn remaining(&self, now: Instant) -> Duration {
+        self.ends.saturating_duration_since(now)
+    }
+
+    /// Share of the volume left while fading out, from 1.0 down to 0.0
+    fn fade(&self, now: Instant) -> f32 {
+        (self.remaining(now).as_secs_f32() / SLEEP_FADE.as_secs_f32()).min(1.0)
+    }
+}
+
+/// A program's picture, for terminals that can show one
+pub struct Eyecatch {
+    pub url: String,
+    pub picture: Picture,
+}
+
 pub struct Tui {
     terminal: Terminal<CrosstermBackend<Stdout>>,
+    mouse: bool,
+    /// How to draw pictures, if the terminal can
+    graphics: Option<Protocol>,
+    /// The picture on screen and where
+    placed: Option<(String, Rect)>,
 }
 
 impl Tui {
@@ -121,24 +385,98 @@ impl Tui {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
         execute!(stdout, EnterAlternateScreen)?;
+        // Log output would land on top of the screen; the log pane has it
+        logging::set_quiet(true);
         let backend = CrosstermBackend::new(stdout);
         let terminal = Terminal::new(backend)?;
-        Ok(Self { terminal })
+        Ok(Self {
+            terminal,
+            mouse: false,
+            graphics: None,
+            placed: None,
+        })
+    }
+
+    /// Report clicks and scrolling as events until the terminal is restored
+    pub fn enable_mouse(&mut self) -> Result<()> {
+        execute!(self.terminal.backend_mut(), EnableMouseCapture)?;
+        self.mouse = true;
+        Ok(())
+    }
+
+    /// Show pictures if the terminal speaks a graphics protocol; returns
+    /// whether it does
+    pub fn enable_graphics(&mut self) -> bool {
+        self.graphics = Protocol::detect();
+        self.graphics.is_some()
     }
 
     pub fn restore(&mut self) -> Result<()> {
+        if self.mouse {
+            execute!(self.terminal.backend_mut(), DisableMouseCapture)?;
+        }
         disable_raw_mode()?;
         execute!(self.terminal.backend_mut(), LeaveAlternateScreen)?;
+        logging::set_quiet(false);
         self.terminal.show_cursor()?;
         Ok(())
     }
 
     pub fn draw(&mut self, state: &AppState) -> Result<()> {
+        let placement = match (self.graphics, &state.eyecatch) {
+            (Some(_), Some(eyecatch)) => {
+                eyecatch_area(self.area()?, state).map(|area| (eyecatch.url.clone(), area))
+            }
+            _ => None,
+        };
+        let changed = placement != self.placed;
+        if changed && self.placed.is_some() {
+            // Repaint every cell to wipe the old picture
+            if let Some(protocol) = self.graphics {
+                write!(self.terminal.backend_mut(), "{}", protocol.clear())?;
+            }
+            self.terminal.clear()?;
+        }
+
         self.terminal.draw(|f| {
             render_ui(f, state);
         })?;
+
+        if changed {
+            if let (Some(protocol), Some((_, area)), Some(eyecatch)) =
+                (self.graphics, placement.as_ref(), state.eyecatch.as_ref())
+            {
+                match eyecatch.picture.encode(
+                    protocol,
+                    area.width,
+                    area.height,
+                    graphics::cell_size(),
+                ) {
+                    Ok(sequence) => {
+                        let backend = self.terminal.backend_mut();
+                        execute!(backend, crossterm::cursor::MoveTo(area.x, area.y))?;
+                        write!(backend, "{}", sequence)?;
+                        backend.flush()?;
+                    }
+                    Err(e) => log::warn!("Failed to draw the program picture: {}", e),
+                }
+            }
+            self.placed = placement;
+        }
+        Ok(())
+    }
+
+    /// Draw an arbitrary screen (used by views other than the live player)
+    pub fn draw_with(&mut self, render: impl FnOnce(&mut Frame)) -> Result<()> {
+        self.terminal.draw(render)?;
         Ok(())
     }
+
+    /// The whole screen, for hit-testing clicks against the layout
+    pub fn area(&self) -> Result<Rect> {
+        let size = self.terminal.size()?;
+        Ok(Rect::new(0, 0, size.width, size.height))
+    }
 }
 
 impl Drop for Tui {
@@ -147,7 +485,7 @@ impl Drop for Tui {
     }
 }
 
-fn truncate_str(s: &str, max_width: usize) -> String {
+pub(crate) fn truncate_str(s: &str, max_width: usize) -> String {
     let width = UnicodeWidthStr::width(s);
     if width <= max_width {
         return s.to_string();
@@ -169,11 +507,8 @@ fn truncate_str(s: &str, max_width: usize) -> String {
     result
 }
 
-fn render_ui(f: &mut Frame, state: &AppState) {
-    let size = f.area();
-
-    // Main layout
-    let chunks = Layout::default()
+fn main_layout(area: Rect) -> std::rc::Rc<[Rect]> {
+    Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .constraints([
@@ -184,27 +519,252 @@ fn render_ui(f: &mut Frame, state: &AppState) {
             Constraint::Length(3), // Status bar
             Constraint::Length(2), // Help
         ])
-        .split(size);
+        .split(area)
+}
+
+/// One box per channel in [`CHANNEL_ORDER`]
+fn channel_layout(area: Rect) -> std::rc::Rc<[Rect]> {
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(33),
+            Constraint::Percentage(34),
+            Constraint::Percentage(33),
+        ])
+        .split(area)
+}
+
+/// Rows of the log pane, borders included
+const LOG_PANE_HEIGHT: u16 = 8;
+
+/// The space for the program and its side panel, and the log pane under
+/// them when that is open
+fn log_layout(area: Rect, state: &AppState) -> (Rect, Option<Rect>) {
+    if !state.show_log {
+        return (area, None);
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(6), Constraint::Length(LOG_PANE_HEIGHT)])
+        .split(area);
+    (chunks[0], Some(chunks[1]))
+}
+
+/// The program panel, and the credits or stats panel beside it when one is
+/// open
+fn program_layout(area: Rect, state: &AppState) -> (Rect, Option<Rect>) {
+    let area = log_layout(area, state).0;
+    if !state.show_credits && !state.show_stats {
+        return (area, None);
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+        .split(area);
+    (chunks[0], Some(chunks[1]))
+}
+
+/// The schedule panel's list and description, inside its border
+fn schedule_layout(area: Rect) -> std::rc::Rc<[Rect]> {
+    let inner = Block::default().borders(Borders::ALL).inner(area);
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(3)])
+        .split(inner)
+}
+
+/// First row shown in a list `height` rows tall, scrolled just enough to
+/// show `selected`
+fn scroll_offset(selected: usize, height: u16) -> usize {
+    (selected + 1).saturating_sub(height as usize)
+}
+
+/// Something on screen that reacts to a click
+#[derive(Debug, PartialEq, Eq)]
+enum Click {
+    Channel(ChannelKind),
+    /// A row of the schedule panel, by index into its programs
+    Program(usize),
+}
+
+/// What a click at `position` lands on, given the whole screen `area`
+fn hit_test(area: Rect, state: &AppState, position: Position) -> Option<Click> {
+    let chunks = main_layout(area);
+    if let Some(i) = channel_layout(chunks[0])
+        .iter()
+        .position(|r| r.contains(position))
+    {
+        return Some(Click::Channel(CHANNEL_ORDER[i]));
+    }
+
+    let schedule = state
+        .schedule
+        .as_ref()
+        .filter(|s| state.show_schedule && s.channel == state.current_channel)?;
+    let list = schedule_layout(program_layout(chunks[2], state).0)[0];
+    if !list.contains(position) {
+        return None;
+    }
+    let index = scroll_offset(schedule.selected, list.height) + (position.y - list.y) as usize;
+    (index < schedule.programs.len()).then_some(Click::Program(index))
+}
+
+fn render_ui(f: &mut Frame, state: &AppState) {
+    let chunks = main_layout(f.area());
 
     // Channel selector
     render_channel_selector(f, chunks[0], state);
 
-    // Now playing info
-    render_now_playing(f, chunks[2], state);
+    // Now playing info, or the day's timetable, and a panel beside them
+    let (program_area, side_area) = program_layout(chunks[2], state);
+    if state.show_schedule {
+        render_schedule(f, program_area, state);
+    } else {
+        render_now_playing(f, program_area, state);
+    }
+    if let Some(area) = side_area {
+        if state.show_stats {
+            render_stats(f, area, state);
+        } else {
+            render_credits(f, area, state);
+        }
+    }
+    if let (_, Some(area)) = log_layout(chunks[2], state) {
+        render_log(f, area, state);
+    }
 
     // Status bar
     render_status_bar(f, chunks[4], state);
 
     // Help
-    render_help(f, chunks[5]);
+    render_help(f, chunks[5], &state.theme, &state.keymap);
 
     // Switching popup (render on top)
     if state.is_switching {
         render_switching_popup(f, state);
     }
+
+    if let Some(picker) = &state.area_picker {
+        render_area_picker(f, picker, &state.theme);
+    }
+
+    if state.show_help {
+        render_help_overlay(f, &state.theme, &state.keymap);
+    }
+}
+
+fn render_help_overlay(f: &mut Frame, theme: &Theme, keymap: &Keymap) {
+    use ratatui::widgets::Clear;
+
+    let area = f.area();
+
+    let key_bindings: Vec<(String, &str)> = tr()
+        .key_bindings
+        .iter()
+        .map(|(keys, action)| {
+            let keys = match keys {
+                HelpKeys::Actions(actions) => keymap.describe_all(actions),
+                HelpKeys::Mouse(gesture) => gesture.to_string(),
+            };
+            (keys, *action)
+        })
+        .collect();
+    let key_width = key_bindings
+        .iter()
+        .map(|(key, _)| key.width())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<Line> = key_bindings
+        .iter()
+        .map(|(key, action)| {
+            Line::from(vec![
+                Span::styled(
+                    format!(" {}{} ", key, " ".repeat(key_width - key.width())),
+                    Style::default().fg(theme.accent),
+                ),
+                Span::raw(*action),
+            ])
+        })
+        .collect();
+
+    let content_width = lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16;
+    let popup_width = (content_width + 3).min(area.width);
+    let popup_height = (lines.len() as u16 + 2).min(area.height);
+    let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+    f.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .title(tr().keys_title)
+        .title_style(theme.title())
+        .title_bottom(Line::from(tr().keys_dismiss).centered())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.highlight))
+        .style(Style::default().bg(theme.background));
+
+    f.render_widget(Paragraph::new(lines).block(block), popup_area);
+}
+
+fn render_area_picker(f: &mut Frame, picker: &AreaPicker, theme: &Theme) {
+    use ratatui::widgets::Clear;
+
+    let area = f.area();
+    let matches = picker.matches();
+
+    // Room for the filter line and every area
+    let popup_width = 32.min(area.width);
+    let popup_height = (picker.areas.len() as u16 + 4).min(area.height);
+    let popup_x = (area.width.saturating_sub(popup_width)) / 2;
+    let popup_y = (area.height.saturating_sub(popup_height)) / 2;
+    let popup_area = Rect::new(popup_x, popup_y, popup_width, popup_height);
+
+    f.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .title(tr().area_picker_title)
+        .title_style(theme.title())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.highlight))
+        .style(Style::default().bg(theme.background));
+
+    let inner = block.inner(popup_area);
+    f.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(inner);
+
+    let filter =
+        Paragraph::new(format!("🔍 {}", picker.filter)).style(Style::default().fg(theme.highlight));
+    f.render_widget(filter, chunks[0]);
+
+    if matches.is_empty() {
+        f.render_widget(
+            Paragraph::new(tr().no_matching_area).style(Style::default().fg(theme.muted)),
+            chunks[1],
+        );
+        return;
+    }
+
+    let items: Vec<ListItem> = matches
+        .iter()
+        .map(|d| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!(" {} ", d.areajp), Style::default().fg(theme.text)),
+                Span::styled(format!("({})", d.area), Style::default().fg(theme.muted)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut list_state = ListState::default().with_selected(Some(picker.selected));
+    f.render_stateful_widget(list, chunks[1], &mut list_state);
 }
 
 fn render_switching_popup(f: &mut Frame, state: &AppState) {
+    let theme = &state.theme;
     use ratatui::widgets::Clear;
 
     let area = f.area();
@@ -224,25 +784,21 @@ fn render_switching_popup(f: &mut Frame, state: &AppState) {
     let spinner = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
     let frame = spinner[state.animation_frame % spinner.len()];
 
-    let text = format!("{} 切替中...", frame);
-    let channel_name = state.current_channel.display_name();
+    let text = format!("{} {}", frame, tr().switching);
+    let channel_name = tr().channel_name(state.current_channel);
 
     let block = Block::default()
         .title(format!(" {} ", channel_name))
-        .title_style(
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        )
+        .title_style(theme.title())
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Yellow))
-        .style(Style::default().bg(Color::Black));
+        .border_style(Style::default().fg(theme.highlight))
+        .style(Style::default().bg(theme.background));
 
     let inner = block.inner(popup_area);
     f.render_widget(block, popup_area);
 
     let paragraph = Paragraph::new(text)
-        .style(Style::default().fg(Color::Yellow))
+        .style(Style::default().fg(theme.highlight))
         .alignment(ratatui::layout::Alignment::Center);
 
     // Center vertically within the popup
@@ -256,18 +812,10 @@ fn render_switching_popup(f: &mut Frame, state: &AppState) {
 }
 
 fn render_channel_selector(f: &mut Frame, area: Rect, state: &AppState) {
-    let channels = [ChannelKind::R1, ChannelKind::R2, ChannelKind::Fm];
-
-    let channel_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(33),
-            Constraint::Percentage(34),
-            Constraint::Percentage(33),
-        ])
-        .split(area);
+    let theme = &state.theme;
+    let channel_chunks = channel_layout(area);
 
-    for (i, &channel) in channels.iter().enumerate() {
+    for (i, &channel) in CHANNEL_ORDER.iter().enumerate() {
         let is_selected = channel == state.current_channel;
         let key = match channel {
             ChannelKind::R1 => "1",
@@ -278,20 +826,17 @@ fn render_channel_selector(f: &mut Frame, area: Rect, state: &AppState) {
         let label = format!("[{}] {}", key, channel.short_name());
 
         let style = if is_selected {
-            Style::default()
-                .fg(Color::Black)
-                .bg(Color::Cyan)
-                .add_modifier(Modifier::BOLD)
+            theme.selected()
         } else {
-            Style::default().fg(Color::Gray)
+            Style::default().fg(theme.subtle)
         };
 
         let block = Block::default()
             .borders(Borders::ALL)
             .border_style(if is_selected {
-                Style::default().fg(Color::Cyan)
+                Style::default().fg(theme.accent)
             } else {
-                Style::default().fg(Color::DarkGray)
+                Style::default().fg(theme.muted)
             });
 
         let paragraph = Paragraph::new(label)
@@ -304,21 +849,18 @@ fn render_channel_selector(f: &mut Frame, area: Rect, state: &AppState) {
 }
 
 fn render_now_playing(f: &mut Frame, area: Rect, state: &AppState) {
+    let theme = &state.theme;
     let info = &state.program_info;
 
     let title = format!(" 📻 NHK {} - {} ", info.station_name, info.area_name);
 
     let block = Block::default()
         .title(title)
-        .title_style(
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        )
+        .title_style(theme.title())
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Cyan));
+        .border_style(Style::default().fg(theme.accent));
 
-    let inner = block.inner(area);
+    let (inner, _) = now_playing_inner(area, state);
     f.render_widget(block, area);
 
     if inner.height < 2 {
@@ -329,22 +871,17 @@ fn render_now_playing(f: &mut Frame, area: Rect, state: &AppState) {
 
     let mut lines = vec![];
 
-    // Program title with time
-    let title_line = if info.start_time.is_empty() {
-        format!(
-            "♪ {}",
-            truncate_str(&info.program_title, content_width.saturating_sub(2))
-        )
-    } else {
-        format!(
-            "♪ {}",
-            truncate_str(&info.program_title, content_width.saturating_sub(2))
-        )
-    };
+    // Program title, starred when it is a favorite
+    let marker = if state.is_favorite() { "★" } else { "♪" };
+    let title_line = format!(
+        "{} {}",
+        marker,
+        truncate_str(&info.program_title, content_width.saturating_sub(2))
+    );
     lines.push(Line::from(Span::styled(
         title_line,
         Style::default()
-            .fg(Color::Yellow)
+            .fg(theme.highlight)
             .add_modifier(Modifier::BOLD),
     )));
 
@@ -352,204 +889,1599 @@ fn render_now_playing(f: &mut Frame, area: Rect, state: &AppState) {
     if !info.start_time.is_empty() {
         lines.push(Line::from(Span::styled(
             format!("  {}", info.start_time),
-            Style::default().fg(Color::Green),
+            Style::default().fg(theme.info),
         )));
     }
 
     // Empty line
     lines.push(Line::from(""));
 
-    // Description (wrap if needed)
-    if !info.description.is_empty() {
-        let desc = truncate_str(&info.description, content_width);
+    let text_area = now_playing_text_area(inner, info);
+    f.render_widget(Paragraph::new(lines), text_area);
+
+    // Description, wrapped and scrolled to what fits
+    let (rows, description) = description_view(inner, info);
+    let description = description
+        .into_iter()
+        .map(|line| {
+            Line::from(Span::styled(
+                line,
+                Style::default().fg(theme.text).add_modifier(Modifier::DIM),
+            ))
+        })
+        .collect();
+    let shown_height = render_scrolled(f, rows, description, state.description_scroll, theme);
+
+    // Neighbouring programs, then where to read more
+    let mut lines = vec![];
+    for (label, slot) in [
+        (tr().next_label, &info.next),
+        (tr().previous_label, &info.previous),
+    ] {
+        if let Some(slot) = slot {
+            let prefix = format!("{}: {} 〜 ", label, slot.start_time);
+            lines.push(Line::from(vec![
+                Span::styled(prefix.clone(), Style::default().fg(theme.info)),
+                Span::styled(
+                    truncate_str(&slot.title, content_width.saturating_sub(prefix.width())),
+                    Style::default().fg(theme.subtle),
+                ),
+            ]));
+        }
+    }
+    if let Some(url) = &info.page_url {
         lines.push(Line::from(Span::styled(
-            desc,
-            Style::default()
-                .fg(Color::White)
-                .add_modifier(Modifier::DIM),
+            truncate_str(&format!("🔗 {}", url), content_width),
+            Style::default().fg(theme.accent),
+        )));
+    }
+    if !info.hashtags.is_empty() {
+        lines.push(Line::from(Span::styled(
+            truncate_str(&info.hashtags.join(" "), content_width),
+            Style::default().fg(theme.info),
         )));
     }
+    let neighbours_y = rows.y + shown_height;
+    let neighbours = Rect {
+        y: neighbours_y,
+        height: text_area.bottom().saturating_sub(neighbours_y),
+        ..text_area
+    };
+    f.render_widget(Paragraph::new(lines), neighbours);
+
+    // Progress through the program along the bottom edge
+    if let Some((start, end)) = info.airs
+        && text_area.height < inner.height
+    {
+        let now = state
+            .playback_time()
+            .unwrap_or_else(|| Utc::now().fixed_offset());
+        let (ratio, minutes_left) = program_progress(start, end, now);
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(theme.accent).bg(theme.muted))
+            .ratio(ratio)
+            .label(format!(
+                "{}〜{}  {}",
+                start.format("%H:%M"),
+                end.format("%H:%M"),
+                (tr().minutes_left)(minutes_left)
+            ));
+        let gauge_area = Rect {
+            y: text_area.bottom(),
+            height: 1,
+            ..inner
+        };
+        f.render_widget(gauge, gauge_area);
+    }
+}
 
-    let paragraph = Paragraph::new(lines);
-    f.render_widget(paragraph, inner);
+/// Draw as much of `lines` as fits in `area` from line `scroll` on, with a
+/// scrollbar when not all of them fit; returns the rows used
+fn render_scrolled(
+    f: &mut Frame,
+    area: Rect,
+    lines: Vec<Line>,
+    scroll: usize,
+    theme: &Theme,
+) -> u16 {
+    let max_scroll = lines.len().saturating_sub(area.height as usize);
+    let scroll = scroll.min(max_scroll);
+    let shown: Vec<Line> = lines
+        .into_iter()
+        .skip(scroll)
+        .take(area.height as usize)
+        .collect();
+    let shown_height = shown.len() as u16;
+    f.render_widget(Paragraph::new(shown), area);
+    if max_scroll > 0 {
+        let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+            .begin_symbol(None)
+            .end_symbol(None)
+            .style(Style::default().fg(theme.muted));
+        let mut scrollbar_state = ScrollbarState::new(max_scroll).position(scroll);
+        f.render_stateful_widget(scrollbar, area, &mut scrollbar_state);
+    }
+    shown_height
 }
 
-fn render_status_bar(f: &mut Frame, area: Rect, state: &AppState) {
-    let status = if state.is_loading {
-        let spinner = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
-        let frame = spinner[state.animation_frame % spinner.len()];
-        format!("{} 読み込み中...", frame)
-    } else {
-        "▶ 再生中".to_string()
-    };
+/// Inside the now-playing panel at `area`: the text, and the picture to its
+/// left when there is one
+fn now_playing_inner(area: Rect, state: &AppState) -> (Rect, Option<Rect>) {
+    let inner = Block::default().borders(Borders::ALL).inner(area);
+    if state.eyecatch.is_none() || inner.width < EYECATCH_MIN_WIDTH {
+        return (inner, None);
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Length(EYECATCH_COLUMNS),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .split(inner);
+    (chunks[2], Some(chunks[0]))
+}
+
+/// Where the program picture goes on a screen of `area`; nowhere while the
+/// panel is hidden or something is drawn over it
+fn eyecatch_area(area: Rect, state: &AppState) -> Option<Rect> {
+    if state.show_schedule || state.show_help || state.is_switching || state.area_picker.is_some() {
+        return None;
+    }
+    let panel = program_layout(main_layout(area)[2], state).0;
+    now_playing_inner(panel, state).1
+}
 
-    let style = if state.is_loading {
-        Style::default().fg(Color::Yellow)
+/// The now-playing panel's text, above the progress gauge when there is one
+fn now_playing_text_area(inner: Rect, info: &ProgramInfo) -> Rect {
+    if info.airs.is_some() && inner.height >= 3 {
+        Rect {
+            height: inner.height - 1,
+            ..inner
+        }
     } else {
-        Style::default().fg(Color::Green)
+        inner
+    }
+}
+
+/// Rows the now-playing text at `inner` gives the description, between the
+/// title and the neighbouring programs and links, and the description
+/// wrapped to fit
+fn description_view(inner: Rect, info: &ProgramInfo) -> (Rect, Vec<String>) {
+    let text_area = now_playing_text_area(inner, info);
+    let header = if info.start_time.is_empty() { 2 } else { 3 };
+    let neighbours = [&info.next, &info.previous]
+        .iter()
+        .filter(|slot| slot.is_some())
+        .count() as u16;
+    let links = u16::from(info.page_url.is_some()) + u16::from(!info.hashtags.is_empty());
+    let rows = Rect {
+        y: text_area.y + header.min(text_area.height),
+        height: text_area.height.saturating_sub(header + neighbours + links),
+        ..text_area
     };
+    let width = inner.width.saturating_sub(2) as usize;
+    (rows, wrap_text(&info.description, width))
+}
 
-    let paragraph = Paragraph::new(status)
-        .style(style)
-        .alignment(ratatui::layout::Alignment::Center);
+/// Lines scrolled off the top when the description is at its end
+fn description_max_scroll(area: Rect, state: &AppState) -> usize {
+    let (inner, _) = now_playing_inner(area, state);
+    let (rows, description) = description_view(inner, &state.program_info);
+    description.len().saturating_sub(rows.height as usize)
+}
 
-    f.render_widget(paragraph, area);
+/// Break `text` into lines at most `width` columns wide, at spaces between
+/// words and anywhere within Japanese text
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    if width == 0 {
+        return lines;
+    }
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut line_width = 0;
+        for c in paragraph.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if line_width + char_width > width {
+                // Carry a word split by the edge over to the next line
+                let carry = line
+                    .rfind(' ')
+                    .map(|i| i + 1)
+                    .filter(|&i| {
+                        !c.is_whitespace()
+                            && i < line.len()
+                            && line[i..].chars().all(|c| c.is_ascii_graphic())
+                    })
+                    .map(|i| line.split_off(i))
+                    .unwrap_or_default();
+                lines.push(line.trim_end().to_string());
+                line_width = carry.width();
+                line = carry;
+                if c.is_whitespace() {
+                    continue;
+                }
+            }
+            line.push(c);
+            line_width += char_width;
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    lines
 }
 
-fn render_help(f: &mut Frame, area: Rect) {
-    let help = Line::from(vec![
-        Span::styled("[1]", Style::default().fg(Color::Cyan)),
-        Span::raw(" R1  "),
-        Span::styled("[2]", Style::default().fg(Color::Cyan)),
-        Span::raw(" R2  "),
-        Span::styled("[3]", Style::default().fg(Color::Cyan)),
-        Span::raw(" FM  "),
-        Span::styled("[←/→]", Style::default().fg(Color::Cyan)),
-        Span::raw(" 切替  "),
-        Span::styled("[q]", Style::default().fg(Color::Red)),
-        Span::raw(" 終了"),
-    ]);
+fn render_log(f: &mut Frame, area: Rect, state: &AppState) {
+    let theme = &state.theme;
+    let block = Block::default()
+        .title(tr().log_title)
+        .title_style(theme.title())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
 
-    let paragraph = Paragraph::new(help).alignment(ratatui::layout::Alignment::Center);
+    if state.log_entries.is_empty() {
+        let text = Paragraph::new(tr().no_log)
+            .style(Style::default().fg(theme.muted))
+            .block(block);
+        f.render_widget(text, area);
+        return;
+    }
 
-    f.render_widget(paragraph, area);
+    let inner = block.inner(area);
+    let skip = state
+        .log_entries
+        .len()
+        .saturating_sub(inner.height as usize);
+    let lines: Vec<Line> = state.log_entries[skip..]
+        .iter()
+        .map(|entry| {
+            let color = match entry.level {
+                log::Level::Error => theme.alert,
+                log::Level::Warn => theme.highlight,
+                _ => theme.muted,
+            };
+            let text = format!(
+                "{} {:<5} {}",
+                entry.time.format("%H:%M:%S"),
+                entry.level,
+                entry.message.replace('\n', " ")
+            );
+            Line::from(Span::styled(
+                truncate_str(&text, inner.width as usize),
+                Style::default().fg(color),
+            ))
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines).block(block), area);
 }
 
-pub async fn run_interactive_player(area: String, initial_kind: ChannelKind) -> Result<()> {
-    let client = Arc::new(NhkRadioClient::new());
-    let config = client.fetch_config().await?;
-
-    let stream_data = config
-        .stream_url
-        .data
+fn render_credits(f: &mut Frame, area: Rect, state: &AppState) {
+    let theme = &state.theme;
+    let block = Block::default()
+        .title(tr().credits_title)
+        .title_style(theme.title())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let lines = credit_lines(&state.program_info, inner.width, theme);
+    render_scrolled(f, inner, lines, state.credits_scroll, theme);
+}
+
+fn render_stats(f: &mut Frame, area: Rect, state: &AppState) {
+    let theme = &state.theme;
+    let block = Block::default()
+        .title(tr().stats_title)
+        .title_style(theme.title())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let Some(stats) = &state.stats else {
+        let text = Paragraph::new(tr().no_stats)
+            .style(Style::default().fg(theme.muted))
+            .wrap(Wrap { trim: true })
+            .block(block);
+        f.render_widget(text, area);
+        return;
+    };
+
+    let rows = stats_rows(stats, state.buffered_secs, Instant::now());
+    let label_width = rows
+        .iter()
+        .map(|(label, _)| label.width())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<Line> = rows
+        .into_iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{}{}  ", label, " ".repeat(label_width - label.width())),
+                    Style::default().fg(theme.muted),
+                ),
+                Span::styled(value, Style::default().fg(theme.text)),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Label and value of each line in the stats panel
+fn stats_rows(
+    stats: &StreamStatsSnapshot,
+    buffered_secs: f64,
+    now: Instant,
+) -> Vec<(&'static str, String)> {
+    let t = tr();
+    vec![
+        (
+            t.stats_bitrate,
+            stats
+                .bitrate
+                .map_or("—".to_string(), |bps| format!("{} kbps", bps / 1000)),
+        ),
+        (t.stats_buffered, format!("{:.1} s", buffered_secs)),
+        (t.stats_fetched, stats.segments_fetched.to_string()),
+        (t.stats_failed, stats.segments_failed.to_string()),
+        (t.stats_missed, stats.segments_missed.to_string()),
+        (t.stats_decode_errors, stats.decode_errors.to_string()),
+        (t.stats_dropped, format!("{:.1} s", stats.dropped_secs)),
+        (
+            t.stats_refreshed,
+            stats.last_refresh.map_or("—".to_string(), |at| {
+                (t.secs_ago)(now.saturating_duration_since(at).as_secs())
+            }),
+        ),
+        (
+            t.stats_downloaded,
+            match stats.bandwidth_cap {
+                Some(cap) => format!(
+                    "{:.1} / {:.1} MB",
+                    stats.bytes_downloaded as f64 / 1_000_000.0,
+                    cap as f64 / 1_000_000.0
+                ),
+                None => format!("{:.1} MB", stats.bytes_downloaded as f64 / 1_000_000.0),
+            },
+        ),
+        (t.stats_underruns, stats.underruns.to_string()),
+        (t.stats_reconnects, stats.reconnects.to_string()),
+    ]
+}
+
+/// The cast and music list, wrapped to a panel `width` columns wide
+fn credit_lines(info: &ProgramInfo, width: u16, theme: &Theme) -> Vec<Line<'static>> {
+    // Leave the rightmost column to the scrollbar
+    let width = width.saturating_sub(2) as usize;
+    let mut lines = vec![];
+    let push = |lines: &mut Vec<Line<'static>>, text: &str, indent: usize, style: Style| {
+        let pad = " ".repeat(indent);
+        for line in wrap_text(text, width.saturating_sub(indent)) {
+            lines.push(Line::from(Span::styled(format!("{}{}", pad, line), style)));
+        }
+    };
+
+    if info.cast.is_empty() && info.music.is_empty() {
+        push(
+            &mut lines,
+            tr().no_credits,
+            0,
+            Style::default().fg(theme.muted),
+        );
+    }
+    if !info.cast.is_empty() {
+        push(&mut lines, tr().cast_heading, 0, theme.title());
+        for name in &info.cast {
+            push(&mut lines, name, 1, Style::default().fg(theme.text));
+        }
+    }
+    if !info.music.is_empty() {
+        if !info.cast.is_empty() {
+            lines.push(Line::default());
+        }
+        push(&mut lines, tr().music_heading, 0, theme.title());
+        for piece in &info.music {
+            push(
+                &mut lines,
+                &format!("♪ {}", piece.title),
+                1,
+                Style::default().fg(theme.highlight),
+            );
+            if !piece.composer.is_empty() {
+                push(
+                    &mut lines,
+                    &credit(Some(tr().composer), &piece.composer),
+                    3,
+                    Style::default().fg(theme.subtle),
+                );
+            }
+            for performer in &piece.performers {
+                push(&mut lines, performer, 3, Style::default().fg(theme.subtle));
+            }
+        }
+    }
+    lines
+}
+
+fn render_schedule(f: &mut Frame, area: Rect, state: &AppState) {
+    let theme = &state.theme;
+    let title = (tr().schedule_title)(tr().channel_name(state.current_channel));
+
+    let block = Block::default()
+        .title(title)
+        .title_style(theme.title())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let Some(schedule) = state
+        .schedule
+        .as_ref()
+        .filter(|s| s.channel == state.current_channel)
+    else {
+        f.render_widget(
+            Paragraph::new(tr().schedule_loading).style(Style::default().fg(theme.muted)),
+            inner,
+        );
+        return;
+    };
+
+    let chunks = schedule_layout(area);
+
+    let now = state
+        .playback_time()
+        .unwrap_or_else(|| Utc::now().fixed_offset());
+    let on_air = on_air_index(&schedule.programs, now);
+    let content_width = chunks[0].width.saturating_sub(2) as usize;
+
+    let items: Vec<ListItem> = schedule
+        .programs
         .iter()
-        .find(|d| d.area == area)
-        .ok_or_else(|| anyhow::anyhow!("Area not found: {}", area))?
-        .clone();
+        .enumerate()
+        .map(|(i, program)| {
+            let times = format!(
+                "{}-{} ",
+                clock_time(&program.start_date),
+                clock_time(&program.end_date)
+            );
+            let name = truncate_str(
+                &program.name,
+                content_width.saturating_sub(times.width() + 2),
+            );
+            let style = if Some(i) == on_air {
+                Style::default()
+                    .fg(theme.highlight)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(theme.text)
+            };
+            let marker = if Some(i) == on_air { "♪ " } else { "  " };
+            ListItem::new(Line::from(vec![
+                Span::styled(marker, style),
+                Span::styled(times, Style::default().fg(theme.info)),
+                Span::styled(name, style),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    // Scrolled explicitly so clicks can be mapped back to rows
+    let mut list_state = ListState::default()
+        .with_offset(scroll_offset(schedule.selected, chunks[0].height))
+        .with_selected(Some(schedule.selected));
+    f.render_stateful_widget(list, chunks[0], &mut list_state);
+
+    if let Some(program) = schedule.programs.get(schedule.selected) {
+        let description = Paragraph::new(program.description.as_str())
+            .style(Style::default().fg(theme.text).add_modifier(Modifier::DIM))
+            .wrap(Wrap { trim: true });
+        f.render_widget(description, chunks[1]);
+    }
+}
+
+fn render_status_bar(f: &mut Frame, area: Rect, state: &AppState) {
+    let theme = &state.theme;
+    let status = if state.is_loading {
+        let spinner = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+        let frame = spinner[state.animation_frame % spinner.len()];
+        format!("{} {}", frame, tr().loading)
+    } else if state.output_lost {
+        format!("⚠ {}", tr().output_lost)
+    } else if state.silent {
+        format!("⚠ {}", tr().silence)
+    } else if state.is_paused {
+        tr().paused.to_string()
+    } else {
+        tr().playing.to_string()
+    };
+
+    let style = if state.is_loading || state.output_lost || state.silent || state.is_paused {
+        Style::default().fg(theme.highlight)
+    } else {
+        Style::default().fg(theme.info)
+    };
+
+    let mut status_spans = vec![Span::styled(status, style)];
+    if state.timeshift_secs > 0.0 {
+        status_spans.push(Span::styled(
+            format!("  {}", (tr().behind_live)(state.timeshift_secs)),
+            Style::default().fg(theme.timeshift),
+        ));
+    } else if let Some(latency) = state.latency_secs {
+        status_spans.push(Span::styled(
+            format!("  LIVE −{:.0}s", latency),
+            Style::default().fg(theme.muted),
+        ));
+    }
+    if let Some(ref recording) = state.recording {
+        status_spans.push(Span::styled(
+            format!("  ● REC {}", clock_duration(recording.elapsed)),
+            Style::default()
+                .fg(theme.alert)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(ref timer) = state.sleep_timer {
+        status_spans.push(Span::styled(
+            format!("  💤 {}", clock_duration(timer.remaining(Instant::now()))),
+            Style::default().fg(theme.info),
+        ));
+    }
+    if let Some(ref reminder) = state.reminder {
+        let text = (tr().reminder)(
+            &reminder.start.format("%H:%M").to_string(),
+            tr().channel_name(reminder.channel),
+            &reminder.title,
+        );
+        status_spans.push(Span::styled(
+            format!(
+                "  {} [{}]",
+                text,
+                state.keymap.hint(&[Action::TuneReminder])
+            ),
+            Style::default()
+                .fg(theme.alert)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some((notice, _)) = state
+        .notice
+        .filter(|(_, at)| at.elapsed() < NOTICE_DURATION)
+    {
+        status_spans.push(Span::styled(
+            format!("  {}", notice),
+            Style::default().fg(theme.highlight),
+        ));
+    }
+    if let Some(ref renderer) = state.renderer {
+        status_spans.push(Span::styled(
+            format!("  📡 {}", truncate_str(renderer, 24)),
+            Style::default().fg(theme.muted),
+        ));
+    } else if let Some(ref device) = state.device {
+        status_spans.push(Span::styled(
+            format!("  🎧 {}", truncate_str(device, 24)),
+            Style::default().fg(theme.muted),
+        ));
+    }
+
+    let mut lines = vec![
+        Line::from(status_spans),
+        Line::from(Span::styled(
+            volume_gauge(state.volume, state.is_muted),
+            Style::default().fg(if state.is_muted {
+                theme.muted
+            } else {
+                theme.accent
+            }),
+        )),
+    ];
+    // A renderer plays the audio itself, so there is nothing to measure
+    if state.renderer.is_none() {
+        lines.push(Line::from(Span::styled(
+            level_meter(state.levels),
+            Style::default().fg(theme.info),
+        )));
+    }
+
+    let paragraph = Paragraph::new(lines).alignment(ratatui::layout::Alignment::Center);
+
+    f.render_widget(paragraph, area);
+}
+
+/// How often the timetables are fetched again for reminders
+const REMINDER_REFRESH: Duration = Duration::from_secs(30 * 60);
+
+/// A duration as `mm:ss`, or `h:mm:ss` past an hour
+fn clock_duration(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{:02}:{:02}", minutes, secs)
+    }
+}
+
+/// Where recordings started with `r` are written
+pub struct RecordTarget {
+    pub dir: PathBuf,
+    pub format: RecordFormat,
+    /// Start a new file whenever the program changes, for `--record` too
+    pub split: bool,
+    /// Write a cue sheet next to each recording, for `--record` too
+    pub cue: bool,
+}
+
+impl RecordTarget {
+    /// Start recording `channel` of `area` to a new file in the directory
+    fn start(&self, area: &str, channel: ChannelKind) -> Result<Recorder> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", self.dir.display(), e))?;
+        Recorder::with_format(self.path(area, channel, Local::now()), self.format)
+    }
+
+    /// File named after the station and start time, e.g.
+    /// `tokyo-fm-20251125-210000.aac`
+    fn path(&self, area: &str, channel: ChannelKind, start: DateTime<Local>) -> PathBuf {
+        self.dir.join(format!(
+            "{}-{}-{}.{}",
+            area,
+            channel.short_name().to_lowercase(),
+            start.format("%Y%m%d-%H%M%S"),
+            self.format.extension()
+        ))
+    }
+}
+
+/// How long a notice stays in the status bar
+const NOTICE_DURATION: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// What is playing, one line for sharing:
+/// "title — NHK station / air time / page"
+fn share_text(info: &ProgramInfo) -> String {
+    let station = format!("NHK {}", info.station_name);
+    let details: Vec<&str> = [
+        station.as_str(),
+        info.start_time.as_str(),
+        info.page_url.as_deref().unwrap_or_default(),
+    ]
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .collect();
+    format!("{} — {}", info.program_title, details.join(" / "))
+}
+
+pub(crate) fn volume_gauge(volume: u8, muted: bool) -> String {
+    if muted {
+        return tr().muted.to_string();
+    }
+    let filled = (volume as usize + 5) / 10;
+    format!(
+        "🔊 {}{} {:>3}%",
+        "█".repeat(filled),
+        "░".repeat(10 - filled),
+        volume
+    )
+}
+
+/// Quietest level the meter shows, in dB below full scale
+const METER_RANGE_DB: f32 = 48.0;
+/// Cells in each channel's bar
+const METER_CELLS: usize = 16;
+
+/// Left and right level bars on a decibel scale, measured before the
+/// volume is applied so they move while muted
+pub(crate) fn level_meter(levels: [f32; 2]) -> String {
+    const PARTIAL: [char; 7] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+    let bar = |rms: f32| {
+        let db = 20.0 * rms.max(f32::MIN_POSITIVE).log10();
+        let fill = ((db + METER_RANGE_DB) / METER_RANGE_DB).clamp(0.0, 1.0);
+        let eighths = (fill * (METER_CELLS * 8) as f32).round() as usize;
+        let mut bar = "█".repeat(eighths / 8);
+        if !eighths.is_multiple_of(8) {
+            bar.push(PARTIAL[eighths % 8 - 1]);
+        }
+        format!("{:<width$}", bar, width = METER_CELLS)
+    };
+    format!("L {}  R {}", bar(levels[0]), bar(levels[1]))
+}
+
+fn render_help(f: &mut Frame, area: Rect, theme: &Theme, keymap: &Keymap) {
+    let t = tr();
+    let key = |actions: &[Action]| format!("[{}]", keymap.hint(actions));
+    let help = Line::from(vec![
+        Span::styled(
+            key(&[Action::R1, Action::R2, Action::Fm]),
+            Style::default().fg(theme.accent),
+        ),
+        Span::raw(format!(" {}  ", t.help_channel)),
+        Span::styled(
+            key(&[Action::PrevChannel, Action::NextChannel]),
+            Style::default().fg(theme.accent),
+        ),
+        Span::raw(format!(" {}  ", t.help_switch)),
+        Span::styled(key(&[Action::Pause]), Style::default().fg(theme.accent)),
+        Span::raw(format!(" {}  ", t.help_pause)),
+        Span::styled(
+            key(&[Action::VolumeUp, Action::VolumeDown]),
+            Style::default().fg(theme.accent),
+        ),
+        Span::raw(format!(" {}  ", t.help_volume)),
+        Span::styled(key(&[Action::Help]), Style::default().fg(theme.accent)),
+        Span::raw(format!(" {}  ", t.help_help)),
+        Span::styled(key(&[Action::Quit]), Style::default().fg(theme.alert)),
+        Span::raw(format!(" {}", t.help_quit)),
+    ]);
+
+    let paragraph = Paragraph::new(help).alignment(ratatui::layout::Alignment::Center);
+
+    f.render_widget(paragraph, area);
+}
+
+/// Tune to `channel` unless it is already playing
+fn switch_channel(
+    state: &mut AppState,
+    playback: &Playback,
+    program: &Option<Root>,
+    area_name: &str,
+    channel: ChannelKind,
+) {
+    if state.current_channel != channel {
+        state.previous_channel = Some(state.current_channel);
+        state.current_channel = channel;
+        state.is_switching = true;
+        state.set_program_info(ProgramInfo::from_program(program, channel, area_name));
+        playback.set_channel(channel);
+    }
+}
+
+/// Where the TUI's controls go: the local output or a DLNA renderer
+enum Playback {
+    Local(PlaybackEngine),
+    Dlna(DlnaPlayback),
+}
+
+impl Playback {
+    fn set_channel(&self, channel: ChannelKind) {
+        match self {
+            Playback::Local(engine) => engine.set_channel(channel),
+            Playback::Dlna(dlna) => dlna.set_channel(channel),
+        }
+    }
+
+    fn set_area(&self, stream_data: StreamData) {
+        match self {
+            Playback::Local(engine) => engine.set_area(stream_data),
+            Playback::Dlna(dlna) => dlna.set_area(stream_data),
+        }
+    }
+
+    fn set_paused(&self, paused: bool) {
+        match self {
+            Playback::Local(engine) => engine.set_paused(paused),
+            Playback::Dlna(dlna) => dlna.set_paused(paused),
+        }
+    }
+
+    fn set_volume(&self, volume: f32) {
+        match self {
+            Playback::Local(engine) => engine.set_volume(volume),
+            Playback::Dlna(dlna) => dlna.set_volume(volume),
+        }
+    }
+
+    /// The local engine, for controls a renderer has no equivalent of
+    fn local(&self) -> Option<&PlaybackEngine> {
+        match self {
+            Playback::Local(engine) => Some(engine),
+            Playback::Dlna(_) => None,
+        }
+    }
+
+    fn state(&self) -> PlaybackState {
+        match self {
+            Playback::Local(engine) => engine.state(),
+            Playback::Dlna(dlna) => dlna.state(),
+        }
+    }
+
+    fn try_event(&self) -> Option<PlaybackEvent> {
+        match self {
+            Playback::Local(engine) => engine.try_event(),
+            Playback::Dlna(dlna) => dlna.try_event(),
+        }
+    }
 
-    let program_url = config
-        .url_program_noa
-        .replace("//", "https://")
-        .replace("{area}", &stream_data.areakey);
+    async fn shutdown(self) {
+        match self {
+            Playback::Local(engine) => drop(engine),
+            Playback::Dlna(dlna) => dlna.shutdown().await,
+        }
+    }
+}
+
+/// Settings of the TUI itself, beside those of the player
+pub struct TuiOptions {
+    pub theme: Theme,
+    pub keymap: Keymap,
+    /// Where `r` puts recordings
+    pub record_target: RecordTarget,
+    /// File kept up to date with what is playing
+    pub now_playing: Option<NowPlayingFile>,
+    pub hooks: Hooks,
+    pub favorites: Favorites,
+    /// Minutes before a favorite airs that it is announced
+    pub reminder_minutes: u32,
+    /// Accounts the music heard is scrobbled to
+    pub scrobble: ScrobbleConfig,
+    pub discord: DiscordConfig,
+    /// Quit after this long, like a sleep timer set at startup
+    pub duration: Option<Duration>,
+    /// Show what is playing in the terminal's title
+    pub terminal_title: bool,
+}
+
+/// Run the TUI, playing locally or, with `renderer`, on a DLNA renderer
+pub async fn run_interactive_player(
+    area: String,
+    initial_kind: ChannelKind,
+    options: PlayerOptions,
+    renderer: Option<DlnaRenderer>,
+    tui_options: TuiOptions,
+) -> Result<()> {
+    let TuiOptions {
+        theme,
+        keymap,
+        record_target,
+        mut now_playing,
+        hooks,
+        favorites,
+        reminder_minutes,
+        scrobble,
+        discord,
+        duration,
+        terminal_title,
+    } = tui_options;
+    let client = Arc::new(NhkRadioClient::with_options(options.http.clone())?);
+    let http_options = options.http.clone();
+
+    // Prefer the cached config so the UI can come up without waiting on the network
+    let cached = cache::load_config().filter(|c| c.stream_url.data.iter().any(|d| d.area == area));
+    let config = match cached {
+        Some(config) => {
+            let refresh_client = client.clone();
+            tokio::spawn(async move {
+                if let Err(e) = refresh_client.fetch_config().await {
+                    log::warn!("Failed to refresh config: {}", e);
+                }
+            });
+            config
+        }
+        None => client.fetch_config().await?,
+    };
 
-    let program = client.fetch_program(&program_url).await.ok();
+    let (mut stream_data, _) = config.find_area(&area)?;
 
-    let initial_info = ProgramInfo::from_program(&program, initial_kind, &stream_data.areajp);
+    let program_url = |stream_data: &StreamData| config.program_url(&stream_data.areakey);
+
+    let mut program: Option<Root> = None;
+
+    let initial_info = ProgramInfo::from_program(&program, initial_kind, &area_label(&stream_data));
 
     let mut state = AppState {
         current_channel: initial_kind,
+        previous_channel: None,
         program_info: initial_info,
         is_loading: true,
         is_switching: false,
+        is_paused: false,
+        volume: options.volume,
+        is_muted: false,
+        recording: None,
+        sleep_timer: duration.map(|duration| SleepTimer {
+            minutes: duration.as_secs().div_ceil(60),
+            ends: Instant::now() + duration,
+        }),
+        favorites,
+        reminder: None,
+        device: options.device.clone(),
+        output_lost: false,
+        silent: false,
+        renderer: renderer.as_ref().map(|r| r.name.clone()),
+        timeshift_secs: 0.0,
+        latency_secs: None,
+        levels: [0.0; 2],
+        description_scroll: 0,
+        eyecatch: None,
+        show_credits: false,
+        show_stats: false,
+        stats: None,
+        buffered_secs: 0.0,
+        show_log: false,
+        log_entries: Vec::new(),
+        notice: None,
+        credits_scroll: 0,
+        show_schedule: false,
+        schedule: None,
+        area_picker: None,
+        show_help: false,
+        theme,
+        keymap,
         animation_frame: 0,
     };
 
-    let (channel_tx, channel_rx) = watch::channel(initial_kind);
-    let (audio_tx, audio_rx) = std::sync::mpsc::channel::<Vec<i16>>();
-    let (playback_notify_tx, playback_notify_rx) = std::sync::mpsc::channel::<()>();
-
-    // Audio playback thread (must be on main thread for rodio)
-    let audio_handle =
-        std::thread::spawn(move || run_audio_thread(audio_rx, channel_rx, playback_notify_tx));
-
-    // Start streaming in background
-    let player_client = client.clone();
-    let player_stream_data = stream_data.clone();
-    let player_channel_rx = channel_tx.subscribe();
-    let player_handle = tokio::spawn(async move {
-        run_stream_loop(
-            player_client,
-            player_stream_data,
-            player_channel_rx,
-            audio_tx,
-        )
-        .await
-    });
+    // Start streaming in background so audio prebuffers while the UI comes up
+    let playback = match renderer {
+        Some(renderer) => {
+            let stream = options.stream;
+            let dlna = DlnaPlayback::start(
+                client.clone(),
+                stream_data.clone(),
+                initial_kind,
+                renderer,
+                stream,
+            )
+            .await?;
+            state.volume = dlna.initial_volume();
+            Playback::Dlna(dlna)
+        }
+        None => Playback::Local(PlaybackEngine::start(
+            client.clone(),
+            stream_data.clone(),
+            initial_kind,
+            options,
+        )),
+    };
+    let mut shown_position = (0.0, None);
+
+    // Program info is fetched concurrently, and again as programs end
+    let (program_tx, mut program_rx) = tokio::sync::mpsc::unbounded_channel::<Root>();
+    let mut program_task = tokio::spawn(refresh_program(
+        client.clone(),
+        program_url(&stream_data),
+        program_tx,
+    ));
+
+    // The day's timetable is fetched per channel while the panel is open
+    let (schedule_tx, mut schedule_rx) =
+        tokio::sync::mpsc::unbounded_channel::<(ChannelKind, DaySchedule)>();
+    let mut requested_schedule = None;
+
+    // Every channel's timetable is watched for favorites about to air
+    let (reminder_tx, mut reminder_rx) =
+        tokio::sync::mpsc::unbounded_channel::<Vec<(ChannelKind, ScheduledProgram)>>();
+    let mut reminder_fetch: Option<(String, Instant)> = None;
+    let mut upcoming = Vec::new();
+    let mut reminded = HashSet::new();
 
     let mut tui = Tui::new()?;
+    tui.enable_mouse()?;
+    let show_pictures = tui.enable_graphics();
+    let (eyecatch_tx, mut eyecatch_rx) = tokio::sync::mpsc::unbounded_channel::<Eyecatch>();
+    let mut requested_eyecatch: Option<String> = None;
+    // Kept open: on X11 the copied text lasts only as long as the clipboard
+    let mut clipboard: Option<arboard::Clipboard> = None;
+    // What is heard, as the now-playing file and hooks last saw it
+    let mut heard: Option<NowPlayingRecord> = None;
+    let mut split = ProgramSplit::default();
+    let mut cue: Option<CueSheet> = None;
+    let mut scrobbler = Scrobbler::start(&scrobble, &http_options);
+    let presence = DiscordPresence::start(&discord);
+    let mut media_controls = MediaControls::start();
+    let mut title = if terminal_title {
+        TerminalTitle::start()
+    } else {
+        None
+    };
 
     state.is_loading = false;
 
     loop {
+        if let Ok(root) = program_rx.try_recv() {
+            program = Some(root);
+            state.set_program_info(ProgramInfo::from_program_at(
+                &program,
+                state.current_channel,
+                &area_label(&stream_data),
+                state.playback_time(),
+            ));
+        }
+
+        // Follows what is heard, so it moves with channel switches and rewinds
+        if let Some((start, end)) = state.program_info.airs {
+            let record = NowPlayingRecord::new(
+                state.current_channel,
+                &state.program_info.area_name,
+                &state.program_info.program_title,
+                Some(start),
+                Some(end),
+            );
+            if let Some(event) = HookEvent::between(heard.as_ref(), &record) {
+                hooks.run(&event);
+                if let Some(file) = now_playing.as_mut()
+                    && let Err(e) = file.update(record.clone())
+                {
+                    log::warn!("{}", e);
+                }
+                heard = Some(record);
+            }
+        }
+
+        if record_target.split
+            && let Some(engine) = playback.local()
+        {
+            let on_air = program
+                .as_ref()
+                .and_then(|program| NowPlaying::from_program(program, state.current_channel));
+            if let Some(recorder) = split.next_file(
+                engine.recording().as_ref(),
+                &stream_data.area,
+                on_air.as_ref(),
+            ) {
+                engine.start_recording(recorder);
+            }
+        }
+
+        if let Some(presence) = presence.as_ref() {
+            presence.update(
+                heard.as_ref().filter(|_| !state.is_paused),
+                state.program_info.station_logo_url.as_deref(),
+            );
+        }
+
+        if let Some(controls) = media_controls.as_mut() {
+            controls.update(heard.as_ref(), state.is_paused);
+        }
+
+        if let Some(title) = title.as_mut() {
+            title.update(heard.as_ref(), state.is_paused);
+        }
+
+        if let Some(scrobbler) = scrobbler.as_mut() {
+            let listening = !state.is_paused && !state.is_switching;
+            let at = state
+                .playback_time()
+                .unwrap_or_else(|| Utc::now().fixed_offset());
+            scrobbler.observe(&state.program_info, listening.then_some(at));
+        }
+
+        if let Ok(eyecatch) = eyecatch_rx.try_recv()
+            && state.program_info.eyecatch_url.as_ref() == Some(&eyecatch.url)
+        {
+            state.eyecatch = Some(eyecatch);
+        }
+
+        if show_pictures && state.program_info.eyecatch_url != requested_eyecatch {
+            requested_eyecatch = state.program_info.eyecatch_url.clone();
+            if let Some(url) = requested_eyecatch.clone() {
+                let image_client = client.clone();
+                let eyecatch_tx = eyecatch_tx.clone();
+                tokio::spawn(async move {
+                    let picture = image_client
+                        .fetch_image(&url)
+                        .await
+                        .and_then(|bytes| Picture::decode(&bytes));
+                    match picture {
+                        Ok(picture) => {
+                            let _ = eyecatch_tx.send(Eyecatch { url, picture });
+                        }
+                        Err(e) => log::warn!("Failed to fetch the program picture: {}", e),
+                    }
+                });
+            }
+        }
+
+        if let Ok((channel, day)) = schedule_rx.try_recv() {
+            let now = state
+                .playback_time()
+                .unwrap_or_else(|| Utc::now().fixed_offset());
+            state.schedule = Some(ScheduleView::new(channel, day.publication, now));
+        }
+
+        if state.show_schedule && requested_schedule != Some(state.current_channel) {
+            let channel = state.current_channel;
+            requested_schedule = Some(channel);
+            let url = program_day_url(
+                &config.url_program_day,
+                &stream_data.areakey,
+                channel,
+                today_jst(),
+            );
+            let schedule_client = client.clone();
+            let schedule_tx = schedule_tx.clone();
+            tokio::spawn(async move {
+                match schedule_client.fetch_program_day(&url).await {
+                    Ok(day) => {
+                        let _ = schedule_tx.send((channel, day));
+                    }
+                    Err(e) => log::warn!("Failed to fetch the day's timetable: {}", e),
+                }
+            });
+        }
+
+        if !state.favorites.is_empty()
+            && reminder_fetch.as_ref().is_none_or(|(areakey, at)| {
+                *areakey != stream_data.areakey || at.elapsed() >= REMINDER_REFRESH
+            })
+        {
+            reminder_fetch = Some((stream_data.areakey.clone(), Instant::now()));
+            let today = today_jst();
+            let urls: Vec<_> = [today, today + chrono::Days::new(1)]
+                .into_iter()
+                .flat_map(|date| {
+                    CHANNEL_ORDER.map(|channel| {
+                        let url = program_day_url(
+                            &config.url_program_day,
+                            &stream_data.areakey,
+                            channel,
+                            date,
+                        );
+                        (channel, url)
+                    })
+                })
+                .collect();
+            let reminder_client = client.clone();
+            let reminder_tx = reminder_tx.clone();
+            tokio::spawn(async move {
+                let mut programs = Vec::new();
+                for (channel, url) in urls {
+                    match reminder_client.fetch_program_day(&url).await {
+                        Ok(day) => programs.extend(
+                            day.publication
+                                .into_iter()
+                                .map(|program| (channel, program)),
+                        ),
+                        Err(e) => log::warn!("Failed to fetch the timetable for reminders: {}", e),
+                    }
+                }
+                let _ = reminder_tx.send(programs);
+            });
+        }
+
+        if let Ok(programs) = reminder_rx.try_recv() {
+            upcoming = programs;
+        }
+
+        let now = Utc::now().fixed_offset();
+        let lead = chrono::Duration::minutes(reminder_minutes as i64);
+        for reminder in state.favorites.upcoming(&upcoming, now, lead) {
+            if reminded.insert((reminder.channel, reminder.start)) {
+                let text = (tr().reminder)(
+                    &reminder.start.format("%H:%M").to_string(),
+                    tr().channel_name(reminder.channel),
+                    &reminder.title,
+                );
+                log::info!("{}", text);
+                favorites::notify_desktop("nhk-radio-player", &text);
+                state.reminder = Some(reminder);
+            }
+        }
+        if state
+            .reminder
+            .as_ref()
+            .is_some_and(|reminder| reminder.start <= now)
+        {
+            state.reminder = None;
+        }
+
         tui.draw(&state)?;
 
-        // Check for playback started notification
-        if playback_notify_rx.try_recv().is_ok() {
-            state.is_switching = false;
+        let snapshot = playback.state();
+        state.latency_secs = snapshot.latency_secs();
+        state.levels = snapshot.levels;
+        state.buffered_secs = snapshot.queued_secs;
+        state.stats = playback.local().map(PlaybackEngine::stats);
+        state.recording = playback.local().and_then(PlaybackEngine::recording);
+        if state.show_log {
+            state.log_entries = logging::recent(LOG_PANE_HEIGHT as usize);
+        }
+
+        if let Some(timer) = state.sleep_timer {
+            let now = Instant::now();
+            if timer.remaining(now).is_zero() {
+                log::info!("Sleep timer of {} minutes ended", timer.minutes);
+                break;
+            }
+            // Whole percents only, so a renderer is not sent a command every frame
+            let volume = state.effective_volume() * timer.fade(now);
+            if (snapshot.volume - volume).abs() >= 0.01 {
+                playback.set_volume(volume);
+            }
+        }
+
+        // Keep the shown program in step with what is heard after rewinding
+        let position = (snapshot.timeshift_secs, snapshot.live_edge);
+        if position != shown_position {
+            shown_position = position;
+            // The recording takes the newest audio, whatever is heard
+            if record_target.cue
+                && let Some(sheet) =
+                    CueSheet::follow(&mut cue, state.recording.as_ref(), snapshot.live_edge)
+            {
+                let recorded = ProgramInfo::from_program_at(
+                    &program,
+                    state.current_channel,
+                    &area_label(&stream_data),
+                    snapshot.live_edge,
+                );
+                if let Err(e) = sheet.mark_program(
+                    &recorded.program_title,
+                    &recorded.station_name,
+                    recorded.airs,
+                    &recorded.music,
+                ) {
+                    log::warn!("{}", e);
+                }
+            }
+            state.timeshift_secs = snapshot.timeshift_secs;
+            state.set_program_info(ProgramInfo::from_program_at(
+                &program,
+                state.current_channel,
+                &area_label(&stream_data),
+                state.playback_time(),
+            ));
+        }
+
+        while let Some(event) = playback.try_event() {
+            match event {
+                PlaybackEvent::Started => {
+                    state.is_switching = false;
+                    state.silent = false;
+                }
+                PlaybackEvent::Error(e) => {
+                    log::error!("Playback failed: {}", e);
+                    hooks.run(&HookEvent::Error { message: e });
+                }
+                PlaybackEvent::OutputLost => state.output_lost = true,
+                PlaybackEvent::OutputRestored => state.output_lost = false,
+                PlaybackEvent::Silence => {
+                    log::warn!("{}", tr().silence);
+                    state.silent = true;
+                    hooks.run(&HookEvent::Error {
+                        message: tr().silence.to_string(),
+                    });
+                }
+                PlaybackEvent::SoundReturned => state.silent = false,
+                PlaybackEvent::ChannelChanged(_) => state.silent = false,
+            }
+        }
+
+        // Media keys pause and switch channels like their TUI keys
+        while let Some(key) = media_controls.as_ref().and_then(MediaControls::try_key) {
+            match key {
+                MediaKey::Toggle | MediaKey::Play | MediaKey::Pause => {
+                    state.is_paused = match key {
+                        MediaKey::Toggle => !state.is_paused,
+                        _ => key == MediaKey::Pause,
+                    };
+                    playback.set_paused(state.is_paused);
+                }
+                MediaKey::Next | MediaKey::Previous => {
+                    let channel = if key == MediaKey::Next {
+                        state.current_channel.next()
+                    } else {
+                        state.current_channel.prev()
+                    };
+                    switch_channel(
+                        &mut state,
+                        &playback,
+                        &program,
+                        &area_label(&stream_data),
+                        channel,
+                    );
+                }
+            }
         }
 
         // Handle input with timeout for animation
         if event::poll(std::time::Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => {
-                            break;
+            let event = event::read()?;
+            if let Event::Mouse(mouse) = event {
+                match mouse.kind {
+                    // The area picker is keyboard-only
+                    _ if state.area_picker.is_some() => {}
+                    MouseEventKind::Down(MouseButton::Left) if state.show_help => {
+                        state.show_help = false;
+                    }
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        let position = Position::new(mouse.column, mouse.row);
+                        match hit_test(tui.area()?, &state, position) {
+                            Some(Click::Channel(channel)) => switch_channel(
+                                &mut state,
+                                &playback,
+                                &program,
+                                &area_label(&stream_data),
+                                channel,
+                            ),
+                            Some(Click::Program(index)) => {
+                                if let Some(schedule) = state.schedule.as_mut() {
+                                    schedule.selected = index;
+                                }
+                            }
+                            None => {}
                         }
-                        KeyCode::Char('1') => {
-                            if state.current_channel != ChannelKind::R1 {
-                                state.current_channel = ChannelKind::R1;
-                                state.is_switching = true;
-                                state.program_info = ProgramInfo::from_program(
-                                    &program,
-                                    ChannelKind::R1,
-                                    &stream_data.areajp,
-                                );
-                                let _ = channel_tx.send(ChannelKind::R1);
+                    }
+                    MouseEventKind::ScrollUp => {
+                        state.volume = (state.volume + VOLUME_STEP).min(100);
+                        state.is_muted = false;
+                        playback.set_volume(state.effective_volume());
+                    }
+                    MouseEventKind::ScrollDown => {
+                        state.volume = state.volume.saturating_sub(VOLUME_STEP);
+                        playback.set_volume(state.effective_volume());
+                    }
+                    _ => {}
+                }
+            }
+            if let Event::Key(key) = event
+                && key.kind == KeyEventKind::Press
+            {
+                match key.code {
+                    // Any key dismisses the help overlay
+                    _ if state.show_help => state.show_help = false,
+                    // The area picker takes every key while it is open
+                    code if state.area_picker.is_some() => {
+                        let Some(picker) = state.area_picker.as_mut() else {
+                            continue;
+                        };
+                        match code {
+                            KeyCode::Esc => state.area_picker = None,
+                            KeyCode::Up => picker.select_prev(),
+                            KeyCode::Down => picker.select_next(),
+                            KeyCode::Backspace => picker.pop(),
+                            KeyCode::Char(c) => picker.push(c),
+                            KeyCode::Enter => {
+                                let chosen = picker.chosen().cloned();
+                                state.area_picker = None;
+                                if let Some(area) = chosen
+                                    && area.area != stream_data.area
+                                {
+                                    stream_data = area;
+                                    playback.set_area(stream_data.clone());
+                                    state.is_switching = true;
+
+                                    // Listings of the old area no longer apply
+                                    program = None;
+                                    state.set_program_info(ProgramInfo::from_program(
+                                        &program,
+                                        state.current_channel,
+                                        &area_label(&stream_data),
+                                    ));
+                                    state.schedule = None;
+                                    requested_schedule = None;
+                                    program_task.abort();
+                                    let (program_tx, new_program_rx) =
+                                        tokio::sync::mpsc::unbounded_channel();
+                                    program_rx = new_program_rx;
+                                    program_task = tokio::spawn(refresh_program(
+                                        client.clone(),
+                                        program_url(&stream_data),
+                                        program_tx,
+                                    ));
+                                }
                             }
+                            _ => {}
                         }
-                        KeyCode::Char('2') => {
-                            if state.current_channel != ChannelKind::R2 {
-                                state.current_channel = ChannelKind::R2;
-                                state.is_switching = true;
-                                state.program_info = ProgramInfo::from_program(
-                                    &program,
-                                    ChannelKind::R2,
-                                    &stream_data.areajp,
-                                );
-                                let _ = channel_tx.send(ChannelKind::R2);
+                    }
+                    code => match state.keymap.action(code) {
+                        Some(Action::Quit) => break,
+                        Some(Action::Pause) => {
+                            state.is_paused = !state.is_paused;
+                            playback.set_paused(state.is_paused);
+                        }
+                        Some(Action::VolumeUp) => {
+                            state.volume = (state.volume + VOLUME_STEP).min(100);
+                            state.is_muted = false;
+                            playback.set_volume(state.effective_volume());
+                        }
+                        Some(Action::VolumeDown) => {
+                            state.volume = state.volume.saturating_sub(VOLUME_STEP);
+                            playback.set_volume(state.effective_volume());
+                        }
+                        Some(Action::Rewind) => {
+                            if let Some(engine) = playback.local() {
+                                engine.rewind(REWIND_STEP);
                             }
                         }
-                        KeyCode::Char('3') => {
-                            if state.current_channel != ChannelKind::Fm {
-                                state.current_channel = ChannelKind::Fm;
-                                state.is_switching = true;
-                                state.program_info = ProgramInfo::from_program(
-                                    &program,
-                                    ChannelKind::Fm,
-                                    &stream_data.areajp,
-                                );
-                                let _ = channel_tx.send(ChannelKind::Fm);
+                        Some(Action::Live) => {
+                            if let Some(engine) = playback.local() {
+                                engine.go_live();
                             }
                         }
-                        KeyCode::Left | KeyCode::Char('h') => {
-                            let new_channel = state.current_channel.prev();
-                            if state.current_channel != new_channel {
-                                state.current_channel = new_channel;
-                                state.is_switching = true;
-                                state.program_info = ProgramInfo::from_program(
-                                    &program,
-                                    new_channel,
-                                    &stream_data.areajp,
+                        Some(Action::Device) => {
+                            if let Some(engine) = playback.local() {
+                                let devices = output_devices().unwrap_or_default();
+                                state.device = next_device(&devices, state.device.as_deref());
+                                engine.set_device(state.device.clone());
+                            }
+                        }
+                        Some(Action::Equalizer) => {
+                            if let Some(engine) = playback.local() {
+                                let preset = engine.equalizer().next();
+                                engine.set_equalizer(preset);
+                                state.notice = Some((tr().eq_preset(preset), Instant::now()));
+                            }
+                        }
+                        Some(Action::Help) => state.show_help = true,
+                        Some(Action::Area) => {
+                            state.area_picker = Some(AreaPicker::new(
+                                config.stream_url.data.clone(),
+                                &stream_data.area,
+                            ));
+                        }
+                        Some(Action::Schedule) => {
+                            state.show_schedule = !state.show_schedule;
+                            // Reopening retries a fetch that never arrived
+                            if state.show_schedule
+                                && state.schedule.as_ref().map(|s| s.channel)
+                                    != Some(state.current_channel)
+                            {
+                                requested_schedule = None;
+                            }
+                        }
+                        Some(Action::Up) if state.show_schedule => {
+                            if let Some(schedule) = state.schedule.as_mut() {
+                                schedule.select_prev();
+                            }
+                        }
+                        Some(Action::Down) if state.show_schedule => {
+                            if let Some(schedule) = state.schedule.as_mut() {
+                                schedule.select_next();
+                            }
+                        }
+                        Some(direction @ (Action::Up | Action::Down)) => {
+                            // The credits panel takes the keys while it is open
+                            let (panel, side) = program_layout(main_layout(tui.area()?)[2], &state);
+                            let (scroll, max) = match side.filter(|_| state.show_credits) {
+                                Some(area) => {
+                                    let inner = Block::default().borders(Borders::ALL).inner(area);
+                                    let lines = credit_lines(
+                                        &state.program_info,
+                                        inner.width,
+                                        &state.theme,
+                                    );
+                                    (
+                                        &mut state.credits_scroll,
+                                        lines.len().saturating_sub(inner.height as usize),
+                                    )
+                                }
+                                None => {
+                                    let max = description_max_scroll(panel, &state);
+                                    (&mut state.description_scroll, max)
+                                }
+                            };
+                            let current = (*scroll).min(max);
+                            *scroll = if direction == Action::Up {
+                                current.saturating_sub(1)
+                            } else {
+                                (current + 1).min(max)
+                            };
+                        }
+                        Some(Action::Credits) => {
+                            state.show_credits = !state.show_credits;
+                            state.show_stats = false;
+                        }
+                        Some(Action::Stats) => {
+                            state.show_stats = !state.show_stats;
+                            state.show_credits = false;
+                        }
+                        Some(Action::Log) => state.show_log = !state.show_log,
+                        Some(Action::OpenPage) => {
+                            if let Some(url) = &state.program_info.page_url
+                                && let Err(e) = browser::open_url(url)
+                            {
+                                log::warn!("{}", e);
+                            }
+                        }
+                        Some(Action::Copy) => {
+                            let copied = match clipboard.as_mut() {
+                                Some(clipboard) => Ok(clipboard),
+                                None => arboard::Clipboard::new().map(|new| clipboard.insert(new)),
+                            }
+                            .and_then(|clipboard| {
+                                clipboard.set_text(share_text(&state.program_info))
+                            });
+                            match copied {
+                                Ok(()) => state.notice = Some((tr().copied, Instant::now())),
+                                Err(e) => log::warn!("Failed to copy to the clipboard: {}", e),
+                            }
+                        }
+                        Some(Action::Favorite) => {
+                            let info = &state.program_info;
+                            if info.airs.is_some() {
+                                let added = state.favorites.toggle(
+                                    &info.series_id,
+                                    &info.series_name,
+                                    &info.program_title,
                                 );
-                                let _ = channel_tx.send(new_channel);
+                                match state.favorites.save() {
+                                    Ok(()) => {
+                                        let notice = if added {
+                                            tr().favorite_added
+                                        } else {
+                                            tr().favorite_removed
+                                        };
+                                        state.notice = Some((notice, Instant::now()));
+                                    }
+                                    Err(e) => log::warn!("Failed to save favorites: {}", e),
+                                }
                             }
                         }
-                        KeyCode::Right | KeyCode::Char('l') => {
-                            let new_channel = state.current_channel.next();
-                            if state.current_channel != new_channel {
-                                state.current_channel = new_channel;
-                                state.is_switching = true;
-                                state.program_info = ProgramInfo::from_program(
+                        Some(Action::TuneReminder) => {
+                            if let Some(reminder) = state.reminder.take() {
+                                switch_channel(
+                                    &mut state,
+                                    &playback,
                                     &program,
-                                    new_channel,
-                                    &stream_data.areajp,
+                                    &area_label(&stream_data),
+                                    reminder.channel,
                                 );
-                                let _ = channel_tx.send(new_channel);
                             }
                         }
-                        _ => {}
-                    }
+                        Some(Action::Sleep) => {
+                            state.sleep_timer =
+                                SleepTimer::next(state.sleep_timer.as_ref(), Instant::now());
+                            // Undo a fade-out already under way
+                            playback.set_volume(state.effective_volume());
+                        }
+                        // A renderer fetches the stream itself, so there is nothing to tee
+                        Some(Action::Record) => {
+                            if let Some(engine) = playback.local() {
+                                if let Some(path) = engine.stop_recording() {
+                                    log::info!("Saved the recording to {}", path.display());
+                                } else {
+                                    match record_target
+                                        .start(&stream_data.area, state.current_channel)
+                                    {
+                                        Ok(recorder) => engine.start_recording(recorder),
+                                        Err(e) => log::warn!("{}", e),
+                                    }
+                                }
+                            }
+                        }
+                        Some(Action::SearchHashtags) => {
+                            let hashtags = &state.program_info.hashtags;
+                            if !hashtags.is_empty()
+                                && let Err(e) = browser::hashtag_search_url(hashtags)
+                                    .and_then(|url| browser::open_url(&url))
+                            {
+                                log::warn!("{}", e);
+                            }
+                        }
+                        Some(Action::Mute) => {
+                            state.is_muted = !state.is_muted;
+                            playback.set_volume(state.effective_volume());
+                        }
+                        Some(
+                            action @ (Action::R1
+                            | Action::R2
+                            | Action::Fm
+                            | Action::PrevChannel
+                            | Action::NextChannel
+                            | Action::LastChannel),
+                        ) => {
+                            let channel = match action {
+                                Action::R1 => ChannelKind::R1,
+                                Action::R2 => ChannelKind::R2,
+                                Action::Fm => ChannelKind::Fm,
+                                Action::PrevChannel => state.current_channel.prev(),
+                                Action::LastChannel => {
+                                    state.previous_channel.unwrap_or(state.current_channel)
+                                }
+                                _ => state.current_channel.next(),
+                            };
+                            switch_channel(
+                                &mut state,
+                                &playback,
+                                &program,
+                                &area_label(&stream_data),
+                                channel,
+                            );
+                        }
+                        None => {}
+                    },
                 }
             }
         }
@@ -558,8 +2490,398 @@ pub async fn run_interactive_player(area: String, initial_kind: ChannelKind) ->
     }
 
     drop(tui);
-    player_handle.abort();
-    drop(audio_handle);
+    program_task.abort();
+    playback.shutdown().await;
+    if let Some(file) = now_playing {
+        file.remove();
+    }
 
+    let mut session = SessionState::load();
+    // A renderer's volume is its own, not the one to play locally at
+    if state.renderer.is_none() {
+        session.volume = Some(state.volume);
+    }
+    session.set_station(&stream_data.area, state.current_channel);
+    if let Err(e) = session.save() {
+        log::warn!("Failed to save session state: {}", e);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program_info::MusicPiece;
+    use chrono::TimeZone;
+
+    fn program(name: &str, start: &str, end: &str) -> ScheduledProgram {
+        ScheduledProgram::named(
+            name,
+            &format!("2025-11-25T{}:00+09:00", start),
+            &format!("2025-11-25T{}:00+09:00", end),
+        )
+    }
+
+    fn event(name: &str, start: &str, end: &str) -> serde_json::Value {
+        let at = |time: &str| {
+            DateTime::parse_from_rfc3339(&format!("2025-11-25T{}:00+09:00", time)).unwrap()
+        };
+        crate::mock::broadcast_event(name, at(start), at(end))
+    }
+
+    #[test]
+    fn test_program_info_neighbours() {
+        let channel = serde_json::json!({
+            "previous": event("ニュース", "21:00", "21:10"),
+            "present": event("NHKジャーナル", "21:10", "22:00"),
+            "following": event("ラジオ深夜便", "22:00", "23:00"),
+        });
+        let root: Root = serde_json::from_value(serde_json::json!({
+            "r1": channel, "r2": {}, "r3": {}
+        }))
+        .unwrap();
+        let program = Some(root);
+
+        let info = ProgramInfo::from_program(&program, ChannelKind::R1, "東京");
+        assert_eq!(info.program_title, "NHKジャーナル");
+        assert_eq!(info.previous.as_ref().unwrap().title, "ニュース");
+        let next = info.next.unwrap();
+        assert_eq!(
+            (next.start_time.as_str(), next.title.as_str()),
+            ("22:00", "ラジオ深夜便")
+        );
+
+        // Listening behind live shifts the neighbours too
+        let at = DateTime::parse_from_rfc3339("2025-11-25T21:05:00+09:00").unwrap();
+        let info = ProgramInfo::from_program_at(&program, ChannelKind::R1, "東京", Some(at));
+        assert_eq!(info.program_title, "ニュース");
+        assert!(info.previous.is_none());
+        assert_eq!(info.next.unwrap().title, "NHKジャーナル");
+
+        let empty = ProgramInfo::from_program(&program, ChannelKind::R2, "東京");
+        assert!(empty.previous.is_none() && empty.next.is_none());
+    }
+
+    #[test]
+    fn test_program_info_credits() {
+        let mut present = event("クラシック音楽館", "21:00", "23:00");
+        present["misc"]["actList"] = serde_json::json!([
+            { "role": "司会", "name": "山田太郎", "nameRuby": "" },
+            { "name": "NHK交響楽団", "nameRuby": "" }
+        ]);
+        present["misc"]["musicList"] = serde_json::json!([{
+            "name": "交響曲第5番", "nameruby": "", "lyricist": "",
+            "composer": "ベートーベン", "arranger": "", "location": "",
+            "provider": "", "label": "", "duration": "", "code": "",
+            "byArtist": [
+                { "name": "NHK交響楽団", "role": "", "part": "管弦楽" },
+                { "name": "鈴木花子", "role": "指揮", "part": "" }
+            ]
+        }]);
+        let root: Root = serde_json::from_value(serde_json::json!({
+            "r1": {}, "r2": {}, "r3": { "present": present }
+        }))
+        .unwrap();
+
+        let info = ProgramInfo::from_program(&Some(root), ChannelKind::Fm, "東京");
+        assert_eq!(info.cast, ["司会: 山田太郎", "NHK交響楽団"]);
+        assert_eq!(
+            info.music,
+            [MusicPiece {
+                title: "交響曲第5番".to_string(),
+                composer: "ベートーベン".to_string(),
+                performers: vec![
+                    "管弦楽: NHK交響楽団".to_string(),
+                    "指揮: 鈴木花子".to_string()
+                ],
+                artists: vec!["NHK交響楽団".to_string(), "鈴木花子".to_string()],
+                duration: None,
+            }]
+        );
+
+        let lines = credit_lines(&info, 40, &Theme::default());
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[5].to_string(), " ♪ 交響曲第5番");
+        assert_eq!(lines[6].to_string(), "   作曲: ベートーベン");
+    }
+
+    #[test]
+    fn test_share_text() {
+        let mut info = ProgramInfo::from_program(&None, ChannelKind::Fm, "東京");
+        info.program_title = "クラシック音楽館".to_string();
+        info.start_time = "2025年11月25日 午後9:00".to_string();
+        info.page_url = Some("https://www.nhk.jp/p/ongakukan/rs/1234/".to_string());
+        assert_eq!(
+            share_text(&info),
+            "クラシック音楽館 — NHK FM / 2025年11月25日 午後9:00 / https://www.nhk.jp/p/ongakukan/rs/1234/"
+        );
+        info.start_time.clear();
+        info.page_url = None;
+        assert_eq!(share_text(&info), "クラシック音楽館 — NHK FM");
+    }
+
+    #[test]
+    fn test_recording() {
+        assert_eq!(clock_duration(Duration::from_secs(75)), "01:15");
+        assert_eq!(
+            clock_duration(Duration::from_secs(3 * 3600 + 62)),
+            "3:01:02"
+        );
+
+        let target = RecordTarget {
+            dir: PathBuf::from("rec"),
+            format: RecordFormat::Mp3,
+            split: false,
+            cue: false,
+        };
+        let start = Local.with_ymd_and_hms(2025, 11, 25, 21, 0, 5).unwrap();
+        assert_eq!(
+            target.path("tokyo", ChannelKind::Fm, start),
+            PathBuf::from("rec").join("tokyo-fm-20251125-210005.mp3")
+        );
+    }
+
+    #[test]
+    fn test_sleep_timer() {
+        let now = Instant::now();
+        let first = SleepTimer::next(None, now).unwrap();
+        assert_eq!(first.minutes, 15);
+        assert_eq!(first.remaining(now), Duration::from_secs(15 * 60));
+        assert_eq!(first.fade(now), 1.0);
+        assert_eq!(first.fade(first.ends - SLEEP_FADE / 2), 0.5);
+        assert_eq!(first.fade(first.ends + SLEEP_FADE), 0.0);
+
+        // Each press restarts the countdown at the next preset, then turns it off
+        let later = now + Duration::from_secs(60);
+        let second = SleepTimer::next(Some(&first), later).unwrap();
+        assert_eq!(second.minutes, 30);
+        assert_eq!(second.remaining(later), Duration::from_secs(30 * 60));
+        let last = SleepTimer {
+            minutes: 90,
+            ends: now,
+        };
+        assert_eq!(SleepTimer::next(Some(&last), now), None);
+    }
+
+    #[test]
+    fn test_next_program_refresh() {
+        let root: Root = serde_json::from_value(serde_json::json!({
+            "r1": { "present": event("NHKジャーナル", "21:10", "22:00") },
+            "r2": { "present": event("基礎英語", "21:00", "21:15") },
+            "r3": {},
+        }))
+        .unwrap();
+        let at =
+            |t: &str| DateTime::parse_from_rfc3339(&format!("2025-11-25T{}+09:00", t)).unwrap();
+
+        // Just after the earliest end, on any channel
+        assert_eq!(
+            next_program_refresh(&root, at("21:14:00")),
+            std::time::Duration::from_secs(60) + PROGRAM_END_GRACE
+        );
+        // A stale listing is retried soon, a far-off end still gets a check
+        assert_eq!(
+            next_program_refresh(&root, at("21:30:00")),
+            PROGRAM_REFRESH_MIN
+        );
+        assert_eq!(
+            next_program_refresh(&root, at("20:00:00")),
+            PROGRAM_REFRESH_MAX
+        );
+    }
+
+    #[test]
+    fn test_area_picker() {
+        let area = crate::mock::stream_data;
+        let areas = vec![
+            area("sapporo", "札幌"),
+            area("tokyo", "東京"),
+            area("osaka", "大阪"),
+        ];
+
+        let mut picker = AreaPicker::new(areas, "tokyo");
+        assert_eq!(picker.chosen().unwrap().area, "tokyo");
+        picker.select_next();
+        picker.select_next();
+        assert_eq!(picker.chosen().unwrap().area, "osaka");
+
+        // Typing narrows the list and starts again from the top
+        picker.push('s');
+        assert_eq!(picker.matches().len(), 2);
+        assert_eq!(picker.chosen().unwrap().area, "sapporo");
+        picker.push('k');
+        assert_eq!(picker.chosen().unwrap().area, "osaka");
+        picker.push('z');
+        assert!(picker.chosen().is_none());
+        picker.pop();
+        assert_eq!(picker.chosen().unwrap().area, "osaka");
+    }
+
+    #[test]
+    fn test_hit_test() {
+        let programs = (0..30)
+            .map(|i| program(&format!("番組{}", i), "00:00", "00:10"))
+            .collect();
+        let at = DateTime::parse_from_rfc3339("2025-11-25T12:00:00+09:00").unwrap();
+        let mut state = AppState {
+            current_channel: ChannelKind::R1,
+            previous_channel: None,
+            program_info: ProgramInfo::from_program(&None, ChannelKind::R1, "東京"),
+            is_loading: false,
+            is_switching: false,
+            is_paused: false,
+            volume: 50,
+            is_muted: false,
+            recording: None,
+            sleep_timer: None,
+            favorites: Favorites::default(),
+            reminder: None,
+            device: None,
+            output_lost: false,
+            silent: false,
+            renderer: None,
+            timeshift_secs: 0.0,
+            latency_secs: None,
+            levels: [0.0; 2],
+            description_scroll: 0,
+            eyecatch: None,
+            show_credits: false,
+            show_stats: false,
+            stats: None,
+            buffered_secs: 0.0,
+            show_log: false,
+            log_entries: Vec::new(),
+            notice: None,
+            credits_scroll: 0,
+            show_schedule: false,
+            schedule: Some(ScheduleView::new(ChannelKind::R1, programs, at)),
+            area_picker: None,
+            show_help: false,
+            theme: Theme::default(),
+            keymap: Keymap::default(),
+            animation_frame: 0,
+        };
+        let screen = Rect::new(0, 0, 80, 30);
+        let click = |state: &AppState, x, y| hit_test(screen, state, Position::new(x, y));
+
+        // Channel boxes sit in rows 1-3 inside the margin
+        assert_eq!(click(&state, 5, 2), Some(Click::Channel(ChannelKind::R1)));
+        assert_eq!(click(&state, 40, 2), Some(Click::Channel(ChannelKind::R2)));
+        assert_eq!(click(&state, 70, 2), Some(Click::Channel(ChannelKind::Fm)));
+        assert_eq!(click(&state, 40, 0), None);
+
+        // Schedule rows only count while the panel is open
+        let list = schedule_layout(main_layout(screen)[2])[0];
+        assert_eq!(click(&state, list.x, list.y), None);
+        state.show_schedule = true;
+        assert_eq!(click(&state, list.x, list.y), Some(Click::Program(0)));
+        assert_eq!(click(&state, list.x, list.y + 2), Some(Click::Program(2)));
+
+        // Rows follow the list as it scrolls to the selection
+        state.schedule.as_mut().unwrap().selected = 29;
+        let first = 30 - list.height as usize;
+        assert_eq!(click(&state, list.x, list.y), Some(Click::Program(first)));
+    }
+
+    #[test]
+    fn test_level_meter() {
+        assert_eq!(
+            level_meter([0.0, 1.0]),
+            format!("L {}  R {}", " ".repeat(16), "█".repeat(16))
+        );
+        // -24 dB fills half the bar, -25.5 dB half a cell less
+        assert_eq!(
+            level_meter([10f32.powf(-24.0 / 20.0), 10f32.powf(-25.5 / 20.0)]),
+            format!(
+                "L {}{}  R {}▌{}",
+                "█".repeat(8),
+                " ".repeat(8),
+                "█".repeat(7),
+                " ".repeat(8)
+            )
+        );
+    }
+
+    #[test]
+    fn test_stats_rows() {
+        let now = Instant::now();
+        let stats = StreamStatsSnapshot {
+            bitrate: Some(48_000),
+            segments_fetched: 120,
+            segments_failed: 2,
+            dropped_secs: 1.25,
+            last_refresh: now.checked_sub(std::time::Duration::from_secs(3)),
+            ..Default::default()
+        };
+        let rows = stats_rows(&stats, 12.34, now);
+        assert_eq!(rows[0].1, "48 kbps");
+        assert_eq!(rows[1].1, "12.3 s");
+        assert_eq!(rows[2].1, "120");
+        assert_eq!(rows[6].1, "1.2 s");
+        assert_eq!(rows[7].1, "3秒前");
+        assert_eq!(rows[8].1, "0.0 MB");
+
+        let rows = stats_rows(&StreamStatsSnapshot::default(), 0.0, now);
+        assert_eq!(rows[0].1, "—");
+        assert_eq!(rows[7].1, "—");
+
+        let stats = StreamStatsSnapshot {
+            bytes_downloaded: 12_345_678,
+            bandwidth_cap: Some(100_000_000),
+            ..Default::default()
+        };
+        assert_eq!(stats_rows(&stats, 0.0, now)[8].1, "12.3 / 100.0 MB");
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        assert_eq!(
+            wrap_text("クラシック音楽の名曲を", 8),
+            ["クラシッ", "ク音楽の", "名曲を"]
+        );
+        assert_eq!(
+            wrap_text("NHK Symphony Orchestra", 10),
+            ["NHK", "Symphony", "Orchestra"]
+        );
+        assert_eq!(
+            wrap_text("演奏 NHK交響楽団\n指揮", 12),
+            ["演奏 NHK交響", "楽団", "指揮"]
+        );
+        assert!(wrap_text("番組", 0).is_empty());
+    }
+
+    #[test]
+    fn test_program_progress() {
+        let time =
+            |t: &str| DateTime::parse_from_rfc3339(&format!("2025-11-25T{}+09:00", t)).unwrap();
+        let (start, end) = (time("21:10:00"), time("22:00:00"));
+        assert_eq!(program_progress(start, end, time("21:35:00")), (0.5, 25));
+        assert_eq!(program_progress(start, end, time("21:47:30")).1, 13);
+        assert_eq!(program_progress(start, end, time("23:00:00")), (1.0, 0));
+        assert_eq!(program_progress(start, end, time("20:00:00")), (0.0, 50));
+    }
+
+    #[test]
+    fn test_schedule_view_selects_on_air() {
+        let programs = vec![
+            program("ニュース", "21:00", "21:10"),
+            program("NHKジャーナル", "21:10", "22:00"),
+            program("ラジオ深夜便", "22:00", "23:00"),
+        ];
+        let at = DateTime::parse_from_rfc3339("2025-11-25T21:30:00+09:00").unwrap();
+        assert_eq!(on_air_index(&programs, at), Some(1));
+
+        let mut view = ScheduleView::new(ChannelKind::R1, programs, at);
+        assert_eq!(view.selected, 1);
+        view.select_next();
+        view.select_next();
+        assert_eq!(view.selected, 2);
+        for _ in 0..3 {
+            view.select_prev();
+        }
+        assert_eq!(view.selected, 0);
+
+        let later = DateTime::parse_from_rfc3339("2025-11-26T01:00:00+09:00").unwrap();
+        assert_eq!(on_air_index(&view.programs, later), None);
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::Result;
+use dialoguer::{Confirm, Select, theme::ColorfulTheme};
+use rodio::Source;
+use std::time::Duration;
+
+use crate::client::NhkRadioClient;
+use crate::config::Config;
+use crate::player::ChannelKind;
+use crate::theme::PRESETS;
+
+const LANGS: [(&str, &str); 2] = [("ja", "日本語"), ("en", "English")];
+
+/// Interactive first-run setup that writes the config file
+pub async fn run_setup_wizard(client: &NhkRadioClient) -> Result<Config> {
+    let theme = ColorfulTheme::default();
+    println!("NHK Radio Player - 初期設定 / First-run setup");
+    println!();
+
+    let radiru = client.fetch_config().await?;
+    let areas: Vec<String> = radiru
+        .stream_url
+        .data
+        .iter()
+        .map(|d| format!("{} ({})", d.areajp, d.area))
+        .collect();
+    let area_idx = Select::with_theme(&theme)
+        .with_prompt("地域 / Area")
+        .items(&areas)
+        .default(0)
+        .interact()?;
+
+    let channels = [ChannelKind::R1, ChannelKind::R2, ChannelKind::Fm];
+    let channel_labels: Vec<String> = channels
+        .iter()
+        .map(|c| format!("{} ({})", c.display_name(), c.short_name()))
+        .collect();
+    let channel_idx = Select::with_theme(&theme)
+        .with_prompt("チャンネル / Channel")
+        .items(&channel_labels)
+        .default(0)
+        .interact()?;
+
+    loop {
+        println!("テスト音を再生します / Playing a test tone...");
+        if let Err(e) = play_test_tone() {
+            println!("音声出力を初期化できません / Audio output failed: {}", e);
+        }
+        let heard = Confirm::with_theme(&theme)
+            .with_prompt("聞こえましたか? / Did you hear the tone?")
+            .default(true)
+            .interact()?;
+        if heard {
+            break;
+        }
+        let retry = Confirm::with_theme(&theme)
+            .with_prompt("もう一度試しますか? / Try again?")
+            .default(true)
+            .interact()?;
+        if !retry {
+            println!("音声デバイスの設定を確認してください / Please check your audio device.");
+            break;
+        }
+    }
+
+    let theme_idx = Select::with_theme(&theme)
+        .with_prompt("テーマ / Theme")
+        .items(PRESETS)
+        .default(0)
+        .interact()?;
+
+    let lang_labels: Vec<&str> = LANGS.iter().map(|(_, label)| *label).collect();
+    let lang_idx = Select::with_theme(&theme)
+        .with_prompt("言語 / Language")
+        .items(&lang_labels)
+        .default(0)
+        .interact()?;
+
+    let mut config = Config::load().unwrap_or_default();
+    config.area = Some(radiru.stream_url.data[area_idx].area.clone());
+    config.channel = Some(channels[channel_idx].short_name().to_lowercase());
+    config.theme = Some(PRESETS[theme_idx].to_string());
+    config.lang = Some(LANGS[lang_idx].0.to_string());
+    config.save()?;
+
+    if let Some(path) = Config::path() {
+        println!("設定を保存しました / Saved config to {}", path.display());
+    }
+    println!();
+
+    Ok(config)
+}
+
+fn play_test_tone() -> Result<()> {
+    let (_stream, stream_handle) = rodio::OutputStream::try_default()?;
+    let sink = rodio::Sink::try_new(&stream_handle)?;
+    let tone = rodio::source::SineWave::new(440.0)
+        .take_duration(Duration::from_millis(800))
+        .amplify(0.2);
+    sink.append(tone);
+    sink.sleep_until_end();
+    Ok(())
+}